@@ -11,10 +11,10 @@ wit_bindgen::generate!({
         export wasi:http/client@0.3.0;
         export spin:key-value/key-value@3.0.0;
         export spin:mqtt/mqtt@3.0.0;
-        export spin:mysql/mysql@3.0.0;
+        export spin:mysql/mysql@3.2.0;
         export spin:postgres/postgres@3.0.0;
-        export spin:postgres/postgres@4.2.0;
-        export spin:redis/redis@3.0.0;
+        export spin:postgres/postgres@4.3.0;
+        export spin:redis/redis@3.1.0;
         export spin:sqlite/sqlite@3.1.0;
         export spin:variables/variables@3.0.0;
         export wasi:config/store@0.2.0-draft-2024-09-27;
@@ -101,11 +101,9 @@ impl exports::wasi::http0_3_0::client::Guest for Adapter {
         exports::wasi::http0_3_0::client::Response,
         exports::wasi::http0_3_0::client::ErrorCode,
     > {
-        Err(
-            exports::wasi::http0_3_0::client::ErrorCode::InternalError(Some(
-                format_deny_error("wasi:http/client"),
-            )),
-        )
+        Err(exports::wasi::http0_3_0::client::ErrorCode::InternalError(
+            Some(format_deny_error("wasi:http/client")),
+        ))
     }
 }
 impl exports::spin::key_value::key_value::GuestStore for Adapter {
@@ -230,16 +228,18 @@ impl exports::spin::postgres3_0_0::postgres::GuestConnection for Adapter {
         unreachable!()
     }
 }
-impl exports::spin::mysql::mysql::Guest for Adapter {
+impl exports::spin::mysql3_2_0::mysql::Guest for Adapter {
     type Connection = Adapter;
+    type Transaction = Adapter;
 }
-impl exports::spin::mysql::mysql::GuestConnection for Adapter {
+impl exports::spin::mysql3_2_0::mysql::GuestConnection for Adapter {
     #[allow(unused_variables)]
     #[allow(async_fn_in_trait)]
     async fn open(
         address: _rt::String,
-    ) -> Result<exports::spin::mysql::mysql::Connection, exports::spin::mysql::mysql::Error> {
-        Err(exports::spin::mysql::mysql::Error::Other(
+    ) -> Result<exports::spin::mysql3_2_0::mysql::Connection, exports::spin::mysql3_2_0::mysql::Error>
+    {
+        Err(exports::spin::mysql3_2_0::mysql::Error::Other(
             format_deny_error("spin:mysql/mysql"),
         ))
     }
@@ -249,16 +249,97 @@ impl exports::spin::mysql::mysql::GuestConnection for Adapter {
     async fn query(
         &self,
         statement: _rt::String,
-        params: _rt::Vec<exports::spin::mysql::mysql::ParameterValue>,
+        params: _rt::Vec<exports::spin::mysql3_2_0::mysql::ParameterValue>,
+    ) -> Result<
+        (
+            _rt::Vec<exports::spin::mysql3_2_0::mysql::Column>,
+            wit_bindgen::rt::async_support::StreamReader<exports::spin::mysql3_2_0::mysql::Row>,
+            wit_bindgen::rt::async_support::FutureReader<
+                Result<(), exports::spin::mysql3_2_0::mysql::Error>,
+            >,
+        ),
+        exports::spin::mysql3_2_0::mysql::Error,
+    > {
+        unreachable!()
+    }
+
+    #[allow(unused_variables)]
+    #[allow(async_fn_in_trait)]
+    async fn query_with_options(
+        &self,
+        statement: _rt::String,
+        params: _rt::Vec<exports::spin::mysql3_2_0::mysql::ParameterValue>,
+        options: exports::spin::mysql3_2_0::mysql::QueryOptions,
+    ) -> Result<
+        (
+            _rt::Vec<exports::spin::mysql3_2_0::mysql::Column>,
+            wit_bindgen::rt::async_support::StreamReader<exports::spin::mysql3_2_0::mysql::Row>,
+            wit_bindgen::rt::async_support::FutureReader<
+                Result<(), exports::spin::mysql3_2_0::mysql::Error>,
+            >,
+        ),
+        exports::spin::mysql3_2_0::mysql::Error,
+    > {
+        unreachable!()
+    }
+
+    #[allow(unused_variables)]
+    #[allow(async_fn_in_trait)]
+    async fn execute(
+        &self,
+        statement: _rt::String,
+        params: _rt::Vec<exports::spin::mysql3_2_0::mysql::ParameterValue>,
+    ) -> Result<(), exports::spin::mysql3_2_0::mysql::Error> {
+        unreachable!()
+    }
+
+    #[allow(unused_variables)]
+    #[allow(async_fn_in_trait)]
+    async fn begin_transaction(
+        &self,
+    ) -> Result<
+        exports::spin::mysql3_2_0::mysql::Transaction,
+        exports::spin::mysql3_2_0::mysql::Error,
+    > {
+        unreachable!()
+    }
+}
+impl exports::spin::mysql3_2_0::mysql::GuestTransaction for Adapter {
+    #[allow(unused_variables)]
+    #[allow(async_fn_in_trait)]
+    async fn query(
+        &self,
+        statement: _rt::String,
+        params: _rt::Vec<exports::spin::mysql3_2_0::mysql::ParameterValue>,
+    ) -> Result<
+        (
+            _rt::Vec<exports::spin::mysql3_2_0::mysql::Column>,
+            wit_bindgen::rt::async_support::StreamReader<exports::spin::mysql3_2_0::mysql::Row>,
+            wit_bindgen::rt::async_support::FutureReader<
+                Result<(), exports::spin::mysql3_2_0::mysql::Error>,
+            >,
+        ),
+        exports::spin::mysql3_2_0::mysql::Error,
+    > {
+        unreachable!()
+    }
+
+    #[allow(unused_variables)]
+    #[allow(async_fn_in_trait)]
+    async fn query_with_options(
+        &self,
+        statement: _rt::String,
+        params: _rt::Vec<exports::spin::mysql3_2_0::mysql::ParameterValue>,
+        options: exports::spin::mysql3_2_0::mysql::QueryOptions,
     ) -> Result<
         (
-            _rt::Vec<exports::spin::mysql::mysql::Column>,
-            wit_bindgen::rt::async_support::StreamReader<exports::spin::mysql::mysql::Row>,
+            _rt::Vec<exports::spin::mysql3_2_0::mysql::Column>,
+            wit_bindgen::rt::async_support::StreamReader<exports::spin::mysql3_2_0::mysql::Row>,
             wit_bindgen::rt::async_support::FutureReader<
-                Result<(), exports::spin::mysql::mysql::Error>,
+                Result<(), exports::spin::mysql3_2_0::mysql::Error>,
             >,
         ),
-        exports::spin::mysql::mysql::Error,
+        exports::spin::mysql3_2_0::mysql::Error,
     > {
         unreachable!()
     }
@@ -268,15 +349,31 @@ impl exports::spin::mysql::mysql::GuestConnection for Adapter {
     async fn execute(
         &self,
         statement: _rt::String,
-        params: _rt::Vec<exports::spin::mysql::mysql::ParameterValue>,
-    ) -> Result<(), exports::spin::mysql::mysql::Error> {
+        params: _rt::Vec<exports::spin::mysql3_2_0::mysql::ParameterValue>,
+    ) -> Result<(), exports::spin::mysql3_2_0::mysql::Error> {
+        unreachable!()
+    }
+
+    #[allow(unused_variables)]
+    #[allow(async_fn_in_trait)]
+    async fn commit(
+        this: exports::spin::mysql3_2_0::mysql::Transaction,
+    ) -> Result<(), exports::spin::mysql3_2_0::mysql::Error> {
+        unreachable!()
+    }
+
+    #[allow(unused_variables)]
+    #[allow(async_fn_in_trait)]
+    async fn rollback(
+        this: exports::spin::mysql3_2_0::mysql::Transaction,
+    ) -> Result<(), exports::spin::mysql3_2_0::mysql::Error> {
         unreachable!()
     }
 }
 impl exports::spin::postgres3_0_0::postgres::Guest for Adapter {
     type Connection = Adapter;
 }
-impl exports::spin::postgres4_2_0::postgres::GuestConnectionBuilder for Adapter {
+impl exports::spin::postgres4_3_0::postgres::GuestConnectionBuilder for Adapter {
     #[allow(unused_variables)]
     #[allow(async_fn_in_trait)]
     fn new(address: _rt::String) -> Self {
@@ -287,7 +384,7 @@ impl exports::spin::postgres4_2_0::postgres::GuestConnectionBuilder for Adapter
     fn set_ca_root(
         &self,
         certificate: _rt::String,
-    ) -> Result<(), exports::spin::postgres4_2_0::postgres::Error> {
+    ) -> Result<(), exports::spin::postgres4_3_0::postgres::Error> {
         Ok(())
     }
     #[allow(unused_variables)]
@@ -295,10 +392,10 @@ impl exports::spin::postgres4_2_0::postgres::GuestConnectionBuilder for Adapter
     fn build(
         &self,
     ) -> Result<
-        exports::spin::postgres4_2_0::postgres::Connection,
-        exports::spin::postgres4_2_0::postgres::Error,
+        exports::spin::postgres4_3_0::postgres::Connection,
+        exports::spin::postgres4_3_0::postgres::Error,
     > {
-        Err(exports::spin::postgres4_2_0::postgres::Error::Other(
+        Err(exports::spin::postgres4_3_0::postgres::Error::Other(
             format_deny_error("spin:postgres/postgres"),
         ))
     }
@@ -307,24 +404,24 @@ impl exports::spin::postgres4_2_0::postgres::GuestConnectionBuilder for Adapter
     async fn build_async(
         &self,
     ) -> Result<
-        exports::spin::postgres4_2_0::postgres::Connection,
-        exports::spin::postgres4_2_0::postgres::Error,
+        exports::spin::postgres4_3_0::postgres::Connection,
+        exports::spin::postgres4_3_0::postgres::Error,
     > {
-        Err(exports::spin::postgres4_2_0::postgres::Error::Other(
+        Err(exports::spin::postgres4_3_0::postgres::Error::Other(
             format_deny_error("spin:postgres/postgres"),
         ))
     }
 }
-impl exports::spin::postgres4_2_0::postgres::GuestConnection for Adapter {
+impl exports::spin::postgres4_3_0::postgres::GuestConnection for Adapter {
     #[allow(unused_variables)]
     #[allow(async_fn_in_trait)]
     fn open(
         address: _rt::String,
     ) -> Result<
-        exports::spin::postgres4_2_0::postgres::Connection,
-        exports::spin::postgres4_2_0::postgres::Error,
+        exports::spin::postgres4_3_0::postgres::Connection,
+        exports::spin::postgres4_3_0::postgres::Error,
     > {
-        Err(exports::spin::postgres4_2_0::postgres::Error::Other(
+        Err(exports::spin::postgres4_3_0::postgres::Error::Other(
             format_deny_error("spin:postgres/postgres"),
         ))
     }
@@ -333,10 +430,10 @@ impl exports::spin::postgres4_2_0::postgres::GuestConnection for Adapter {
     async fn open_async(
         address: _rt::String,
     ) -> Result<
-        exports::spin::postgres4_2_0::postgres::Connection,
-        exports::spin::postgres4_2_0::postgres::Error,
+        exports::spin::postgres4_3_0::postgres::Connection,
+        exports::spin::postgres4_3_0::postgres::Error,
     > {
-        Err(exports::spin::postgres4_2_0::postgres::Error::Other(
+        Err(exports::spin::postgres4_3_0::postgres::Error::Other(
             format_deny_error("spin:postgres/postgres"),
         ))
     }
@@ -345,10 +442,72 @@ impl exports::spin::postgres4_2_0::postgres::GuestConnection for Adapter {
     fn query(
         &self,
         statement: _rt::String,
-        params: _rt::Vec<exports::spin::postgres4_2_0::postgres::ParameterValue>,
+        params: _rt::Vec<exports::spin::postgres4_3_0::postgres::ParameterValue>,
+    ) -> Result<
+        exports::spin::postgres4_3_0::postgres::RowSet,
+        exports::spin::postgres4_3_0::postgres::Error,
+    > {
+        unreachable!()
+    }
+    #[allow(unused_variables)]
+    #[allow(async_fn_in_trait)]
+    async fn query_async(
+        &self,
+        statement: _rt::String,
+        params: _rt::Vec<exports::spin::postgres4_3_0::postgres::ParameterValue>,
+    ) -> Result<
+        (
+            _rt::Vec<exports::spin::postgres4_3_0::postgres::Column>,
+            wit_bindgen::rt::async_support::StreamReader<
+                exports::spin::postgres4_3_0::postgres::Row,
+            >,
+            wit_bindgen::rt::async_support::FutureReader<
+                Result<(), exports::spin::postgres4_3_0::postgres::Error>,
+            >,
+        ),
+        exports::spin::postgres4_3_0::postgres::Error,
+    > {
+        unreachable!()
+    }
+    #[allow(unused_variables)]
+    #[allow(async_fn_in_trait)]
+    fn execute(
+        &self,
+        statement: _rt::String,
+        params: _rt::Vec<exports::spin::postgres4_3_0::postgres::ParameterValue>,
+    ) -> Result<u64, exports::spin::postgres4_3_0::postgres::Error> {
+        unreachable!()
+    }
+    #[allow(unused_variables)]
+    #[allow(async_fn_in_trait)]
+    async fn execute_async(
+        &self,
+        statement: _rt::String,
+        params: _rt::Vec<exports::spin::postgres4_3_0::postgres::ParameterValue>,
+    ) -> Result<u64, exports::spin::postgres4_3_0::postgres::Error> {
+        unreachable!()
+    }
+    #[allow(unused_variables)]
+    #[allow(async_fn_in_trait)]
+    fn begin_transaction(
+        &self,
+    ) -> Result<
+        exports::spin::postgres4_3_0::postgres::Transaction,
+        exports::spin::postgres4_3_0::postgres::Error,
+    > {
+        unreachable!()
+    }
+}
+impl exports::spin::postgres4_3_0::postgres::GuestTransaction for Adapter {
+    #[allow(unused_variables)]
+    #[allow(async_fn_in_trait)]
+    fn query(
+        &self,
+        statement: _rt::String,
+        params: _rt::Vec<exports::spin::postgres4_3_0::postgres::ParameterValue>,
     ) -> Result<
-        exports::spin::postgres4_2_0::postgres::RowSet,
-        exports::spin::postgres4_2_0::postgres::Error,
+        exports::spin::postgres4_3_0::postgres::RowSet,
+        exports::spin::postgres4_3_0::postgres::Error,
     > {
         unreachable!()
     }
@@ -357,18 +516,18 @@ impl exports::spin::postgres4_2_0::postgres::GuestConnection for Adapter {
     async fn query_async(
         &self,
         statement: _rt::String,
-        params: _rt::Vec<exports::spin::postgres4_2_0::postgres::ParameterValue>,
+        params: _rt::Vec<exports::spin::postgres4_3_0::postgres::ParameterValue>,
     ) -> Result<
         (
-            _rt::Vec<exports::spin::postgres4_2_0::postgres::Column>,
+            _rt::Vec<exports::spin::postgres4_3_0::postgres::Column>,
             wit_bindgen::rt::async_support::StreamReader<
-                exports::spin::postgres4_2_0::postgres::Row,
+                exports::spin::postgres4_3_0::postgres::Row,
             >,
             wit_bindgen::rt::async_support::FutureReader<
-                Result<(), exports::spin::postgres4_2_0::postgres::Error>,
+                Result<(), exports::spin::postgres4_3_0::postgres::Error>,
             >,
         ),
-        exports::spin::postgres4_2_0::postgres::Error,
+        exports::spin::postgres4_3_0::postgres::Error,
     > {
         unreachable!()
     }
@@ -377,8 +536,8 @@ impl exports::spin::postgres4_2_0::postgres::GuestConnection for Adapter {
     fn execute(
         &self,
         statement: _rt::String,
-        params: _rt::Vec<exports::spin::postgres4_2_0::postgres::ParameterValue>,
-    ) -> Result<u64, exports::spin::postgres4_2_0::postgres::Error> {
+        params: _rt::Vec<exports::spin::postgres4_3_0::postgres::ParameterValue>,
+    ) -> Result<u64, exports::spin::postgres4_3_0::postgres::Error> {
         unreachable!()
     }
     #[allow(unused_variables)]
@@ -386,22 +545,38 @@ impl exports::spin::postgres4_2_0::postgres::GuestConnection for Adapter {
     async fn execute_async(
         &self,
         statement: _rt::String,
-        params: _rt::Vec<exports::spin::postgres4_2_0::postgres::ParameterValue>,
-    ) -> Result<u64, exports::spin::postgres4_2_0::postgres::Error> {
+        params: _rt::Vec<exports::spin::postgres4_3_0::postgres::ParameterValue>,
+    ) -> Result<u64, exports::spin::postgres4_3_0::postgres::Error> {
+        unreachable!()
+    }
+    #[allow(unused_variables)]
+    #[allow(async_fn_in_trait)]
+    fn commit(
+        this: exports::spin::postgres4_3_0::postgres::Transaction,
+    ) -> Result<(), exports::spin::postgres4_3_0::postgres::Error> {
+        unreachable!()
+    }
+    #[allow(unused_variables)]
+    #[allow(async_fn_in_trait)]
+    fn rollback(
+        this: exports::spin::postgres4_3_0::postgres::Transaction,
+    ) -> Result<(), exports::spin::postgres4_3_0::postgres::Error> {
         unreachable!()
     }
 }
-impl exports::spin::postgres4_2_0::postgres::Guest for Adapter {
+impl exports::spin::postgres4_3_0::postgres::Guest for Adapter {
     type ConnectionBuilder = Adapter;
     type Connection = Adapter;
+    type Transaction = Adapter;
 }
-impl exports::spin::redis::redis::GuestConnection for Adapter {
+impl exports::spin::redis3_1_0::redis::GuestConnection for Adapter {
     #[allow(unused_variables)]
     #[allow(async_fn_in_trait)]
     async fn open(
         address: _rt::String,
-    ) -> Result<exports::spin::redis::redis::Connection, exports::spin::redis::redis::Error> {
-        Err(exports::spin::redis::redis::Error::Other(
+    ) -> Result<exports::spin::redis3_1_0::redis::Connection, exports::spin::redis3_1_0::redis::Error>
+    {
+        Err(exports::spin::redis3_1_0::redis::Error::Other(
             format_deny_error("spin:redis/redis"),
         ))
     }
@@ -410,8 +585,8 @@ impl exports::spin::redis::redis::GuestConnection for Adapter {
     async fn publish(
         &self,
         channel: _rt::String,
-        payload: exports::spin::redis::redis::Payload,
-    ) -> Result<(), exports::spin::redis::redis::Error> {
+        payload: exports::spin::redis3_1_0::redis::Payload,
+    ) -> Result<(), exports::spin::redis3_1_0::redis::Error> {
         unreachable!()
     }
     #[allow(unused_variables)]
@@ -419,8 +594,10 @@ impl exports::spin::redis::redis::GuestConnection for Adapter {
     async fn get(
         &self,
         key: _rt::String,
-    ) -> Result<Option<exports::spin::redis::redis::Payload>, exports::spin::redis::redis::Error>
-    {
+    ) -> Result<
+        Option<exports::spin::redis3_1_0::redis::Payload>,
+        exports::spin::redis3_1_0::redis::Error,
+    > {
         unreachable!()
     }
     #[allow(unused_variables)]
@@ -428,13 +605,13 @@ impl exports::spin::redis::redis::GuestConnection for Adapter {
     async fn set(
         &self,
         key: _rt::String,
-        value: exports::spin::redis::redis::Payload,
-    ) -> Result<(), exports::spin::redis::redis::Error> {
+        value: exports::spin::redis3_1_0::redis::Payload,
+    ) -> Result<(), exports::spin::redis3_1_0::redis::Error> {
         unreachable!()
     }
     #[allow(unused_variables)]
     #[allow(async_fn_in_trait)]
-    async fn incr(&self, key: _rt::String) -> Result<i64, exports::spin::redis::redis::Error> {
+    async fn incr(&self, key: _rt::String) -> Result<i64, exports::spin::redis3_1_0::redis::Error> {
         unreachable!()
     }
     #[allow(unused_variables)]
@@ -442,7 +619,7 @@ impl exports::spin::redis::redis::GuestConnection for Adapter {
     async fn del(
         &self,
         keys: _rt::Vec<_rt::String>,
-    ) -> Result<u32, exports::spin::redis::redis::Error> {
+    ) -> Result<u32, exports::spin::redis3_1_0::redis::Error> {
         unreachable!()
     }
     #[allow(unused_variables)]
@@ -451,7 +628,7 @@ impl exports::spin::redis::redis::GuestConnection for Adapter {
         &self,
         key: _rt::String,
         values: _rt::Vec<_rt::String>,
-    ) -> Result<u32, exports::spin::redis::redis::Error> {
+    ) -> Result<u32, exports::spin::redis3_1_0::redis::Error> {
         unreachable!()
     }
     #[allow(unused_variables)]
@@ -459,7 +636,7 @@ impl exports::spin::redis::redis::GuestConnection for Adapter {
     async fn smembers(
         &self,
         key: _rt::String,
-    ) -> Result<_rt::Vec<_rt::String>, exports::spin::redis::redis::Error> {
+    ) -> Result<_rt::Vec<_rt::String>, exports::spin::redis3_1_0::redis::Error> {
         unreachable!()
     }
     #[allow(unused_variables)]
@@ -468,7 +645,7 @@ impl exports::spin::redis::redis::GuestConnection for Adapter {
         &self,
         key: _rt::String,
         values: _rt::Vec<_rt::String>,
-    ) -> Result<u32, exports::spin::redis::redis::Error> {
+    ) -> Result<u32, exports::spin::redis3_1_0::redis::Error> {
         unreachable!()
     }
     #[allow(unused_variables)]
@@ -476,16 +653,174 @@ impl exports::spin::redis::redis::GuestConnection for Adapter {
     async fn execute(
         &self,
         command: _rt::String,
-        arguments: _rt::Vec<exports::spin::redis::redis::RedisParameter>,
+        arguments: _rt::Vec<exports::spin::redis3_1_0::redis::RedisParameter>,
+    ) -> Result<
+        _rt::Vec<exports::spin::redis3_1_0::redis::RedisResult>,
+        exports::spin::redis3_1_0::redis::Error,
+    > {
+        unreachable!()
+    }
+    #[allow(unused_variables)]
+    #[allow(async_fn_in_trait)]
+    async fn execute_pipeline(
+        &self,
+        commands: _rt::Vec<exports::spin::redis3_1_0::redis::Command>,
+        atomic: bool,
+    ) -> Result<
+        _rt::Vec<_rt::Vec<exports::spin::redis3_1_0::redis::RedisResult>>,
+        exports::spin::redis3_1_0::redis::Error,
+    > {
+        unreachable!()
+    }
+    #[allow(unused_variables)]
+    #[allow(async_fn_in_trait)]
+    async fn eval_script(
+        &self,
+        script: _rt::String,
+        keys: _rt::Vec<_rt::String>,
+        arguments: _rt::Vec<exports::spin::redis3_1_0::redis::RedisParameter>,
+    ) -> Result<
+        _rt::Vec<exports::spin::redis3_1_0::redis::RedisResult>,
+        exports::spin::redis3_1_0::redis::Error,
+    > {
+        unreachable!()
+    }
+    #[allow(unused_variables)]
+    #[allow(async_fn_in_trait)]
+    async fn hget(
+        &self,
+        key: _rt::String,
+        field: _rt::String,
+    ) -> Result<
+        Option<exports::spin::redis3_1_0::redis::Payload>,
+        exports::spin::redis3_1_0::redis::Error,
+    > {
+        unreachable!()
+    }
+    #[allow(unused_variables)]
+    #[allow(async_fn_in_trait)]
+    async fn hset(
+        &self,
+        key: _rt::String,
+        fields: _rt::Vec<(_rt::String, exports::spin::redis3_1_0::redis::Payload)>,
+    ) -> Result<u32, exports::spin::redis3_1_0::redis::Error> {
+        unreachable!()
+    }
+    #[allow(unused_variables)]
+    #[allow(async_fn_in_trait)]
+    async fn hgetall(
+        &self,
+        key: _rt::String,
+    ) -> Result<
+        _rt::Vec<(_rt::String, exports::spin::redis3_1_0::redis::Payload)>,
+        exports::spin::redis3_1_0::redis::Error,
+    > {
+        unreachable!()
+    }
+    #[allow(unused_variables)]
+    #[allow(async_fn_in_trait)]
+    async fn hdel(
+        &self,
+        key: _rt::String,
+        fields: _rt::Vec<_rt::String>,
+    ) -> Result<u32, exports::spin::redis3_1_0::redis::Error> {
+        unreachable!()
+    }
+    #[allow(unused_variables)]
+    #[allow(async_fn_in_trait)]
+    async fn hincrby(
+        &self,
+        key: _rt::String,
+        field: _rt::String,
+        increment: i64,
+    ) -> Result<i64, exports::spin::redis3_1_0::redis::Error> {
+        unreachable!()
+    }
+    #[allow(unused_variables)]
+    #[allow(async_fn_in_trait)]
+    async fn zadd(
+        &self,
+        key: _rt::String,
+        members: _rt::Vec<(f64, _rt::String)>,
+    ) -> Result<u32, exports::spin::redis3_1_0::redis::Error> {
+        unreachable!()
+    }
+    #[allow(unused_variables)]
+    #[allow(async_fn_in_trait)]
+    async fn zrem(
+        &self,
+        key: _rt::String,
+        members: _rt::Vec<_rt::String>,
+    ) -> Result<u32, exports::spin::redis3_1_0::redis::Error> {
+        unreachable!()
+    }
+    #[allow(unused_variables)]
+    #[allow(async_fn_in_trait)]
+    async fn zscore(
+        &self,
+        key: _rt::String,
+        member: _rt::String,
+    ) -> Result<Option<f64>, exports::spin::redis3_1_0::redis::Error> {
+        unreachable!()
+    }
+    #[allow(unused_variables)]
+    #[allow(async_fn_in_trait)]
+    async fn zcard(
+        &self,
+        key: _rt::String,
+    ) -> Result<u64, exports::spin::redis3_1_0::redis::Error> {
+        unreachable!()
+    }
+    #[allow(unused_variables)]
+    #[allow(async_fn_in_trait)]
+    async fn zrange(
+        &self,
+        key: _rt::String,
+        start: i64,
+        stop: i64,
+    ) -> Result<_rt::Vec<(_rt::String, f64)>, exports::spin::redis3_1_0::redis::Error> {
+        unreachable!()
+    }
+    #[allow(unused_variables)]
+    #[allow(async_fn_in_trait)]
+    async fn zrange_by_score(
+        &self,
+        key: _rt::String,
+        min: f64,
+        max: f64,
+    ) -> Result<_rt::Vec<(_rt::String, f64)>, exports::spin::redis3_1_0::redis::Error> {
+        unreachable!()
+    }
+}
+impl exports::spin::redis3_1_0::redis::GuestSubscription for Adapter {
+    #[allow(unused_variables)]
+    #[allow(async_fn_in_trait)]
+    async fn open(
+        address: _rt::String,
+        channels: _rt::Vec<_rt::String>,
+    ) -> Result<
+        exports::spin::redis3_1_0::redis::Subscription,
+        exports::spin::redis3_1_0::redis::Error,
+    > {
+        Err(exports::spin::redis3_1_0::redis::Error::Other(
+            format_deny_error("spin:redis/redis"),
+        ))
+    }
+    #[allow(unused_variables)]
+    #[allow(async_fn_in_trait)]
+    async fn receive(
+        &self,
+        timeout_ms: u32,
     ) -> Result<
-        _rt::Vec<exports::spin::redis::redis::RedisResult>,
-        exports::spin::redis::redis::Error,
+        Option<exports::spin::redis3_1_0::redis::Message>,
+        exports::spin::redis3_1_0::redis::Error,
     > {
         unreachable!()
     }
 }
-impl exports::spin::redis::redis::Guest for Adapter {
+impl exports::spin::redis3_1_0::redis::Guest for Adapter {
     type Connection = Adapter;
+    type Subscription = Adapter;
 }
 impl exports::spin::sqlite3_1_0::sqlite::GuestConnection for Adapter {
     #[allow(unused_variables)]
@@ -127,3 +127,102 @@ fn allow_list(inherits: InheritConfiguration) -> Vec<&'static str> {
 
     allow
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIT_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../wit");
+
+    /// Builds a component which imports the given interfaces from Spin's WIT.
+    fn component_importing(interfaces: &[&str]) -> Vec<u8> {
+        let mut resolve = wit_parser::Resolve::default();
+        resolve.push_dir(WIT_DIR).expect("should parse Spin WIT");
+        let imports: String = interfaces
+            .iter()
+            .map(|interface| format!("import {interface};\n"))
+            .collect();
+        let wit = format!("package test:dependency;\nworld dependency {{\n{imports}}}\n");
+        let package_id = resolve.push_str("test", &wit).expect("should parse WIT");
+        let world_id = resolve
+            .select_world(&[package_id], Some("dependency"))
+            .expect("should select world");
+
+        let mut wasm = wit_component::dummy_module(
+            &resolve,
+            world_id,
+            wit_parser::ManglingAndAbi::Legacy(wit_parser::LiftLowerAbi::Sync),
+        );
+        wit_component::embed_component_metadata(
+            &mut wasm,
+            &resolve,
+            world_id,
+            wit_component::StringEncoding::UTF8,
+        )
+        .expect("should embed component metadata");
+
+        let mut encoder = wit_component::ComponentEncoder::default()
+            .validate(true)
+            .module(&wasm)
+            .expect("should set module");
+        encoder.encode().expect("should encode component")
+    }
+
+    fn imports(component: &[u8]) -> Vec<String> {
+        let mut graph = CompositionGraph::new();
+        let package = Package::from_bytes("component", None, component, graph.types_mut())
+            .expect("should parse component");
+        graph.types()[package.ty()]
+            .imports
+            .keys()
+            .cloned()
+            .collect()
+    }
+
+    const OUTBOUND_INTERFACES: &[&str] = &[
+        "spin:mysql/mysql@3.2.0",
+        "spin:postgres/postgres@4.3.0",
+        "spin:redis/redis@3.1.0",
+    ];
+
+    #[test]
+    fn inherited_interfaces_are_not_denied() {
+        let interfaces = [OUTBOUND_INTERFACES, &["spin:variables/variables@3.0.0"]].concat();
+        let component = component_importing(&interfaces);
+
+        let plugged = apply_deny_adapter(
+            &component,
+            InheritConfiguration::Some(vec!["allowed_outbound_hosts".into()]),
+        )
+        .unwrap();
+
+        let imports = imports(&plugged);
+        for interface in OUTBOUND_INTERFACES {
+            assert!(
+                imports.iter().any(|import| import == interface),
+                "{interface} should still be imported, got {imports:?}"
+            );
+        }
+        assert!(
+            !imports
+                .iter()
+                .any(|import| import.starts_with("spin:variables/")),
+            "variables should be denied, got {imports:?}"
+        );
+    }
+
+    #[test]
+    fn interfaces_are_denied_unless_inherited() {
+        let component = component_importing(OUTBOUND_INTERFACES);
+
+        let plugged = apply_deny_adapter(&component, InheritConfiguration::None).unwrap();
+
+        let imports = imports(&plugged);
+        for interface in OUTBOUND_INTERFACES {
+            assert!(
+                !imports.iter().any(|import| import == interface),
+                "{interface} should be denied, got {imports:?}"
+            );
+        }
+    }
+}
@@ -40,10 +40,10 @@ const ALLOWED_OUTBOUND_HOSTS: &[&str] = &[
     "fermyon:spin/postgres@2.0.0",
     "fermyon:spin/redis@2.0.0",
     "spin:mqtt/mqtt@3.0.0",
-    "spin:mysql/mysql@3.0.0",
+    "spin:mysql/mysql@3.2.0",
     "spin:postgres/postgres@3.0.0",
-    "spin:postgres/postgres@4.2.0",
-    "spin:redis/redis@3.0.0",
+    "spin:postgres/postgres@4.3.0",
+    "spin:redis/redis@3.1.0",
     "wasi:http/client@0.3.0",
     "wasi:http/client@0.3.0-rc-2026-03-15",
     "wasi:http/outgoing-handler@0.2.6",
@@ -297,12 +297,15 @@ impl ConnectionSemaphore {
 /// topping out at 10000) would collapse every sample into the lowest bucket. These boundaries sit
 /// near typical alerting cutoffs (75%, 90%, 95%, 99%). Pass the result to `spin_telemetry::init`.
 ///
-/// The metric names here must match the identifiers used in the `histogram_f64!` calls above.
+/// The metric names here must match the identifiers used in the `histogram_f64!` calls above,
+/// plus `outbound_connection_pool_utilization`, which factors that pool their own connections
+/// record on the same scale.
 pub fn metric_histogram_buckets() -> Vec<spin_telemetry::HistogramBuckets> {
     let boundaries = vec![0.25, 0.5, 0.75, 0.9, 0.95, 0.99];
     [
         "outbound_connection_factor_utilization",
         "outbound_connection_global_utilization",
+        "outbound_connection_pool_utilization",
     ]
     .into_iter()
     .map(|metric_name| spin_telemetry::HistogramBuckets {
@@ -0,0 +1,1079 @@
+//! A shared cache for outbound HTTP responses.
+//!
+//! Follows the rules of [RFC 9111](https://www.rfc-editor.org/rfc/rfc9111)
+//! for shared caches: responses to `GET` requests are stored according to
+//! their `Cache-Control` and `Expires` headers, stored responses are selected
+//! according to their `Vary` header, and stale responses are revalidated with
+//! `If-None-Match` or `If-Modified-Since`. Responses are never served stale.
+//!
+//! Responses are held in memory and may also be persisted to a directory, as
+//! one JSON file per response, by a background thread so that requests never
+//! wait on the disk. Each component has its own cached responses,
+//! and responses to requests with credentials are only stored or served if
+//! they are explicitly `public`.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::{Arc, Mutex, mpsc},
+    task::{Context, Poll, ready},
+    thread::JoinHandle,
+    time::SystemTime,
+};
+
+use anyhow::Context as _;
+use bytes::Bytes;
+use http::{
+    HeaderMap, HeaderName, HeaderValue, Method, Response, StatusCode, Uri,
+    header::{
+        AGE, AUTHORIZATION, CACHE_CONTROL, CONTENT_LENGTH, COOKIE, DATE, ETAG, EXPIRES, IF_MATCH,
+        IF_MODIFIED_SINCE, IF_NONE_MATCH, IF_RANGE, IF_UNMODIFIED_SINCE, LAST_MODIFIED, SET_COOKIE,
+        TRANSFER_ENCODING, VARY,
+    },
+};
+use http_body::{Body, Frame, SizeHint};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::policy::HostPattern;
+
+/// The default maximum total size of cached response bodies.
+pub const DEFAULT_MAX_SIZE: u64 = 64 * 1024 * 1024;
+/// The default maximum size of a single cached response body.
+pub const DEFAULT_MAX_ENTRY_SIZE: u64 = 1024 * 1024;
+
+/// Configuration for the outbound HTTP response cache.
+#[derive(Clone, Debug)]
+pub struct CacheConfig {
+    /// Hosts whose responses may be cached.
+    pub hosts: Vec<HostPattern>,
+    /// The maximum total size of cached response bodies, in bytes.
+    pub max_size: u64,
+    /// The maximum size of a single cached response body, in bytes; larger
+    /// responses are not cached.
+    pub max_entry_size: u64,
+    /// If set, cached responses are persisted in this directory.
+    pub dir: Option<PathBuf>,
+}
+
+/// A shared cache of outbound HTTP responses.
+pub struct HttpCache {
+    hosts: Vec<HostPattern>,
+    max_size: u64,
+    max_entry_size: u64,
+    dir: Option<PathBuf>,
+    /// Persists responses to `dir`, if set.
+    writer: Option<DiskWriter>,
+    state: Mutex<CacheState>,
+}
+
+/// A view of an [`HttpCache`] for a single component; components never see
+/// each other's cached responses.
+#[derive(Clone)]
+pub(crate) struct ComponentCache {
+    cache: Arc<HttpCache>,
+    component_id: Arc<str>,
+}
+
+#[derive(Default)]
+struct CacheState {
+    /// Cached responses keyed by component ID and URL; there may be several
+    /// responses for a URL which vary by request headers.
+    entries: HashMap<(String, String), Vec<Entry>>,
+    /// The total size of all cached response bodies.
+    size: u64,
+    /// Incremented on each use, for least-recently-used eviction.
+    clock: u64,
+}
+
+/// The result of looking up a request in the cache.
+pub(crate) enum CacheLookup {
+    /// A fresh response which may be returned as-is.
+    Fresh(Response<Bytes>),
+    /// A stale response which must be revalidated by sending the request with
+    /// these additional conditional headers.
+    Stale(HeaderMap),
+    /// No usable response is cached.
+    Miss,
+}
+
+impl HttpCache {
+    /// Opens the cache described by the given config, loading any persisted
+    /// responses.
+    pub fn open(config: CacheConfig) -> anyhow::Result<Self> {
+        let CacheConfig {
+            hosts,
+            max_size,
+            max_entry_size,
+            dir,
+        } = config;
+        let writer = match &dir {
+            Some(dir) => {
+                std::fs::create_dir_all(dir).with_context(|| {
+                    format!("failed to create HTTP cache directory {}", dir.display())
+                })?;
+                Some(DiskWriter::spawn()?)
+            }
+            None => None,
+        };
+        let cache = Self {
+            hosts,
+            max_size,
+            max_entry_size,
+            dir,
+            writer,
+            state: Default::default(),
+        };
+        if let Some(dir) = &cache.dir {
+            cache.load(dir)?;
+        }
+        Ok(cache)
+    }
+
+    /// Returns a view of the cache for the given component.
+    pub(crate) fn for_component(self: &Arc<Self>, component_id: &str) -> ComponentCache {
+        ComponentCache {
+            cache: self.clone(),
+            component_id: component_id.into(),
+        }
+    }
+
+    /// Returns true if the cache applies to requests to the given URI.
+    pub(crate) fn applies_to(&self, uri: &Uri) -> bool {
+        uri.host()
+            .is_some_and(|host| self.hosts.iter().any(|pattern| pattern.matches(host)))
+    }
+
+    /// Returns true if the response to the given request may be served from,
+    /// or stored in, the cache.
+    ///
+    /// Requests with their own conditional headers bypass the cache.
+    pub(crate) fn is_cacheable_request(&self, method: &Method, headers: &HeaderMap) -> bool {
+        method == Method::GET
+            && !CacheControl::parse(headers).no_store
+            && ![
+                IF_MATCH,
+                IF_MODIFIED_SINCE,
+                IF_NONE_MATCH,
+                IF_RANGE,
+                IF_UNMODIFIED_SINCE,
+            ]
+            .iter()
+            .any(|name| headers.contains_key(name))
+    }
+
+    /// Returns true if a response with the given status and headers to a
+    /// request with the given headers may be stored.
+    ///
+    /// Responses to requests with credentials must be explicitly `public`.
+    pub(crate) fn is_storable(
+        &self,
+        request_headers: &HeaderMap,
+        status: StatusCode,
+        headers: &HeaderMap,
+    ) -> bool {
+        let cache_control = CacheControl::parse(headers);
+        let has_freshness = cache_control.s_maxage.is_some()
+            || cache_control.max_age.is_some()
+            || headers.contains_key(EXPIRES);
+        let has_validator = headers.contains_key(ETAG) || headers.contains_key(LAST_MODIFIED);
+        let too_large = content_length(headers).is_some_and(|len| len > self.max_entry_size);
+        is_cacheable_status(status)
+            && !cache_control.no_store
+            && !cache_control.private
+            && (cache_control.public || !has_credentials(request_headers))
+            && (has_freshness || has_validator)
+            && !vary_names(headers).any(|name| name == "*")
+            && !headers.contains_key(SET_COOKIE)
+            && !too_large
+    }
+
+    /// Looks up a cached response to the given request.
+    fn lookup(&self, component_id: &str, uri: &Uri, request_headers: &HeaderMap) -> CacheLookup {
+        let request_cache_control = CacheControl::parse(request_headers);
+        let credentials = has_credentials(request_headers);
+        let mut state = self.state.lock().unwrap();
+        let clock = state.tick();
+        let Some(entry) = state
+            .entries
+            .get_mut(&entry_key(component_id, uri))
+            .and_then(|entries| {
+                entries.iter_mut().find(|e| {
+                    // Responses to requests without credentials may not be
+                    // for everyone, so only public ones are served to
+                    // requests with credentials
+                    e.matches(request_headers)
+                        && (!credentials || CacheControl::parse(&e.header_map()).public)
+                })
+            })
+        else {
+            return CacheLookup::Miss;
+        };
+        entry.last_used = clock;
+
+        let headers = entry.header_map();
+        let age = entry.current_age();
+        let fresh = !CacheControl::parse(&headers).no_cache
+            && !request_cache_control.no_cache
+            && age < entry.freshness_lifetime(&headers)
+            && request_cache_control
+                .max_age
+                .is_none_or(|max_age| age <= max_age);
+        if fresh {
+            return CacheLookup::Fresh(entry.to_response(headers, age));
+        }
+
+        let mut conditional = HeaderMap::new();
+        if let Some(etag) = headers.get(ETAG) {
+            conditional.insert(IF_NONE_MATCH, etag.clone());
+        } else if let Some(last_modified) = headers.get(LAST_MODIFIED) {
+            conditional.insert(IF_MODIFIED_SINCE, last_modified.clone());
+        }
+        if conditional.is_empty() {
+            CacheLookup::Miss
+        } else {
+            CacheLookup::Stale(conditional)
+        }
+    }
+
+    /// Stores a response to the given request, if it is storable.
+    fn store(
+        &self,
+        component_id: &str,
+        uri: &Uri,
+        request_headers: &HeaderMap,
+        status: StatusCode,
+        headers: &HeaderMap,
+        body: Bytes,
+    ) {
+        if !self.is_storable(request_headers, status, headers)
+            || body.len() as u64 > self.max_entry_size
+        {
+            return;
+        }
+        let vary = vary_names(headers)
+            .map(|name| {
+                let value = request_headers
+                    .get(&name)
+                    .and_then(|value| value.to_str().ok())
+                    .map(ToOwned::to_owned);
+                (name, value)
+            })
+            .collect();
+        let entry = Entry {
+            component_id: component_id.to_owned(),
+            url: uri.to_string(),
+            vary,
+            status: status.as_u16(),
+            headers: header_pairs(headers),
+            body,
+            response_time: SystemTime::now(),
+            initial_age: age_header(headers),
+            last_used: 0,
+        };
+        self.persist(&entry);
+        self.insert(entry);
+    }
+
+    /// Updates the cached response to the given request with the headers of
+    /// a `304 Not Modified` response, returning the updated response.
+    fn revalidated(
+        &self,
+        component_id: &str,
+        uri: &Uri,
+        request_headers: &HeaderMap,
+        not_modified: &HeaderMap,
+    ) -> Option<Response<Bytes>> {
+        let mut state = self.state.lock().unwrap();
+        let clock = state.tick();
+        let entry = state
+            .entries
+            .get_mut(&entry_key(component_id, uri))?
+            .iter_mut()
+            .find(|e| e.matches(request_headers))?;
+
+        let mut headers = entry.header_map();
+        for name in not_modified.keys() {
+            if name == CONTENT_LENGTH || name == TRANSFER_ENCODING {
+                continue;
+            }
+            headers.remove(name);
+            for value in not_modified.get_all(name) {
+                headers.append(name, value.clone());
+            }
+        }
+        entry.headers = header_pairs(&headers);
+        entry.response_time = SystemTime::now();
+        entry.initial_age = age_header(not_modified);
+        entry.last_used = clock;
+        let response = entry.to_response(headers, entry.initial_age);
+        let entry = entry.clone();
+        drop(state);
+        self.persist(&entry);
+        Some(response)
+    }
+
+    /// Removes any cached responses for the given URI.
+    ///
+    /// Called after a successful request with an unsafe method, which may
+    /// have changed the resource.
+    fn invalidate(&self, component_id: &str, uri: &Uri) {
+        let entries = {
+            let mut state = self.state.lock().unwrap();
+            let Some(entries) = state.entries.remove(&entry_key(component_id, uri)) else {
+                return;
+            };
+            for entry in &entries {
+                state.size -= entry.body.len() as u64;
+            }
+            entries
+        };
+        for entry in &entries {
+            self.unpersist(entry);
+        }
+    }
+
+    fn insert(&self, entry: Entry) {
+        let evicted = self.insert_locked(entry);
+        for entry in &evicted {
+            self.unpersist(entry);
+        }
+    }
+
+    /// Adds an entry to the in-memory cache, returning the entries evicted to
+    /// make room for it.
+    fn insert_locked(&self, mut entry: Entry) -> Vec<Entry> {
+        let mut state = self.state.lock().unwrap();
+        entry.last_used = state.tick();
+        let size = entry.body.len() as u64;
+        let entries = state
+            .entries
+            .entry((entry.component_id.clone(), entry.url.clone()))
+            .or_default();
+        let replaced = entries
+            .iter()
+            .position(|e| e.vary == entry.vary)
+            .map(|idx| entries.swap_remove(idx));
+        entries.push(entry);
+        if let Some(replaced) = replaced {
+            state.size -= replaced.body.len() as u64;
+        }
+        state.size += size;
+
+        // Evict least recently used responses until under the size limit
+        let mut evicted = Vec::new();
+        while state.size > self.max_size {
+            let Some((key, idx)) = state
+                .entries
+                .iter()
+                .flat_map(|(key, entries)| {
+                    entries
+                        .iter()
+                        .enumerate()
+                        .map(move |(idx, e)| (e.last_used, key, idx))
+                })
+                .min()
+                .map(|(_, key, idx)| (key.clone(), idx))
+            else {
+                break;
+            };
+            let entries = state.entries.get_mut(&key).unwrap();
+            let entry = entries.swap_remove(idx);
+            if entries.is_empty() {
+                state.entries.remove(&key);
+            }
+            state.size -= entry.body.len() as u64;
+            evicted.push(entry);
+        }
+        evicted
+    }
+
+    fn load(&self, dir: &Path) -> anyhow::Result<()> {
+        let read_dir = std::fs::read_dir(dir)
+            .with_context(|| format!("failed to read HTTP cache directory {}", dir.display()))?;
+        for dir_entry in read_dir {
+            let path = dir_entry?.path();
+            if path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+            let entry = std::fs::read(&path)
+                .map_err(anyhow::Error::from)
+                .and_then(|json| Ok(serde_json::from_slice::<Entry>(&json)?));
+            match entry {
+                Ok(entry) => self.insert(entry),
+                Err(err) => {
+                    tracing::warn!(?err, path = %path.display(), "Ignoring invalid HTTP cache file");
+                    let _ = std::fs::remove_file(&path);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn persist(&self, entry: &Entry) {
+        if let (Some(writer), Some(path)) = (&self.writer, self.entry_path(entry)) {
+            writer.send(DiskOp::Write(path, entry.clone()));
+        }
+    }
+
+    fn unpersist(&self, entry: &Entry) {
+        if let (Some(writer), Some(path)) = (&self.writer, self.entry_path(entry)) {
+            writer.send(DiskOp::Remove(path));
+        }
+    }
+
+    fn entry_path(&self, entry: &Entry) -> Option<PathBuf> {
+        let mut hasher = Sha256::new();
+        hasher.update(&entry.component_id);
+        hasher.update([0]);
+        hasher.update(&entry.url);
+        for (name, value) in &entry.vary {
+            hasher.update([0]);
+            hasher.update(name);
+            hasher.update([0]);
+            hasher.update(value.as_deref().unwrap_or_default());
+        }
+        let file_name = format!("{:x}.json", hasher.finalize());
+        Some(self.dir.as_ref()?.join(file_name))
+    }
+}
+
+impl ComponentCache {
+    pub(crate) fn applies_to(&self, uri: &Uri) -> bool {
+        self.cache.applies_to(uri)
+    }
+
+    pub(crate) fn is_cacheable_request(&self, method: &Method, headers: &HeaderMap) -> bool {
+        self.cache.is_cacheable_request(method, headers)
+    }
+
+    pub(crate) fn is_storable(
+        &self,
+        request_headers: &HeaderMap,
+        status: StatusCode,
+        headers: &HeaderMap,
+    ) -> bool {
+        self.cache.is_storable(request_headers, status, headers)
+    }
+
+    /// Looks up a cached response to the given request.
+    pub(crate) fn lookup(&self, uri: &Uri, request_headers: &HeaderMap) -> CacheLookup {
+        self.cache.lookup(&self.component_id, uri, request_headers)
+    }
+
+    /// Stores a response to the given request, if it is storable.
+    pub(crate) fn store(
+        &self,
+        uri: &Uri,
+        request_headers: &HeaderMap,
+        status: StatusCode,
+        headers: &HeaderMap,
+        body: Bytes,
+    ) {
+        self.cache.store(
+            &self.component_id,
+            uri,
+            request_headers,
+            status,
+            headers,
+            body,
+        )
+    }
+
+    /// Updates the cached response to the given request with the headers of
+    /// a `304 Not Modified` response, returning the updated response.
+    pub(crate) fn revalidated(
+        &self,
+        uri: &Uri,
+        request_headers: &HeaderMap,
+        not_modified: &HeaderMap,
+    ) -> Option<Response<Bytes>> {
+        self.cache
+            .revalidated(&self.component_id, uri, request_headers, not_modified)
+    }
+
+    /// Removes any cached responses for the given URI.
+    pub(crate) fn invalidate(&self, uri: &Uri) {
+        self.cache.invalidate(&self.component_id, uri)
+    }
+}
+
+/// Writes and removes persisted responses on a dedicated thread, in the order
+/// they were requested.
+///
+/// Dropping the writer waits for the queued operations to finish.
+struct DiskWriter {
+    ops: Option<mpsc::Sender<DiskOp>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+enum DiskOp {
+    Write(PathBuf, Entry),
+    Remove(PathBuf),
+}
+
+impl DiskWriter {
+    fn spawn() -> anyhow::Result<Self> {
+        let (ops, rx) = mpsc::channel();
+        let thread = std::thread::Builder::new()
+            .name("http-cache-writer".into())
+            .spawn(move || {
+                for op in rx {
+                    match op {
+                        DiskOp::Write(path, entry) => {
+                            let res = serde_json::to_vec(&entry)
+                                .map_err(std::io::Error::from)
+                                .and_then(|json| std::fs::write(&path, json));
+                            if let Err(err) = res {
+                                tracing::warn!(?err, path = %path.display(), "Failed to persist cached HTTP response");
+                            }
+                        }
+                        DiskOp::Remove(path) => {
+                            let _ = std::fs::remove_file(path);
+                        }
+                    }
+                }
+            })
+            .context("failed to start HTTP cache writer")?;
+        Ok(Self {
+            ops: Some(ops),
+            thread: Some(thread),
+        })
+    }
+
+    fn send(&self, op: DiskOp) {
+        if let Some(ops) = &self.ops {
+            let _ = ops.send(op);
+        }
+    }
+}
+
+impl Drop for DiskWriter {
+    fn drop(&mut self) {
+        // Closing the channel ends the thread once the queue is drained
+        self.ops.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl CacheState {
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+}
+
+/// A cached response.
+#[derive(Clone, Serialize, Deserialize)]
+struct Entry {
+    /// The component which made the request.
+    #[serde(default)]
+    component_id: String,
+    url: String,
+    /// The request headers named by the response's `Vary` header and the
+    /// values they had in the request.
+    vary: Vec<(String, Option<String>)>,
+    status: u16,
+    headers: Vec<(String, String)>,
+    #[serde(with = "body_base64")]
+    body: Bytes,
+    /// When the response was received.
+    response_time: SystemTime,
+    /// The age of the response when it was received, in seconds.
+    initial_age: u64,
+    #[serde(skip)]
+    last_used: u64,
+}
+
+impl Entry {
+    /// Returns true if the given request headers match those this response
+    /// varies by.
+    fn matches(&self, request_headers: &HeaderMap) -> bool {
+        self.vary.iter().all(|(name, value)| {
+            request_headers
+                .get(name.as_str())
+                .and_then(|value| value.to_str().ok())
+                == value.as_deref()
+        })
+    }
+
+    fn header_map(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.headers {
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::try_from(name.as_str()),
+                HeaderValue::try_from(value.as_str()),
+            ) {
+                headers.append(name, value);
+            }
+        }
+        headers
+    }
+
+    /// The current age of the response, in seconds.
+    fn current_age(&self) -> u64 {
+        let resident_time = SystemTime::now()
+            .duration_since(self.response_time)
+            .unwrap_or_default();
+        self.initial_age + resident_time.as_secs()
+    }
+
+    /// How long the response is fresh for, in seconds.
+    fn freshness_lifetime(&self, headers: &HeaderMap) -> u64 {
+        let cache_control = CacheControl::parse(headers);
+        if let Some(max_age) = cache_control.s_maxage.or(cache_control.max_age) {
+            return max_age;
+        }
+        // Invalid `Expires` values mean the response has already expired
+        let expires = http_date(headers, EXPIRES).unwrap_or(SystemTime::UNIX_EPOCH);
+        let date = http_date(headers, DATE).unwrap_or(self.response_time);
+        expires
+            .duration_since(date)
+            .map(|lifetime| lifetime.as_secs())
+            .unwrap_or_default()
+    }
+
+    fn to_response(&self, mut headers: HeaderMap, age: u64) -> Response<Bytes> {
+        headers.insert(AGE, HeaderValue::from(age));
+        let mut response = Response::new(self.body.clone());
+        *response.status_mut() = StatusCode::from_u16(self.status).unwrap_or(StatusCode::OK);
+        *response.headers_mut() = headers;
+        response
+    }
+}
+
+/// The `Cache-Control` directives relevant to a shared cache.
+#[derive(Debug, Default)]
+struct CacheControl {
+    no_store: bool,
+    no_cache: bool,
+    private: bool,
+    public: bool,
+    max_age: Option<u64>,
+    s_maxage: Option<u64>,
+}
+
+impl CacheControl {
+    fn parse(headers: &HeaderMap) -> Self {
+        let mut cache_control = Self::default();
+        let directives = headers
+            .get_all(CACHE_CONTROL)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','));
+        for directive in directives {
+            let (name, arg) = match directive.split_once('=') {
+                Some((name, arg)) => (name, Some(arg.trim().trim_matches('"'))),
+                None => (directive, None),
+            };
+            let seconds = || arg.and_then(|arg| arg.parse().ok());
+            match name.trim().to_ascii_lowercase().as_str() {
+                "no-store" => cache_control.no_store = true,
+                "no-cache" => cache_control.no_cache = true,
+                "private" => cache_control.private = true,
+                "public" => cache_control.public = true,
+                // Invalid ages are treated as already stale
+                "max-age" => cache_control.max_age = Some(seconds().unwrap_or_default()),
+                "s-maxage" => cache_control.s_maxage = Some(seconds().unwrap_or_default()),
+                _ => {}
+            }
+        }
+        cache_control
+    }
+}
+
+fn entry_key(component_id: &str, uri: &Uri) -> (String, String) {
+    (component_id.to_owned(), uri.to_string())
+}
+
+/// Returns true if the given request headers carry credentials.
+fn has_credentials(request_headers: &HeaderMap) -> bool {
+    request_headers.contains_key(AUTHORIZATION) || request_headers.contains_key(COOKIE)
+}
+
+/// Returns true if responses with the given status are cacheable by default.
+fn is_cacheable_status(status: StatusCode) -> bool {
+    matches!(
+        status.as_u16(),
+        200 | 203 | 204 | 300 | 301 | 308 | 404 | 405 | 410 | 414 | 501
+    )
+}
+
+/// Returns the lowercased header names listed in the `Vary` header.
+fn vary_names(headers: &HeaderMap) -> impl Iterator<Item = String> + '_ {
+    headers
+        .get_all(VARY)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|name| name.trim().to_ascii_lowercase())
+        .filter(|name| !name.is_empty())
+}
+
+fn content_length(headers: &HeaderMap) -> Option<u64> {
+    headers.get(CONTENT_LENGTH)?.to_str().ok()?.parse().ok()
+}
+
+fn age_header(headers: &HeaderMap) -> u64 {
+    headers
+        .get(AGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+        .unwrap_or_default()
+}
+
+fn http_date(headers: &HeaderMap, name: HeaderName) -> Option<SystemTime> {
+    httpdate::parse_http_date(headers.get(name)?.to_str().ok()?).ok()
+}
+
+fn header_pairs(headers: &HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect()
+}
+
+mod body_base64 {
+    use bytes::Bytes;
+    use serde::{Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(body: &Bytes, serializer: S) -> Result<S::Ok, S::Error> {
+        spin_serde::base64::serialize(&Some(body.to_vec()), serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Bytes, D::Error> {
+        Ok(spin_serde::base64::deserialize(deserializer)?
+            .unwrap_or_default()
+            .into())
+    }
+}
+
+pin_project_lite::pin_project! {
+    /// A response body which stores the response in the cache once it has
+    /// been read in full.
+    pub(crate) struct CachingBody<B> {
+        #[pin]
+        body: B,
+        // `None` once the body has exceeded the maximum entry size or failed
+        buffered: Option<Vec<u8>>,
+        pending: Option<PendingStore>,
+    }
+}
+
+/// A response waiting for its body before being stored.
+pub(crate) struct PendingStore {
+    pub cache: ComponentCache,
+    pub uri: Uri,
+    pub request_headers: HeaderMap,
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+}
+
+impl<B> CachingBody<B> {
+    pub fn new(body: B, pending: PendingStore) -> Self {
+        Self {
+            body,
+            buffered: Some(Vec::new()),
+            pending: Some(pending),
+        }
+    }
+}
+
+impl<B: Body<Data = Bytes>> Body for CachingBody<B> {
+    type Data = Bytes;
+    type Error = B::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let mut this = self.project();
+        let frame = ready!(this.body.as_mut().poll_frame(cx));
+        match &frame {
+            Some(Ok(frame)) => {
+                if let (Some(data), Some(buffered)) = (frame.data_ref(), this.buffered.as_mut()) {
+                    let max_entry_size = this
+                        .pending
+                        .as_ref()
+                        .map_or(0, |p| p.cache.cache.max_entry_size);
+                    if (buffered.len() + data.len()) as u64 > max_entry_size {
+                        *this.buffered = None;
+                    } else {
+                        buffered.extend_from_slice(data);
+                    }
+                }
+            }
+            Some(Err(_)) => *this.buffered = None,
+            None => {}
+        }
+        if (frame.is_none() || this.body.is_end_stream())
+            && let (Some(buffered), Some(pending)) = (this.buffered.take(), this.pending.take())
+        {
+            pending.cache.store(
+                &pending.uri,
+                &pending.request_headers,
+                pending.status,
+                &pending.headers,
+                buffered.into(),
+            );
+        }
+        Poll::Ready(frame)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.body.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.body.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache(max_size: u64) -> ComponentCache {
+        Arc::new(
+            HttpCache::open(CacheConfig {
+                hosts: vec!["example.com".parse().unwrap()],
+                max_size,
+                max_entry_size: DEFAULT_MAX_ENTRY_SIZE,
+                dir: None,
+            })
+            .unwrap(),
+        )
+        .for_component("component")
+    }
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| {
+                (
+                    HeaderName::from_static(name),
+                    HeaderValue::from_static(value),
+                )
+            })
+            .collect()
+    }
+
+    fn store(cache: &ComponentCache, url: &'static str, request: &HeaderMap, response: &HeaderMap) {
+        cache.store(
+            &Uri::from_static(url),
+            request,
+            StatusCode::OK,
+            response,
+            Bytes::from_static(b"body"),
+        );
+    }
+
+    #[test]
+    fn serves_fresh_responses() {
+        let cache = cache(DEFAULT_MAX_SIZE);
+        let uri = Uri::from_static("http://example.com/config.json");
+        assert!(cache.applies_to(&uri));
+        assert!(!cache.applies_to(&Uri::from_static("http://other.com/")));
+
+        store(
+            &cache,
+            "http://example.com/config.json",
+            &HeaderMap::new(),
+            &headers(&[("cache-control", "public, max-age=60")]),
+        );
+        let CacheLookup::Fresh(resp) = cache.lookup(&uri, &HeaderMap::new()) else {
+            panic!("expected fresh response");
+        };
+        assert_eq!(resp.body().as_ref(), b"body");
+        assert_eq!(resp.headers()[AGE], "0");
+
+        // The request may demand revalidation
+        let no_cache = headers(&[("cache-control", "no-cache")]);
+        assert!(matches!(cache.lookup(&uri, &no_cache), CacheLookup::Miss));
+    }
+
+    #[test]
+    fn revalidates_stale_responses() {
+        let cache = cache(DEFAULT_MAX_SIZE);
+        let uri = Uri::from_static("http://example.com/config.json");
+        store(
+            &cache,
+            "http://example.com/config.json",
+            &HeaderMap::new(),
+            &headers(&[("cache-control", "no-cache"), ("etag", "\"v1\"")]),
+        );
+        let CacheLookup::Stale(conditional) = cache.lookup(&uri, &HeaderMap::new()) else {
+            panic!("expected stale response");
+        };
+        assert_eq!(conditional[IF_NONE_MATCH], "\"v1\"");
+
+        let resp = cache
+            .revalidated(&uri, &HeaderMap::new(), &headers(&[("x-updated", "yes")]))
+            .unwrap();
+        assert_eq!(resp.body().as_ref(), b"body");
+        assert_eq!(resp.headers()["x-updated"], "yes");
+        assert_eq!(resp.headers()[ETAG], "\"v1\"");
+    }
+
+    #[test]
+    fn selects_responses_by_vary() {
+        let cache = cache(DEFAULT_MAX_SIZE);
+        let uri = Uri::from_static("http://example.com/");
+        store(
+            &cache,
+            "http://example.com/",
+            &headers(&[("accept", "application/json")]),
+            &headers(&[("cache-control", "max-age=60"), ("vary", "Accept")]),
+        );
+        let json = headers(&[("accept", "application/json")]);
+        assert!(matches!(cache.lookup(&uri, &json), CacheLookup::Fresh(_)));
+        let html = headers(&[("accept", "text/html")]);
+        assert!(matches!(cache.lookup(&uri, &html), CacheLookup::Miss));
+    }
+
+    #[test]
+    fn does_not_store_uncacheable_responses() {
+        let cache = cache(DEFAULT_MAX_SIZE);
+        for response in [
+            headers(&[("cache-control", "no-store, max-age=60")]),
+            headers(&[("cache-control", "private, max-age=60")]),
+            headers(&[("cache-control", "max-age=60"), ("vary", "*")]),
+            headers(&[("cache-control", "max-age=60"), ("set-cookie", "a=b")]),
+            headers(&[("content-type", "text/plain")]),
+        ] {
+            assert!(
+                !cache.is_storable(&HeaderMap::new(), StatusCode::OK, &response),
+                "{response:?}"
+            );
+        }
+        let cacheable = headers(&[("cache-control", "max-age=60")]);
+        assert!(!cache.is_storable(
+            &HeaderMap::new(),
+            StatusCode::INTERNAL_SERVER_ERROR,
+            &cacheable
+        ));
+
+        assert!(!cache.is_cacheable_request(&Method::POST, &HeaderMap::new()));
+        assert!(
+            !cache.is_cacheable_request(&Method::GET, &headers(&[("if-none-match", "\"v1\"")]))
+        );
+    }
+
+    #[test]
+    fn only_public_responses_are_shared_with_credentials() {
+        let cache = cache(DEFAULT_MAX_SIZE);
+        let uri = Uri::from_static("http://example.com/");
+        let fresh = headers(&[("cache-control", "max-age=60")]);
+        let public = headers(&[("cache-control", "public, max-age=60")]);
+        for credentials in [
+            headers(&[("authorization", "Bearer secret")]),
+            headers(&[("cookie", "session=secret")]),
+        ] {
+            assert!(!cache.is_storable(&credentials, StatusCode::OK, &fresh));
+            assert!(cache.is_storable(&credentials, StatusCode::OK, &public));
+        }
+
+        // A response to a request without credentials isn't served to one with
+        // credentials unless it is public
+        let credentials = headers(&[("authorization", "Bearer secret")]);
+        store(&cache, "http://example.com/", &HeaderMap::new(), &fresh);
+        assert!(matches!(
+            cache.lookup(&uri, &credentials),
+            CacheLookup::Miss
+        ));
+        store(&cache, "http://example.com/", &HeaderMap::new(), &public);
+        assert!(matches!(
+            cache.lookup(&uri, &credentials),
+            CacheLookup::Fresh(_)
+        ));
+    }
+
+    #[test]
+    fn components_do_not_share_responses() {
+        let cache = cache(DEFAULT_MAX_SIZE);
+        let other = cache.cache.for_component("other");
+        let uri = Uri::from_static("http://example.com/");
+        store(
+            &cache,
+            "http://example.com/",
+            &HeaderMap::new(),
+            &headers(&[("cache-control", "public, max-age=60")]),
+        );
+        assert!(matches!(
+            cache.lookup(&uri, &HeaderMap::new()),
+            CacheLookup::Fresh(_)
+        ));
+        assert!(matches!(
+            other.lookup(&uri, &HeaderMap::new()),
+            CacheLookup::Miss
+        ));
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        // Room for two four-byte bodies
+        let cache = cache(8);
+        let fresh = headers(&[("cache-control", "max-age=60")]);
+        store(&cache, "http://example.com/a", &HeaderMap::new(), &fresh);
+        store(&cache, "http://example.com/b", &HeaderMap::new(), &fresh);
+        cache.lookup(&Uri::from_static("http://example.com/a"), &HeaderMap::new());
+        store(&cache, "http://example.com/c", &HeaderMap::new(), &fresh);
+
+        let cached = |url| {
+            matches!(
+                cache.lookup(&Uri::from_static(url), &HeaderMap::new()),
+                CacheLookup::Fresh(_)
+            )
+        };
+        assert!(cached("http://example.com/a"));
+        assert!(!cached("http://example.com/b"));
+        assert!(cached("http://example.com/c"));
+
+        cache.invalidate(&Uri::from_static("http://example.com/a"));
+        assert!(!cached("http://example.com/a"));
+    }
+
+    #[test]
+    fn persists_responses() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = CacheConfig {
+            hosts: vec!["example.com".parse().unwrap()],
+            max_size: DEFAULT_MAX_SIZE,
+            max_entry_size: DEFAULT_MAX_ENTRY_SIZE,
+            dir: Some(dir.path().to_owned()),
+        };
+        let cache = Arc::new(HttpCache::open(config.clone()).unwrap()).for_component("component");
+        store(
+            &cache,
+            "http://example.com/",
+            &HeaderMap::new(),
+            &headers(&[("cache-control", "max-age=60")]),
+        );
+        drop(cache);
+
+        let cache = Arc::new(HttpCache::open(config).unwrap()).for_component("component");
+        assert!(matches!(
+            cache.lookup(&Uri::from_static("http://example.com/"), &HeaderMap::new()),
+            CacheLookup::Fresh(_)
+        ));
+    }
+
+    #[test]
+    fn expires_sets_freshness() {
+        let cache = cache(DEFAULT_MAX_SIZE);
+        let uri = Uri::from_static("http://example.com/");
+        store(
+            &cache,
+            "http://example.com/",
+            &HeaderMap::new(),
+            &headers(&[("expires", "Thu, 01 Jan 1970 00:00:00 GMT")]),
+        );
+        assert!(matches!(
+            cache.lookup(&uri, &HeaderMap::new()),
+            CacheLookup::Miss
+        ));
+    }
+}
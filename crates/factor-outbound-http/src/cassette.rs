@@ -0,0 +1,426 @@
+//! Recording and replaying of outbound HTTP requests for hermetic testing.
+//!
+//! A cassette file holds one JSON-encoded interaction (a request and its
+//! response) per line.
+
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use anyhow::Context as _;
+use bytes::Bytes;
+use http::{HeaderMap, HeaderName, Request, Response};
+use http_body_util::{BodyExt, Full};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use spin_world::async_trait;
+
+use crate::{
+    ErrorCode, HttpResult,
+    intercept::{HyperBody, InterceptOutcome, InterceptRequest, OutboundHttpInterceptor},
+};
+
+/// Whether a [`Cassette`] records outbound requests or replays recorded
+/// responses.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CassetteMode {
+    /// Send requests to the network and record them with their responses.
+    Record,
+    /// Serve recorded responses without touching the network.
+    Replay,
+}
+
+/// The parts of a request that must be equal for a recorded interaction to
+/// match it.
+#[derive(Clone, Debug)]
+pub struct RequestMatcher {
+    /// Match the request method.
+    pub method: bool,
+    /// Match the full request URL.
+    pub url: bool,
+    /// Match the values of these request headers.
+    pub headers: Vec<HeaderName>,
+    /// Match a hash of the request body.
+    pub body: bool,
+}
+
+impl Default for RequestMatcher {
+    fn default() -> Self {
+        Self {
+            method: true,
+            url: true,
+            headers: vec![],
+            body: false,
+        }
+    }
+}
+
+/// Configuration for recording or replaying outbound HTTP requests.
+#[derive(Clone, Debug)]
+pub struct CassetteConfig {
+    /// The path of the cassette file.
+    pub path: PathBuf,
+    /// Whether to record or replay.
+    pub mode: CassetteMode,
+    /// How replayed requests are matched to recorded interactions.
+    pub matcher: RequestMatcher,
+}
+
+/// Records outbound HTTP interactions to, or replays them from, a cassette file.
+///
+/// In replay mode this acts as an [`OutboundHttpInterceptor`] which completes
+/// every request with a recorded response, failing requests which match no
+/// recorded interaction.
+pub struct Cassette {
+    path: PathBuf,
+    matcher: RequestMatcher,
+    state: Mutex<CassetteState>,
+}
+
+enum CassetteState {
+    Record(File),
+    Replay {
+        interactions: Vec<Interaction>,
+        played: Vec<bool>,
+    },
+}
+
+impl Cassette {
+    /// Opens the cassette described by the given config.
+    ///
+    /// In record mode, any existing cassette file is truncated, with a warning.
+    pub fn open(config: CassetteConfig) -> anyhow::Result<Self> {
+        let CassetteConfig {
+            path,
+            mode,
+            matcher,
+        } = config;
+        let state = match mode {
+            CassetteMode::Record => {
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent).with_context(|| {
+                        format!("failed to create cassette directory {}", parent.display())
+                    })?;
+                }
+                if std::fs::metadata(&path).is_ok_and(|meta| meta.len() > 0) {
+                    terminal::warn!(
+                        "Overwriting existing outbound HTTP cassette {}",
+                        path.display()
+                    );
+                }
+                let file = File::create(&path).with_context(|| {
+                    format!("failed to create cassette file {}", path.display())
+                })?;
+                CassetteState::Record(file)
+            }
+            CassetteMode::Replay => {
+                let interactions = read_interactions(&path)?;
+                let played = vec![false; interactions.len()];
+                CassetteState::Replay {
+                    interactions,
+                    played,
+                }
+            }
+        };
+        Ok(Self {
+            path,
+            matcher,
+            state: Mutex::new(state),
+        })
+    }
+
+    /// Returns true if this cassette is recording.
+    pub fn is_recording(&self) -> bool {
+        matches!(*self.state.lock().unwrap(), CassetteState::Record(_))
+    }
+
+    /// Records an interaction.
+    pub(crate) fn record(&self, request: &Request<Bytes>, response: &Response<Bytes>) {
+        let interaction = Interaction {
+            request: self.recorded_request(request),
+            response: RecordedResponse {
+                status: response.status().as_u16(),
+                headers: header_pairs(response.headers()),
+                body: Some(response.body().to_vec()),
+            },
+        };
+        let mut state = self.state.lock().unwrap();
+        let CassetteState::Record(file) = &mut *state else {
+            return;
+        };
+        let res = serde_json::to_writer(&mut *file, &interaction)
+            .map_err(std::io::Error::from)
+            .and_then(|()| file.write_all(b"\n"));
+        if let Err(err) = res {
+            tracing::error!(
+                ?err,
+                path = %self.path.display(),
+                "Failed to record outbound HTTP interaction"
+            );
+        }
+    }
+
+    /// Returns the recorded response for the given request, if any.
+    fn replay(&self, request: &Request<Bytes>) -> Option<Response<HyperBody>> {
+        let recorded = self.recorded_request(request);
+        let mut state = self.state.lock().unwrap();
+        let CassetteState::Replay {
+            interactions,
+            played,
+        } = &mut *state
+        else {
+            return None;
+        };
+        let matching = interactions
+            .iter()
+            .enumerate()
+            .filter(|(_, interaction)| self.matches(&interaction.request, &recorded))
+            .map(|(idx, _)| idx)
+            .collect::<Vec<_>>();
+        // Replay matching interactions in recorded order, repeating the last
+        // one once all have been played.
+        let idx = matching
+            .iter()
+            .copied()
+            .find(|&idx| !played[idx])
+            .or(matching.last().copied())?;
+        played[idx] = true;
+        interactions[idx].response.to_response()
+    }
+
+    fn recorded_request(&self, request: &Request<Bytes>) -> RecordedRequest {
+        // Only the headers used for matching are recorded, to avoid writing
+        // credentials into cassette files.
+        let headers = self
+            .matcher
+            .headers
+            .iter()
+            .filter_map(|name| {
+                let value = request.headers().get(name)?.to_str().ok()?;
+                Some((name.to_string(), value.to_string()))
+            })
+            .collect();
+        RecordedRequest {
+            method: request.method().to_string(),
+            url: request.uri().to_string(),
+            headers,
+            body_sha256: format!("{:x}", Sha256::digest(request.body())),
+        }
+    }
+
+    fn matches(&self, recorded: &RecordedRequest, request: &RecordedRequest) -> bool {
+        (!self.matcher.method || recorded.method == request.method)
+            && (!self.matcher.url || recorded.url == request.url)
+            && self.matcher.headers.iter().all(|name| {
+                recorded.headers.get(name.as_str()) == request.headers.get(name.as_str())
+            })
+            && (!self.matcher.body || recorded.body_sha256 == request.body_sha256)
+    }
+}
+
+#[async_trait]
+impl OutboundHttpInterceptor for Cassette {
+    async fn intercept(&self, request: InterceptRequest) -> HttpResult<InterceptOutcome> {
+        if self.is_recording() {
+            return Ok(InterceptOutcome::Continue(request));
+        }
+        let (parts, body) = request.into_hyper_request().into_parts();
+        let body = body.collect().await?.to_bytes();
+        let request = Request::from_parts(parts, body);
+        match self.replay(&request) {
+            Some(resp) => Ok(InterceptOutcome::Complete(resp)),
+            None => {
+                let msg = format!(
+                    "no interaction recorded in cassette {} matches outbound request {} {}",
+                    self.path.display(),
+                    request.method(),
+                    request.uri(),
+                );
+                tracing::error!("{msg}");
+                Err(ErrorCode::InternalError(Some(msg)).into())
+            }
+        }
+    }
+}
+
+fn read_interactions(path: &Path) -> anyhow::Result<Vec<Interaction>> {
+    let file = File::open(path)
+        .with_context(|| format!("failed to open cassette file {}", path.display()))?;
+    BufReader::new(file)
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, Ok(line) if line.trim().is_empty()))
+        .map(|(idx, line)| {
+            let line = line?;
+            serde_json::from_str(&line).with_context(|| {
+                format!(
+                    "invalid interaction on line {} of cassette file {}",
+                    idx + 1,
+                    path.display()
+                )
+            })
+        })
+        .collect()
+}
+
+fn header_pairs(headers: &HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect()
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Interaction {
+    request: RecordedRequest,
+    response: RecordedResponse,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RecordedRequest {
+    method: String,
+    url: String,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    body_sha256: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RecordedResponse {
+    status: u16,
+    #[serde(default)]
+    headers: Vec<(String, String)>,
+    #[serde(default, with = "spin_serde::base64")]
+    body: Option<Vec<u8>>,
+}
+
+impl RecordedResponse {
+    fn to_response(&self) -> Option<Response<HyperBody>> {
+        let mut builder = Response::builder().status(self.status);
+        for (name, value) in &self.headers {
+            builder = builder.header(name, value);
+        }
+        let body = Full::new(Bytes::from(self.body.clone().unwrap_or_default()))
+            .map_err(|never| match never {})
+            .boxed_unsync();
+        builder
+            .body(body)
+            .inspect_err(|err| tracing::error!(?err, "Invalid recorded response"))
+            .ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: &str, url: &str, accept: &str, body: &'static str) -> Request<Bytes> {
+        Request::builder()
+            .method(method)
+            .uri(url)
+            .header("accept", accept)
+            .body(Bytes::from_static(body.as_bytes()))
+            .unwrap()
+    }
+
+    fn response(body: &'static str) -> Response<Bytes> {
+        Response::builder()
+            .status(200)
+            .header("content-type", "text/plain")
+            .body(Bytes::from_static(body.as_bytes()))
+            .unwrap()
+    }
+
+    async fn body_of(resp: Response<HyperBody>) -> Bytes {
+        resp.into_body().collect().await.unwrap().to_bytes()
+    }
+
+    #[tokio::test]
+    async fn records_and_replays() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("cassettes/test.jsonl");
+        let matcher = RequestMatcher {
+            headers: vec![HeaderName::from_static("accept")],
+            body: true,
+            ..Default::default()
+        };
+
+        let recorder = Cassette::open(CassetteConfig {
+            path: path.clone(),
+            mode: CassetteMode::Record,
+            matcher: matcher.clone(),
+        })?;
+        assert!(recorder.is_recording());
+        let req = request("POST", "https://example.com/a", "text/plain", "one");
+        recorder.record(&req, &response("first"));
+        recorder.record(&req, &response("second"));
+        drop(recorder);
+
+        let player = Cassette::open(CassetteConfig {
+            path,
+            mode: CassetteMode::Replay,
+            matcher,
+        })?;
+        assert!(!player.is_recording());
+
+        // Matching interactions are replayed in order, then the last repeats
+        for expected in ["first", "second", "second"] {
+            let resp = player.replay(&req).expect("should match");
+            assert_eq!(resp.status(), 200);
+            assert_eq!(resp.headers()["content-type"], "text/plain");
+            assert_eq!(body_of(resp).await, expected);
+        }
+
+        // Any differing matched part prevents a match
+        for req in [
+            request("PUT", "https://example.com/a", "text/plain", "one"),
+            request("POST", "https://example.com/b", "text/plain", "one"),
+            request("POST", "https://example.com/a", "text/html", "one"),
+            request("POST", "https://example.com/a", "text/plain", "two"),
+        ] {
+            assert!(player.replay(&req).is_none(), "{req:?}");
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unmatched_replay_fails() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("empty.jsonl");
+        std::fs::write(&path, "")?;
+        let player = Cassette::open(CassetteConfig {
+            path,
+            mode: CassetteMode::Replay,
+            matcher: Default::default(),
+        })?;
+        let req: InterceptRequest = Request::get("https://example.com/")
+            .body(Vec::new())
+            .unwrap()
+            .into();
+        let Err(err) = player.intercept(req).await else {
+            panic!("expected unmatched request to fail");
+        };
+        let err = err.downcast()?;
+        assert!(
+            matches!(&err, ErrorCode::InternalError(Some(msg)) if msg.contains("GET https://example.com/")),
+            "{err:?}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn missing_replay_cassette_fails() {
+        Cassette::open(CassetteConfig {
+            path: "does-not-exist.jsonl".into(),
+            mode: CassetteMode::Replay,
+            matcher: Default::default(),
+        })
+        .err()
+        .expect("expected missing cassette to fail");
+    }
+}
@@ -0,0 +1,222 @@
+use std::sync::Arc;
+
+use anyhow::Context as _;
+use http::{HeaderName, HeaderValue};
+use spin_expressions::{ProviderResolver as ExpressionResolver, Template};
+use spin_world::async_trait;
+use wasmtime_wasi_http::p2::HttpResult;
+
+use crate::{
+    ErrorCode,
+    intercept::{InterceptOutcome, InterceptRequest, OutboundHttpInterceptor},
+    policy::HostPattern,
+};
+
+/// Headers injected by the host into outbound requests to matching hosts.
+#[derive(Clone, Debug)]
+pub struct HeaderRule {
+    /// The host(s) this rule applies to.
+    pub hosts: Vec<HostPattern>,
+    /// The headers to set, replacing any set by the guest.
+    ///
+    /// Values may reference variables, e.g. `Bearer {{ api_key }}`.
+    pub headers: Vec<(HeaderName, String)>,
+}
+
+/// The hosts a rule applies to and the headers it injects.
+type InjectionRule = (Vec<HostPattern>, Vec<(HeaderName, Template)>);
+
+/// Injects headers into outbound requests according to [`HeaderRule`]s.
+///
+/// Header values are resolved by the host, so credentials never enter guest
+/// memory. Injection happens after `allowed_outbound_hosts` checks; a request
+/// is only ever given the headers of rules matching its own host. Redirects
+/// which the host follows on the guest's behalf must not leave the origin of
+/// a request with injected headers; see [`HeaderInjector::allows_redirect`].
+pub struct HeaderInjector {
+    rules: Vec<InjectionRule>,
+    /// Resolves variables referenced by header values.
+    resolver: Option<Arc<ExpressionResolver>>,
+}
+
+impl HeaderInjector {
+    pub fn new(
+        rules: Vec<HeaderRule>,
+        resolver: Option<Arc<ExpressionResolver>>,
+    ) -> anyhow::Result<Self> {
+        let rules = rules
+            .into_iter()
+            .map(|rule| {
+                let headers = rule
+                    .headers
+                    .into_iter()
+                    .map(|(name, value)| {
+                        let template = Template::new(value)
+                            .with_context(|| format!("invalid value for header {name:?}"))?;
+                        Ok((name, template))
+                    })
+                    .collect::<anyhow::Result<_>>()?;
+                Ok((rule.hosts, headers))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self { rules, resolver })
+    }
+
+    /// Returns true if a request to `from` may be automatically redirected to
+    /// `to` without sending its injected headers, if any, to another origin.
+    pub(crate) fn allows_redirect(&self, from: &reqwest::Url, to: &reqwest::Url) -> bool {
+        from.origin() == to.origin()
+            || from
+                .host_str()
+                .is_none_or(|host| self.headers_for(host).next().is_none())
+    }
+
+    /// Returns the headers to inject into requests to the given host.
+    fn headers_for<'a>(
+        &'a self,
+        host: &'a str,
+    ) -> impl Iterator<Item = &'a (HeaderName, Template)> {
+        self.rules
+            .iter()
+            .filter(move |(hosts, _)| hosts.iter().any(|pattern| pattern.matches(host)))
+            .flat_map(|(_, headers)| headers)
+    }
+
+    async fn resolve(
+        &self,
+        name: &HeaderName,
+        template: &Template,
+    ) -> Result<HeaderValue, ErrorCode> {
+        // Literal values don't need any variables
+        let resolver = self.resolver.clone().unwrap_or_default();
+        let value = resolver.resolve_template(template).await.map_err(|err| {
+            tracing::error!(
+                %err, header = %name, "error.type" = "injected_header_unresolved",
+                "Error resolving injected outbound HTTP header"
+            );
+            ErrorCode::InternalError(Some(format!("failed to resolve header {name}")))
+        })?;
+        let mut value = HeaderValue::try_from(value).map_err(|_| {
+            tracing::error!(
+                header = %name, "error.type" = "injected_header_invalid",
+                "Resolved injected outbound HTTP header is not a valid header value"
+            );
+            ErrorCode::InternalError(Some(format!("invalid value for header {name}")))
+        })?;
+        // Keep injected values, which are typically credentials, out of logs
+        value.set_sensitive(true);
+        Ok(value)
+    }
+}
+
+#[async_trait]
+impl OutboundHttpInterceptor for HeaderInjector {
+    async fn intercept(&self, mut request: InterceptRequest) -> HttpResult<InterceptOutcome> {
+        let Some(host) = request.uri().host().map(str::to_owned) else {
+            return Ok(InterceptOutcome::Continue(request));
+        };
+        for (name, template) in self.headers_for(&host) {
+            let value = self.resolve(name, template).await?;
+            request.headers_mut().insert(name.clone(), value);
+        }
+        Ok(InterceptOutcome::Continue(request))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn injector() -> HeaderInjector {
+        HeaderInjector::new(
+            vec![
+                HeaderRule {
+                    hosts: vec!["api.example.com".parse().unwrap()],
+                    headers: vec![(http::header::AUTHORIZATION, "Bearer secret".into())],
+                },
+                HeaderRule {
+                    hosts: vec!["*.example.com".parse().unwrap()],
+                    headers: vec![(HeaderName::from_static("x-team"), "spin".into())],
+                },
+            ],
+            None,
+        )
+        .unwrap()
+    }
+
+    async fn intercept(injector: &HeaderInjector, uri: &str) -> HttpResult<http::HeaderMap> {
+        let request = http::Request::builder()
+            .uri(uri)
+            .header("authorization", "from-guest")
+            .body(Vec::new())
+            .unwrap();
+        match injector.intercept(request.into()).await? {
+            InterceptOutcome::Continue(request) => Ok(request.headers().clone()),
+            InterceptOutcome::Complete(_) => panic!("request should not be completed"),
+        }
+    }
+
+    #[tokio::test]
+    async fn injects_headers_for_matching_hosts() {
+        let injector = injector();
+
+        let headers = intercept(&injector, "https://api.example.com/v1")
+            .await
+            .unwrap();
+        assert_eq!(headers["authorization"], "Bearer secret");
+        assert!(headers["authorization"].is_sensitive());
+        assert_eq!(headers["x-team"], "spin");
+
+        let headers = intercept(&injector, "https://www.example.com/")
+            .await
+            .unwrap();
+        assert_eq!(headers["authorization"], "from-guest");
+        assert_eq!(headers["x-team"], "spin");
+
+        let headers = intercept(&injector, "https://example.org/").await.unwrap();
+        assert_eq!(headers["authorization"], "from-guest");
+        assert!(!headers.contains_key("x-team"));
+    }
+
+    #[test]
+    fn cross_origin_redirects_are_not_allowed_with_injected_headers() {
+        let injector = injector();
+        let url = |s: &str| reqwest::Url::parse(s).unwrap();
+
+        let api = url("https://api.example.com/v1");
+        assert!(injector.allows_redirect(&api, &url("https://api.example.com/v2")));
+        assert!(!injector.allows_redirect(&api, &url("https://evil.example.org/")));
+        assert!(!injector.allows_redirect(&api, &url("http://api.example.com/v1")));
+
+        let other = url("https://example.org/");
+        assert!(injector.allows_redirect(&other, &url("https://example.net/")));
+    }
+
+    #[tokio::test]
+    async fn unresolvable_headers_fail_requests() {
+        let injector = HeaderInjector::new(
+            vec![HeaderRule {
+                hosts: vec!["*".parse().unwrap()],
+                headers: vec![(http::header::AUTHORIZATION, "{{ api_key }}".into())],
+            }],
+            None,
+        )
+        .unwrap();
+        intercept(&injector, "https://example.com/")
+            .await
+            .unwrap_err();
+    }
+
+    #[test]
+    fn invalid_templates_are_rejected() {
+        HeaderInjector::new(
+            vec![HeaderRule {
+                hosts: vec!["*".parse().unwrap()],
+                headers: vec![(http::header::AUTHORIZATION, "{{ unclosed".into())],
+            }],
+            None,
+        )
+        .err()
+        .unwrap();
+    }
+}
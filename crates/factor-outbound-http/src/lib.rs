@@ -1,4 +1,9 @@
+pub mod cache;
+pub mod cassette;
+pub mod inject;
 pub mod intercept;
+pub mod policy;
+pub mod proxy;
 pub mod runtime_config;
 mod spin;
 mod wasi;
@@ -9,18 +14,28 @@ pub mod wasi_2026_03_15;
 use std::{net::SocketAddr, sync::Arc};
 
 use anyhow::Context;
+use cache::{ComponentCache, HttpCache};
+use cassette::Cassette;
 use http::{
     HeaderValue, Uri,
     uri::{Authority, Parts, PathAndQuery, Scheme},
 };
+use inject::HeaderInjector;
 use intercept::OutboundHttpInterceptor;
+use policy::HostPolicies;
+use proxy::Proxy;
 use runtime_config::RuntimeConfig;
 use spin_factor_otel::OtelFactorState;
 use spin_factor_outbound_networking::{
     ComponentTlsClientConfigs, ConnectionSemaphore, OutboundNetworkingFactor,
+    audit::EgressAudit,
     build_connection_semaphore,
-    config::{allowed_hosts::OutboundAllowedHosts, blocked_networks::BlockedNetworks},
+    config::{
+        allowed_hosts::OutboundAllowedHosts, blocked_networks::BlockedNetworks,
+        dns_overrides::DnsOverrides,
+    },
 };
+use spin_factor_variables::VariablesFactor;
 use spin_factors::{
     ConfigureAppContext, Factor, FactorData, PrepareContext, RuntimeFactors, SelfInstanceBuilder,
     anyhow,
@@ -56,6 +71,33 @@ impl Factor for OutboundHttpFactor {
     ) -> anyhow::Result<Self::AppState> {
         let config = ctx.take_runtime_config().unwrap_or_default();
         let networking = ctx.app_state::<OutboundNetworkingFactor>().ok();
+        let cassette = config
+            .cassette
+            .map(Cassette::open)
+            .transpose()
+            .context("failed to open outbound HTTP cassette")?
+            .map(Arc::new);
+        // Proxy credentials and injected headers may reference variables
+        let resolver = ctx
+            .app_state::<VariablesFactor>()
+            .ok()
+            .map(|variables| variables.expression_resolver().clone());
+        let proxy = config
+            .proxy
+            .map(|proxy| Arc::new(Proxy::new(proxy, resolver.clone())));
+        let header_injector = if config.inject_headers.is_empty() {
+            None
+        } else {
+            let injector = HeaderInjector::new(config.inject_headers, resolver)
+                .context("invalid outbound HTTP header injection")?;
+            Some(Arc::new(injector))
+        };
+        let cache = config
+            .cache
+            .map(HttpCache::open)
+            .transpose()
+            .context("failed to open outbound HTTP cache")?
+            .map(Arc::new);
 
         Ok(AppState {
             wasi_http_clients: wasi::HttpClients::new(config.connection_pooling_enabled),
@@ -66,6 +108,11 @@ impl Factor for OutboundHttpFactor {
                 config.max_concurrent_connections,
                 config.wait_timeout,
             ),
+            host_policies: HostPolicies::new(config.host_policies),
+            cassette,
+            proxy,
+            cache,
+            header_injector,
         })
     }
 
@@ -77,6 +124,8 @@ impl Factor for OutboundHttpFactor {
         let allowed_hosts = outbound_networking.allowed_hosts();
         let blocked_networks = outbound_networking.blocked_networks();
         let component_tls_configs = outbound_networking.component_tls_configs();
+        let dns_overrides = outbound_networking.dns_overrides();
+        let egress_audit = outbound_networking.egress_audit();
         let otel = OtelFactorState::from_prepare_context(&mut ctx)?;
         Ok(InstanceState {
             wasi_http_ctx: WasiHttpCtx::new(),
@@ -90,6 +139,17 @@ impl Factor for OutboundHttpFactor {
                 wasi_http_clients: ctx.app_state().wasi_http_clients.clone(),
                 connection_pooling_enabled: ctx.app_state().connection_pooling_enabled,
                 semaphore: ctx.app_state().semaphore.clone(),
+                host_policies: ctx.app_state().host_policies.clone(),
+                cassette: ctx.app_state().cassette.clone(),
+                proxy: ctx.app_state().proxy.clone(),
+                cache: ctx
+                    .app_state()
+                    .cache
+                    .as_ref()
+                    .map(|cache| cache.for_component(ctx.app_component().id())),
+                header_injector: ctx.app_state().header_injector.clone(),
+                dns_overrides,
+                egress_audit,
                 otel,
             },
         })
@@ -122,6 +182,20 @@ struct InstanceHttpHooks {
     connection_pooling_enabled: bool,
     /// Semaphore to limit concurrent outbound connections.
     semaphore: ConnectionSemaphore,
+    /// Per-host timeout and retry policies.
+    host_policies: HostPolicies,
+    /// Records or replays outbound requests, if configured.
+    cassette: Option<Arc<Cassette>>,
+    /// Forward proxy for outbound requests, if configured.
+    proxy: Option<Arc<Proxy>>,
+    /// This component's view of the shared response cache, if configured.
+    cache: Option<ComponentCache>,
+    /// Injects configured headers into outbound requests.
+    header_injector: Option<Arc<HeaderInjector>>,
+    /// Static DNS overrides for outbound connections.
+    dns_overrides: DnsOverrides,
+    /// Records completed requests to the egress audit log, if configured.
+    egress_audit: Option<EgressAudit>,
     /// Manages access to the OtelFactor state.
     otel: OtelFactorState,
 }
@@ -209,6 +283,16 @@ pub struct AppState {
     connection_pooling_enabled: bool,
     /// Semaphore to limit concurrent outbound connections.
     semaphore: ConnectionSemaphore,
+    /// Per-host timeout and retry policies.
+    host_policies: HostPolicies,
+    /// Records or replays outbound requests, if configured.
+    cassette: Option<Arc<Cassette>>,
+    /// Forward proxy for outbound requests, if configured.
+    proxy: Option<Arc<Proxy>>,
+    /// Shared response cache, if configured.
+    cache: Option<Arc<HttpCache>>,
+    /// Injects configured headers into outbound requests.
+    header_injector: Option<Arc<HeaderInjector>>,
 }
 
 /// Removes IPs in the given [`BlockedNetworks`].
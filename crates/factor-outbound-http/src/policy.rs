@@ -0,0 +1,245 @@
+use std::{str::FromStr, sync::Arc, time::Duration};
+
+use anyhow::Context as _;
+use http::Method;
+use wasmtime_wasi_http::p2::types::OutgoingRequestConfig;
+
+use crate::ErrorCode;
+
+/// Timeout and retry policy applied to outbound requests to matching hosts.
+#[derive(Clone, Debug, Default)]
+pub struct HostPolicy {
+    /// The host(s) this policy applies to.
+    pub hosts: Vec<HostPattern>,
+    /// If set, caps the time allowed to establish a connection.
+    pub connect_timeout: Option<Duration>,
+    /// If set, caps the time allowed to wait for the response headers.
+    pub first_byte_timeout: Option<Duration>,
+    /// If set, caps the time allowed for the whole request, including any
+    /// retries and reading the response body.
+    pub total_timeout: Option<Duration>,
+    /// If set, failed requests with idempotent methods will be retried.
+    pub retry: Option<RetryPolicy>,
+}
+
+impl HostPolicy {
+    /// Caps the timeouts in the given config at the timeouts of this policy.
+    ///
+    /// Timeouts requested by the guest are only ever shortened, never
+    /// extended.
+    pub(crate) fn apply_timeouts(&self, config: &mut OutgoingRequestConfig) {
+        if let Some(connect_timeout) = self.connect_timeout {
+            config.connect_timeout = config.connect_timeout.min(connect_timeout);
+        }
+        if let Some(first_byte_timeout) = self.first_byte_timeout {
+            config.first_byte_timeout = config.first_byte_timeout.min(first_byte_timeout);
+        }
+    }
+
+    /// Returns the retry policy for a request with the given method, if
+    /// requests with that method may be retried.
+    pub(crate) fn retry_for(&self, method: &Method) -> Option<RetryPolicy> {
+        self.retry
+            .filter(|retry| retry.max_retries > 0 && method.is_idempotent())
+    }
+}
+
+/// Retry policy for outbound requests.
+///
+/// Requests are retried after connection errors and `5xx` responses, waiting
+/// with exponential backoff between attempts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The maximum number of retries after the initial attempt.
+    pub max_retries: u32,
+    /// The wait before the first retry; doubled for each subsequent retry.
+    pub initial_backoff: Duration,
+    /// The maximum wait between retries.
+    pub max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Returns the wait before the given retry (starting from 0).
+    pub fn backoff(&self, retry: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_backoff)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 2,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Returns true if a request failing with the given error may be retried.
+pub(crate) fn is_retryable_error(err: &ErrorCode) -> bool {
+    matches!(
+        err,
+        ErrorCode::DnsTimeout
+            | ErrorCode::ConnectionRefused
+            | ErrorCode::ConnectionTerminated
+            | ErrorCode::ConnectionTimeout
+            | ErrorCode::ConnectionReadTimeout
+            | ErrorCode::ConnectionWriteTimeout
+    )
+}
+
+/// A pattern matching host names.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HostPattern {
+    /// Matches any host (`*`).
+    Any,
+    /// Matches exactly the given host (`example.com`).
+    Exact(String),
+    /// Matches any subdomain of the given domain (`*.example.com`).
+    Subdomain(String),
+}
+
+impl HostPattern {
+    /// Returns true if the given host matches this pattern.
+    pub fn matches(&self, host: &str) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Exact(exact) => exact.eq_ignore_ascii_case(host),
+            Self::Subdomain(domain) => host
+                .to_ascii_lowercase()
+                .strip_suffix(domain.as_str())
+                .and_then(|sub| sub.strip_suffix('.'))
+                .is_some_and(|sub| !sub.is_empty()),
+        }
+    }
+}
+
+impl FromStr for HostPattern {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s == "*" {
+            return Ok(Self::Any);
+        }
+        let (pattern, host) = match s.strip_prefix("*.") {
+            Some(domain) => (Self::Subdomain(domain.to_ascii_lowercase()), domain),
+            None => (Self::Exact(s.to_ascii_lowercase()), s),
+        };
+        let authority: http::uri::Authority = host
+            .parse()
+            .with_context(|| format!("invalid host pattern {s:?}"))?;
+        anyhow::ensure!(
+            authority.port().is_none() && authority.as_str() == host && !host.contains('*'),
+            "invalid host pattern {s:?}; expected a host name, `*.<domain>`, or `*`"
+        );
+        Ok(pattern)
+    }
+}
+
+/// An ordered list of [`HostPolicy`]s shared by all instances of an app.
+#[derive(Clone, Debug, Default)]
+pub struct HostPolicies(Arc<[HostPolicy]>);
+
+impl HostPolicies {
+    pub fn new(policies: impl IntoIterator<Item = HostPolicy>) -> Self {
+        Self(policies.into_iter().collect())
+    }
+
+    /// Returns the first policy matching the given host, if any.
+    pub fn get(&self, host: &str) -> Option<&HostPolicy> {
+        self.0
+            .iter()
+            .find(|policy| policy.hosts.iter().any(|pattern| pattern.matches(host)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn host_patterns_match() {
+        let any: HostPattern = "*".parse().unwrap();
+        assert!(any.matches("example.com"));
+
+        let exact: HostPattern = "Example.com".parse().unwrap();
+        assert!(exact.matches("example.com"));
+        assert!(!exact.matches("api.example.com"));
+
+        let sub: HostPattern = "*.example.com".parse().unwrap();
+        assert!(sub.matches("api.example.com"));
+        assert!(sub.matches("a.b.example.com"));
+        assert!(!sub.matches("example.com"));
+        assert!(!sub.matches("badexample.com"));
+
+        "example.com:443".parse::<HostPattern>().unwrap_err();
+        "api.*.com".parse::<HostPattern>().unwrap_err();
+    }
+
+    #[test]
+    fn first_matching_policy_wins() {
+        let policies = HostPolicies::new([
+            HostPolicy {
+                hosts: vec!["api.example.com".parse().unwrap()],
+                connect_timeout: Some(Duration::from_secs(1)),
+                ..Default::default()
+            },
+            HostPolicy {
+                hosts: vec!["*.example.com".parse().unwrap()],
+                connect_timeout: Some(Duration::from_secs(2)),
+                ..Default::default()
+            },
+        ]);
+        let timeout = |host: &str| policies.get(host).and_then(|p| p.connect_timeout);
+        assert_eq!(timeout("api.example.com"), Some(Duration::from_secs(1)));
+        assert_eq!(timeout("www.example.com"), Some(Duration::from_secs(2)));
+        assert_eq!(timeout("example.org"), None);
+    }
+
+    #[test]
+    fn timeouts_are_only_shortened() {
+        let policy = HostPolicy {
+            connect_timeout: Some(Duration::from_secs(1)),
+            first_byte_timeout: Some(Duration::from_secs(60)),
+            ..Default::default()
+        };
+        let mut config = OutgoingRequestConfig {
+            use_tls: false,
+            connect_timeout: Duration::from_secs(10),
+            first_byte_timeout: Duration::from_secs(10),
+            between_bytes_timeout: Duration::from_secs(10),
+        };
+        policy.apply_timeouts(&mut config);
+        assert_eq!(config.connect_timeout, Duration::from_secs(1));
+        assert_eq!(config.first_byte_timeout, Duration::from_secs(10));
+    }
+
+    #[test]
+    fn retries_only_idempotent_methods() {
+        let policy = HostPolicy {
+            retry: Some(RetryPolicy::default()),
+            ..Default::default()
+        };
+        assert!(policy.retry_for(&Method::GET).is_some());
+        assert!(policy.retry_for(&Method::PUT).is_some());
+        assert!(policy.retry_for(&Method::POST).is_none());
+        assert!(policy.retry_for(&Method::PATCH).is_none());
+    }
+
+    #[test]
+    fn backoff_is_capped() {
+        let retry = RetryPolicy {
+            max_retries: 10,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(500),
+        };
+        assert_eq!(retry.backoff(0), Duration::from_millis(100));
+        assert_eq!(retry.backoff(1), Duration::from_millis(200));
+        assert_eq!(retry.backoff(2), Duration::from_millis(400));
+        assert_eq!(retry.backoff(3), Duration::from_millis(500));
+        assert_eq!(retry.backoff(40), Duration::from_millis(500));
+    }
+}
@@ -1,6 +1,11 @@
 #[cfg(feature = "spin-cli")]
 pub mod spin;
 
+use crate::{
+    cache::CacheConfig, cassette::CassetteConfig, inject::HeaderRule, policy::HostPolicy,
+    proxy::ProxyConfig,
+};
+
 /// Runtime configuration for outbound HTTP.
 #[derive(Debug)]
 pub struct RuntimeConfig {
@@ -10,6 +15,17 @@ pub struct RuntimeConfig {
     pub max_concurrent_connections: Option<usize>,
     /// If set, limits how long `acquire` will wait for a connection permit.
     pub wait_timeout: Option<std::time::Duration>,
+    /// Timeout and retry policies for outbound requests; the first policy
+    /// matching a request's host applies.
+    pub host_policies: Vec<HostPolicy>,
+    /// If set, outbound requests are recorded to or replayed from a cassette.
+    pub cassette: Option<CassetteConfig>,
+    /// If set, outbound requests are sent through a forward proxy.
+    pub proxy: Option<ProxyConfig>,
+    /// If set, responses from matching hosts are cached.
+    pub cache: Option<CacheConfig>,
+    /// Headers injected into outbound requests to matching hosts.
+    pub inject_headers: Vec<HeaderRule>,
 }
 
 impl Default for RuntimeConfig {
@@ -18,6 +34,11 @@ impl Default for RuntimeConfig {
             connection_pooling_enabled: true,
             max_concurrent_connections: None,
             wait_timeout: None,
+            host_policies: Vec::new(),
+            cassette: None,
+            proxy: None,
+            cache: None,
+            inject_headers: Vec::new(),
         }
     }
 }
@@ -1,6 +1,22 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::Context as _;
+use http::HeaderName;
 use serde::Deserialize;
 use spin_factors::runtime_config::toml::GetTomlValue;
 
+use crate::{
+    cache::{CacheConfig, DEFAULT_MAX_ENTRY_SIZE, DEFAULT_MAX_SIZE},
+    cassette::{CassetteConfig, CassetteMode, RequestMatcher},
+    inject::HeaderRule,
+    policy::{HostPolicy, RetryPolicy},
+    proxy::ProxyConfig,
+};
+
 /// Get the runtime configuration for outbound HTTP from a TOML table.
 ///
 /// Expects table to be in the format:
@@ -9,9 +25,50 @@ use spin_factors::runtime_config::toml::GetTomlValue;
 /// connection_pooling = true # optional, defaults to true
 /// max_connections = 10      # optional, defaults to unlimited; 0 = no connections allowed
 /// # max_concurrent_requests is deprecated, use max_connections instead
+///
+/// # Zero or more per-host policies; the first policy matching a host applies
+/// [[outbound_http.policy]]
+/// hosts = ["api.example.com", "*.example.org"]
+/// connect_timeout = "2s"       # optional
+/// first_byte_timeout = "10s"   # optional
+/// total_timeout = "30s"        # optional; includes retries and the response body
+/// retries = 3                  # optional, defaults to 0; only idempotent methods are retried
+/// retry_backoff = "100ms"      # optional, defaults to 100ms; doubled for each retry
+/// retry_max_backoff = "5s"     # optional, defaults to 5s
+///
+/// # Optionally record outbound requests to, or replay them from, a file
+/// [outbound_http.cassette]
+/// path = "cassettes/api.jsonl" # relative to the runtime config file
+/// mode = "replay"              # "record" or "replay"
+/// match_method = true          # optional, defaults to true
+/// match_url = true             # optional, defaults to true
+/// match_headers = ["accept"]   # optional, defaults to none
+/// match_body = true            # optional, defaults to false
+///
+/// # Optionally send outbound requests through a forward proxy
+/// [outbound_http.proxy]
+/// url = "http://proxy.example.com:3128"    # `http://`, `socks5://`, or `socks5h://`; not `https://`
+/// no_proxy = ["localhost", "*.internal"]   # optional; hosts to connect to directly
+/// username = "{{ proxy_username }}"        # optional; may reference variables
+/// password = "{{ proxy_password }}"        # optional; may reference variables
+///
+/// # Optionally cache responses from some hosts
+/// [outbound_http.cache]
+/// hosts = ["config.example.com"] # hosts whose responses may be cached
+/// max_size = 67108864            # optional, in bytes; defaults to 64 MiB
+/// max_entry_size = 1048576       # optional, in bytes; defaults to 1 MiB
+/// persist = true                 # optional, defaults to false; requires a state dir
+///
+/// # Zero or more sets of headers to inject into requests to matching hosts,
+/// # replacing any set by the guest; all matching sets apply
+/// [[outbound_http.inject_headers]]
+/// hosts = ["api.example.com"]
+/// headers = { authorization = "Bearer {{ api_key }}" } # values may reference variables
 /// ```
 pub fn config_from_table(
     table: &impl GetTomlValue,
+    state_dir: Option<PathBuf>,
+    runtime_config_dir: Option<&Path>,
 ) -> anyhow::Result<Option<super::RuntimeConfig>> {
     if let Some(outbound_http) = table.get("outbound_http") {
         let toml = outbound_http.clone().try_into::<OutboundHttpToml>()?;
@@ -34,10 +91,47 @@ pub fn config_from_table(
             (None, None) => None,
         };
 
+        let host_policies = toml
+            .policy
+            .into_iter()
+            .map(HostPolicyToml::into_host_policy)
+            .collect::<anyhow::Result<_>>()
+            .context("invalid `[[outbound_http.policy]]`")?;
+
+        let cassette = toml
+            .cassette
+            .map(|cassette| cassette.into_cassette_config(runtime_config_dir))
+            .transpose()
+            .context("invalid `[outbound_http.cassette]`")?;
+
+        let proxy = toml
+            .proxy
+            .map(ProxyToml::into_proxy_config)
+            .transpose()
+            .context("invalid `[outbound_http.proxy]`")?;
+
+        let cache = toml
+            .cache
+            .map(|cache| cache.into_cache_config(state_dir))
+            .transpose()
+            .context("invalid `[outbound_http.cache]`")?;
+
+        let inject_headers = toml
+            .inject_headers
+            .into_iter()
+            .map(InjectHeadersToml::into_header_rule)
+            .collect::<anyhow::Result<_>>()
+            .context("invalid `[[outbound_http.inject_headers]]`")?;
+
         Ok(Some(super::RuntimeConfig {
             connection_pooling_enabled: toml.connection_pooling,
             max_concurrent_connections: max_connections,
             wait_timeout: None,
+            host_policies,
+            cassette,
+            proxy,
+            cache,
+            inject_headers,
         }))
     } else {
         Ok(None)
@@ -54,4 +148,408 @@ struct OutboundHttpToml {
     /// Deprecated. Use `max_connections` instead.
     #[serde(default)]
     max_concurrent_requests: Option<usize>,
+    #[serde(default)]
+    policy: Vec<HostPolicyToml>,
+    #[serde(default)]
+    cassette: Option<CassetteToml>,
+    #[serde(default)]
+    proxy: Option<ProxyToml>,
+    #[serde(default)]
+    cache: Option<CacheToml>,
+    #[serde(default)]
+    inject_headers: Vec<InjectHeadersToml>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct HostPolicyToml {
+    hosts: Vec<String>,
+    #[serde(default, with = "spin_serde::duration::option")]
+    connect_timeout: Option<Duration>,
+    #[serde(default, with = "spin_serde::duration::option")]
+    first_byte_timeout: Option<Duration>,
+    #[serde(default, with = "spin_serde::duration::option")]
+    total_timeout: Option<Duration>,
+    #[serde(default)]
+    retries: u32,
+    #[serde(default, with = "spin_serde::duration::option")]
+    retry_backoff: Option<Duration>,
+    #[serde(default, with = "spin_serde::duration::option")]
+    retry_max_backoff: Option<Duration>,
+}
+
+impl HostPolicyToml {
+    fn into_host_policy(self) -> anyhow::Result<HostPolicy> {
+        anyhow::ensure!(!self.hosts.is_empty(), "'hosts' list may not be empty");
+        let hosts = self
+            .hosts
+            .iter()
+            .map(|host| host.parse())
+            .collect::<anyhow::Result<_>>()?;
+
+        let retry = (self.retries > 0).then(|| {
+            let default = RetryPolicy::default();
+            RetryPolicy {
+                max_retries: self.retries,
+                initial_backoff: self.retry_backoff.unwrap_or(default.initial_backoff),
+                max_backoff: self.retry_max_backoff.unwrap_or(default.max_backoff),
+            }
+        });
+
+        Ok(HostPolicy {
+            hosts,
+            connect_timeout: self.connect_timeout,
+            first_byte_timeout: self.first_byte_timeout,
+            total_timeout: self.total_timeout,
+            retry,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CassetteToml {
+    path: PathBuf,
+    mode: CassetteMode,
+    #[serde(default = "default_true")]
+    match_method: bool,
+    #[serde(default = "default_true")]
+    match_url: bool,
+    #[serde(default)]
+    match_headers: Vec<String>,
+    #[serde(default)]
+    match_body: bool,
+}
+
+impl CassetteToml {
+    fn into_cassette_config(
+        self,
+        runtime_config_dir: Option<&Path>,
+    ) -> anyhow::Result<CassetteConfig> {
+        let headers = self
+            .match_headers
+            .iter()
+            .map(|name| {
+                HeaderName::try_from(name).with_context(|| format!("invalid header name {name:?}"))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        // Relative paths are resolved against the runtime config file's
+        // directory, like other paths in the runtime config.
+        let path = match runtime_config_dir {
+            Some(dir) => dir.join(self.path),
+            None => self.path,
+        };
+        Ok(CassetteConfig {
+            path,
+            mode: self.mode,
+            matcher: RequestMatcher {
+                method: self.match_method,
+                url: self.match_url,
+                headers,
+                body: self.match_body,
+            },
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProxyToml {
+    url: String,
+    #[serde(default)]
+    no_proxy: Vec<String>,
+    #[serde(default)]
+    username: Option<String>,
+    #[serde(default)]
+    password: Option<String>,
+}
+
+impl ProxyToml {
+    fn into_proxy_config(self) -> anyhow::Result<ProxyConfig> {
+        let no_proxy = self
+            .no_proxy
+            .iter()
+            .map(|host| host.parse())
+            .collect::<anyhow::Result<_>>()?;
+        Ok(ProxyConfig {
+            url: self.url.parse()?,
+            no_proxy,
+            username: self.username,
+            password: self.password,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CacheToml {
+    hosts: Vec<String>,
+    #[serde(default)]
+    max_size: Option<u64>,
+    #[serde(default)]
+    max_entry_size: Option<u64>,
+    #[serde(default)]
+    persist: bool,
+}
+
+impl CacheToml {
+    fn into_cache_config(self, state_dir: Option<PathBuf>) -> anyhow::Result<CacheConfig> {
+        anyhow::ensure!(!self.hosts.is_empty(), "'hosts' list may not be empty");
+        let hosts = self
+            .hosts
+            .iter()
+            .map(|host| host.parse())
+            .collect::<anyhow::Result<_>>()?;
+        let dir = if self.persist {
+            let state_dir =
+                state_dir.context("'persist' requires a state directory, but none is set")?;
+            Some(state_dir.join("http-cache"))
+        } else {
+            None
+        };
+        Ok(CacheConfig {
+            hosts,
+            max_size: self.max_size.unwrap_or(DEFAULT_MAX_SIZE),
+            max_entry_size: self.max_entry_size.unwrap_or(DEFAULT_MAX_ENTRY_SIZE),
+            dir,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct InjectHeadersToml {
+    hosts: Vec<String>,
+    headers: BTreeMap<String, String>,
+}
+
+impl InjectHeadersToml {
+    fn into_header_rule(self) -> anyhow::Result<HeaderRule> {
+        anyhow::ensure!(!self.hosts.is_empty(), "'hosts' list may not be empty");
+        let hosts = self
+            .hosts
+            .iter()
+            .map(|host| host.parse())
+            .collect::<anyhow::Result<_>>()?;
+        let headers = self
+            .headers
+            .into_iter()
+            .map(|(name, value)| {
+                let name = HeaderName::try_from(&name)
+                    .with_context(|| format!("invalid header name {name:?}"))?;
+                Ok((name, value))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(HeaderRule { hosts, headers })
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_host_policies() -> anyhow::Result<()> {
+        let config = config_from_table(
+            &toml::toml! {
+                [[outbound_http.policy]]
+                hosts = ["api.example.com"]
+                connect_timeout = "2s"
+                total_timeout = 30
+                retries = 3
+                retry_backoff = "50ms"
+
+                [[outbound_http.policy]]
+                hosts = ["*"]
+                first_byte_timeout = "1m"
+            },
+            None,
+            None,
+        )?
+        .context("expected config")?;
+
+        let [api, any] = config.host_policies.as_slice() else {
+            panic!("expected two policies, got {:?}", config.host_policies);
+        };
+        assert_eq!(api.connect_timeout, Some(Duration::from_secs(2)));
+        assert_eq!(api.total_timeout, Some(Duration::from_secs(30)));
+        assert_eq!(
+            api.retry,
+            Some(RetryPolicy {
+                max_retries: 3,
+                initial_backoff: Duration::from_millis(50),
+                max_backoff: RetryPolicy::default().max_backoff,
+            })
+        );
+        assert_eq!(any.first_byte_timeout, Some(Duration::from_secs(60)));
+        assert_eq!(any.retry, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_cassette() -> anyhow::Result<()> {
+        let config = config_from_table(
+            &toml::toml! {
+                [outbound_http.cassette]
+                path = "cassette.jsonl"
+                mode = "record"
+                match_headers = ["Accept"]
+                match_body = true
+            },
+            None,
+            Some(Path::new("/config")),
+        )?
+        .context("expected config")?;
+        let cassette = config.cassette.context("expected cassette config")?;
+        assert_eq!(cassette.path, PathBuf::from("/config/cassette.jsonl"));
+        assert_eq!(cassette.mode, CassetteMode::Record);
+        assert!(cassette.matcher.method && cassette.matcher.url && cassette.matcher.body);
+        assert_eq!(
+            cassette.matcher.headers,
+            [HeaderName::from_static("accept")]
+        );
+
+        config_from_table(
+            &toml::toml! {
+                [outbound_http.cassette]
+                path = "cassette.jsonl"
+                mode = "rewind"
+            },
+            None,
+            None,
+        )
+        .unwrap_err();
+        Ok(())
+    }
+
+    #[test]
+    fn test_proxy() -> anyhow::Result<()> {
+        let config = config_from_table(
+            &toml::toml! {
+                [outbound_http.proxy]
+                url = "socks5h://127.0.0.1:1080"
+                no_proxy = ["localhost"]
+                password = "{{ proxy_password }}"
+            },
+            None,
+            None,
+        )?
+        .context("expected config")?;
+        let proxy = config.proxy.context("expected proxy config")?;
+        assert_eq!(proxy.url, "socks5h://127.0.0.1:1080".parse()?);
+        assert_eq!(proxy.no_proxy, ["localhost".parse()?]);
+        assert_eq!(proxy.username, None);
+        assert_eq!(proxy.password.as_deref(), Some("{{ proxy_password }}"));
+
+        config_from_table(
+            &toml::toml! {
+                [outbound_http.proxy]
+                url = "ftp://proxy.example.com"
+            },
+            None,
+            None,
+        )
+        .unwrap_err();
+
+        let err = config_from_table(
+            &toml::toml! {
+                [outbound_http.proxy]
+                url = "https://proxy.example.com"
+            },
+            None,
+            None,
+        )
+        .unwrap_err();
+        assert!(format!("{err:#}").contains("not supported"), "{err:#}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_cache() -> anyhow::Result<()> {
+        let table = toml::toml! {
+            [outbound_http.cache]
+            hosts = ["config.example.com"]
+            max_entry_size = 1024
+            persist = true
+        };
+        let config =
+            config_from_table(&table, Some("/state".into()), None)?.context("expected config")?;
+        let cache = config.cache.context("expected cache config")?;
+        assert_eq!(cache.hosts, ["config.example.com".parse()?]);
+        assert_eq!(cache.max_size, DEFAULT_MAX_SIZE);
+        assert_eq!(cache.max_entry_size, 1024);
+        assert_eq!(cache.dir, Some(PathBuf::from("/state/http-cache")));
+
+        // Persisting requires a state dir
+        config_from_table(&table, None, None).unwrap_err();
+        Ok(())
+    }
+
+    #[test]
+    fn test_inject_headers() -> anyhow::Result<()> {
+        let config = config_from_table(
+            &toml::toml! {
+                [[outbound_http.inject_headers]]
+                hosts = ["api.example.com", "*.example.org"]
+                headers = { Authorization = "Bearer {{ api_key }}", "x-team" = "spin" }
+            },
+            None,
+            None,
+        )?
+        .context("expected config")?;
+        let [rule] = config.inject_headers.as_slice() else {
+            panic!("expected one rule, got {:?}", config.inject_headers);
+        };
+        assert_eq!(
+            rule.hosts,
+            ["api.example.com".parse()?, "*.example.org".parse()?]
+        );
+        assert_eq!(
+            rule.headers,
+            [
+                (
+                    http::header::AUTHORIZATION,
+                    "Bearer {{ api_key }}".to_owned()
+                ),
+                (HeaderName::from_static("x-team"), "spin".to_owned()),
+            ]
+        );
+
+        config_from_table(
+            &toml::toml! {
+                [[outbound_http.inject_headers]]
+                hosts = ["api.example.com"]
+                headers = { "bad header" = "value" }
+            },
+            None,
+            None,
+        )
+        .unwrap_err();
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_host_policies() {
+        for table in [
+            toml::toml! {
+                [[outbound_http.policy]]
+                hosts = []
+            },
+            toml::toml! {
+                [[outbound_http.policy]]
+                hosts = ["example.com:443"]
+            },
+            toml::toml! {
+                [[outbound_http.policy]]
+                hosts = ["example.com"]
+                connect_timeout = "soon"
+            },
+        ] {
+            config_from_table(&table, None, None).unwrap_err();
+        }
+    }
 }
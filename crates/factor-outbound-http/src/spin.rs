@@ -1,9 +1,13 @@
-use std::sync::Arc;
+use std::{net::SocketAddr, sync::Arc, time::Instant};
 
+use bytes::Bytes;
 use futures::stream::TryStreamExt as _;
 use http_body_util::BodyExt;
 use opentelemetry_semantic_conventions::attribute as otel_attribute;
-use spin_factor_outbound_networking::config::blocked_networks::BlockedNetworks;
+use spin_factor_outbound_networking::{
+    audit::EgressRecord,
+    config::{blocked_networks::BlockedNetworks, dns_overrides::DnsOverrides},
+};
 use spin_world::MAX_HOST_BUFFERED_BYTES;
 use spin_world::v1::{
     http as spin_http,
@@ -11,7 +15,17 @@ use spin_world::v1::{
 };
 use tracing::{Span, field::Empty, instrument};
 
-use crate::intercept::InterceptOutcome;
+use crate::{
+    cache::{CacheLookup, ComponentCache},
+    cassette::Cassette,
+    intercept::{InterceptOutcome, OutboundHttpInterceptor},
+    proxy::Proxy,
+    wasi::full_body,
+};
+
+/// The maximum number of redirects followed for a request, matching reqwest's
+/// default policy.
+const MAX_REDIRECTS: usize = 10;
 
 impl spin_http::Host for crate::InstanceState {
     #[instrument(name = "spin_outbound_http.send_request", skip_all,
@@ -80,7 +94,28 @@ impl spin_http::Host for crate::InstanceState {
 
         spin_telemetry::inject_trace_context(req.headers_mut());
 
-        if let Some(interceptor) = &self.hooks.request_interceptor {
+        // Run any configured request interceptor, followed by any header
+        // injection and finally any cassette (which completes requests with
+        // recorded responses when replaying), so that cassettes see requests
+        // as they would be sent
+        let interceptors = self
+            .hooks
+            .request_interceptor
+            .iter()
+            .cloned()
+            .chain(
+                self.hooks
+                    .header_injector
+                    .clone()
+                    .map(|injector| injector as Arc<dyn OutboundHttpInterceptor>),
+            )
+            .chain(
+                self.hooks
+                    .cassette
+                    .clone()
+                    .map(|cassette| cassette as Arc<dyn OutboundHttpInterceptor>),
+            );
+        for interceptor in interceptors {
             let intercepted_request = std::mem::take(&mut req).into();
             match interceptor.intercept(intercepted_request).await {
                 Ok(InterceptOutcome::Continue(intercepted_request)) => {
@@ -94,20 +129,75 @@ impl spin_http::Host for crate::InstanceState {
             }
         }
 
+        // Serve the request from the cache if possible
+        let cached_request = match self
+            .hooks
+            .cache
+            .clone()
+            .filter(|cache| cache.applies_to(req.uri()))
+        {
+            Some(cache) => {
+                let mut cached_request = CachedRequest::new(cache, &req);
+                if let Some(resp) = cached_request.lookup(&mut req) {
+                    return response_from_hyper(resp.map(full_body)).await;
+                }
+                Some(cached_request)
+            }
+            None => None,
+        };
+
+        // Keep a copy of the request if it is to be recorded
+        let recording = self
+            .hooks
+            .cassette
+            .clone()
+            .filter(|cassette| cassette.is_recording())
+            .map(|cassette| (cassette, req.clone().map(Bytes::from)));
+
         // Convert http::Request to reqwest::Request
-        let req = reqwest::Request::try_from(req).map_err(|_| HttpError::InvalidUrl)?;
-
-        // Allow reuse of Client's internal connection pool for multiple requests
-        // in a single component execution
-        let client = self.hooks.spin_http_client.get_or_insert_with(|| {
-            let mut builder = reqwest::Client::builder().dns_resolver(Arc::new(SpinDnsResolver(
-                self.hooks.blocked_networks.clone(),
-            )));
-            if !self.hooks.connection_pooling_enabled {
-                builder = builder.pool_max_idle_per_host(0);
+        let mut req = reqwest::Request::try_from(req).map_err(|_| HttpError::InvalidUrl)?;
+
+        // Proxies resolve host names themselves, bypassing the DNS resolver
+        // which enforces blocked networks, so check IP address hosts here
+        if let Some(proxy) = &self.hooks.proxy
+            && let Some(ip) = req.url().host_str().and_then(crate::wasi::parse_ip_host)
+            && ProxyFilter::new(proxy, &self.hooks).applies_to(req.url())
+        {
+            let port = req.url().port_or_known_default().unwrap_or_default();
+            crate::remove_blocked_addrs(
+                &self.hooks.blocked_networks,
+                &mut vec![SocketAddr::new(ip, port)],
+            )
+            .map_err(|_| HttpError::DestinationNotAllowed)?;
+        }
+
+        // Static DNS overrides depend on the port connected to, which
+        // reqwest's resolver isn't told, so requests to an overridden host and
+        // port are sent with a client of their own
+        let dns_override = req
+            .url()
+            .host_str()
+            .zip(req.url().port_or_known_default())
+            .and_then(|(host, port)| {
+                Some((host.to_owned(), self.hooks.dns_overrides.get(host, port)?))
+            });
+        let client = match &dns_override {
+            Some((host, addr)) => {
+                crate::remove_blocked_addrs(&self.hooks.blocked_networks, &mut vec![*addr])
+                    .map_err(|_| HttpError::DestinationNotAllowed)?;
+                spin_http_client(&self.hooks, Some((host, *addr))).await?
             }
-            builder.build().unwrap()
-        });
+            // Allow reuse of Client's internal connection pool for multiple
+            // requests in a single component execution
+            None => match &self.hooks.spin_http_client {
+                Some(client) => client.clone(),
+                None => {
+                    let client = spin_http_client(&self.hooks, None).await?;
+                    self.hooks.spin_http_client = Some(client.clone());
+                    client
+                }
+            },
+        };
 
         // If we're limiting concurrent outbound requests, acquire a permit
         // Note: since we don't have access to the underlying connection, we can only
@@ -118,19 +208,272 @@ impl spin_http::Host for crate::InstanceState {
             .acquire()
             .await
             .map_err(|_| HttpError::TooManyRequests)?;
+        let audit = self.hooks.egress_audit.clone().map(|audit| {
+            let record = EgressRecord {
+                scheme: req.url().scheme().to_owned(),
+                // Excludes any userinfo
+                authority: match req.url().port() {
+                    Some(port) => format!("{}:{port}", req.url().host_str().unwrap_or_default()),
+                    None => req.url().host_str().unwrap_or_default().to_owned(),
+                },
+                bytes_sent: req
+                    .body()
+                    .and_then(|body| body.as_bytes())
+                    .map(|b| b.len() as u64),
+                ..Default::default()
+            };
+            (audit, record, Instant::now())
+        });
+        if let Some((_, addr)) = dns_override {
+            connect_to_override(&mut req, addr)?;
+        }
         let resp = client.execute(req).await.map_err(log_reqwest_error)?;
         drop(permit);
+        let audit = audit.map(|(audit, mut record, started)| {
+            record.resolved_ip = resp.remote_addr().map(|addr| addr.ip());
+            (audit, record, started)
+        });
 
         tracing::trace!("Returning response from outbound request to {req_url}");
         span.record(
             otel_attribute::HTTP_RESPONSE_STATUS_CODE,
             resp.status().as_u16(),
         );
-        response_from_reqwest(resp).await
+        let resp = response_from_reqwest(resp).await?;
+        if let Some((audit, mut record, started)) = audit {
+            record.bytes_received = Some(resp.body.as_ref().map_or(0, |body| body.len() as u64));
+            record.duration = Some(started.elapsed());
+            audit.record(record);
+        }
+        if let Some((cassette, recorded_request)) = recording {
+            record_response(&cassette, &recorded_request, &resp);
+        }
+        match cached_request {
+            Some(cached_request) => cached_request.update(resp).await,
+            None => Ok(resp),
+        }
     }
 }
 
+/// A request to a host whose responses are cached.
+struct CachedRequest {
+    cache: ComponentCache,
+    method: http::Method,
+    uri: http::Uri,
+    /// The request headers, before any added for revalidation.
+    request_headers: http::HeaderMap,
+    revalidating: bool,
+}
+
+impl CachedRequest {
+    fn new(cache: ComponentCache, req: &http::Request<Vec<u8>>) -> Self {
+        Self {
+            cache,
+            method: req.method().clone(),
+            uri: req.uri().clone(),
+            request_headers: req.headers().clone(),
+            revalidating: false,
+        }
+    }
+
+    /// Returns a fresh cached response to the request, if any, otherwise
+    /// adding the headers needed to revalidate any stale cached response.
+    fn lookup(&mut self, req: &mut http::Request<Vec<u8>>) -> Option<http::Response<Bytes>> {
+        if !self
+            .cache
+            .is_cacheable_request(&self.method, &self.request_headers)
+        {
+            return None;
+        }
+        match self.cache.lookup(&self.uri, &self.request_headers) {
+            CacheLookup::Fresh(resp) => Some(resp),
+            CacheLookup::Stale(conditional) => {
+                req.headers_mut().extend(conditional);
+                self.revalidating = true;
+                None
+            }
+            CacheLookup::Miss => None,
+        }
+    }
+
+    /// Updates the cache with the response to the request, returning the
+    /// response to give the guest.
+    async fn update(self, resp: Response) -> Result<Response, HttpError> {
+        let Ok(status) = http::StatusCode::from_u16(resp.status) else {
+            return Ok(resp);
+        };
+        if !self.method.is_safe() {
+            // Requests which may change a resource invalidate its cached responses
+            if !status.is_client_error() && !status.is_server_error() {
+                self.cache.invalidate(&self.uri);
+            }
+            return Ok(resp);
+        }
+        if !self
+            .cache
+            .is_cacheable_request(&self.method, &self.request_headers)
+        {
+            return Ok(resp);
+        }
+
+        let headers = resp
+            .headers
+            .iter()
+            .flatten()
+            .filter_map(|(key, val)| {
+                Some((
+                    http::HeaderName::try_from(key).ok()?,
+                    http::HeaderValue::try_from(val).ok()?,
+                ))
+            })
+            .collect::<http::HeaderMap>();
+        if self.revalidating
+            && status == http::StatusCode::NOT_MODIFIED
+            && let Some(cached) = self
+                .cache
+                .revalidated(&self.uri, &self.request_headers, &headers)
+        {
+            return response_from_hyper(cached.map(full_body)).await;
+        }
+        let body = Bytes::from(resp.body.clone().unwrap_or_default());
+        self.cache
+            .store(&self.uri, &self.request_headers, status, &headers, body);
+        Ok(resp)
+    }
+}
+
+/// Records a request and its response to the given cassette.
+fn record_response(cassette: &Cassette, request: &http::Request<Bytes>, resp: &Response) {
+    let mut builder = http::Response::builder().status(resp.status);
+    for (key, val) in resp.headers.iter().flatten() {
+        builder = builder.header(key, val);
+    }
+    match builder.body(Bytes::from(resp.body.clone().unwrap_or_default())) {
+        Ok(recorded) => cassette.record(request, &recorded),
+        Err(err) => tracing::error!("Error recording outbound response: {err}"),
+    }
+}
+
+/// Builds the client for `fermyon:spin/http` requests, which sends requests
+/// through the configured proxy, if any.
+async fn spin_http_client(
+    hooks: &crate::InstanceHttpHooks,
+    dns_override: Option<(&str, SocketAddr)>,
+) -> Result<reqwest::Client, HttpError> {
+    let mut builder = reqwest::Client::builder()
+        .dns_resolver(Arc::new(SpinDnsResolver(hooks.blocked_networks.clone())));
+    if !hooks.connection_pooling_enabled {
+        builder = builder.pool_max_idle_per_host(0);
+    }
+    match dns_override {
+        // Requests to overridden hosts are never proxied
+        Some((host, addr)) => builder = builder.resolve(host, addr).no_proxy(),
+        None => {
+            if let Some(proxy) = &hooks.proxy {
+                builder = builder.proxy(reqwest_proxy(ProxyFilter::new(proxy, hooks)).await?);
+            }
+        }
+    }
+    if let Some(injector) = &hooks.header_injector {
+        // Injected headers must not follow a redirect to another origin, so
+        // such redirects are returned to the guest instead of being followed
+        let injector = injector.clone();
+        builder = builder.redirect(reqwest::redirect::Policy::custom(move |attempt| {
+            if !injector.allows_redirect(&attempt.previous()[0], attempt.url()) {
+                attempt.stop()
+            } else if attempt.previous().len() > MAX_REDIRECTS {
+                attempt.error("too many redirects")
+            } else {
+                attempt.follow()
+            }
+        }));
+    }
+    builder.build().map_err(|err| {
+        tracing::error!("Error building outbound HTTP client: {err}");
+        HttpError::RuntimeError
+    })
+}
+
+/// Decides which requests are sent through a proxy.
+///
+/// Self requests, hosts excluded by `no_proxy`, and hosts with a static DNS
+/// override are sent directly.
+#[derive(Clone)]
+struct ProxyFilter {
+    proxy: Arc<Proxy>,
+    self_authority: Option<http::uri::Authority>,
+    dns_overrides: DnsOverrides,
+}
+
+impl ProxyFilter {
+    fn new(proxy: &Arc<Proxy>, hooks: &crate::InstanceHttpHooks) -> Self {
+        Self {
+            proxy: proxy.clone(),
+            self_authority: hooks
+                .self_request_origin
+                .as_ref()
+                .map(|origin| origin.authority.clone()),
+            dns_overrides: hooks.dns_overrides.clone(),
+        }
+    }
+
+    fn applies_to(&self, url: &reqwest::Url) -> bool {
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+        let is_self_request = self.self_authority.as_ref().is_some_and(|authority| {
+            authority.host() == host && authority.port_u16() == url.port()
+        });
+        let is_overridden = url
+            .port_or_known_default()
+            .is_some_and(|port| self.dns_overrides.get(host, port).is_some());
+        !is_self_request && !is_overridden && self.proxy.applies_to(host)
+    }
+}
+
+/// Builds a [`reqwest::Proxy`] sending the requests the given filter applies
+/// to through its proxy.
+async fn reqwest_proxy(filter: ProxyFilter) -> Result<reqwest::Proxy, HttpError> {
+    let proxy_url: reqwest::Url = filter.proxy.url().to_string().parse().map_err(|err| {
+        tracing::error!("Invalid outbound HTTP proxy URL: {err}");
+        HttpError::RuntimeError
+    })?;
+    let credentials = filter
+        .proxy
+        .credentials()
+        .await
+        .map_err(|_| HttpError::RuntimeError)?
+        .cloned();
+    let mut reqwest_proxy =
+        reqwest::Proxy::custom(move |url| filter.applies_to(url).then(|| proxy_url.clone()));
+    if let Some(credentials) = credentials {
+        reqwest_proxy = reqwest_proxy.basic_auth(&credentials.username, &credentials.password);
+    }
+    Ok(reqwest_proxy)
+}
+
+/// Sends a request to an overridden host to the port of the overriding
+/// address, keeping the `Host` header of the original URL.
+///
+/// reqwest always connects to a port given in the URL, rather than that of
+/// an address the host is resolved to.
+fn connect_to_override(req: &mut reqwest::Request, addr: SocketAddr) -> Result<(), HttpError> {
+    let url = req.url();
+    let host = match url.port() {
+        Some(port) => format!("{}:{port}", url.host_str().unwrap_or_default()),
+        None => url.host_str().unwrap_or_default().to_owned(),
+    };
+    let host = http::HeaderValue::try_from(host).map_err(|_| HttpError::InvalidUrl)?;
+    req.headers_mut().entry(http::header::HOST).or_insert(host);
+    req.url_mut()
+        .set_port(Some(addr.port()))
+        .map_err(|()| HttpError::InvalidUrl)
+}
+
 /// Resolves DNS using Tokio's resolver, filtering out blocked IPs.
+///
+/// Static DNS overrides are applied by [`spin_http_client`] rather than here,
+/// as the resolver isn't told which port will be connected to.
 struct SpinDnsResolver(BlockedNetworks);
 
 impl reqwest::dns::Resolve for SpinDnsResolver {
@@ -303,3 +646,29 @@ fn headers_from_map(map: &http::HeaderMap) -> Vec<(String, String)> {
         })
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dns_overrides_keep_the_original_host_header() {
+        let addr = "127.0.0.1:8443".parse().unwrap();
+
+        let mut req = reqwest::Request::new(
+            reqwest::Method::GET,
+            "https://api.example.com/path".parse().unwrap(),
+        );
+        connect_to_override(&mut req, addr).unwrap();
+        assert_eq!(req.url().as_str(), "https://api.example.com:8443/path");
+        assert_eq!(req.headers()[http::header::HOST], "api.example.com");
+
+        let mut req = reqwest::Request::new(
+            reqwest::Method::GET,
+            "http://api.example.com:8080/".parse().unwrap(),
+        );
+        connect_to_override(&mut req, addr).unwrap();
+        assert_eq!(req.url().as_str(), "http://api.example.com:8443/");
+        assert_eq!(req.headers()[http::header::HOST], "api.example.com:8080");
+    }
+}
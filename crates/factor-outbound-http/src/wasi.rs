@@ -2,40 +2,49 @@ use std::{
     error::Error,
     future::Future,
     io::IoSlice,
-    net::SocketAddr,
+    net::{IpAddr, SocketAddr},
     pin::Pin,
-    sync::Arc,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
     task::{self, Context, Poll},
     time::Duration,
 };
 
-use bytes::{Buf, Bytes};
+use bytes::{Buf, Bytes, BytesMut};
+use futures::StreamExt as _;
 use http::{
     HeaderMap, Uri,
-    header::{CONTENT_LENGTH, HOST},
-    uri::Scheme,
+    header::{CONTENT_LENGTH, HOST, PROXY_AUTHORIZATION},
+    uri::{Authority, Scheme},
 };
 use http_body::{Body, Frame, SizeHint};
-use http_body_util::{BodyExt, combinators::UnsyncBoxBody};
+use http_body_util::{BodyExt, Full, StreamBody, combinators::UnsyncBoxBody};
 use hyper_util::{
     client::legacy::{
         Client,
-        connect::{Connected, Connection},
+        connect::{Connected, Connection, HttpInfo},
     },
     rt::{TokioExecutor, TokioIo},
 };
 use opentelemetry_semantic_conventions::attribute as otel_attribute;
 use spin_factor_outbound_networking::{
-    ComponentTlsClientConfigs, TlsClientConfig,
-    config::{allowed_hosts::OutboundAllowedHosts, blocked_networks::BlockedNetworks},
+    ComponentTlsClientConfigs, PinMismatch, TlsClientConfig,
+    audit::{EgressAudit, EgressRecord},
+    config::{
+        allowed_hosts::OutboundAllowedHosts, blocked_networks::BlockedNetworks,
+        dns_overrides::DnsOverrides,
+    },
 };
 use spin_factors::RuntimeFactorsInstanceState;
 use tokio::{
-    io::{AsyncRead, AsyncWrite, ReadBuf},
+    io::{AsyncRead, AsyncReadExt as _, AsyncWrite, AsyncWriteExt as _, ReadBuf},
     net::TcpStream,
-    time::timeout,
+    time::{Instant, timeout, timeout_at},
 };
 use tokio_rustls::client::TlsStream;
+use tokio_socks::{TargetAddr, tcp::Socks5Stream};
 use tower_service::Service;
 use tracing::{Instrument, Span, field::Empty, instrument};
 use wasmtime::component::HasData;
@@ -54,7 +63,12 @@ use spin_factor_outbound_networking::{ConnectionPermit, ConnectionSemaphore};
 
 use crate::{
     InstanceHttpHooks, OutboundHttpFactor, SelfRequestOrigin,
+    cache::{CacheLookup, CachingBody, ComponentCache, PendingStore},
+    cassette::Cassette,
+    inject::HeaderInjector,
     intercept::{InterceptOutcome, OutboundHttpInterceptor},
+    policy::{HostPolicies, HostPolicy, RetryPolicy, is_retryable_error},
+    proxy::{Proxy, ProxyCredentials, ProxyKind, ProxyUrl},
     wasi_2023_10_18, wasi_2023_11_10, wasi_2026_03_15,
 };
 
@@ -62,6 +76,9 @@ use tracing_opentelemetry::OpenTelemetrySpanExt as _;
 
 const DEFAULT_TIMEOUT: Duration = Duration::from_secs(600);
 
+/// The largest request body that is buffered so that a request can be retried.
+const MAX_RETRY_BODY_SIZE: usize = 1024 * 1024;
+
 pub(crate) struct HasHttp;
 
 impl HasData for HasHttp {
@@ -120,6 +137,13 @@ impl p3::WasiHttpHooks for InstanceHttpHooks {
             blocked_networks: self.blocked_networks.clone(),
             http_clients: self.wasi_http_clients.clone(),
             semaphore: self.semaphore.clone(),
+            host_policies: self.host_policies.clone(),
+            cassette: self.cassette.clone(),
+            proxy: self.proxy.clone(),
+            cache: self.cache.clone(),
+            header_injector: self.header_injector.clone(),
+            dns_overrides: self.dns_overrides.clone(),
+            egress_audit: self.egress_audit.clone(),
         };
         let config = OutgoingRequestConfig {
             use_tls: request.uri().scheme() == Some(&Scheme::HTTPS),
@@ -264,6 +288,126 @@ impl<B: Body<Error = p2_types::ErrorCode> + Unpin> Body for BetweenBytesTimeoutB
     }
 }
 
+pin_project_lite::pin_project! {
+    /// A body that fails with [`ErrorCode::HttpResponseTimeout`] if it has
+    /// not been fully read by the given deadline.
+    struct DeadlineBody<B> {
+        #[pin]
+        body: B,
+        #[pin]
+        sleep: tokio::time::Sleep,
+        expired: bool,
+    }
+}
+
+impl<B: Body<Error = ErrorCode>> Body for DeadlineBody<B> {
+    type Data = B::Data;
+    type Error = ErrorCode;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let me = self.project();
+        if *me.expired {
+            return Poll::Ready(None);
+        }
+        if let Poll::Ready(frame) = me.body.poll_frame(cx) {
+            return Poll::Ready(frame);
+        }
+        task::ready!(me.sleep.poll(cx));
+        *me.expired = true;
+        Poll::Ready(Some(Err(ErrorCode::HttpResponseTimeout)))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.expired || self.body.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.body.size_hint()
+    }
+}
+
+/// A request body that counts the bytes sent, which may be streamed well
+/// after the request is made.
+struct CountingBody {
+    body: HyperOutgoingBody,
+    sent: Arc<AtomicU64>,
+}
+
+impl Body for CountingBody {
+    type Data = Bytes;
+    type Error = ErrorCode;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let frame = task::ready!(Pin::new(&mut self.body).poll_frame(cx));
+        if let Some(Ok(frame)) = &frame
+            && let Some(data) = frame.data_ref()
+        {
+            self.sent.fetch_add(data.len() as u64, Ordering::Relaxed);
+        }
+        Poll::Ready(frame)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.body.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.body.size_hint()
+    }
+}
+
+/// A response body that counts the bytes received, recording the request to
+/// the egress audit log once dropped.
+struct AuditedBody {
+    body: UnsyncBoxBody<Bytes, ErrorCode>,
+    audit: EgressAudit,
+    record: EgressRecord,
+    /// The bytes of the request body sent so far.
+    sent: Arc<AtomicU64>,
+    started: Instant,
+}
+
+impl Body for AuditedBody {
+    type Data = Bytes;
+    type Error = ErrorCode;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let frame = task::ready!(Pin::new(&mut self.body).poll_frame(cx));
+        if let Some(Ok(frame)) = &frame
+            && let Some(data) = frame.data_ref()
+        {
+            *self.record.bytes_received.get_or_insert(0) += data.len() as u64;
+        }
+        Poll::Ready(frame)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.body.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.body.size_hint()
+    }
+}
+
+impl Drop for AuditedBody {
+    fn drop(&mut self) {
+        let mut record = std::mem::take(&mut self.record);
+        record.bytes_sent = Some(self.sent.load(Ordering::Relaxed));
+        record.duration = Some(self.started.elapsed());
+        self.audit.record(record);
+    }
+}
+
 pub(crate) fn add_to_linker<C>(ctx: &mut C) -> anyhow::Result<()>
 where
     C: spin_factors::InitContext<OutboundHttpFactor>,
@@ -374,6 +518,13 @@ impl p2::WasiHttpHooks for InstanceHttpHooks {
             blocked_networks: self.blocked_networks.clone(),
             http_clients: self.wasi_http_clients.clone(),
             semaphore: self.semaphore.clone(),
+            host_policies: self.host_policies.clone(),
+            cassette: self.cassette.clone(),
+            proxy: self.proxy.clone(),
+            cache: self.cache.clone(),
+            header_injector: self.header_injector.clone(),
+            dns_overrides: self.dns_overrides.clone(),
+            egress_audit: self.egress_audit.clone(),
         };
         Ok(HostFutureIncomingResponse::Pending(
             wasmtime_wasi::runtime::spawn(
@@ -400,6 +551,13 @@ struct RequestSender {
     request_interceptor: Option<Arc<dyn OutboundHttpInterceptor>>,
     http_clients: HttpClients,
     semaphore: ConnectionSemaphore,
+    host_policies: HostPolicies,
+    cassette: Option<Arc<Cassette>>,
+    proxy: Option<Arc<Proxy>>,
+    cache: Option<ComponentCache>,
+    header_injector: Option<Arc<HeaderInjector>>,
+    dns_overrides: DnsOverrides,
+    egress_audit: Option<EgressAudit>,
 }
 
 impl RequestSender {
@@ -413,13 +571,32 @@ impl RequestSender {
         // If the current span has opentelemetry trace context, inject it into the request
         spin_telemetry::inject_trace_context(&mut request);
 
-        // Run any configured request interceptor
+        // Run any configured request interceptor, followed by any header
+        // injection and finally any cassette (which completes requests with
+        // recorded responses when replaying), so that cassettes see requests
+        // as they would be sent
+        let interceptors = self
+            .request_interceptor
+            .iter()
+            .cloned()
+            .chain(
+                self.header_injector
+                    .clone()
+                    .map(|injector| injector as Arc<dyn OutboundHttpInterceptor>),
+            )
+            .chain(
+                self.cassette
+                    .clone()
+                    .map(|cassette| cassette as Arc<dyn OutboundHttpInterceptor>),
+            );
         let mut override_connect_addr = None;
-        if let Some(interceptor) = &self.request_interceptor {
+        for interceptor in interceptors {
             let intercept_request = std::mem::take(&mut request).into();
             match interceptor.intercept(intercept_request).await? {
                 InterceptOutcome::Continue(mut req) => {
-                    override_connect_addr = req.override_connect_addr.take();
+                    if let Some(addr) = req.override_connect_addr.take() {
+                        override_connect_addr = Some(addr);
+                    }
                     request = req.into_hyper_request();
                 }
                 InterceptOutcome::Complete(resp) => {
@@ -451,9 +628,281 @@ impl RequestSender {
             "http.request.header.content-length",
         );
 
-        Ok(self
-            .send_request(request, config, override_connect_addr)
-            .await?)
+        match self
+            .cache
+            .as_ref()
+            .filter(|cache| cache.applies_to(request.uri()))
+        {
+            Some(cache) => Ok(self
+                .send_with_cache(cache, request, config, override_connect_addr)
+                .await?),
+            None => Ok(self
+                .send_uncached(request, config, override_connect_addr)
+                .await?),
+        }
+    }
+
+    /// Sends the request, serving it from the given cache where possible and
+    /// storing cacheable responses.
+    async fn send_with_cache(
+        &self,
+        cache: &ComponentCache,
+        mut request: OutgoingRequest,
+        config: OutgoingRequestConfig,
+        override_connect_addr: Option<SocketAddr>,
+    ) -> Result<IncomingResponse, ErrorCode> {
+        let uri = request.uri().clone();
+        let between_bytes_timeout = config.between_bytes_timeout;
+
+        if !request.method().is_safe() {
+            // Requests which may change a resource invalidate its cached responses
+            let resp = self
+                .send_uncached(request, config, override_connect_addr)
+                .await?;
+            let status = resp.resp.status();
+            if !status.is_client_error() && !status.is_server_error() {
+                cache.invalidate(&uri);
+            }
+            return Ok(resp);
+        }
+        if !cache.is_cacheable_request(request.method(), request.headers()) {
+            return self
+                .send_uncached(request, config, override_connect_addr)
+                .await;
+        }
+
+        let request_headers = request.headers().clone();
+        let revalidating = match cache.lookup(&uri, &request_headers) {
+            CacheLookup::Fresh(resp) => {
+                tracing::debug!(%uri, "Serving outbound HTTP response from cache");
+                return Ok(IncomingResponse {
+                    resp: resp.map(full_body),
+                    worker: None,
+                    between_bytes_timeout,
+                });
+            }
+            CacheLookup::Stale(conditional) => {
+                request.headers_mut().extend(conditional);
+                true
+            }
+            CacheLookup::Miss => false,
+        };
+
+        let mut resp = self
+            .send_uncached(request, config, override_connect_addr)
+            .await?;
+        let status = resp.resp.status();
+        if revalidating
+            && status == http::StatusCode::NOT_MODIFIED
+            && let Some(cached) = cache.revalidated(&uri, &request_headers, resp.resp.headers())
+        {
+            tracing::debug!(%uri, "Serving revalidated outbound HTTP response from cache");
+            resp.resp = cached.map(full_body);
+            return Ok(resp);
+        }
+        if cache.is_storable(&request_headers, status, resp.resp.headers()) {
+            let pending = PendingStore {
+                cache: cache.clone(),
+                uri,
+                request_headers,
+                status,
+                headers: resp.resp.headers().clone(),
+            };
+            resp.resp = resp
+                .resp
+                .map(|body| CachingBody::new(body, pending).boxed_unsync());
+        }
+        Ok(resp)
+    }
+
+    /// Sends the request without consulting the cache.
+    async fn send_uncached(
+        &self,
+        request: OutgoingRequest,
+        config: OutgoingRequestConfig,
+        override_connect_addr: Option<SocketAddr>,
+    ) -> Result<IncomingResponse, ErrorCode> {
+        match self
+            .cassette
+            .as_ref()
+            .filter(|cassette| cassette.is_recording())
+        {
+            Some(cassette) => {
+                self.send_and_record(cassette, request, config, override_connect_addr)
+                    .await
+            }
+            None => {
+                self.send_with_host_policy(request, config, override_connect_addr)
+                    .await
+            }
+        }
+    }
+
+    /// Sends the request, recording it and its response to the given cassette.
+    ///
+    /// The request and response bodies are buffered in memory.
+    async fn send_and_record(
+        &self,
+        cassette: &Cassette,
+        request: OutgoingRequest,
+        config: OutgoingRequestConfig,
+        override_connect_addr: Option<SocketAddr>,
+    ) -> Result<IncomingResponse, ErrorCode> {
+        let (parts, body) = request.into_parts();
+        let body = body.collect().await?.to_bytes();
+        let recorded_request = http::Request::from_parts(parts.clone(), body.clone());
+        let request = http::Request::from_parts(parts, full_body(body));
+
+        let mut resp = self
+            .send_with_host_policy(request, config, override_connect_addr)
+            .await?;
+
+        let (parts, body) = resp.resp.into_parts();
+        let body = body.collect().await?.to_bytes();
+        cassette.record(
+            &recorded_request,
+            &http::Response::from_parts(parts.clone(), body.clone()),
+        );
+        resp.resp = http::Response::from_parts(parts, full_body(body));
+        Ok(resp)
+    }
+
+    /// Sends the request, applying the policy matching its host, if any.
+    async fn send_with_host_policy(
+        &self,
+        request: OutgoingRequest,
+        config: OutgoingRequestConfig,
+        override_connect_addr: Option<SocketAddr>,
+    ) -> Result<IncomingResponse, ErrorCode> {
+        let policy = request
+            .uri()
+            .host()
+            .and_then(|host| self.host_policies.get(host))
+            .cloned();
+        match policy {
+            Some(policy) => {
+                self.send_with_policy(request, config, override_connect_addr, policy)
+                    .await
+            }
+            None => {
+                self.send_request(request, config, override_connect_addr)
+                    .await
+            }
+        }
+    }
+
+    /// Sends the request, applying the timeouts and retries of the given policy.
+    async fn send_with_policy(
+        &self,
+        request: OutgoingRequest,
+        mut config: OutgoingRequestConfig,
+        override_connect_addr: Option<SocketAddr>,
+        policy: HostPolicy,
+    ) -> Result<IncomingResponse, ErrorCode> {
+        policy.apply_timeouts(&mut config);
+
+        let send = async {
+            match policy.retry_for(request.method()) {
+                Some(retry) => {
+                    self.send_with_retries(request, config, override_connect_addr, retry)
+                        .await
+                }
+                None => {
+                    self.send_request(request, config, override_connect_addr)
+                        .await
+                }
+            }
+        };
+
+        let Some(total_timeout) = policy.total_timeout else {
+            return send.await;
+        };
+        let deadline = Instant::now() + total_timeout;
+        let mut resp = timeout_at(deadline, send)
+            .await
+            .map_err(|_| ErrorCode::HttpResponseTimeout)??;
+        resp.resp = resp.resp.map(|body| {
+            DeadlineBody {
+                body,
+                sleep: tokio::time::sleep_until(deadline),
+                expired: false,
+            }
+            .boxed_unsync()
+        });
+        Ok(resp)
+    }
+
+    /// Sends the request, retrying after connection errors and `5xx`
+    /// responses.
+    ///
+    /// The request body is buffered in memory so that it can be replayed.
+    /// Requests with bodies larger than [`MAX_RETRY_BODY_SIZE`] are sent
+    /// once, without retries.
+    async fn send_with_retries(
+        &self,
+        request: OutgoingRequest,
+        config: OutgoingRequestConfig,
+        override_connect_addr: Option<SocketAddr>,
+        retry: RetryPolicy,
+    ) -> Result<IncomingResponse, ErrorCode> {
+        let (parts, body) = request.into_parts();
+        let body = match buffer_for_retry(body).await? {
+            Ok(body) => body,
+            Err(body) => {
+                tracing::debug!("Not retrying outbound HTTP request with a large body");
+                let request = http::Request::from_parts(parts, body);
+                return self
+                    .send_request(request, config, override_connect_addr)
+                    .await;
+            }
+        };
+        let OutgoingRequestConfig {
+            use_tls,
+            connect_timeout,
+            first_byte_timeout,
+            between_bytes_timeout,
+        } = config;
+
+        let mut attempt = 0;
+        loop {
+            let request = http::Request::from_parts(parts.clone(), full_body(body.clone()));
+            let config = OutgoingRequestConfig {
+                use_tls,
+                connect_timeout,
+                first_byte_timeout,
+                between_bytes_timeout,
+            };
+            let result = self
+                .send_request(request, config, override_connect_addr)
+                .await;
+
+            let retryable = match &result {
+                Ok(resp) => resp.resp.status().is_server_error(),
+                Err(err) => is_retryable_error(err),
+            };
+            if !retryable || attempt >= retry.max_retries {
+                return result;
+            }
+            let backoff = retry.backoff(attempt);
+            attempt += 1;
+            match &result {
+                Ok(resp) => tracing::debug!(
+                    status = resp.resp.status().as_u16(),
+                    ?backoff,
+                    attempt,
+                    "Retrying outbound HTTP request after server error"
+                ),
+                Err(err) => tracing::debug!(
+                    ?err,
+                    ?backoff,
+                    attempt,
+                    "Retrying outbound HTTP request after connection error"
+                ),
+            }
+            // Release the failed response (and its connection) before waiting
+            drop(result);
+            tokio::time::sleep(backoff).await;
+        }
     }
 
     async fn prepare_request(
@@ -527,9 +976,45 @@ impl RequestSender {
         Ok(())
     }
 
+    /// Returns the proxy to send the given request through, if any.
+    async fn proxy_for(
+        &self,
+        authority: Option<Authority>,
+        default_port: u16,
+        override_connect_addr: Option<SocketAddr>,
+    ) -> Result<Option<ProxyConnect>, ErrorCode> {
+        let Some(proxy) = &self.proxy else {
+            return Ok(None);
+        };
+        // Requests routed to a specific address by an interceptor are never proxied
+        if override_connect_addr.is_some() {
+            return Ok(None);
+        }
+        let Some(authority) = authority else {
+            return Ok(None);
+        };
+        // Nor are requests to hosts with a static DNS override, which is
+        // usually there to reach something the proxy can't
+        let port = authority.port_u16().unwrap_or(default_port);
+        if self.dns_overrides.get(authority.host(), port).is_some() {
+            return Ok(None);
+        }
+        let is_self_request = self
+            .self_request_origin
+            .as_ref()
+            .is_some_and(|origin| origin.authority == authority);
+        if is_self_request || !proxy.applies_to(authority.host()) {
+            return Ok(None);
+        }
+        Ok(Some(ProxyConnect {
+            url: proxy.url().clone(),
+            credentials: proxy.credentials().await?.cloned(),
+        }))
+    }
+
     async fn send_request(
-        self,
-        request: OutgoingRequest,
+        &self,
+        mut request: OutgoingRequest,
         config: OutgoingRequestConfig,
         override_connect_addr: Option<SocketAddr>,
     ) -> Result<IncomingResponse, ErrorCode> {
@@ -547,13 +1032,64 @@ impl RequestSender {
             None
         };
 
+        let default_port = if use_tls { 443 } else { 80 };
+        let proxy = self
+            .proxy_for(
+                request.uri().authority().cloned(),
+                default_port,
+                override_connect_addr,
+            )
+            .await?;
+        // Plain HTTP requests are forwarded by HTTP proxies rather than tunnelled,
+        // so must carry the proxy credentials themselves
+        if let Some(ProxyConnect {
+            url,
+            credentials: Some(credentials),
+        }) = &proxy
+            && url.kind == ProxyKind::Http
+            && !use_tls
+        {
+            request
+                .headers_mut()
+                .insert(PROXY_AUTHORIZATION, credentials.basic_auth());
+        }
+
+        let audit = self.egress_audit.clone().map(|audit| {
+            let record = EgressRecord {
+                scheme: request.uri().scheme_str().unwrap_or_default().to_owned(),
+                // Excludes any userinfo
+                authority: request
+                    .uri()
+                    .authority()
+                    .map(|authority| match authority.port() {
+                        Some(port) => format!("{}:{port}", authority.host()),
+                        None => authority.host().to_owned(),
+                    })
+                    .unwrap_or_default(),
+                bytes_received: Some(0),
+                ..Default::default()
+            };
+            let sent = Arc::new(AtomicU64::new(0));
+            let counted = sent.clone();
+            request = std::mem::take(&mut request).map(|body| {
+                CountingBody {
+                    body,
+                    sent: counted,
+                }
+                .boxed_unsync()
+            });
+            (audit, record, sent, Instant::now())
+        });
+
         let resp = CONNECT_OPTIONS.scope(
             ConnectOptions {
-                blocked_networks: self.blocked_networks,
+                blocked_networks: self.blocked_networks.clone(),
                 connect_timeout,
                 tls_client_config,
                 override_connect_addr,
-                semaphore: self.semaphore,
+                dns_overrides: self.dns_overrides.clone(),
+                semaphore: self.semaphore.clone(),
+                proxy,
             },
             async move {
                 if use_tls {
@@ -588,6 +1124,26 @@ impl RequestSender {
 
         record_content_length_header(&span, resp.headers(), "http.response.header.content-length");
 
+        let resp = match audit {
+            Some((audit, mut record, sent, started)) => {
+                record.resolved_ip = resp
+                    .extensions()
+                    .get::<HttpInfo>()
+                    .map(|info| info.remote_addr().ip());
+                resp.map(|body| {
+                    AuditedBody {
+                        body,
+                        audit,
+                        record,
+                        sent,
+                        started,
+                    }
+                    .boxed_unsync()
+                })
+            }
+            None => resp,
+        };
+
         Ok(IncomingResponse {
             resp,
             worker: None,
@@ -647,8 +1203,19 @@ struct ConnectOptions {
     tls_client_config: Option<TlsClientConfig>,
     /// If set, override the address to connect to instead of using the given `uri`'s authority.
     override_connect_addr: Option<SocketAddr>,
+    /// Static DNS overrides, consulted before resolving the `uri`'s host.
+    dns_overrides: DnsOverrides,
     /// Semaphore to limit concurrent outbound connections.
     semaphore: ConnectionSemaphore,
+    /// The forward proxy to connect through, if any.
+    proxy: Option<ProxyConnect>,
+}
+
+/// A forward proxy to connect through, with its resolved credentials.
+#[derive(Clone)]
+struct ProxyConnect {
+    url: ProxyUrl,
+    credentials: Option<ProxyCredentials>,
 }
 
 impl ConnectOptions {
@@ -657,37 +1224,119 @@ impl ConnectOptions {
         &self,
         uri: &Uri,
         default_port: u16,
+    ) -> Result<PermittedTcpStream, ErrorCode> {
+        match &self.proxy {
+            // HTTP proxies forward plain HTTP requests sent directly to them
+            Some(proxy) if proxy.url.kind == ProxyKind::Http => {
+                // The proxy resolves host names itself, so blocked networks
+                // can only be enforced for IP address hosts
+                let authority = uri.authority().ok_or(ErrorCode::HttpRequestUriInvalid)?;
+                if let Some(ip) = parse_ip_host(authority.host()) {
+                    let port = authority.port_u16().unwrap_or(default_port);
+                    crate::remove_blocked_addrs(
+                        &self.blocked_networks,
+                        &mut vec![SocketAddr::new(ip, port)],
+                    )?;
+                }
+                let mut stream = self.connect_proxy(proxy).await?;
+                stream.proxied = true;
+                Ok(stream)
+            }
+            Some(proxy) => self.connect_tunnel(proxy, uri, default_port).await,
+            None => self.connect_direct(uri, default_port).await,
+        }
+    }
+
+    /// Establish a TCP connection directly to the given URI and default port.
+    async fn connect_direct(
+        &self,
+        uri: &Uri,
+        default_port: u16,
     ) -> Result<PermittedTcpStream, ErrorCode> {
         let mut socket_addrs = match self.override_connect_addr {
             Some(override_connect_addr) => vec![override_connect_addr],
             None => {
                 let authority = uri.authority().ok_or(ErrorCode::HttpRequestUriInvalid)?;
-
-                let host_and_port = if authority.port().is_some() {
-                    authority.as_str().to_string()
+                let port = authority.port_u16().unwrap_or(default_port);
+                if let Some(addr) = self.dns_overrides.get(authority.host(), port) {
+                    vec![addr]
                 } else {
-                    format!("{}:{}", authority.as_str(), default_port)
-                };
-
-                let socket_addrs = tokio::net::lookup_host(&host_and_port)
-                    .await
-                    .map_err(|err| {
-                        tracing::debug!(?host_and_port, ?err, "Error resolving host");
-                        dns_error("address not available".into(), 0)
-                    })?
-                    .collect::<Vec<_>>();
-                tracing::debug!(?host_and_port, ?socket_addrs, "Resolved host");
-                socket_addrs
+                    let host_and_port = if authority.port().is_some() {
+                        authority.as_str().to_string()
+                    } else {
+                        format!("{}:{}", authority.as_str(), default_port)
+                    };
+                    lookup_host(&host_and_port).await?
+                }
             }
         };
 
         // Remove blocked IPs
         crate::remove_blocked_addrs(&self.blocked_networks, &mut socket_addrs)?;
 
+        self.connect_addrs(&socket_addrs).await
+    }
+
+    /// Establish a TCP connection to the given proxy.
+    async fn connect_proxy(&self, proxy: &ProxyConnect) -> Result<PermittedTcpStream, ErrorCode> {
+        // The proxy address comes from the runtime config rather than the
+        // guest, so it isn't subject to blocked networks.
+        let socket_addrs = lookup_host(&proxy.url.host_and_port()).await?;
+        self.connect_addrs(&socket_addrs).await
+    }
+
+    /// Establish a TCP connection to the given URI and default port,
+    /// tunnelled through the given proxy.
+    ///
+    /// Blocked networks are only enforced where the destination address is
+    /// known locally, i.e. for IP address hosts and `socks5://` proxies.
+    async fn connect_tunnel(
+        &self,
+        proxy: &ProxyConnect,
+        uri: &Uri,
+        default_port: u16,
+    ) -> Result<PermittedTcpStream, ErrorCode> {
+        let authority = uri.authority().ok_or(ErrorCode::HttpRequestUriInvalid)?;
+        let host = authority.host();
+        let port = authority.port_u16().unwrap_or(default_port);
+        let is_ip = parse_ip_host(host).is_some();
+
+        // Host names are resolved by the proxy, except by `socks5://` proxies
+        let resolve_locally = is_ip || proxy.url.kind == ProxyKind::Socks5 { remote_dns: false };
+        let target = if resolve_locally {
+            let mut socket_addrs = lookup_host(&format!("{host}:{port}")).await?;
+            crate::remove_blocked_addrs(&self.blocked_networks, &mut socket_addrs)?;
+            let addr = socket_addrs
+                .first()
+                .ok_or_else(|| dns_error("address not available".into(), 0))?;
+            TargetAddr::Ip(*addr)
+        } else {
+            TargetAddr::Domain(host.into(), port)
+        };
+
+        let mut stream = self.connect_proxy(proxy).await?;
+        let credentials = proxy.credentials.as_ref();
+        let handshake = async {
+            match proxy.url.kind {
+                ProxyKind::Http => http_connect(&mut stream, &target, credentials).await,
+                ProxyKind::Socks5 { .. } => socks5_connect(&mut stream, target, credentials).await,
+            }
+        };
+        timeout(self.connect_timeout, handshake)
+            .await
+            .map_err(|_| ErrorCode::ConnectionTimeout)??;
+        Ok(stream)
+    }
+
+    /// Establish a TCP connection to one of the given addresses.
+    async fn connect_addrs(
+        &self,
+        socket_addrs: &[SocketAddr],
+    ) -> Result<PermittedTcpStream, ErrorCode> {
         let connect = async {
             // If we're limiting concurrent outbound requests, acquire a permit
             let permit = self.semaphore.acquire().await;
-            (TcpStream::connect(&*socket_addrs).await, permit)
+            (TcpStream::connect(socket_addrs).await, permit)
         };
 
         // Make sure that the connect timeout applies to both acquiring the outbound request permit and establishing the TCP connection,
@@ -702,6 +1351,7 @@ impl ConnectOptions {
         })?;
         Ok(PermittedTcpStream {
             inner: stream,
+            proxied: false,
             _permit: permit,
         })
     }
@@ -712,7 +1362,10 @@ impl ConnectOptions {
         uri: &Uri,
         default_port: u16,
     ) -> Result<TlsStream<PermittedTcpStream>, ErrorCode> {
-        let tcp_stream = self.connect_tcp(uri, default_port).await?;
+        let tcp_stream = match &self.proxy {
+            Some(proxy) => self.connect_tunnel(proxy, uri, default_port).await?,
+            None => self.connect_direct(uri, default_port).await?,
+        };
 
         let mut tls_client_config = self.tls_client_config.as_deref().unwrap().clone();
         tls_client_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
@@ -725,12 +1378,132 @@ impl ConnectOptions {
             })?
             .to_owned();
         connector.connect(domain, tcp_stream).await.map_err(|e| {
+            if let Some(mismatch) = PinMismatch::from_io_error(&e) {
+                tracing::warn!("{mismatch}");
+                return ErrorCode::InternalError(Some(mismatch.to_string()));
+            }
             tracing::warn!("tls protocol error: {e:?}");
             ErrorCode::TlsProtocolError
         })
     }
 }
 
+/// Resolves the given `host:port` to socket addresses.
+async fn lookup_host(host_and_port: &str) -> Result<Vec<SocketAddr>, ErrorCode> {
+    let socket_addrs = tokio::net::lookup_host(host_and_port)
+        .await
+        .map_err(|err| {
+            tracing::debug!(?host_and_port, ?err, "Error resolving host");
+            dns_error("address not available".into(), 0)
+        })?
+        .collect::<Vec<_>>();
+    tracing::debug!(?host_and_port, ?socket_addrs, "Resolved host");
+    Ok(socket_addrs)
+}
+
+/// Returns the IP address of a URI host which is an IP address literal.
+pub(crate) fn parse_ip_host(host: &str) -> Option<IpAddr> {
+    host.trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse()
+        .ok()
+}
+
+/// The maximum size of a proxy's response to a `CONNECT` request.
+const MAX_CONNECT_RESPONSE_SIZE: usize = 8 * 1024;
+
+/// Opens a tunnel to the given target through an HTTP proxy.
+async fn http_connect(
+    stream: &mut PermittedTcpStream,
+    target: &TargetAddr<'_>,
+    credentials: Option<&ProxyCredentials>,
+) -> Result<(), ErrorCode> {
+    let target = match target {
+        TargetAddr::Ip(addr) => addr.to_string(),
+        TargetAddr::Domain(host, port) => format!("{host}:{port}"),
+    };
+    let mut request = format!("CONNECT {target} HTTP/1.1\r\nHost: {target}\r\n");
+    if let Some(credentials) = credentials {
+        let auth = credentials.basic_auth();
+        request.push_str(&format!(
+            "{PROXY_AUTHORIZATION}: {}\r\n",
+            auth.to_str().unwrap_or_default()
+        ));
+    }
+    request.push_str("\r\n");
+    stream
+        .write_all(request.as_bytes())
+        .await
+        .map_err(|_| ErrorCode::ConnectionTerminated)?;
+
+    // Read the response head a byte at a time so that nothing sent through
+    // the tunnel afterwards is consumed
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        if head.len() >= MAX_CONNECT_RESPONSE_SIZE {
+            return Err(ErrorCode::HttpProtocolError);
+        }
+        let byte = stream
+            .read_u8()
+            .await
+            .map_err(|_| ErrorCode::ConnectionTerminated)?;
+        head.push(byte);
+    }
+
+    // e.g. `HTTP/1.1 200 Connection established`
+    let head = String::from_utf8_lossy(&head);
+    let status = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse::<u16>().ok())
+        .ok_or(ErrorCode::HttpProtocolError)?;
+    match status {
+        200..=299 => Ok(()),
+        403 | 407 => {
+            tracing::warn!(status, %target, "Proxy refused CONNECT request");
+            Err(ErrorCode::HttpRequestDenied)
+        }
+        _ => {
+            tracing::warn!(status, %target, "Proxy failed CONNECT request");
+            Err(ErrorCode::DestinationUnavailable)
+        }
+    }
+}
+
+/// Opens a tunnel to the given target through a SOCKS5 proxy.
+async fn socks5_connect(
+    stream: &mut PermittedTcpStream,
+    target: TargetAddr<'_>,
+    credentials: Option<&ProxyCredentials>,
+) -> Result<(), ErrorCode> {
+    let result = match credentials {
+        Some(credentials) => {
+            Socks5Stream::connect_with_password_and_socket(
+                stream,
+                target,
+                &credentials.username,
+                &credentials.password,
+            )
+            .await
+        }
+        None => Socks5Stream::connect_with_socket(stream, target).await,
+    };
+    result.map(drop).map_err(|err| {
+        tracing::warn!(?err, "SOCKS5 proxy error");
+        match err {
+            tokio_socks::Error::ConnectionNotAllowedByRuleset
+            | tokio_socks::Error::NoAcceptableAuthMethods
+            | tokio_socks::Error::PasswordAuthFailure(_) => ErrorCode::HttpRequestDenied,
+            tokio_socks::Error::NetworkUnreachable | tokio_socks::Error::HostUnreachable => {
+                ErrorCode::DestinationUnavailable
+            }
+            tokio_socks::Error::TtlExpired => ErrorCode::ConnectionTimeout,
+            tokio_socks::Error::ConnectionRefused => ErrorCode::ConnectionRefused,
+            _ => ErrorCode::ConnectionTerminated,
+        }
+    })
+}
+
 /// A connector the uses `ConnectOptions`
 #[derive(Clone)]
 struct HttpConnector;
@@ -841,6 +1614,9 @@ impl AsyncWrite for RustlsStream {
 struct PermittedTcpStream {
     /// The wrapped TCP stream.
     inner: TcpStream,
+    /// Whether this stream is connected to an HTTP proxy which forwards
+    /// requests, rather than to the destination.
+    proxied: bool,
     /// A permit indicating that this stream is allowed to exist.
     ///
     /// When this stream is dropped, the permit is also dropped, allowing another
@@ -850,7 +1626,8 @@ struct PermittedTcpStream {
 
 impl Connection for PermittedTcpStream {
     fn connected(&self) -> Connected {
-        self.inner.connected()
+        // Requests to forwarding proxies use absolute-form URIs
+        self.inner.connected().proxy(self.proxied)
     }
 }
 
@@ -913,6 +1690,43 @@ fn hyper_legacy_request_error(err: hyper_util::client::legacy::Error) -> ErrorCo
     ErrorCode::HttpProtocolError
 }
 
+/// Buffers a request body so that it can be sent more than once.
+///
+/// If the body is larger than [`MAX_RETRY_BODY_SIZE`] or has trailers,
+/// returns a body equivalent to the original instead.
+async fn buffer_for_retry(
+    mut body: UnsyncBoxBody<Bytes, ErrorCode>,
+) -> Result<Result<Bytes, UnsyncBoxBody<Bytes, ErrorCode>>, ErrorCode> {
+    if body.size_hint().lower() > MAX_RETRY_BODY_SIZE as u64 {
+        return Ok(Err(body));
+    }
+    let mut buffered = BytesMut::new();
+    while let Some(frame) = body.frame().await {
+        let trailers = match frame?.into_data() {
+            Ok(data) => {
+                buffered.extend_from_slice(&data);
+                if buffered.len() <= MAX_RETRY_BODY_SIZE {
+                    continue;
+                }
+                None
+            }
+            // Trailers aren't kept for a retry, so send the body as-is
+            Err(trailers) => Some(Ok(trailers)),
+        };
+        let read = std::iter::once(Ok(Frame::data(buffered.freeze()))).chain(trailers);
+        let frames = futures::stream::iter(read).chain(http_body_util::BodyStream::new(body));
+        return Ok(Err(StreamBody::new(frames).boxed_unsync()));
+    }
+    Ok(Ok(buffered.freeze()))
+}
+
+/// Returns a body with the given contents.
+pub(crate) fn full_body(bytes: Bytes) -> UnsyncBoxBody<Bytes, ErrorCode> {
+    Full::new(bytes)
+        .map_err(|never| match never {})
+        .boxed_unsync()
+}
+
 fn dns_error(rcode: String, info_code: u16) -> ErrorCode {
     ErrorCode::DnsError(
         wasmtime_wasi_http::p2::bindings::http::types::DnsErrorPayload {
@@ -962,7 +1776,9 @@ mod tests {
             tls_client_config: None,
             // Skip DNS by supplying the address directly.
             override_connect_addr: Some("127.0.0.1:1".parse().unwrap()),
+            dns_overrides: Default::default(),
             semaphore: conn_semaphore,
+            proxy: None,
         };
 
         // `connect_tcp` must time out while waiting for a permit rather than
@@ -976,4 +1792,113 @@ mod tests {
             "expected ConnectionTimeout"
         );
     }
+
+    #[tokio::test]
+    async fn dns_overrides_apply_per_port() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let options = ConnectOptions {
+            blocked_networks: BlockedNetworks::default(),
+            connect_timeout: Duration::from_secs(5),
+            tls_client_config: None,
+            override_connect_addr: None,
+            dns_overrides: DnsOverrides::new([(
+                ("test.example".into(), 443),
+                listener.local_addr().unwrap(),
+            )]),
+            semaphore: ConnectionSemaphore::new(None, None, "test", "app-id".into(), None),
+            proxy: None,
+        };
+
+        // The default port of the scheme is overridden...
+        options
+            .connect_direct(&Uri::from_static("https://test.example/"), 443)
+            .await
+            .expect("connection to the overriding address");
+        listener.accept().await.unwrap();
+
+        // ...as is the same port given explicitly
+        options
+            .connect_direct(&Uri::from_static("https://test.example:443/"), 443)
+            .await
+            .expect("connection to the overriding address");
+        listener.accept().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn http_proxy_rejects_blocked_ip_hosts() {
+        let options = ConnectOptions {
+            blocked_networks: BlockedNetworks::new([], true),
+            connect_timeout: Duration::from_millis(50),
+            tls_client_config: None,
+            override_connect_addr: None,
+            dns_overrides: Default::default(),
+            semaphore: ConnectionSemaphore::new(None, None, "test", "app-id".into(), None),
+            proxy: Some(ProxyConnect {
+                url: "http://proxy.example.com:3128".parse().unwrap(),
+                credentials: None,
+            }),
+        };
+
+        // The host is checked before connecting to the proxy.
+        let result = options
+            .connect_tcp(&Uri::from_static("http://10.0.0.1/"), 80)
+            .await;
+
+        assert!(
+            matches!(result, Err(ErrorCode::DestinationIpProhibited)),
+            "expected DestinationIpProhibited"
+        );
+    }
+
+    #[tokio::test]
+    async fn streamed_request_bodies_are_counted_as_sent() {
+        let chunks = [b"hello, ".as_slice(), b"world"]
+            .map(|chunk| Ok(Frame::data(Bytes::from_static(chunk))));
+        let streamed = StreamBody::new(futures::stream::iter(chunks)).boxed_unsync();
+        let sent = Arc::new(AtomicU64::new(0));
+        let body = CountingBody {
+            body: streamed,
+            sent: sent.clone(),
+        };
+        // A streamed body has no exact size up front...
+        assert_eq!(body.size_hint().exact(), None);
+
+        // ...so the bytes are counted as they're sent
+        let collected = body.collect().await.unwrap().to_bytes();
+        assert_eq!(collected, "hello, world");
+        assert_eq!(sent.load(Ordering::Relaxed), 12);
+    }
+
+    #[tokio::test]
+    async fn large_bodies_are_not_buffered_for_retries() {
+        let small = Bytes::from_static(b"hello");
+        let buffered = buffer_for_retry(full_body(small.clone())).await.unwrap();
+        assert_eq!(buffered.unwrap(), small);
+
+        let large = Bytes::from(vec![b'x'; MAX_RETRY_BODY_SIZE + 1]);
+        let chunks = large
+            .chunks(1024)
+            .map(|chunk| Ok(Frame::data(Bytes::copy_from_slice(chunk))))
+            .collect::<Vec<_>>();
+        let streamed = StreamBody::new(futures::stream::iter(chunks)).boxed_unsync();
+        let body = buffer_for_retry(streamed).await.unwrap().unwrap_err();
+        // The body that is sent instead is unchanged.
+        assert_eq!(body.collect().await.unwrap().to_bytes(), large);
+    }
+
+    #[tokio::test]
+    async fn bodies_with_trailers_are_not_buffered_for_retries() {
+        let mut trailers = HeaderMap::new();
+        trailers.insert("x-checksum", http::HeaderValue::from_static("abc"));
+        let frames = vec![
+            Ok(Frame::data(Bytes::from_static(b"hello"))),
+            Ok(Frame::trailers(trailers.clone())),
+        ];
+        let streamed = StreamBody::new(futures::stream::iter(frames)).boxed_unsync();
+        let body = buffer_for_retry(streamed).await.unwrap().unwrap_err();
+        // The body that is sent instead keeps its trailers.
+        let collected = body.collect().await.unwrap();
+        assert_eq!(collected.trailers(), Some(&trailers));
+        assert_eq!(collected.to_bytes(), "hello");
+    }
 }
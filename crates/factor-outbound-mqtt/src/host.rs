@@ -6,38 +6,57 @@ use spin_core::{
     wasmtime::component::{Accessor, Resource},
 };
 use spin_factor_otel::OtelFactorState;
-use spin_factor_outbound_networking::config::allowed_hosts::OutboundAllowedHosts;
-use spin_factor_outbound_networking::{ConnectionPermit, ConnectionSemaphore};
+use spin_factor_outbound_networking::audit::{AuditedConnection, EgressAudit};
+use spin_factor_outbound_networking::config::{
+    allowed_hosts::OutboundAllowedHosts, dns_overrides::DnsOverrides,
+};
+use spin_factor_outbound_networking::{
+    ComponentTlsClientConfigs, ConnectionPermit, ConnectionSemaphore,
+};
 use spin_world::spin::mqtt::mqtt as v3;
 use spin_world::v2::mqtt as v2;
 use tracing::{Level, instrument};
 
 use crate::{ClientCreator, allowed_hosts::AllowedHostChecker};
 
+/// The port MQTT connects to if none is given.
+const DEFAULT_PORT: u16 = 1883;
+/// The port MQTT over TLS connects to if none is given.
+const DEFAULT_TLS_PORT: u16 = 8883;
+
 pub struct InstanceState {
     allowed_hosts: AllowedHostChecker,
     connections: spin_resource_table::Table<(Arc<dyn MqttClient>, ConnectionPermit)>,
     create_client: Arc<dyn ClientCreator>,
+    dns_overrides: DnsOverrides,
+    tls_configs: ComponentTlsClientConfigs,
     semaphore: ConnectionSemaphore,
     otel: OtelFactorState,
     max_payload_size_bytes: Option<usize>,
+    egress_audit: Option<EgressAudit>,
 }
 
 impl InstanceState {
     pub fn new(
         allowed_hosts: OutboundAllowedHosts,
         create_client: Arc<dyn ClientCreator>,
+        dns_overrides: DnsOverrides,
+        tls_configs: ComponentTlsClientConfigs,
         semaphore: ConnectionSemaphore,
         otel: OtelFactorState,
         max_payload_size_bytes: Option<usize>,
+        egress_audit: Option<EgressAudit>,
     ) -> Self {
         Self {
             allowed_hosts: AllowedHostChecker::new(allowed_hosts),
             create_client,
+            dns_overrides,
+            tls_configs,
             connections: spin_resource_table::Table::new(1024),
             semaphore,
             otel,
             max_payload_size_bytes,
+            egress_audit,
         }
     }
 }
@@ -52,6 +71,28 @@ pub trait MqttClient: Send + Sync {
     ) -> Result<(), v3::Error>;
 }
 
+/// An [`MqttClient`] which tallies the messages it publishes, which are
+/// recorded to the egress audit log once the connection is dropped.
+struct AuditedMqttClient {
+    client: Arc<dyn MqttClient>,
+    audit: AuditedConnection,
+}
+
+#[async_trait]
+impl MqttClient for AuditedMqttClient {
+    async fn publish_bytes(
+        &self,
+        topic: String,
+        qos: v3::Qos,
+        payload: Vec<u8>,
+    ) -> Result<(), v3::Error> {
+        let size = topic.len() + payload.len();
+        self.client.publish_bytes(topic, qos, payload).await?;
+        self.audit.sent(size);
+        Ok(())
+    }
+}
+
 impl InstanceState {
     async fn is_address_allowed(&self, address: &str) -> Result<bool> {
         self.allowed_hosts.is_address_allowed(address).await
@@ -69,8 +110,16 @@ impl InstanceState {
             .acquire()
             .await
             .map_err(|_| v2::Error::TooManyConnections)?;
-        let client =
-            (self.create_client).create(address, username, password, keep_alive_interval)?;
+        let client = create_client(
+            self.create_client.as_ref(),
+            address,
+            username,
+            password,
+            keep_alive_interval,
+            &self.dns_overrides,
+            &self.tls_configs,
+            self.egress_audit.as_ref(),
+        )?;
         self.connections
             .push((client, permit))
             .map(Resource::new_own)
@@ -121,13 +170,23 @@ impl<T: Send> v3::HostConnectionWithStore<T> for crate::MqttFactorData {
         password: String,
         keep_alive_interval_in_secs: u64,
     ) -> Result<Resource<v3::Connection>, v3::Error> {
-        let (allowed_host_checker, create_client, semaphore) = accessor.with(|mut access| {
+        let (
+            allowed_host_checker,
+            client_creator,
+            dns_overrides,
+            tls_configs,
+            semaphore,
+            egress_audit,
+        ) = accessor.with(|mut access| {
             let host = access.get();
             host.otel.reparent_tracing_span();
             (
                 host.allowed_hosts.clone(),
                 host.create_client.clone(),
+                host.dns_overrides.clone(),
+                host.tls_configs.clone(),
                 host.semaphore.clone(),
+                host.egress_audit.clone(),
             )
         });
 
@@ -146,11 +205,15 @@ impl<T: Send> v3::HostConnectionWithStore<T> for crate::MqttFactorData {
             .await
             .map_err(|_| v3::Error::TooManyConnections)?;
 
-        let client = create_client.create(
+        let client = create_client(
+            client_creator.as_ref(),
             address,
             username,
             password,
             Duration::from_secs(keep_alive_interval_in_secs),
+            &dns_overrides,
+            &tls_configs,
+            egress_audit.as_ref(),
         )?;
 
         accessor.with(|mut access| {
@@ -284,6 +347,146 @@ impl v2::HostConnection for InstanceState {
     }
 }
 
+/// Creates a client for `address`, applying any static DNS override, and
+/// audits its connection if egress auditing is enabled.
+#[allow(clippy::too_many_arguments)]
+fn create_client(
+    creator: &dyn ClientCreator,
+    address: String,
+    username: String,
+    password: String,
+    keep_alive_interval: Duration,
+    dns_overrides: &DnsOverrides,
+    tls_configs: &ComponentTlsClientConfigs,
+    egress_audit: Option<&EgressAudit>,
+) -> Result<Arc<dyn MqttClient>, v3::Error> {
+    let audit = egress_audit.and_then(|audit| audit_connection(audit, &address, dns_overrides));
+    let client = match apply_dns_overrides(address, dns_overrides, tls_configs)? {
+        (address, None) => creator.create(address, username, password, keep_alive_interval),
+        (address, Some(tls_config)) => creator.create_with_tls_config(
+            address,
+            username,
+            password,
+            keep_alive_interval,
+            tls_config,
+        ),
+    }?;
+    Ok(match audit {
+        Some(audit) => Arc::new(AuditedMqttClient { client, audit }),
+        None => client,
+    })
+}
+
+/// Starts auditing a connection to the broker at `address`, recording the IP
+/// connected to if it is known without resolving the host name.
+fn audit_connection(
+    egress_audit: &EgressAudit,
+    address: &str,
+    dns_overrides: &DnsOverrides,
+) -> Option<AuditedConnection> {
+    let url = url::Url::parse(address).ok()?;
+    let port = match url.scheme() {
+        "mqtts" | "ssl" => url.port().unwrap_or(DEFAULT_TLS_PORT),
+        _ => url.port().unwrap_or(DEFAULT_PORT),
+    };
+    let host = url.host()?;
+    let audit = egress_audit.connection(url.scheme(), format!("{host}:{port}"));
+    match host {
+        url::Host::Ipv4(ip) => audit.resolved_ip(ip.into()),
+        url::Host::Ipv6(ip) => audit.resolved_ip(ip.into()),
+        url::Host::Domain(name) => {
+            if let Some(addr) = dns_overrides.get(name, port) {
+                audit.resolved_ip(addr.ip());
+            }
+        }
+    }
+    Some(audit)
+}
+
+/// Rewrites an MQTT address to connect to the overriding address if its host
+/// and port have a static DNS override.
+///
+/// The client takes the TLS server name from the address, so a rewritten TLS
+/// address is returned with a TLS config verifying the broker's certificate
+/// against the original host name.
+fn apply_dns_overrides(
+    address: String,
+    dns_overrides: &DnsOverrides,
+    tls_configs: &ComponentTlsClientConfigs,
+) -> Result<(String, Option<Arc<rustls::ClientConfig>>), v3::Error> {
+    if dns_overrides.is_empty() {
+        return Ok((address, None));
+    }
+    let Ok(mut url) = url::Url::parse(&address) else {
+        return Ok((address, None));
+    };
+    let (use_tls, default_port) = match url.scheme() {
+        "mqtt" | "tcp" => (false, DEFAULT_PORT),
+        "mqtts" | "ssl" => (true, DEFAULT_TLS_PORT),
+        _ => return Ok((address, None)),
+    };
+    let Some(host) = url.host_str().map(str::to_owned) else {
+        return Ok((address, None));
+    };
+    let Some(addr) = dns_overrides.get(&host, url.port().unwrap_or(default_port)) else {
+        return Ok((address, None));
+    };
+    if url.set_ip_host(addr.ip()).is_err() || url.set_port(Some(addr.port())).is_err() {
+        return Ok((address, None));
+    }
+    let tls_config = if use_tls {
+        let tls_config = tls_configs
+            .get_client_config(&host)
+            .verifying_server_name(&host)
+            .map_err(other_error_v3)?;
+        Some(tls_config.inner())
+    } else {
+        None
+    };
+    Ok((url.into(), tls_config))
+}
+
 pub fn other_error_v3(e: impl std::fmt::Display) -> v3::Error {
     v3::Error::Other(e.to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(address: &str) -> (String, Option<Arc<rustls::ClientConfig>>) {
+        let overrides = DnsOverrides::new([
+            (
+                ("broker.internal".into(), DEFAULT_PORT),
+                "127.0.0.1:11883".parse().unwrap(),
+            ),
+            (
+                ("broker.internal".into(), DEFAULT_TLS_PORT),
+                "127.0.0.1:18883".parse().unwrap(),
+            ),
+        ]);
+        let tls_configs = ComponentTlsClientConfigs::default();
+        apply_dns_overrides(address.into(), &overrides, &tls_configs).unwrap()
+    }
+
+    #[test]
+    fn dns_overrides_apply_to_plaintext_addresses() {
+        let (address, tls_config) = apply("mqtt://broker.internal?client_id=spin");
+        assert_eq!(address, "mqtt://127.0.0.1:11883?client_id=spin");
+        assert!(tls_config.is_none());
+
+        let (address, _) = apply("mqtt://broker.internal:1884");
+        assert_eq!(address, "mqtt://broker.internal:1884");
+    }
+
+    #[test]
+    fn dns_overrides_apply_to_tls_addresses() {
+        let (address, tls_config) = apply("mqtts://broker.internal");
+        assert_eq!(address, "mqtts://127.0.0.1:18883");
+        assert!(tls_config.is_some());
+
+        let (address, tls_config) = apply("mqtts://other.internal");
+        assert_eq!(address, "mqtts://other.internal");
+        assert!(tls_config.is_none());
+    }
+}
@@ -75,17 +75,22 @@ impl Factor for OutboundMqttFactor {
         &self,
         mut ctx: PrepareContext<T, Self>,
     ) -> anyhow::Result<Self::InstanceBuilder> {
-        let allowed_hosts = ctx
-            .instance_builder::<OutboundNetworkingFactor>()?
-            .allowed_hosts();
+        let outbound_networking = ctx.instance_builder::<OutboundNetworkingFactor>()?;
+        let allowed_hosts = outbound_networking.allowed_hosts();
+        let dns_overrides = outbound_networking.dns_overrides();
+        let tls_configs = outbound_networking.component_tls_configs();
+        let egress_audit = outbound_networking.egress_audit();
         let otel = OtelFactorState::from_prepare_context(&mut ctx)?;
 
         Ok(InstanceState::new(
             allowed_hosts,
             self.create_client.clone(),
+            dns_overrides,
+            tls_configs,
             ctx.app_state().semaphore.clone(),
             otel,
             ctx.app_state().max_payload_size_bytes,
+            egress_audit,
         ))
     }
 }
@@ -109,27 +114,28 @@ const MQTT_CHANNEL_CAP: usize = 1000;
 impl NetworkedMqttClient {
     /// Create a [`ClientCreator`] that creates a [`NetworkedMqttClient`].
     pub fn creator() -> Arc<dyn ClientCreator> {
-        Arc::new(|address, username, password, keep_alive_interval| {
-            Ok(Arc::new(NetworkedMqttClient::create(
-                address,
-                username,
-                password,
-                keep_alive_interval,
-            )?) as _)
-        })
+        Arc::new(NetworkedMqttClientCreator)
     }
 
     /// Create a new [`NetworkedMqttClient`] with the given address, username, password, and keep alive interval.
+    ///
+    /// TLS connections use `tls_config` if given, or else the client's default.
     pub fn create(
         address: String,
         username: String,
         password: String,
         keep_alive_interval: Duration,
+        tls_config: Option<Arc<rustls::ClientConfig>>,
     ) -> Result<Self, v3::Error> {
         let mut conn_opts = rumqttc::MqttOptions::parse_url(address).map_err(|e| {
             tracing::error!("MQTT URL parse error: {e:?}");
             v3::Error::InvalidAddress
         })?;
+        if let Some(tls_config) = tls_config {
+            conn_opts.set_transport(rumqttc::Transport::tls_with_config(
+                rumqttc::TlsConfiguration::Rustls(tls_config),
+            ));
+        }
         conn_opts.set_credentials(username, password);
         conn_opts.set_keep_alive(keep_alive_interval);
         let (client, event_loop) = AsyncClient::new(conn_opts, MQTT_CHANNEL_CAP);
@@ -180,6 +186,44 @@ impl MqttClient for NetworkedMqttClient {
     }
 }
 
+/// Creates [`NetworkedMqttClient`]s.
+struct NetworkedMqttClientCreator;
+
+impl ClientCreator for NetworkedMqttClientCreator {
+    fn create(
+        &self,
+        address: String,
+        username: String,
+        password: String,
+        keep_alive_interval: Duration,
+    ) -> Result<Arc<dyn MqttClient>, v3::Error> {
+        Ok(Arc::new(NetworkedMqttClient::create(
+            address,
+            username,
+            password,
+            keep_alive_interval,
+            None,
+        )?))
+    }
+
+    fn create_with_tls_config(
+        &self,
+        address: String,
+        username: String,
+        password: String,
+        keep_alive_interval: Duration,
+        tls_config: Arc<rustls::ClientConfig>,
+    ) -> Result<Arc<dyn MqttClient>, v3::Error> {
+        Ok(Arc::new(NetworkedMqttClient::create(
+            address,
+            username,
+            password,
+            keep_alive_interval,
+            Some(tls_config),
+        )?))
+    }
+}
+
 /// A trait for creating MQTT client.
 #[async_trait]
 pub trait ClientCreator: Send + Sync {
@@ -190,6 +234,23 @@ pub trait ClientCreator: Send + Sync {
         password: String,
         keep_alive_interval: Duration,
     ) -> Result<Arc<dyn MqttClient>, v3::Error>;
+
+    /// Creates a client for a TLS address which verifies the broker's
+    /// certificate with `tls_config`, as for an address redirected by a static
+    /// DNS override.
+    ///
+    /// By default, `tls_config` is ignored.
+    fn create_with_tls_config(
+        &self,
+        address: String,
+        username: String,
+        password: String,
+        keep_alive_interval: Duration,
+        tls_config: Arc<rustls::ClientConfig>,
+    ) -> Result<Arc<dyn MqttClient>, v3::Error> {
+        let _ = tls_config;
+        self.create(address, username, password, keep_alive_interval)
+    }
 }
 
 impl<F> ClientCreator for F
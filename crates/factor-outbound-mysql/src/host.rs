@@ -1,22 +1,216 @@
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use anyhow::Result;
 use opentelemetry_semantic_conventions::attribute as otel_attribute;
 use spin_core::wasmtime::component::{Accessor, FutureReader, Resource, StreamReader};
 use spin_factor_outbound_networking::ConnectionPermit;
+use spin_factor_outbound_networking::audit::AuditedConnection;
 use spin_telemetry::traces::{self, Blame};
 use spin_world::MAX_HOST_BUFFERED_BYTES;
-use spin_world::spin::mysql::mysql as v3;
+use spin_world::spin::mysql3_2_0::mysql as v3;
 use spin_world::v1::mysql as v1;
 use spin_world::v2::mysql as v2;
 use spin_world::v2::rdbms_types as v2_types;
-use tokio::sync::Mutex;
+use tokio::sync::{Mutex, mpsc};
 use tracing::field::Empty;
 use tracing::{Level, instrument};
 
-use crate::client::Client;
+use crate::client::{Client, QueryAsyncResult, audit_target};
 use crate::{InstanceState, InstanceStateInner, MysqlFactorData};
 
+/// The most rows read ahead of the guest if it doesn't say otherwise.
+const DEFAULT_PREFETCH_ROWS: usize = 4;
+/// The most rows the guest may ask to be read ahead of it.
+const MAX_PREFETCH_ROWS: usize = 1024;
+
+pub(crate) struct Connection<C> {
+    client: AuditedClient<C>,
+    _permit: ConnectionPermit,
+    /// Set while a transaction is open on the connection.
+    in_transaction: Arc<AtomicBool>,
+}
+
+impl<C> Connection<C> {
+    /// The connection's client, for statements run outside a transaction.
+    ///
+    /// Statements can't be run on the connection itself while a transaction
+    /// is open on it, as they would silently run as part of the transaction.
+    fn client(&self) -> Result<&AuditedClient<C>, v2::Error> {
+        if self.in_transaction.load(Ordering::Acquire) {
+            let err = v2::Error::Other(
+                "a transaction is open on this connection; run statements on the transaction instead"
+                    .into(),
+            );
+            traces::mark_as_error(&err, Some(Blame::Guest));
+            return Err(err);
+        }
+        Ok(&self.client)
+    }
+}
+
+/// A transaction, pinned to the client of the connection it was begun on.
+///
+/// A transaction which is dropped while still open, e.g. because the instance
+/// was torn down, is rolled back so its connection isn't left mid-transaction.
+pub(crate) struct Transaction<C: Client> {
+    client: AuditedClient<C>,
+    /// Cleared once the transaction has been committed or rolled back.
+    open: bool,
+    _guard: TransactionGuard,
+}
+
+impl<C: Client> Transaction<C> {
+    async fn commit(mut self) -> Result<(), v2::Error> {
+        let result = self.client.commit_transaction().await;
+        self.open = false;
+        result
+    }
+
+    async fn rollback(mut self) -> Result<(), v2::Error> {
+        let result = self.client.rollback_transaction().await;
+        self.open = false;
+        result
+    }
+}
+
+impl<C: Client> Drop for Transaction<C> {
+    fn drop(&mut self) {
+        if !self.open {
+            return;
+        }
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            tracing::warn!("no runtime to roll back abandoned MySQL transaction");
+            return;
+        };
+        let client = self.client.clone();
+        runtime.spawn(async move {
+            if let Err(e) = client.rollback_transaction().await {
+                tracing::warn!("failed to roll back abandoned MySQL transaction: {e:?}");
+            }
+        });
+    }
+}
+
+/// A connection's client, which tallies the statements sent and rows
+/// received on it. These are recorded to the egress audit log once the
+/// connection and any transactions on it are dropped.
+pub(crate) struct AuditedClient<C> {
+    client: Arc<Mutex<C>>,
+    audit: Option<Arc<AuditedConnection>>,
+}
+
+impl<C> Clone for AuditedClient<C> {
+    fn clone(&self) -> Self {
+        Self {
+            client: self.client.clone(),
+            audit: self.audit.clone(),
+        }
+    }
+}
+
+impl<C: Client> AuditedClient<C> {
+    fn sent(&self, statement: &str, params: usize) {
+        if let Some(audit) = &self.audit {
+            audit.sent(statement.len() + params);
+        }
+    }
+
+    async fn execute(
+        &self,
+        statement: String,
+        params: Vec<v2_types::ParameterValue>,
+    ) -> Result<(), v2::Error> {
+        self.sent(&statement, params.iter().map(|p| p.memory_size()).sum());
+        self.client.lock().await.execute(statement, params).await
+    }
+
+    async fn query(
+        &self,
+        statement: String,
+        params: Vec<v2_types::ParameterValue>,
+        max_result_bytes: usize,
+    ) -> Result<v2_types::RowSet, v2::Error> {
+        self.sent(&statement, params.iter().map(|p| p.memory_size()).sum());
+        let rowset = self
+            .client
+            .lock()
+            .await
+            .query(statement, params, max_result_bytes)
+            .await?;
+        if let Some(audit) = &self.audit {
+            audit.received(rowset.rows.iter().flatten().map(|v| v.memory_size()).sum());
+        }
+        Ok(rowset)
+    }
+
+    async fn query_async(
+        &self,
+        statement: String,
+        params: Vec<v3::ParameterValue>,
+        max_result_bytes: usize,
+        prefetch_rows: usize,
+    ) -> Result<QueryAsyncResult, v3::Error> {
+        self.sent(&statement, params.iter().map(|p| p.memory_size()).sum());
+        let result = C::query_async(
+            self.client.clone(),
+            statement,
+            params,
+            max_result_bytes,
+            prefetch_rows,
+        )
+        .await?;
+        let Some(audit) = self.audit.clone() else {
+            return Ok(result);
+        };
+        // Count the rows as they're streamed to the guest
+        let mut streamed = result.rows;
+        let (rows_tx, rows) = mpsc::channel(prefetch_rows);
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = rows_tx.closed() => return,
+                    row = streamed.recv() => {
+                        let Some(row) = row else { return };
+                        audit.received(row.iter().map(|v| v.memory_size()).sum());
+                        if rows_tx.send(row).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+            }
+        });
+        Ok(QueryAsyncResult { rows, ..result })
+    }
+
+    async fn begin_transaction(&self) -> Result<(), v2::Error> {
+        self.client.lock().await.begin_transaction().await
+    }
+
+    async fn commit_transaction(&self) -> Result<(), v2::Error> {
+        self.client.lock().await.commit_transaction().await
+    }
+
+    async fn rollback_transaction(&self) -> Result<(), v2::Error> {
+        self.client.lock().await.rollback_transaction().await
+    }
+}
+
+/// Marks a connection as having an open transaction for as long as it lives.
+struct TransactionGuard(Arc<AtomicBool>);
+
+impl TransactionGuard {
+    fn acquire(in_transaction: &Arc<AtomicBool>) -> Option<Self> {
+        (!in_transaction.swap(true, Ordering::AcqRel)).then(|| Self(in_transaction.clone()))
+    }
+}
+
+impl Drop for TransactionGuard {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
 impl<C: Client> InstanceStateInner<C> {
     async fn open_connection(
         &mut self,
@@ -38,16 +232,33 @@ impl<C: Client> InstanceStateInner<C> {
             traces::mark_as_error(&err, Some(Blame::Guest));
             return Err(err);
         }
-        let client = C::build_client(address).await.map_err(|e| {
-            // The guest supplies the address and credentials; connection
-            // failures (wrong password, TLS error, unreachable host, etc.)
-            // are the guest's problem.
-            let err = v2::Error::ConnectionFailed(format!("{e:?}"));
-            traces::mark_as_error(&err, Some(Blame::Guest));
-            err
-        })?;
+        let client = C::build_client(address, &self.dns_overrides)
+            .await
+            .map_err(|e| {
+                // The guest supplies the address and credentials; connection
+                // failures (wrong password, TLS error, unreachable host, etc.)
+                // are the guest's problem.
+                let err = v2::Error::ConnectionFailed(format!("{e:?}"));
+                traces::mark_as_error(&err, Some(Blame::Guest));
+                err
+            })?;
+        let audit = self.egress_audit.as_ref().and_then(|egress_audit| {
+            let (authority, resolved_ip) = audit_target(address, &self.dns_overrides)?;
+            let audit = egress_audit.connection("mysql", authority);
+            if let Some(ip) = resolved_ip {
+                audit.resolved_ip(ip);
+            }
+            Some(Arc::new(audit))
+        });
         self.connections
-            .push((Arc::new(Mutex::new(client)), permit))
+            .push(Connection {
+                client: AuditedClient {
+                    client: Arc::new(Mutex::new(client)),
+                    audit,
+                },
+                _permit: permit,
+                in_transaction: Default::default(),
+            })
             .map_err(|_| {
                 // The guest exceeded the host-imposed connection limit.
                 let err = v2::Error::ConnectionFailed("too many connections".into());
@@ -56,17 +267,66 @@ impl<C: Client> InstanceStateInner<C> {
             })
     }
 
-    fn get_client(&mut self, connection: u32) -> Result<Arc<Mutex<C>>, v2::Error> {
+    fn get_client(&mut self, connection: u32) -> Result<AuditedClient<C>, v2::Error> {
         self.connections
             .get(connection)
-            .map(|(conn, _permit)| conn.clone())
             .ok_or_else(|| {
                 // The connection table is managed entirely by the host, so a
                 // missing handle indicates a host-side bug, not a guest mistake.
                 let err = v2::Error::ConnectionFailed("no connection found".into());
                 traces::mark_as_error(&err, Some(Blame::Host));
                 err
+            })?
+            .client()
+            .cloned()
+    }
+
+    /// Records a new transaction on a connection, returning its handle and
+    /// the connection's client to begin it on.
+    fn open_transaction(&mut self, connection: u32) -> Result<(u32, AuditedClient<C>), v2::Error> {
+        let connection = self.connections.get(connection).ok_or_else(|| {
+            let err = v2::Error::ConnectionFailed("no connection found".into());
+            traces::mark_as_error(&err, Some(Blame::Host));
+            err
+        })?;
+        let guard = TransactionGuard::acquire(&connection.in_transaction).ok_or_else(|| {
+            let err = v2::Error::Other("a transaction is already open on this connection".into());
+            traces::mark_as_error(&err, Some(Blame::Guest));
+            err
+        })?;
+        let client = connection.client.clone();
+        let rep = self
+            .transactions
+            .push(Transaction {
+                client: client.clone(),
+                open: true,
+                _guard: guard,
             })
+            .map_err(|_| {
+                let err = v2::Error::Other("too many transactions".into());
+                traces::mark_as_error(&err, Some(Blame::Guest));
+                err
+            })?;
+        Ok((rep, client))
+    }
+
+    fn get_transaction_client(&mut self, transaction: u32) -> Result<AuditedClient<C>, v2::Error> {
+        self.transactions
+            .get(transaction)
+            .map(|transaction| transaction.client.clone())
+            .ok_or_else(|| {
+                let err = v2::Error::Other("no transaction found".into());
+                traces::mark_as_error(&err, Some(Blame::Host));
+                err
+            })
+    }
+
+    fn take_transaction(&mut self, transaction: u32) -> Result<Transaction<C>, v2::Error> {
+        self.transactions.remove(transaction).ok_or_else(|| {
+            let err = v2::Error::Other("no transaction found".into());
+            traces::mark_as_error(&err, Some(Blame::Host));
+            err
+        })
     }
 
     async fn is_address_allowed(&self, address: &str) -> Result<bool> {
@@ -129,8 +389,6 @@ impl<C: Client, T> v3::HostConnectionWithStore<T> for MysqlFactorData<C> {
             state.get_client(connection.rep())?
         };
         client
-            .lock()
-            .await
             .execute(statement, params.into_iter().map(Into::into).collect())
             .await
             .map_err(track_db_error_on_span)?;
@@ -151,10 +409,176 @@ impl<C: Client, T> v3::HostConnectionWithStore<T> for MysqlFactorData<C> {
             state.get_client(connection.rep())?
         };
 
-        let (columns, stream, future) =
-            C::query_async(client, statement, params, MAX_HOST_BUFFERED_BYTES)
-                .await
-                .map_err(|v| v3::Error::from(track_db_error_on_span(v2::Error::from(v))))?;
+        Self::query_on_client(accessor, client, statement, params, DEFAULT_PREFETCH_ROWS).await
+    }
+
+    #[instrument(name = "spin_outbound_mysql.query", skip(accessor, connection, params, options), err(level = Level::INFO), fields(otel.kind = "client", {otel_attribute::DB_SYSTEM_NAME} = "mysql", otel.name = statement))]
+    async fn query_with_options(
+        accessor: &Accessor<T, Self>,
+        connection: Resource<v3::Connection>,
+        statement: String,
+        params: Vec<v3::ParameterValue>,
+        options: v3::QueryOptions,
+    ) -> Result<QueryTuple, v3::Error> {
+        let state = accessor.with(|mut access| access.get().inner.clone());
+        let client = {
+            let mut state = state.lock().await;
+            state.otel.reparent_tracing_span();
+            state.get_client(connection.rep())?
+        };
+
+        Self::query_on_client(accessor, client, statement, params, prefetch_rows(&options)).await
+    }
+
+    #[instrument(name = "spin_outbound_mysql.begin_transaction", skip(accessor, connection), err(level = Level::INFO), fields(otel.kind = "client", {otel_attribute::DB_SYSTEM_NAME} = "mysql"))]
+    async fn begin_transaction(
+        accessor: &Accessor<T, Self>,
+        connection: Resource<v3::Connection>,
+    ) -> Result<Resource<v3::Transaction>, v3::Error> {
+        let state = accessor.with(|mut access| access.get().inner.clone());
+        let (rep, client) = {
+            let mut state = state.lock().await;
+            state.otel.reparent_tracing_span();
+            state.open_transaction(connection.rep())?
+        };
+        let begun = client.begin_transaction().await;
+        if let Err(err) = begun {
+            if let Some(mut transaction) = state.lock().await.transactions.remove(rep) {
+                transaction.open = false;
+            }
+            return Err(track_db_error_on_span(err).into());
+        }
+        Ok(Resource::new_own(rep))
+    }
+}
+
+impl<C: Client> v3::HostTransaction for InstanceState<C> {
+    async fn drop(&mut self, transaction: Resource<v3::Transaction>) -> Result<()> {
+        // The guest dropped the transaction without committing it
+        let transaction = self
+            .inner
+            .lock()
+            .await
+            .transactions
+            .remove(transaction.rep());
+        if let Some(transaction) = transaction
+            && let Err(e) = transaction.rollback().await
+        {
+            tracing::warn!("failed to roll back dropped MySQL transaction: {e:?}");
+        }
+        Ok(())
+    }
+}
+
+impl<C: Client, T> v3::HostTransactionWithStore<T> for MysqlFactorData<C> {
+    #[instrument(name = "spin_outbound_mysql.execute", skip(accessor, transaction, params), err(level = Level::INFO), fields(otel.kind = "client", {otel_attribute::DB_SYSTEM_NAME} = "mysql", otel.name = statement))]
+    async fn execute(
+        accessor: &Accessor<T, Self>,
+        transaction: Resource<v3::Transaction>,
+        statement: String,
+        params: Vec<v3::ParameterValue>,
+    ) -> Result<(), v3::Error> {
+        let state = accessor.with(|mut access| access.get().inner.clone());
+        let client = {
+            let mut state = state.lock().await;
+            state.otel.reparent_tracing_span();
+            state.get_transaction_client(transaction.rep())?
+        };
+        client
+            .execute(statement, params.into_iter().map(Into::into).collect())
+            .await
+            .map_err(track_db_error_on_span)?;
+        Ok(())
+    }
+
+    #[instrument(name = "spin_outbound_mysql.query", skip(accessor, transaction, params), err(level = Level::INFO), fields(otel.kind = "client", {otel_attribute::DB_SYSTEM_NAME} = "mysql", otel.name = statement))]
+    async fn query(
+        accessor: &Accessor<T, Self>,
+        transaction: Resource<v3::Transaction>,
+        statement: String,
+        params: Vec<v3::ParameterValue>,
+    ) -> Result<QueryTuple, v3::Error> {
+        let state = accessor.with(|mut access| access.get().inner.clone());
+        let client = {
+            let mut state = state.lock().await;
+            state.otel.reparent_tracing_span();
+            state.get_transaction_client(transaction.rep())?
+        };
+
+        Self::query_on_client(accessor, client, statement, params, DEFAULT_PREFETCH_ROWS).await
+    }
+
+    #[instrument(name = "spin_outbound_mysql.query", skip(accessor, transaction, params, options), err(level = Level::INFO), fields(otel.kind = "client", {otel_attribute::DB_SYSTEM_NAME} = "mysql", otel.name = statement))]
+    async fn query_with_options(
+        accessor: &Accessor<T, Self>,
+        transaction: Resource<v3::Transaction>,
+        statement: String,
+        params: Vec<v3::ParameterValue>,
+        options: v3::QueryOptions,
+    ) -> Result<QueryTuple, v3::Error> {
+        let state = accessor.with(|mut access| access.get().inner.clone());
+        let client = {
+            let mut state = state.lock().await;
+            state.otel.reparent_tracing_span();
+            state.get_transaction_client(transaction.rep())?
+        };
+
+        Self::query_on_client(accessor, client, statement, params, prefetch_rows(&options)).await
+    }
+
+    #[instrument(name = "spin_outbound_mysql.commit", skip(accessor, this), err(level = Level::INFO), fields(otel.kind = "client", {otel_attribute::DB_SYSTEM_NAME} = "mysql"))]
+    async fn commit(
+        accessor: &Accessor<T, Self>,
+        this: Resource<v3::Transaction>,
+    ) -> Result<(), v3::Error> {
+        Self::take_transaction(accessor, this)
+            .await?
+            .commit()
+            .await
+            .map_err(track_db_error_on_span)?;
+        Ok(())
+    }
+
+    #[instrument(name = "spin_outbound_mysql.rollback", skip(accessor, this), err(level = Level::INFO), fields(otel.kind = "client", {otel_attribute::DB_SYSTEM_NAME} = "mysql"))]
+    async fn rollback(
+        accessor: &Accessor<T, Self>,
+        this: Resource<v3::Transaction>,
+    ) -> Result<(), v3::Error> {
+        Self::take_transaction(accessor, this)
+            .await?
+            .rollback()
+            .await
+            .map_err(track_db_error_on_span)?;
+        Ok(())
+    }
+}
+
+impl<C: Client> MysqlFactorData<C> {
+    async fn take_transaction<T>(
+        accessor: &Accessor<T, Self>,
+        transaction: Resource<v3::Transaction>,
+    ) -> Result<Transaction<C>, v3::Error> {
+        let state = accessor.with(|mut access| access.get().inner.clone());
+        let mut state = state.lock().await;
+        state.otel.reparent_tracing_span();
+        Ok(state.take_transaction(transaction.rep())?)
+    }
+
+    async fn query_on_client<T>(
+        accessor: &Accessor<T, Self>,
+        client: AuditedClient<C>,
+        statement: String,
+        params: Vec<v3::ParameterValue>,
+        prefetch_rows: usize,
+    ) -> Result<QueryTuple, v3::Error> {
+        let QueryAsyncResult {
+            columns,
+            rows: stream,
+            error: future,
+        } = client
+            .query_async(statement, params, MAX_HOST_BUFFERED_BYTES, prefetch_rows)
+            .await
+            .map_err(|v| v3::Error::from(track_db_error_on_span(v2::Error::from(v))))?;
 
         let (stream, future) = accessor
             .with(|mut access| {
@@ -175,6 +599,13 @@ impl<C: Client, T> v3::HostConnectionWithStore<T> for MysqlFactorData<C> {
     }
 }
 
+/// The number of rows to read ahead of the guest for a query with `options`.
+fn prefetch_rows(options: &v3::QueryOptions) -> usize {
+    options.prefetch_rows.map_or(DEFAULT_PREFETCH_ROWS, |rows| {
+        (rows as usize).clamp(1, MAX_PREFETCH_ROWS)
+    })
+}
+
 impl<C: Client> v2::Host for InstanceState<C> {}
 
 impl<C: Client> v2::HostConnection for InstanceState<C> {
@@ -206,8 +637,6 @@ impl<C: Client> v2::HostConnection for InstanceState<C> {
         state.otel.reparent_tracing_span();
         state
             .get_client(connection.rep())?
-            .lock()
-            .await
             .execute(statement, params)
             .await
             .map_err(track_db_error_on_span)
@@ -225,8 +654,6 @@ impl<C: Client> v2::HostConnection for InstanceState<C> {
         state.otel.reparent_tracing_span();
         state
             .get_client(connection.rep())?
-            .lock()
-            .await
             .query(statement, params, MAX_HOST_BUFFERED_BYTES)
             .await
             .map_err(track_db_error_on_span)
@@ -239,7 +666,7 @@ impl<C: Client> v2::HostConnection for InstanceState<C> {
     }
 }
 
-impl<C: Send> v2_types::Host for InstanceState<C> {
+impl<C: Client> v2_types::Host for InstanceState<C> {
     fn convert_error(&mut self, error: v2::Error) -> Result<v2::Error> {
         Ok(error)
     }
@@ -319,3 +746,125 @@ fn track_db_error_on_span(err: v2::Error) -> v2::Error {
     traces::mark_as_error(&err, Some(blame));
     err
 }
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::AtomicUsize;
+
+    use spin_factor_outbound_networking::ConnectionSemaphore;
+    use spin_factor_outbound_networking::config::dns_overrides::DnsOverrides;
+    use spin_world::async_trait;
+
+    use super::*;
+
+    /// A client which only counts the transactions rolled back on it.
+    #[derive(Default)]
+    struct RollbackCounter(Arc<AtomicUsize>);
+
+    #[async_trait]
+    impl Client for RollbackCounter {
+        async fn build_client(_address: &str, _dns_overrides: &DnsOverrides) -> Result<Self> {
+            Ok(Self::default())
+        }
+
+        async fn execute(
+            &mut self,
+            _statement: String,
+            _params: Vec<v2_types::ParameterValue>,
+        ) -> Result<(), v2::Error> {
+            Ok(())
+        }
+
+        async fn query(
+            &mut self,
+            _statement: String,
+            _params: Vec<v2_types::ParameterValue>,
+            _max_result_bytes: usize,
+        ) -> Result<v2_types::RowSet, v2::Error> {
+            unimplemented!()
+        }
+
+        async fn query_async(
+            _client: Arc<Mutex<Self>>,
+            _statement: String,
+            _params: Vec<v3::ParameterValue>,
+            _max_result_bytes: usize,
+            _prefetch_rows: usize,
+        ) -> Result<QueryAsyncResult, v3::Error> {
+            unimplemented!()
+        }
+
+        async fn begin_transaction(&mut self) -> Result<(), v2::Error> {
+            Ok(())
+        }
+
+        async fn commit_transaction(&mut self) -> Result<(), v2::Error> {
+            Ok(())
+        }
+
+        async fn rollback_transaction(&mut self) -> Result<(), v2::Error> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    async fn connection(rollbacks: &Arc<AtomicUsize>) -> Connection<RollbackCounter> {
+        let semaphore = ConnectionSemaphore::new(None, None, "mysql", Arc::from("test-app"), None);
+        Connection {
+            client: AuditedClient {
+                client: Arc::new(Mutex::new(RollbackCounter(rollbacks.clone()))),
+                audit: None,
+            },
+            _permit: semaphore.acquire().await.unwrap(),
+            in_transaction: Default::default(),
+        }
+    }
+
+    fn begin(connection: &Connection<RollbackCounter>) -> Option<Transaction<RollbackCounter>> {
+        Some(Transaction {
+            client: connection.client.clone(),
+            open: true,
+            _guard: TransactionGuard::acquire(&connection.in_transaction)?,
+        })
+    }
+
+    #[tokio::test]
+    async fn connection_statements_fail_during_transaction() {
+        let rollbacks = Arc::default();
+        let connection = connection(&rollbacks).await;
+
+        let transaction = begin(&connection).unwrap();
+        assert!(matches!(connection.client(), Err(v2::Error::Other(_))));
+        assert!(begin(&connection).is_none());
+
+        transaction.commit().await.unwrap();
+        connection.client().unwrap();
+        assert_eq!(rollbacks.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn abandoned_transaction_is_rolled_back() {
+        let rollbacks = Arc::<AtomicUsize>::default();
+        let connection = connection(&rollbacks).await;
+
+        drop(begin(&connection).unwrap());
+        connection.client().unwrap();
+        // The rollback runs on a spawned task
+        while rollbacks.load(Ordering::SeqCst) == 0 {
+            tokio::task::yield_now().await;
+        }
+
+        begin(&connection).unwrap().rollback().await.unwrap();
+        assert_eq!(rollbacks.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_prefetch_rows() {
+        let options = |prefetch_rows| v3::QueryOptions { prefetch_rows };
+        assert_eq!(prefetch_rows(&options(None)), DEFAULT_PREFETCH_ROWS);
+        assert_eq!(prefetch_rows(&options(Some(100))), 100);
+        // A channel needs room for at least one row
+        assert_eq!(prefetch_rows(&options(Some(0))), 1);
+        assert_eq!(prefetch_rows(&options(Some(u32::MAX))), MAX_PREFETCH_ROWS);
+    }
+}
@@ -9,11 +9,13 @@ use mysql_async::Conn as MysqlClient;
 use runtime_config::RuntimeConfig;
 use spin_factor_otel::OtelFactorState;
 use spin_factor_outbound_networking::{
-    ConnectionPermit, ConnectionSemaphore, OutboundNetworkingFactor, build_connection_semaphore,
-    config::allowed_hosts::OutboundAllowedHosts,
+    ConnectionSemaphore, OutboundNetworkingFactor,
+    audit::EgressAudit,
+    build_connection_semaphore,
+    config::{allowed_hosts::OutboundAllowedHosts, dns_overrides::DnsOverrides},
 };
 use spin_factors::{Factor, FactorData, InitContext, RuntimeFactors, SelfInstanceBuilder};
-use spin_world::spin::mysql::mysql as v3;
+use spin_world::spin::mysql3_2_0::mysql as v3;
 use spin_world::v1::mysql as v1;
 use spin_world::v2::mysql as v2;
 use tokio::sync::Mutex;
@@ -60,15 +62,19 @@ impl<C: Send + Sync + Client + 'static> Factor for OutboundMysqlFactor<C> {
         &self,
         mut ctx: spin_factors::PrepareContext<T, Self>,
     ) -> anyhow::Result<Self::InstanceBuilder> {
-        let allowed_hosts = ctx
-            .instance_builder::<OutboundNetworkingFactor>()?
-            .allowed_hosts();
+        let outbound_networking = ctx.instance_builder::<OutboundNetworkingFactor>()?;
+        let allowed_hosts = outbound_networking.allowed_hosts();
+        let dns_overrides = outbound_networking.dns_overrides();
+        let egress_audit = outbound_networking.egress_audit();
         let otel = OtelFactorState::from_prepare_context(&mut ctx)?;
 
         Ok(InstanceState {
             inner: Arc::new(Mutex::new(InstanceStateInner {
                 allowed_hosts,
+                dns_overrides,
+                egress_audit,
                 connections: Default::default(),
+                transactions: Default::default(),
                 otel,
             })),
             semaphore: ctx.app_state().semaphore.clone(),
@@ -90,18 +96,22 @@ impl<C> OutboundMysqlFactor<C> {
     }
 }
 
-pub struct InstanceStateInner<C> {
+pub struct InstanceStateInner<C: Client> {
     allowed_hosts: OutboundAllowedHosts,
-    connections: spin_resource_table::Table<(Arc<Mutex<C>>, ConnectionPermit)>,
+    dns_overrides: DnsOverrides,
+    /// Records connections to the egress audit log, if enabled.
+    egress_audit: Option<EgressAudit>,
+    connections: spin_resource_table::Table<host::Connection<C>>,
+    transactions: spin_resource_table::Table<host::Transaction<C>>,
     otel: OtelFactorState,
 }
 
-pub struct InstanceState<C> {
+pub struct InstanceState<C: Client> {
     pub(crate) inner: Arc<Mutex<InstanceStateInner<C>>>,
     pub semaphore: ConnectionSemaphore,
 }
 
-impl<C: Send + 'static> SelfInstanceBuilder for InstanceState<C> {}
+impl<C: Client> SelfInstanceBuilder for InstanceState<C> {}
 
 pub struct MysqlFactorData<C: Client>(OutboundMysqlFactor<C>);
 
@@ -1,12 +1,13 @@
 use anyhow::{Result, bail};
 use spin_factor_outbound_mysql::OutboundMysqlFactor;
-use spin_factor_outbound_mysql::client::Client;
+use spin_factor_outbound_mysql::client::{Client, QueryAsyncResult};
 use spin_factor_outbound_networking::OutboundNetworkingFactor;
+use spin_factor_outbound_networking::config::dns_overrides::DnsOverrides;
 use spin_factor_variables::VariablesFactor;
 use spin_factors::{RuntimeFactors, anyhow};
 use spin_factors_test::{TestEnvironment, toml};
 use spin_world::async_trait;
-use spin_world::spin::mysql::mysql as v3;
+use spin_world::spin::mysql3_2_0::mysql as v3;
 use spin_world::v2::mysql::HostConnection;
 use spin_world::v2::mysql::{self as v2};
 use spin_world::v2::rdbms_types::{ParameterValue, RowSet};
@@ -110,7 +111,7 @@ pub struct MockClient {}
 
 #[async_trait]
 impl Client for MockClient {
-    async fn build_client(_address: &str) -> anyhow::Result<Self>
+    async fn build_client(_address: &str, _dns_overrides: &DnsOverrides) -> anyhow::Result<Self>
     where
         Self: Sized,
     {
@@ -142,17 +143,27 @@ impl Client for MockClient {
         _statement: String,
         _params: Vec<v3::ParameterValue>,
         _max_result_bytes: usize,
-    ) -> Result<
-        (
-            Vec<v3::Column>,
-            mpsc::Receiver<v3::Row>,
-            oneshot::Receiver<Result<(), v3::Error>>,
-        ),
-        v3::Error,
-    > {
-        let (_, rows_rx) = mpsc::channel(4);
-        let (err_tx, err_rx) = oneshot::channel();
+        prefetch_rows: usize,
+    ) -> Result<QueryAsyncResult, v3::Error> {
+        let (_, rows) = mpsc::channel(prefetch_rows);
+        let (err_tx, error) = oneshot::channel();
         _ = err_tx.send(Ok(()));
-        Ok((Vec::new(), rows_rx, err_rx))
+        Ok(QueryAsyncResult {
+            columns: Vec::new(),
+            rows,
+            error,
+        })
+    }
+
+    async fn begin_transaction(&mut self) -> Result<(), v2::Error> {
+        Ok(())
+    }
+
+    async fn commit_transaction(&mut self) -> Result<(), v2::Error> {
+        Ok(())
+    }
+
+    async fn rollback_transaction(&mut self) -> Result<(), v2::Error> {
+        Ok(())
     }
 }
@@ -0,0 +1,455 @@
+//! Egress auditing: a structured record of the outbound connections each
+//! component attempts.
+//!
+//! Every `allowed_outbound_hosts` check is audited, covering HTTP, database,
+//! MQTT, and socket connections alike. Factors which know more about a
+//! connection, such as its resolved IP or how many bytes it carried, may also
+//! record it with [`EgressAudit::record`], or tally it as it goes with an
+//! [`AuditedConnection`].
+//!
+//! Audit files are written by a background thread, so recording an event
+//! never waits on the disk.
+
+use std::{
+    fs::File,
+    io::Write as _,
+    net::IpAddr,
+    path::PathBuf,
+    sync::{
+        Arc, OnceLock,
+        atomic::{AtomicU64, Ordering},
+        mpsc,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::Context as _;
+use serde::{Deserialize, Serialize};
+use spin_outbound_networking_config::allowed_hosts::AllowedHostsAuditor;
+
+/// Egress audit configuration.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EgressAuditConfig {
+    /// Which events to record.
+    pub mode: EgressAuditMode,
+    /// Where to record them.
+    pub sink: EgressAuditSink,
+}
+
+/// Which events an [`EgressAuditLog`] records.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EgressAuditMode {
+    /// Record all events.
+    #[default]
+    All,
+    /// Record denied connections only.
+    Denied,
+}
+
+/// Where an [`EgressAuditLog`] records events.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EgressAuditSink {
+    /// Append JSON lines to the given file.
+    File(PathBuf),
+    /// Emit OpenTelemetry log records, with the JSON event as the body.
+    Otel,
+}
+
+/// An app-wide log of outbound connections.
+pub struct EgressAuditLog {
+    app_id: Arc<str>,
+    mode: EgressAuditMode,
+    sink: Sink,
+}
+
+enum Sink {
+    File(FileWriter),
+    Otel,
+}
+
+/// Appends lines to a file on a dedicated thread.
+///
+/// Dropping the writer waits for the queued lines to be written.
+struct FileWriter {
+    lines: Option<mpsc::Sender<Vec<u8>>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl FileWriter {
+    fn spawn(mut file: File) -> anyhow::Result<Self> {
+        let (lines, rx) = mpsc::channel::<Vec<u8>>();
+        let thread = std::thread::Builder::new()
+            .name("egress-audit-writer".into())
+            .spawn(move || {
+                for line in rx {
+                    // A single write keeps lines whole
+                    if let Err(err) = file.write_all(&line) {
+                        tracing::warn!(
+                            %err, "error.type" = "egress_audit_write_failed",
+                            "Failed to write egress audit event"
+                        );
+                    }
+                }
+            })
+            .context("failed to start egress audit writer")?;
+        Ok(Self {
+            lines: Some(lines),
+            thread: Some(thread),
+        })
+    }
+
+    fn write(&self, line: Vec<u8>) {
+        if let Some(lines) = &self.lines {
+            let _ = lines.send(line);
+        }
+    }
+}
+
+impl Drop for FileWriter {
+    fn drop(&mut self) {
+        // Closing the channel ends the thread once the queue is drained
+        self.lines.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl EgressAuditLog {
+    /// Opens the log described by the given config, creating or appending to
+    /// its file if any.
+    pub fn open(config: EgressAuditConfig, app_id: Arc<str>) -> anyhow::Result<Self> {
+        let sink = match config.sink {
+            EgressAuditSink::File(path) => {
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent).with_context(|| {
+                        format!("failed to create directory '{}'", parent.display())
+                    })?;
+                }
+                let file = File::options()
+                    .create(true)
+                    .append(true)
+                    .open(&path)
+                    .with_context(|| format!("failed to open '{}'", path.display()))?;
+                Sink::File(FileWriter::spawn(file)?)
+            }
+            EgressAuditSink::Otel => Sink::Otel,
+        };
+        Ok(Self {
+            app_id,
+            mode: config.mode,
+            sink,
+        })
+    }
+
+    fn write(&self, event: &EgressEvent) {
+        if self.mode == EgressAuditMode::Denied && event.decision == Decision::Allowed {
+            return;
+        }
+        let mut line = match serde_json::to_vec(event) {
+            Ok(line) => line,
+            Err(err) => {
+                tracing::warn!(%err, "Failed to serialize egress audit event");
+                return;
+            }
+        };
+        match &self.sink {
+            Sink::File(writer) => {
+                line.push(b'\n');
+                writer.write(line);
+            }
+            Sink::Otel => spin_telemetry::logs::handle_host_log(
+                String::from_utf8_lossy(&line).into_owned(),
+                [
+                    ("event.name", "spin.egress_audit".to_string()),
+                    ("component_id", event.component_id.to_string()),
+                    ("decision", event.decision.as_str().to_string()),
+                ],
+            ),
+        }
+    }
+}
+
+/// A component's view of an [`EgressAuditLog`].
+#[derive(Clone)]
+pub struct EgressAudit {
+    log: Arc<EgressAuditLog>,
+    component_id: Arc<str>,
+}
+
+impl EgressAudit {
+    pub fn new(log: Arc<EgressAuditLog>, component_id: impl Into<Arc<str>>) -> Self {
+        Self {
+            log,
+            component_id: component_id.into(),
+        }
+    }
+
+    /// Records an allowed connection or request, with whatever is known
+    /// about it.
+    pub fn record(&self, record: EgressRecord) {
+        self.log.write(&EgressEvent {
+            timestamp_ms: timestamp_ms(),
+            app_id: &self.log.app_id,
+            component_id: &self.component_id,
+            event: "connection",
+            scheme: &record.scheme,
+            authority: &record.authority,
+            decision: Decision::Allowed,
+            resolved_ip: record.resolved_ip,
+            bytes_sent: record.bytes_sent,
+            bytes_received: record.bytes_received,
+            duration_ms: record.duration.map(|d| d.as_millis() as u64),
+        });
+    }
+
+    /// Starts auditing a connection, which is recorded once the returned
+    /// [`AuditedConnection`] is dropped.
+    pub fn connection(
+        &self,
+        scheme: impl Into<String>,
+        authority: impl Into<String>,
+    ) -> AuditedConnection {
+        AuditedConnection {
+            audit: self.clone(),
+            scheme: scheme.into(),
+            authority: authority.into(),
+            resolved_ip: OnceLock::new(),
+            bytes_sent: AtomicU64::new(0),
+            bytes_received: AtomicU64::new(0),
+            started: Instant::now(),
+        }
+    }
+
+    /// Records a connection denied by host policy other than
+    /// `allowed_outbound_hosts`, such as blocked networks.
+    pub fn denied(&self, scheme: &str, authority: &str) {
+        self.check(scheme, authority, false);
+    }
+
+    fn check(&self, scheme: &str, authority: &str, allowed: bool) {
+        self.log.write(&EgressEvent {
+            timestamp_ms: timestamp_ms(),
+            app_id: &self.log.app_id,
+            component_id: &self.component_id,
+            event: "check",
+            scheme,
+            authority,
+            decision: if allowed {
+                Decision::Allowed
+            } else {
+                Decision::Denied
+            },
+            resolved_ip: None,
+            bytes_sent: None,
+            bytes_received: None,
+            duration_ms: None,
+        });
+    }
+}
+
+impl AllowedHostsAuditor for EgressAudit {
+    fn audit(&self, scheme: &str, authority: &str, allowed: bool) {
+        self.check(scheme, authority, allowed);
+    }
+}
+
+/// Details of an outbound connection or request for [`EgressAudit::record`].
+#[derive(Clone, Debug, Default)]
+pub struct EgressRecord {
+    pub scheme: String,
+    pub authority: String,
+    /// The IP address actually connected to.
+    pub resolved_ip: Option<IpAddr>,
+    pub bytes_sent: Option<u64>,
+    pub bytes_received: Option<u64>,
+    pub duration: Option<Duration>,
+}
+
+/// A connection which tallies its traffic as it goes, recording it to the
+/// egress audit log once dropped.
+pub struct AuditedConnection {
+    audit: EgressAudit,
+    scheme: String,
+    authority: String,
+    resolved_ip: OnceLock<IpAddr>,
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+    started: Instant,
+}
+
+impl AuditedConnection {
+    /// Sets the IP address connected to, unless already set.
+    pub fn resolved_ip(&self, ip: IpAddr) {
+        _ = self.resolved_ip.set(ip);
+    }
+
+    /// Adds to the bytes sent over the connection.
+    pub fn sent(&self, bytes: usize) {
+        self.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Adds to the bytes received over the connection.
+    pub fn received(&self, bytes: usize) {
+        self.bytes_received
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+}
+
+impl Drop for AuditedConnection {
+    fn drop(&mut self) {
+        self.audit.record(EgressRecord {
+            scheme: std::mem::take(&mut self.scheme),
+            authority: std::mem::take(&mut self.authority),
+            resolved_ip: self.resolved_ip.get().copied(),
+            bytes_sent: Some(*self.bytes_sent.get_mut()),
+            bytes_received: Some(*self.bytes_received.get_mut()),
+            duration: Some(self.started.elapsed()),
+        });
+    }
+}
+
+/// A line of the audit log.
+#[derive(Serialize)]
+struct EgressEvent<'a> {
+    timestamp_ms: u64,
+    app_id: &'a str,
+    component_id: &'a str,
+    /// Either "check", for `allowed_outbound_hosts` and other policy
+    /// decisions, or "connection", for completed connections and requests.
+    event: &'static str,
+    scheme: &'a str,
+    authority: &'a str,
+    decision: Decision,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    resolved_ip: Option<IpAddr>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bytes_sent: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bytes_received: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duration_ms: Option<u64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Decision {
+    Allowed,
+    Denied,
+}
+
+impl Decision {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Decision::Allowed => "allowed",
+            Decision::Denied => "denied",
+        }
+    }
+}
+
+fn timestamp_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_events(path: &std::path::Path) -> Vec<serde_json::Value> {
+        std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    fn audit(path: PathBuf, mode: EgressAuditMode) -> EgressAudit {
+        let config = EgressAuditConfig {
+            mode,
+            sink: EgressAuditSink::File(path),
+        };
+        let log = EgressAuditLog::open(config, "app".into()).unwrap();
+        EgressAudit::new(Arc::new(log), "component")
+    }
+
+    #[test]
+    fn records_checks_and_connections() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("logs/egress.jsonl");
+        let audit = audit(path.clone(), EgressAuditMode::All);
+
+        audit.audit("https", "example.com:443", true);
+        audit.audit("tcp", "10.0.0.1:25", false);
+        audit.record(EgressRecord {
+            scheme: "https".into(),
+            authority: "example.com:443".into(),
+            resolved_ip: Some("93.184.215.14".parse().unwrap()),
+            bytes_received: Some(1256),
+            duration: Some(Duration::from_millis(42)),
+            ..Default::default()
+        });
+        // Dropping the log waits for its events to be written
+        drop(audit);
+
+        let events = read_events(&path);
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0]["component_id"], "component");
+        assert_eq!(events[0]["event"], "check");
+        assert_eq!(events[0]["decision"], "allowed");
+        assert_eq!(events[1]["authority"], "10.0.0.1:25");
+        assert_eq!(events[1]["decision"], "denied");
+        assert_eq!(events[2]["event"], "connection");
+        assert_eq!(events[2]["resolved_ip"], "93.184.215.14");
+        assert_eq!(events[2]["bytes_received"], 1256);
+        assert_eq!(events[2]["duration_ms"], 42);
+        assert!(events[2].get("bytes_sent").is_none());
+    }
+
+    #[test]
+    fn records_audited_connections_once_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("egress.jsonl");
+        let audit = audit(path.clone(), EgressAuditMode::All);
+
+        let connection = audit.connection("postgres", "db.example.com:5432");
+        connection.resolved_ip("10.0.0.5".parse().unwrap());
+        connection.resolved_ip("10.0.0.6".parse().unwrap());
+        connection.sent(20);
+        connection.sent(12);
+        connection.received(100);
+        drop(audit);
+        assert!(!path.exists() || read_events(&path).is_empty());
+        drop(connection);
+
+        let events = read_events(&path);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0]["event"], "connection");
+        assert_eq!(events[0]["scheme"], "postgres");
+        assert_eq!(events[0]["authority"], "db.example.com:5432");
+        assert_eq!(events[0]["resolved_ip"], "10.0.0.5");
+        assert_eq!(events[0]["bytes_sent"], 32);
+        assert_eq!(events[0]["bytes_received"], 100);
+        assert!(events[0]["duration_ms"].is_u64());
+    }
+
+    #[test]
+    fn denied_mode_skips_allowed_events() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("egress.jsonl");
+        let audit = audit(path.clone(), EgressAuditMode::Denied);
+
+        audit.audit("https", "example.com:443", true);
+        audit.record(EgressRecord::default());
+        audit.denied("tcp", "10.0.0.1:25");
+        drop(audit);
+
+        let events = read_events(&path);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0]["authority"], "10.0.0.1:25");
+    }
+}
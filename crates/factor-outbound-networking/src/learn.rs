@@ -0,0 +1,209 @@
+//! Learn mode for `allowed_outbound_hosts`: connections a component's manifest
+//! doesn't allow are permitted, and the entries which would have allowed them
+//! are reported instead.
+
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{Context as _, bail};
+use toml_edit::{Array, DocumentMut, Item, Value};
+
+/// A trait for handling `allowed_outbound_hosts` entries found in learn mode
+pub trait LearnedHostHandler: Send + Sync {
+    /// Called the first time a component is found to need an entry. Calls
+    /// are serialized.
+    fn handle_learned_host(&self, learned: &LearnedHost);
+}
+
+impl<F: Fn(&LearnedHost) + Send + Sync> LearnedHostHandler for F {
+    fn handle_learned_host(&self, learned: &LearnedHost) {
+        self(learned);
+    }
+}
+
+/// An `allowed_outbound_hosts` entry needed by a component.
+#[derive(Debug)]
+pub struct LearnedHost {
+    pub component_id: String,
+    /// The most specific entry which allows the observed connection.
+    pub allowed_host: String,
+    /// The manifest the app was loaded from, if it was loaded from a file.
+    pub manifest_path: Option<PathBuf>,
+}
+
+impl LearnedHost {
+    /// Adds this entry to the component's `allowed_outbound_hosts` in the
+    /// app manifest, returning false if it was already there.
+    pub fn add_to_manifest(&self) -> anyhow::Result<bool> {
+        let Some(path) = &self.manifest_path else {
+            bail!("the app was not loaded from a manifest file");
+        };
+        add_to_manifest(path, &self.component_id, &self.allowed_host)
+    }
+}
+
+/// Reports each entry learned by an app's components once.
+pub(crate) struct HostsLearner {
+    handler: Arc<dyn LearnedHostHandler>,
+    manifest_path: Option<PathBuf>,
+    /// (component ID, entry) pairs already reported
+    learned: Mutex<HashSet<(String, String)>>,
+}
+
+impl HostsLearner {
+    pub fn new(handler: Arc<dyn LearnedHostHandler>, manifest_path: Option<PathBuf>) -> Self {
+        Self {
+            handler,
+            manifest_path,
+            learned: Default::default(),
+        }
+    }
+
+    pub fn learn(&self, component_id: &str, allowed_host: &str) {
+        // Hold the lock while handling so that handlers may edit the manifest
+        let mut learned = self.learned.lock().unwrap();
+        if learned.insert((component_id.to_string(), allowed_host.to_string())) {
+            self.handler.handle_learned_host(&LearnedHost {
+                component_id: component_id.to_string(),
+                allowed_host: allowed_host.to_string(),
+                manifest_path: self.manifest_path.clone(),
+            });
+        }
+    }
+}
+
+fn add_to_manifest(path: &Path, component_id: &str, allowed_host: &str) -> anyhow::Result<bool> {
+    let manifest = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read manifest '{}'", path.display()))?;
+    let Some(updated) = add_allowed_host(&manifest, component_id, allowed_host)? else {
+        return Ok(false);
+    };
+    std::fs::write(path, updated)
+        .with_context(|| format!("failed to write manifest '{}'", path.display()))?;
+    Ok(true)
+}
+
+/// Returns the given manifest with an `allowed_outbound_hosts` entry added to
+/// a component, or `None` if the component already has it.
+fn add_allowed_host(
+    manifest: &str,
+    component_id: &str,
+    allowed_host: &str,
+) -> anyhow::Result<Option<String>> {
+    let mut doc: DocumentMut = manifest.parse().context("manifest is not valid TOML")?;
+    let component = match doc.get_mut("component") {
+        // Version 2: `[component.<id>]`
+        Some(Item::Table(components)) => components
+            .get_mut(component_id)
+            .and_then(Item::as_table_like_mut),
+        // Version 1: `[[component]]` with `id = "<id>"`
+        Some(Item::ArrayOfTables(components)) => components
+            .iter_mut()
+            .find(|c| c.get("id").and_then(Item::as_str) == Some(component_id))
+            .map(|c| c as &mut dyn toml_edit::TableLike),
+        _ => None,
+    }
+    .with_context(|| format!("component '{component_id}' is not defined in the manifest"))?;
+
+    match component.get_mut("allowed_outbound_hosts") {
+        Some(item) => {
+            let hosts = item
+                .as_array_mut()
+                .context("allowed_outbound_hosts is not an array")?;
+            if hosts.iter().any(|h| h.as_str() == Some(allowed_host)) {
+                return Ok(None);
+            }
+            hosts.push(allowed_host);
+        }
+        None => {
+            let hosts = Array::from_iter([allowed_host]);
+            component.insert("allowed_outbound_hosts", Item::Value(Value::Array(hosts)));
+        }
+    }
+    Ok(Some(doc.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adds_allowed_hosts_to_v2_manifest() {
+        let manifest = r#"spin_manifest_version = 2
+
+[component.first]
+source = "first.wasm"
+allowed_outbound_hosts = ["https://example.com"] # existing
+
+[component.second]
+source = "second.wasm"
+"#;
+        let manifest = add_allowed_host(manifest, "first", "redis://cache")
+            .unwrap()
+            .unwrap();
+        let manifest = add_allowed_host(&manifest, "second", "https://example.com")
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            manifest,
+            r#"spin_manifest_version = 2
+
+[component.first]
+source = "first.wasm"
+allowed_outbound_hosts = ["https://example.com", "redis://cache"] # existing
+
+[component.second]
+source = "second.wasm"
+allowed_outbound_hosts = ["https://example.com"]
+"#
+        );
+        assert!(
+            add_allowed_host(&manifest, "first", "redis://cache")
+                .unwrap()
+                .is_none()
+        );
+        add_allowed_host(&manifest, "third", "redis://cache").unwrap_err();
+    }
+
+    #[test]
+    fn adds_allowed_hosts_to_v1_manifest() {
+        let manifest = r#"spin_manifest_version = "1"
+
+[[component]]
+id = "first"
+source = "first.wasm"
+"#;
+        let manifest = add_allowed_host(manifest, "first", "https://example.com")
+            .unwrap()
+            .unwrap();
+        assert!(manifest.contains(r#"allowed_outbound_hosts = ["https://example.com"]"#));
+    }
+
+    #[test]
+    fn reports_each_learned_host_once() {
+        let learned = Arc::new(Mutex::new(Vec::new()));
+        let handler = {
+            let learned = learned.clone();
+            move |host: &LearnedHost| {
+                learned
+                    .lock()
+                    .unwrap()
+                    .push((host.component_id.clone(), host.allowed_host.clone()))
+            }
+        };
+        let learner = HostsLearner::new(Arc::new(handler), None);
+        learner.learn("first", "https://example.com");
+        learner.learn("second", "https://example.com");
+        learner.learn("first", "https://example.com");
+        assert_eq!(
+            *learned.lock().unwrap(),
+            [
+                ("first".to_string(), "https://example.com".to_string()),
+                ("second".to_string(), "https://example.com".to_string()),
+            ]
+        );
+    }
+}
@@ -1,4 +1,6 @@
 mod allowed_hosts;
+pub mod audit;
+pub mod learn;
 pub mod runtime_config;
 mod tls;
 
@@ -7,29 +9,37 @@ use std::{collections::HashMap, sync::Arc};
 use futures_util::FutureExt as _;
 use opentelemetry_semantic_conventions::attribute as otel_attribute;
 use spin_factor_variables::VariablesFactor;
-use spin_factor_wasi::{SocketAddrUse, SocketPermitState, WasiFactor};
+use spin_factor_wasi::{
+    NameOverride, SocketAddrUse, SocketAuditor, SocketPermitState, SocketTally, WasiFactor,
+};
 use spin_factors::{
     ConfigureAppContext, Error, Factor, FactorInstanceBuilder, PrepareContext, RuntimeFactors,
     anyhow::{self, Context},
 };
-use spin_locked_app::APP_NAME_KEY;
+use spin_locked_app::{APP_NAME_KEY, ORIGIN_KEY};
 use spin_outbound_networking_config::allowed_hosts::{DisallowedHostHandler, OutboundAllowedHosts};
 use url::Url;
 
 use crate::{
-    allowed_hosts::allowed_outbound_hosts, runtime_config::RuntimeConfig, tls::TlsClientConfigs,
+    allowed_hosts::allowed_outbound_hosts,
+    audit::{AuditedConnection, EgressAudit, EgressAuditLog},
+    learn::{HostsLearner, LearnedHostHandler},
+    runtime_config::RuntimeConfig,
+    tls::TlsClientConfigs,
 };
 pub use allowed_hosts::validate_service_chaining_for_components;
 pub use spin_connection_semaphore::{ConnectionPermit, ConnectionSemaphore, LimitedSemaphore};
 
-pub use crate::tls::{ComponentTlsClientConfigs, TlsClientConfig};
+pub use crate::tls::{ComponentTlsClientConfigs, PinMismatch, TlsClientConfig};
 use config::allowed_hosts::AllowedHostsConfig;
 use config::blocked_networks::BlockedNetworks;
+use config::dns_overrides::{DnsOverrides, SocketDnsOverrides};
 pub use spin_outbound_networking_config as config;
 
 #[derive(Default)]
 pub struct OutboundNetworkingFactor {
     disallowed_host_handler: Option<Arc<dyn DisallowedHostHandler>>,
+    learned_host_handler: Option<Arc<dyn LearnedHostHandler>>,
 }
 
 impl OutboundNetworkingFactor {
@@ -42,6 +52,13 @@ impl OutboundNetworkingFactor {
     pub fn set_disallowed_host_handler(&mut self, handler: impl DisallowedHostHandler + 'static) {
         self.disallowed_host_handler = Some(Arc::new(handler));
     }
+
+    /// Puts instances in learn mode: connections disallowed by their
+    /// `allowed_outbound_hosts` are allowed, and the handler is called with
+    /// the entries which would have allowed them.
+    pub fn set_learned_host_handler(&mut self, handler: impl LearnedHostHandler + 'static) {
+        self.learned_host_handler = Some(Arc::new(handler));
+    }
 }
 
 impl Factor for OutboundNetworkingFactor {
@@ -74,9 +91,12 @@ impl Factor for OutboundNetworkingFactor {
             max_socket_connections,
             max_total_connections,
             wait_timeout,
+            dns_overrides,
+            egress_audit,
         } = ctx.take_runtime_config().unwrap_or_default();
 
         let blocked_networks = BlockedNetworks::new(block_networks, block_private_networks);
+        let dns_overrides = DnsOverrides::new(dns_overrides);
         let tls_client_configs = TlsClientConfigs::new(client_tls_configs)?;
         // Build the shared global semaphore from its limit, so the two are bound together from
         // creation as the pair is plumbed into per-factor `ConnectionSemaphore`s.
@@ -99,6 +119,23 @@ impl Factor for OutboundNetworkingFactor {
             .unwrap_or_else(|| "<unnamed>".into())
             .into();
 
+        let egress_audit_log = egress_audit
+            .map(|config| EgressAuditLog::open(config, app_id.clone()))
+            .transpose()
+            .context("failed to open egress audit log")?
+            .map(Arc::new);
+
+        let hosts_learner = match &self.learned_host_handler {
+            Some(handler) => {
+                let manifest_path = ctx
+                    .app()
+                    .get_metadata(ORIGIN_KEY)?
+                    .and_then(|origin: String| Url::parse(&origin).ok()?.to_file_path().ok());
+                Some(Arc::new(HostsLearner::new(handler.clone(), manifest_path)))
+            }
+            None => None,
+        };
+
         let socket_connection_semaphore =
             if max_socket_connections.is_some() || global_connection_semaphore.is_some() {
                 Some(ConnectionSemaphore::new(
@@ -115,10 +152,13 @@ impl Factor for OutboundNetworkingFactor {
         Ok(AppState {
             component_allowed_hosts,
             blocked_networks,
+            dns_overrides,
             tls_client_configs,
             socket_connection_semaphore,
             global_connection_semaphore,
             app_id,
+            egress_audit_log,
+            hosts_learner,
         })
     }
 
@@ -159,11 +199,26 @@ impl Factor for OutboundNetworkingFactor {
         .map(|res| res.map(Arc::new).map_err(Arc::new))
         .boxed()
         .shared();
-        let allowed_hosts = OutboundAllowedHosts::new(
+        let egress_audit = ctx
+            .app_state()
+            .egress_audit_log
+            .clone()
+            .map(|log| EgressAudit::new(log, ctx.app_component().id()));
+        let mut allowed_hosts = OutboundAllowedHosts::new(
             allowed_hosts_future.clone(),
             self.disallowed_host_handler.clone(),
         );
+        if let Some(audit) = &egress_audit {
+            allowed_hosts = allowed_hosts.with_auditor(Arc::new(audit.clone()));
+        }
+        if let Some(learner) = ctx.app_state().hosts_learner.clone() {
+            let component_id = ctx.app_component().id().to_string();
+            allowed_hosts = allowed_hosts.with_learner(Arc::new(move |allowed_host: &str| {
+                learner.learn(&component_id, allowed_host)
+            }));
+        }
         let blocked_networks = ctx.app_state().blocked_networks.clone();
+        let dns_overrides = ctx.app_state().dns_overrides.clone();
         let permit_state = ctx
             .app_state()
             .socket_connection_semaphore
@@ -176,10 +231,25 @@ impl Factor for OutboundNetworkingFactor {
                     wasi_builder.set_socket_permit_state(state);
                 }
 
+                let socket_dns_overrides = (!dns_overrides.is_empty())
+                    .then(|| Arc::new(SocketDnsOverrides::new(dns_overrides.clone())));
+                if let Some(overrides) = &socket_dns_overrides {
+                    wasi_builder.override_name_lookup(WasiNameOverride(overrides.clone()));
+                }
+                if let Some(audit) = &egress_audit {
+                    wasi_builder.audit_sockets(WasiSocketAuditor {
+                        audit: audit.clone(),
+                        dns_overrides: socket_dns_overrides.clone(),
+                    });
+                }
+
                 let allowed_hosts = allowed_hosts.clone();
+                let egress_audit = egress_audit.clone();
                 wasi_builder.outbound_socket_addr_check(move |addr, addr_use| {
                     let allowed_hosts = allowed_hosts.clone();
                     let blocked_networks = blocked_networks.clone();
+                    let egress_audit = egress_audit.clone();
+                    let socket_dns_overrides = socket_dns_overrides.clone();
                     async move {
                         let scheme = match addr_use {
                             SocketAddrUse::TcpBind => return false,
@@ -188,14 +258,19 @@ impl Factor for OutboundNetworkingFactor {
                             | SocketAddrUse::UdpConnect
                             | SocketAddrUse::UdpOutgoingDatagram => "udp",
                         };
-                        if !allowed_hosts
-                            .check_url(&addr.to_string(), scheme)
-                            .await
-                            .unwrap_or(
-                                // TODO: should this trap (somehow)?
-                                false,
-                            )
+                        // Connections redirected by a DNS override are checked
+                        // against the name and port the guest connected to
+                        let host = match socket_dns_overrides
+                            .as_ref()
+                            .and_then(|overrides| overrides.original_host(addr))
                         {
+                            Some((name, port)) => format!("{name}:{port}"),
+                            None => addr.to_string(),
+                        };
+                        if !allowed_hosts.check_url(&host, scheme).await.unwrap_or(
+                            // TODO: should this trap (somehow)?
+                            false,
+                        ) {
                             return false;
                         }
                         if blocked_networks.is_blocked(&addr) {
@@ -204,6 +279,9 @@ impl Factor for OutboundNetworkingFactor {
                                 ?addr,
                                 "destination IP prohibited by runtime config"
                             );
+                            if let Some(audit) = &egress_audit {
+                                audit.denied(scheme, &addr.to_string());
+                            }
                             return false;
                         }
                         true
@@ -222,16 +300,67 @@ impl Factor for OutboundNetworkingFactor {
         Ok(InstanceBuilder {
             allowed_hosts,
             blocked_networks: ctx.app_state().blocked_networks.clone(),
+            dns_overrides,
             component_tls_client_configs: component_tls_configs,
+            egress_audit,
         })
     }
 }
 
+/// Applies static DNS overrides to `wasi:sockets`.
+struct WasiNameOverride(Arc<SocketDnsOverrides>);
+
+impl NameOverride for WasiNameOverride {
+    fn resolve(&self, name: &str) -> Option<std::net::IpAddr> {
+        self.0.resolve(name)
+    }
+
+    fn redirect(&self, addr: std::net::SocketAddr) -> Option<std::net::SocketAddr> {
+        self.0.redirect(addr)
+    }
+}
+
+/// Records TCP connections made with `wasi:sockets` to the egress audit log.
+struct WasiSocketAuditor {
+    audit: EgressAudit,
+    dns_overrides: Option<Arc<SocketDnsOverrides>>,
+}
+
+impl SocketAuditor for WasiSocketAuditor {
+    fn connect(&self, addr: std::net::SocketAddr) -> Arc<dyn SocketTally> {
+        // Connections redirected by a DNS override are recorded under the
+        // name and port the guest connected to
+        let authority = match self
+            .dns_overrides
+            .as_ref()
+            .and_then(|overrides| overrides.original_host(addr))
+        {
+            Some((name, port)) => format!("{name}:{port}"),
+            None => addr.to_string(),
+        };
+        let connection = self.audit.connection("tcp", authority);
+        connection.resolved_ip(addr.ip());
+        Arc::new(connection)
+    }
+}
+
+impl SocketTally for AuditedConnection {
+    fn sent(&self, bytes: usize) {
+        AuditedConnection::sent(self, bytes)
+    }
+
+    fn received(&self, bytes: usize) {
+        AuditedConnection::received(self, bytes)
+    }
+}
+
 pub struct AppState {
     /// Component ID -> Allowed host list
     component_allowed_hosts: HashMap<String, Arc<[String]>>,
     /// Blocked IP networks
     blocked_networks: BlockedNetworks,
+    /// Static host resolutions
+    dns_overrides: DnsOverrides,
     /// TLS client configs
     tls_client_configs: TlsClientConfigs,
     /// Pre-built semaphore for TCP/UDP socket quota enforcement (global + socket-specific).
@@ -244,6 +373,10 @@ pub struct AppState {
     /// Identifier of this app, used for tenant attribution on tracing events emitted by
     /// outbound factors' connection semaphores. Resolved once at configure-app time.
     app_id: Arc<str>,
+    /// Egress audit log, if configured.
+    egress_audit_log: Option<Arc<EgressAuditLog>>,
+    /// Learns allowed hosts, if in learn mode.
+    hosts_learner: Option<Arc<HostsLearner>>,
 }
 
 impl AppState {
@@ -295,7 +428,9 @@ pub fn build_connection_semaphore(
 pub struct InstanceBuilder {
     allowed_hosts: OutboundAllowedHosts,
     blocked_networks: BlockedNetworks,
+    dns_overrides: DnsOverrides,
     component_tls_client_configs: ComponentTlsClientConfigs,
+    egress_audit: Option<EgressAudit>,
 }
 
 impl InstanceBuilder {
@@ -307,9 +442,19 @@ impl InstanceBuilder {
         self.blocked_networks.clone()
     }
 
+    pub fn dns_overrides(&self) -> DnsOverrides {
+        self.dns_overrides.clone()
+    }
+
     pub fn component_tls_configs(&self) -> ComponentTlsClientConfigs {
         self.component_tls_client_configs.clone()
     }
+
+    /// Returns this component's egress audit, if configured, for recording
+    /// connection details beyond `allowed_outbound_hosts` checks.
+    pub fn egress_audit(&self) -> Option<EgressAudit> {
+        self.egress_audit.clone()
+    }
 }
 
 impl FactorInstanceBuilder for InstanceBuilder {
@@ -20,6 +20,10 @@ pub struct RuntimeConfig {
     pub max_total_connections: Option<usize>,
     /// If set, limits how long `acquire` will wait for a socket connection permit.
     pub wait_timeout: Option<std::time::Duration>,
+    /// Static resolutions of (host, port) to the address to connect to instead.
+    pub dns_overrides: Vec<((String, u16), std::net::SocketAddr)>,
+    /// If set, records outbound connections to an egress audit log.
+    pub egress_audit: Option<crate::audit::EgressAuditConfig>,
 }
 
 /// TLS configuration for one or more component(s) and host(s).
@@ -55,6 +59,22 @@ pub struct ClientTlsRuntimeConfig {
     /// A certificate and private key to be used as the client certificate for
     /// "mutual TLS" (mTLS).
     pub client_cert: Option<ClientCertRuntimeConfig>,
+
+    /// SHA-256 hashes of DER-encoded SubjectPublicKeyInfos.
+    ///
+    /// If non-empty, connections are refused unless a certificate in the
+    /// server's chain has one of these public keys. This is checked in
+    /// addition to the usual certificate verification.
+    pub pinned_public_keys: Vec<[u8; 32]>,
+
+    /// The minimum TLS version to negotiate.
+    ///
+    /// By default this is TLS 1.2.
+    pub min_tls_version: TlsVersion,
+
+    /// If non-empty, the names of the only cipher suites to offer, e.g.
+    /// `TLS13_AES_256_GCM_SHA384`.
+    pub cipher_suites: Vec<String>,
 }
 
 impl Default for ClientTlsRuntimeConfig {
@@ -66,10 +86,23 @@ impl Default for ClientTlsRuntimeConfig {
             use_platform_roots: true,
             use_webpki_roots: true,
             client_cert: None,
+            pinned_public_keys: vec![],
+            min_tls_version: TlsVersion::default(),
+            cipher_suites: vec![],
         }
     }
 }
 
+/// A TLS protocol version.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, serde::Deserialize)]
+pub enum TlsVersion {
+    #[default]
+    #[serde(rename = "1.2")]
+    Tls12,
+    #[serde(rename = "1.3")]
+    Tls13,
+}
+
 #[derive(Debug)]
 pub struct ClientCertRuntimeConfig {
     pub cert_chain: Vec<CertificateDer<'static>>,
@@ -5,14 +5,18 @@ use serde::{Deserialize, Deserializer};
 use spin_factors::runtime_config::toml::GetTomlValue;
 use std::{
     borrow::Cow,
+    collections::BTreeMap,
+    net::SocketAddr,
     path::{Path, PathBuf},
 };
 
-use super::ClientTlsRuntimeConfig;
+use super::{ClientTlsRuntimeConfig, TlsVersion};
+use crate::audit::{EgressAuditConfig, EgressAuditMode, EgressAuditSink};
 
 /// Spin's default handling of the runtime configuration for outbound networking.
 pub struct SpinRuntimeConfig {
     runtime_config_dir: PathBuf,
+    log_dir: Option<PathBuf>,
 }
 
 impl SpinRuntimeConfig {
@@ -23,15 +27,30 @@ impl SpinRuntimeConfig {
     pub fn new(runtime_config_dir: impl Into<PathBuf>) -> Self {
         Self {
             runtime_config_dir: runtime_config_dir.into(),
+            log_dir: None,
         }
     }
 
+    /// Sets the log directory, where the egress audit log is written by
+    /// default.
+    pub fn with_log_dir(mut self, log_dir: Option<PathBuf>) -> Self {
+        self.log_dir = log_dir;
+        self
+    }
+
     /// Get the runtime configuration for client TLS from a TOML table.
     ///
     /// Expects table to be in the format:
     /// ````toml
     /// [outbound_networking]
     /// block_networks = ["1.1.1.1/32", "private"]
+    /// # Connect to the given addresses instead of resolving these hosts and ports
+    /// resolve = { "api.partner.com:443" = "127.0.0.1:8443" }
+    ///
+    /// [outbound_networking.audit]
+    /// mode = "denied"           # optional; "all" (default) or "denied"
+    /// sink = "file"             # optional; "file" (default) or "otel"
+    /// path = "egress.jsonl"     # optional; defaults to egress_audit.jsonl in the log dir
     ///
     /// [[client_tls]]
     /// component_ids = ["example-component"]
@@ -41,6 +60,10 @@ impl SpinRuntimeConfig {
     /// ca_roots_file = "path/to/roots.crt"
     /// client_cert_file = "path/to/client.crt"
     /// client_private_key_file = "path/to/client.key"
+    /// min_tls_version = "1.3"   # optional; "1.2" (default) or "1.3"
+    /// cipher_suites = ["TLS13_AES_256_GCM_SHA384"]  # optional; defaults to all supported
+    /// # Optional; refuse connections unless the server's chain has one of these public keys
+    /// pinned_public_keys = ["sha256/base64-encoded-spki-hash="]
     /// ```
     pub fn config_from_table(
         &self,
@@ -76,10 +99,44 @@ impl SpinRuntimeConfig {
             max_socket_connections: outbound_networking.max_socket_connections,
             max_total_connections: outbound_networking.max_total_connections,
             wait_timeout: None,
+            dns_overrides: parse_dns_overrides(outbound_networking.resolve)
+                .context("invalid `resolve` in [outbound_networking]")?,
+            egress_audit: outbound_networking
+                .audit
+                .map(|audit| self.egress_audit_config(audit))
+                .transpose()
+                .context("invalid [outbound_networking.audit]")?,
         };
         Ok(Some(runtime_config))
     }
 
+    fn egress_audit_config(&self, audit: AuditToml) -> anyhow::Result<EgressAuditConfig> {
+        let sink = match audit.sink {
+            AuditSinkToml::File => {
+                let path = match audit.path {
+                    Some(path) => self.runtime_config_dir.join(path),
+                    None => self
+                        .log_dir
+                        .as_ref()
+                        .context("'path' is required when there is no log directory")?
+                        .join("egress_audit.jsonl"),
+                };
+                EgressAuditSink::File(path)
+            }
+            AuditSinkToml::Otel => {
+                ensure!(
+                    audit.path.is_none(),
+                    "'path' is only valid for sink = \"file\""
+                );
+                EgressAuditSink::Otel
+            }
+        };
+        Ok(EgressAuditConfig {
+            mode: audit.mode,
+            sink,
+        })
+    }
+
     /// Attempts to parse the `[outbound_networking]` table.
     fn outbound_networking_from_table(
         &self,
@@ -121,6 +178,9 @@ impl SpinRuntimeConfig {
             ca_roots_file,
             client_cert_file,
             client_private_key_file,
+            pinned_public_keys,
+            min_tls_version,
+            cipher_suites,
         } = toml_config;
         ensure!(
             !component_ids.is_empty(),
@@ -172,6 +232,9 @@ impl SpinRuntimeConfig {
             use_platform_roots,
             use_webpki_roots,
             client_cert,
+            pinned_public_keys,
+            min_tls_version,
+            cipher_suites,
         })
     }
 
@@ -208,6 +271,12 @@ struct ClientTlsToml {
     ca_roots_file: Option<PathBuf>,
     client_cert_file: Option<PathBuf>,
     client_private_key_file: Option<PathBuf>,
+    #[serde(default, deserialize_with = "deserialize_public_key_pins")]
+    pinned_public_keys: Vec<[u8; 32]>,
+    #[serde(default)]
+    min_tls_version: TlsVersion,
+    #[serde(default)]
+    cipher_suites: Vec<String>,
 }
 
 fn deserialize_hosts<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
@@ -218,6 +287,26 @@ fn deserialize_hosts<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<S
     Ok(hosts)
 }
 
+/// Deserializes `sha256/<base64>` public key pins.
+fn deserialize_public_key_pins<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<[u8; 32]>, D::Error> {
+    use base64::Engine as _;
+    let pins = Vec::<String>::deserialize(deserializer)?;
+    pins.iter()
+        .map(|pin| {
+            pin.strip_prefix("sha256/")
+                .and_then(|hash| base64::engine::general_purpose::STANDARD.decode(hash).ok())
+                .and_then(|hash| <[u8; 32]>::try_from(hash).ok())
+                .ok_or_else(|| {
+                    <D::Error as serde::de::Error>::custom(format!(
+                        "invalid public key pin {pin:?}; expected \"sha256/<base64-encoded SHA-256 hash>\""
+                    ))
+                })
+        })
+        .collect()
+}
+
 #[derive(Debug, Default, Deserialize)]
 #[serde(deny_unknown_fields)]
 struct OutboundNetworkingToml {
@@ -225,6 +314,52 @@ struct OutboundNetworkingToml {
     block_networks: Vec<CidrOrPrivate>,
     max_socket_connections: Option<usize>,
     max_total_connections: Option<usize>,
+    #[serde(default)]
+    resolve: BTreeMap<String, String>,
+    audit: Option<AuditToml>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AuditToml {
+    #[serde(default)]
+    mode: EgressAuditMode,
+    #[serde(default)]
+    sink: AuditSinkToml,
+    path: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum AuditSinkToml {
+    #[default]
+    File,
+    Otel,
+}
+
+/// Parses `"host:port" = "ip:port"` pairs.
+fn parse_dns_overrides(
+    resolve: BTreeMap<String, String>,
+) -> anyhow::Result<Vec<((String, u16), SocketAddr)>> {
+    resolve
+        .into_iter()
+        .map(|(host_and_port, addr)| {
+            let authority: http::uri::Authority = host_and_port
+                .parse()
+                .with_context(|| format!("invalid host {host_and_port:?}"))?;
+            let port = authority.port_u16().with_context(|| {
+                format!("invalid host {host_and_port:?}; expected `<host>:<port>`")
+            })?;
+            ensure!(
+                authority.as_str() == host_and_port && !host_and_port.contains(['*', '@']),
+                "invalid host {host_and_port:?}; expected `<host>:<port>`"
+            );
+            let addr = addr.parse().with_context(|| {
+                format!("invalid address {addr:?} for {host_and_port:?}; expected `<ip>:<port>`")
+            })?;
+            Ok(((authority.host().to_owned(), port), addr))
+        })
+        .collect()
 }
 
 #[derive(Debug)]
@@ -307,6 +442,94 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_dns_overrides() -> anyhow::Result<()> {
+        let config = SpinRuntimeConfig::new("")
+            .config_from_table(&toml::toml! {
+                [outbound_networking]
+                resolve = { "api.partner.com:443" = "127.0.0.1:8443", "[::1]:80" = "[::1]:8080" }
+            })?
+            .context("expected config, got None")?;
+        assert_eq!(
+            config.dns_overrides,
+            [
+                (("[::1]".to_owned(), 80), "[::1]:8080".parse()?),
+                (
+                    ("api.partner.com".to_owned(), 443),
+                    "127.0.0.1:8443".parse()?
+                ),
+            ]
+        );
+
+        for resolve in [
+            toml::toml! { "api.partner.com" = "127.0.0.1:8443" },
+            toml::toml! { "*.partner.com:443" = "127.0.0.1:8443" },
+            toml::toml! { "api.partner.com:443" = "localhost:8443" },
+            toml::toml! { "https://api.partner.com:443" = "127.0.0.1:8443" },
+        ] {
+            let mut table = toml::Table::new();
+            table.insert("resolve".into(), resolve.into());
+            let mut root = toml::Table::new();
+            root.insert("outbound_networking".into(), table.into());
+            SpinRuntimeConfig::new("")
+                .config_from_table(&root)
+                .expect_err(&root.to_string());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_egress_audit() -> anyhow::Result<()> {
+        let config = SpinRuntimeConfig::new("/runtime")
+            .with_log_dir(Some("/logs".into()))
+            .config_from_table(&toml::toml! {
+                [outbound_networking.audit]
+            })?
+            .context("expected config, got None")?;
+        assert_eq!(
+            config.egress_audit,
+            Some(EgressAuditConfig {
+                mode: EgressAuditMode::All,
+                sink: EgressAuditSink::File("/logs/egress_audit.jsonl".into()),
+            })
+        );
+
+        let config = SpinRuntimeConfig::new("/runtime")
+            .config_from_table(&toml::toml! {
+                [outbound_networking.audit]
+                mode = "denied"
+                path = "audit/egress.jsonl"
+            })?
+            .context("expected config, got None")?;
+        assert_eq!(
+            config.egress_audit,
+            Some(EgressAuditConfig {
+                mode: EgressAuditMode::Denied,
+                sink: EgressAuditSink::File("/runtime/audit/egress.jsonl".into()),
+            })
+        );
+
+        let config = SpinRuntimeConfig::new("/runtime")
+            .config_from_table(&toml::toml! {
+                [outbound_networking.audit]
+                sink = "otel"
+            })?
+            .context("expected config, got None")?;
+        assert_eq!(
+            config.egress_audit.map(|audit| audit.sink),
+            Some(EgressAuditSink::Otel)
+        );
+
+        // The default path needs a log directory
+        SpinRuntimeConfig::new("/runtime")
+            .config_from_table(&toml::toml! {
+                [outbound_networking.audit]
+                mode = "all"
+            })
+            .unwrap_err();
+        Ok(())
+    }
+
     #[test]
     fn test_min_tls_config() -> anyhow::Result<()> {
         let config = SpinRuntimeConfig::new("/doesnt-matter");
@@ -352,6 +575,43 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_tls_restrictions() -> anyhow::Result<()> {
+        let config = SpinRuntimeConfig::new("/doesnt-matter");
+
+        let tls_configs = config
+            .tls_configs_from_table(&toml::toml! {
+                [[client_tls]]
+                component_ids = ["test-component"]
+                hosts = ["test-host"]
+                min_tls_version = "1.3"
+                cipher_suites = ["TLS13_AES_256_GCM_SHA384"]
+                pinned_public_keys = ["sha256/bpBn2Xhnz46syT6HQTOSI0bKT1TGIBjDzNH+N0/383I="]
+            })?
+            .context("missing config section")?;
+
+        assert_eq!(tls_configs[0].min_tls_version, TlsVersion::Tls13);
+        assert_eq!(tls_configs[0].cipher_suites, ["TLS13_AES_256_GCM_SHA384"]);
+        assert_eq!(tls_configs[0].pinned_public_keys.len(), 1);
+        assert_eq!(tls_configs[0].pinned_public_keys[0][..2], [0x6e, 0x90]);
+
+        for invalid in [
+            toml::toml! { min_tls_version = "1.1" },
+            toml::toml! { pinned_public_keys = ["bpBn2Xhnz46syT6HQTOSI0bKT1TGIBjDzNH+N0/383I="] },
+            toml::toml! { pinned_public_keys = ["sha256/dG9vIHNob3J0"] },
+        ] {
+            let mut client_tls = toml::toml! {
+                component_ids = ["test-component"]
+                hosts = ["test-host"]
+            };
+            client_tls.extend(invalid);
+            let mut table = toml::Table::new();
+            table.insert("client_tls".into(), vec![client_tls].into());
+            config.tls_configs_from_table(&table).unwrap_err();
+        }
+        Ok(())
+    }
+
     #[test]
     fn test_use_webpki_roots_default_with_explicit_roots() -> anyhow::Result<()> {
         let config = SpinRuntimeConfig::new(TESTDATA_DIR);
@@ -1,8 +1,17 @@
 use std::{collections::HashMap, ops::Deref, sync::Arc};
 
 use anyhow::{Context, ensure};
+use rustls::{
+    CertificateError, DigitallySignedStruct, OtherError, SignatureScheme,
+    client::{
+        WebPkiServerVerifier,
+        danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    },
+};
+use rustls_pki_types::{CertificateDer, ServerName, UnixTime};
+use sha2::{Digest, Sha256};
 
-use crate::runtime_config::{ClientCertRuntimeConfig, ClientTlsRuntimeConfig};
+use crate::runtime_config::{ClientCertRuntimeConfig, ClientTlsRuntimeConfig, TlsVersion};
 
 /// TLS client configs
 #[derive(Default)]
@@ -26,6 +35,9 @@ impl TlsClientConfigs {
             use_platform_roots,
             use_webpki_roots,
             client_cert,
+            pinned_public_keys,
+            min_tls_version,
+            cipher_suites,
         } in client_tls_configs
         {
             ensure!(
@@ -41,6 +53,9 @@ impl TlsClientConfigs {
                 use_webpki_roots,
                 root_certificates,
                 client_cert,
+                pinned_public_keys,
+                min_tls_version,
+                cipher_suites,
             )
             .context("error building TLS client config")?;
             for component in components {
@@ -81,7 +96,7 @@ impl TlsClientConfigs {
 type HostTlsClientConfigs = Arc<HashMap<String, TlsClientConfig>>;
 
 /// TLS configurations for a specific component.
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct ComponentTlsClientConfigs {
     pub(crate) host_client_configs: Option<HostTlsClientConfigs>,
     pub(crate) default_client_config: TlsClientConfig,
@@ -99,14 +114,17 @@ impl ComponentTlsClientConfigs {
 
 /// Shared TLS client configuration
 #[derive(Clone)]
-pub struct TlsClientConfig(Arc<rustls::ClientConfig>);
+pub struct TlsClientConfig(Arc<rustls::ClientConfig>, Arc<dyn ServerCertVerifier>);
 
 impl TlsClientConfig {
     fn new(
         use_platform_roots: bool,
         use_webpki_roots: bool,
-        root_certificates: Vec<rustls_pki_types::CertificateDer<'static>>,
+        root_certificates: Vec<CertificateDer<'static>>,
         client_cert: Option<ClientCertRuntimeConfig>,
+        pinned_public_keys: Vec<[u8; 32]>,
+        min_tls_version: TlsVersion,
+        cipher_suites: Vec<String>,
     ) -> anyhow::Result<Self> {
         anyhow::ensure!(
             use_platform_roots || use_webpki_roots || !root_certificates.is_empty(),
@@ -120,21 +138,67 @@ impl TlsClientConfig {
             );
         }
 
-        let builder = rustls::ClientConfig::builder();
-        let builder = if use_platform_roots {
-            let verifier = rustls_platform_verifier::Verifier::new_with_extra_roots(
-                extra_roots,
-                builder.crypto_provider().clone(),
+        let mut provider = rustls::ClientConfig::builder()
+            .crypto_provider()
+            .as_ref()
+            .clone();
+        if !cipher_suites.is_empty() {
+            for name in &cipher_suites {
+                ensure!(
+                    provider
+                        .cipher_suites
+                        .iter()
+                        .any(|suite| cipher_suite_name(suite) == Some(name.as_str())),
+                    "unsupported cipher suite {name:?}; supported cipher suites are: {}",
+                    provider
+                        .cipher_suites
+                        .iter()
+                        .filter_map(cipher_suite_name)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                );
+            }
+            provider.cipher_suites.retain(|suite| {
+                cipher_suite_name(suite).is_some_and(|name| cipher_suites.iter().any(|s| s == name))
+            });
+        }
+        let versions: &[&rustls::SupportedProtocolVersion] = match min_tls_version {
+            TlsVersion::Tls12 => rustls::DEFAULT_VERSIONS,
+            TlsVersion::Tls13 => &[&rustls::version::TLS13],
+        };
+        let builder = rustls::ClientConfig::builder_with_provider(provider.into())
+            .with_protocol_versions(versions)
+            .context("no configured cipher suite supports the allowed TLS versions")?;
+
+        let verifier: Arc<dyn ServerCertVerifier> = if use_platform_roots {
+            Arc::new(
+                rustls_platform_verifier::Verifier::new_with_extra_roots(
+                    extra_roots,
+                    builder.crypto_provider().clone(),
+                )
+                .context("failed to initialize platform certificate verifier")?,
             )
-            .context("failed to initialize platform certificate verifier")?;
-            builder
-                .dangerous()
-                .with_custom_certificate_verifier(Arc::new(verifier))
         } else {
             let mut root_store = rustls::RootCertStore::empty();
             extra_roots.try_for_each(|cert| root_store.add(cert))?;
-            builder.with_root_certificates(root_store)
+            WebPkiServerVerifier::builder_with_provider(
+                root_store.into(),
+                builder.crypto_provider().clone(),
+            )
+            .build()
+            .context("failed to initialize certificate verifier")?
+        };
+        let verifier = if pinned_public_keys.is_empty() {
+            verifier
+        } else {
+            Arc::new(PinningVerifier {
+                inner: verifier,
+                pinned_public_keys,
+            })
         };
+        let builder = builder
+            .dangerous()
+            .with_custom_certificate_verifier(verifier.clone());
 
         let client_config = if let Some(ClientCertRuntimeConfig {
             cert_chain,
@@ -145,13 +209,32 @@ impl TlsClientConfig {
         } else {
             builder.with_no_client_auth()
         };
-        Ok(Self(client_config.into()))
+        Ok(Self(client_config.into(), verifier))
     }
 
     /// Returns the inner [`rustls::ClientConfig`] for consumption by rustls APIs.
     pub fn inner(&self) -> Arc<rustls::ClientConfig> {
         self.0.clone()
     }
+
+    /// Returns a copy of this config which verifies server certificates
+    /// against `server_name`, whatever server name the connection uses.
+    ///
+    /// This is for clients which take the TLS server name from the address
+    /// they connect to, when that address has been replaced by the IP address
+    /// of a static DNS override.
+    pub fn verifying_server_name(&self, server_name: &str) -> anyhow::Result<Self> {
+        let server_name = ServerName::try_from(server_name.to_owned())
+            .with_context(|| format!("invalid TLS server name {server_name:?}"))?;
+        let mut client_config = (*self.0).clone();
+        client_config
+            .dangerous()
+            .set_certificate_verifier(Arc::new(ServerNameVerifier {
+                inner: self.1.clone(),
+                server_name,
+            }));
+        Ok(Self(client_config.into(), self.1.clone()))
+    }
 }
 
 impl Deref for TlsClientConfig {
@@ -164,10 +247,176 @@ impl Deref for TlsClientConfig {
 
 impl Default for TlsClientConfig {
     fn default() -> Self {
-        Self::new(true, true, vec![], None).expect("default client config should be valid")
+        Self::new(
+            true,
+            true,
+            vec![],
+            None,
+            vec![],
+            TlsVersion::default(),
+            vec![],
+        )
+        .expect("default client config should be valid")
+    }
+}
+
+/// The error with which TLS connections fail if no certificate presented by
+/// the server has a pinned public key.
+#[derive(Debug)]
+pub struct PinMismatch {
+    server_name: String,
+}
+
+impl PinMismatch {
+    /// Returns the pin mismatch which caused the given TLS connection error,
+    /// if any.
+    pub fn from_io_error(err: &std::io::Error) -> Option<&Self> {
+        match err.get_ref()?.downcast_ref::<rustls::Error>()? {
+            rustls::Error::InvalidCertificate(CertificateError::Other(OtherError(err))) => {
+                err.downcast_ref()
+            }
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for PinMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "TLS certificate for {:?} does not match any pinned public key",
+            self.server_name
+        )
     }
 }
 
+impl std::error::Error for PinMismatch {}
+
+/// Verifies server certificates with an inner verifier, then checks that the
+/// chain includes a pinned public key.
+#[derive(Debug)]
+struct PinningVerifier {
+    inner: Arc<dyn ServerCertVerifier>,
+    /// SHA-256 hashes of SubjectPublicKeyInfos
+    pinned_public_keys: Vec<[u8; 32]>,
+}
+
+impl ServerCertVerifier for PinningVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified = self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            ocsp_response,
+            now,
+        )?;
+        let is_pinned = std::iter::once(end_entity)
+            .chain(intermediates)
+            .filter_map(|cert| spki_sha256(cert))
+            .any(|hash| self.pinned_public_keys.contains(&hash));
+        if !is_pinned {
+            let mismatch = PinMismatch {
+                server_name: server_name.to_str().into_owned(),
+            };
+            return Err(rustls::Error::InvalidCertificate(CertificateError::Other(
+                OtherError(Arc::new(mismatch)),
+            )));
+        }
+        Ok(verified)
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+/// Verifies server certificates with an inner verifier against a fixed server
+/// name.
+#[derive(Debug)]
+struct ServerNameVerifier {
+    inner: Arc<dyn ServerCertVerifier>,
+    server_name: ServerName<'static>,
+}
+
+impl ServerCertVerifier for ServerNameVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            &self.server_name,
+            ocsp_response,
+            now,
+        )
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+/// Returns the SHA-256 hash of the DER-encoded SubjectPublicKeyInfo of the
+/// given certificate.
+fn spki_sha256(cert: &[u8]) -> Option<[u8; 32]> {
+    let (_, cert) = x509_parser::parse_x509_certificate(cert).ok()?;
+    Some(Sha256::digest(cert.tbs_certificate.subject_pki.raw).into())
+}
+
+/// Returns the IANA name of the given cipher suite, e.g.
+/// `TLS13_AES_256_GCM_SHA384`.
+fn cipher_suite_name(suite: &rustls::SupportedCipherSuite) -> Option<&'static str> {
+    suite.suite().as_str()
+}
+
 /// Validate host name (authority without port)
 pub(crate) fn validate_host(host: &str) -> anyhow::Result<()> {
     let authority: http::uri::Authority = host
@@ -205,7 +454,7 @@ mod tests {
             root_certificates: vec![],
             use_platform_roots: false,
             use_webpki_roots: true,
-            client_cert: None,
+            ..Default::default()
         }])?;
         let config = configs.get_tls_client_config("test-component", "test-host");
         // Check that we didn't just get the default
@@ -228,6 +477,7 @@ mod tests {
                 cert_chain: test_certs,
                 key_der: test_key,
             }),
+            ..Default::default()
         }])?;
         let config = configs.get_tls_client_config("test-component", "test-host");
         assert!(config.client_auth_cert_resolver.has_certs());
@@ -264,6 +514,196 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_tls_restrictions() -> anyhow::Result<()> {
+        let configs = TlsClientConfigs::new([ClientTlsRuntimeConfig {
+            components: vec!["test-component".into()],
+            hosts: vec!["test-host".into()],
+            min_tls_version: TlsVersion::Tls13,
+            cipher_suites: vec!["TLS13_AES_256_GCM_SHA384".into()],
+            ..Default::default()
+        }])?;
+        let config = configs.get_tls_client_config("test-component", "test-host");
+        let suites = &config.crypto_provider().cipher_suites;
+        assert_eq!(suites.len(), 1);
+        assert_eq!(
+            cipher_suite_name(&suites[0]),
+            Some("TLS13_AES_256_GCM_SHA384")
+        );
+
+        // Unknown suites are rejected, as are suites unusable with the
+        // minimum version
+        for cipher_suite in ["TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256", "NOT_A_SUITE"] {
+            TlsClientConfigs::new([ClientTlsRuntimeConfig {
+                components: vec!["test-component".into()],
+                hosts: vec!["test-host".into()],
+                min_tls_version: TlsVersion::Tls13,
+                cipher_suites: vec![cipher_suite.into()],
+                ..Default::default()
+            }])
+            .err()
+            .unwrap();
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_spki_sha256() -> anyhow::Result<()> {
+        use base64::Engine as _;
+        let cert = &test_certs()?[0];
+        let hash = spki_sha256(cert).context("failed to parse certificate")?;
+        assert_eq!(
+            base64::engine::general_purpose::STANDARD.encode(hash),
+            "bpBn2Xhnz46syT6HQTOSI0bKT1TGIBjDzNH+N0/383I="
+        );
+        assert_eq!(spki_sha256(&cert[..cert.len() / 2]), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_pinning_verifier() -> anyhow::Result<()> {
+        /// Accepts any certificate
+        #[derive(Debug)]
+        struct AcceptAll;
+
+        impl ServerCertVerifier for AcceptAll {
+            fn verify_server_cert(
+                &self,
+                _: &CertificateDer<'_>,
+                _: &[CertificateDer<'_>],
+                _: &ServerName<'_>,
+                _: &[u8],
+                _: UnixTime,
+            ) -> Result<ServerCertVerified, rustls::Error> {
+                Ok(ServerCertVerified::assertion())
+            }
+
+            fn verify_tls12_signature(
+                &self,
+                _: &[u8],
+                _: &CertificateDer<'_>,
+                _: &DigitallySignedStruct,
+            ) -> Result<HandshakeSignatureValid, rustls::Error> {
+                unimplemented!()
+            }
+
+            fn verify_tls13_signature(
+                &self,
+                _: &[u8],
+                _: &CertificateDer<'_>,
+                _: &DigitallySignedStruct,
+            ) -> Result<HandshakeSignatureValid, rustls::Error> {
+                unimplemented!()
+            }
+
+            fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+                vec![]
+            }
+        }
+
+        let certs = test_certs()?;
+        let pin = spki_sha256(&certs[1]).unwrap();
+        let verify = |pinned_public_keys, chain: &[CertificateDer<'_>]| {
+            let verifier = PinningVerifier {
+                inner: Arc::new(AcceptAll),
+                pinned_public_keys,
+            };
+            let server_name = ServerName::try_from("test-host").unwrap();
+            verifier.verify_server_cert(&chain[0], &chain[1..], &server_name, &[], UnixTime::now())
+        };
+
+        // Pins may match any certificate in the chain
+        verify(vec![pin], &certs)?;
+        let err = verify(vec![[0; 32]], &certs).unwrap_err();
+        let err = std::io::Error::new(std::io::ErrorKind::InvalidData, err);
+        let mismatch = PinMismatch::from_io_error(&err).context("expected pin mismatch")?;
+        assert_eq!(
+            mismatch.to_string(),
+            r#"TLS certificate for "test-host" does not match any pinned public key"#
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_server_name_verifier() -> anyhow::Result<()> {
+        /// Accepts certificates for the given server name
+        #[derive(Debug)]
+        struct AcceptName(&'static str);
+
+        impl ServerCertVerifier for AcceptName {
+            fn verify_server_cert(
+                &self,
+                _: &CertificateDer<'_>,
+                _: &[CertificateDer<'_>],
+                server_name: &ServerName<'_>,
+                _: &[u8],
+                _: UnixTime,
+            ) -> Result<ServerCertVerified, rustls::Error> {
+                if server_name.to_str() == self.0 {
+                    Ok(ServerCertVerified::assertion())
+                } else {
+                    Err(rustls::Error::InvalidCertificate(
+                        CertificateError::NotValidForName,
+                    ))
+                }
+            }
+
+            fn verify_tls12_signature(
+                &self,
+                _: &[u8],
+                _: &CertificateDer<'_>,
+                _: &DigitallySignedStruct,
+            ) -> Result<HandshakeSignatureValid, rustls::Error> {
+                unimplemented!()
+            }
+
+            fn verify_tls13_signature(
+                &self,
+                _: &[u8],
+                _: &CertificateDer<'_>,
+                _: &DigitallySignedStruct,
+            ) -> Result<HandshakeSignatureValid, rustls::Error> {
+                unimplemented!()
+            }
+
+            fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+                vec![]
+            }
+        }
+
+        let certs = test_certs()?;
+        let verify = |server_name: &'static str, connected_name: &'static str| {
+            let verifier = ServerNameVerifier {
+                inner: Arc::new(AcceptName("broker.example.com")),
+                server_name: ServerName::try_from(server_name).unwrap(),
+            };
+            let connected_name = ServerName::try_from(connected_name).unwrap();
+            verifier.verify_server_cert(
+                &certs[0],
+                &certs[1..],
+                &connected_name,
+                &[],
+                UnixTime::now(),
+            )
+        };
+
+        // Certificates are verified against the fixed name rather than the
+        // name connected to
+        verify("broker.example.com", "127.0.0.1")?;
+        verify("other.example.com", "broker.example.com").unwrap_err();
+
+        // Configs verifying a fixed name keep the original verifier for
+        // deriving further configs
+        let config = TlsClientConfig::default().verifying_server_name("broker.example.com")?;
+        config.verifying_server_name("other.example.com")?;
+        assert!(
+            TlsClientConfig::default()
+                .verifying_server_name("not a name")
+                .is_err()
+        );
+        Ok(())
+    }
+
     const TESTDATA_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/testdata");
 
     fn test_certs() -> anyhow::Result<Vec<CertificateDer<'static>>> {
@@ -3,6 +3,7 @@ use std::time::Duration;
 
 use spin_factor_outbound_mqtt::{ClientCreator, MqttClient, OutboundMqttFactor};
 use spin_factor_outbound_networking::OutboundNetworkingFactor;
+use spin_factor_outbound_networking::learn::LearnedHost;
 use spin_factor_outbound_networking::runtime_config::RuntimeConfig;
 use spin_factor_outbound_networking::runtime_config::spin::SpinRuntimeConfig;
 use spin_factor_variables::VariablesFactor;
@@ -128,6 +129,135 @@ async fn configures_wasi_socket_addr_check() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn audits_wasi_socket_addr_checks() -> anyhow::Result<()> {
+    let log_dir = tempfile::tempdir()?;
+    let factors = TestFactors {
+        wasi: WasiFactor::new(DummyFilesMounter),
+        variables: VariablesFactor::default(),
+        networking: OutboundNetworkingFactor::new(),
+    };
+    let env = TestEnvironment::new(factors)
+        .extend_manifest(toml! {
+            [component.test-component]
+            source = "does-not-exist.wasm"
+            allowed_outbound_hosts = ["*://123.0.2.1:12345", "*://127.0.0.1:3000"]
+        })
+        .runtime_config(TestFactorsRuntimeConfig {
+            networking: SpinRuntimeConfig::new("")
+                .with_log_dir(Some(log_dir.path().into()))
+                .config_from_table(&toml! {
+                    [outbound_networking]
+                    block_networks = ["private"]
+
+                    [outbound_networking.audit]
+                })?,
+            ..Default::default()
+        })?;
+    let mut state = env.build_instance_state().await?;
+    let mut sockets = WasiFactor::get_sockets_impl(&mut state, get_sockets_view).unwrap();
+
+    let network_resource = sockets.instance_network()?;
+    let network = sockets.table.get(&network_resource)?;
+    for addr in ["123.0.2.1:12345", "123.0.2.1:25", "127.0.0.1:3000"] {
+        let _ = network
+            .check_socket_addr(addr.parse().unwrap(), SocketAddrUse::TcpConnect)
+            .await;
+    }
+    // Dropping the audit log waits for its events to be written
+    drop(sockets);
+    drop(state);
+
+    let events = std::fs::read_to_string(log_dir.path().join("egress_audit.jsonl"))?
+        .lines()
+        .map(serde_json::from_str)
+        .collect::<Result<Vec<serde_json::Value>, _>>()?;
+    let decisions = events
+        .iter()
+        .map(|event| {
+            assert_eq!(event["component_id"], "test-component");
+            (
+                event["authority"].as_str().unwrap(),
+                event["decision"].as_str().unwrap(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        decisions,
+        [
+            ("123.0.2.1:12345", "allowed"),
+            ("123.0.2.1:25", "denied"),
+            // Allowed by allowed_outbound_hosts but blocked by block_networks
+            ("127.0.0.1:3000", "allowed"),
+            ("127.0.0.1:3000", "denied"),
+        ]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn learns_wasi_socket_addr_checks() -> anyhow::Result<()> {
+    let learned = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let mut networking = OutboundNetworkingFactor::new();
+    networking.set_learned_host_handler({
+        let learned = learned.clone();
+        move |host: &LearnedHost| {
+            learned
+                .lock()
+                .unwrap()
+                .push((host.component_id.clone(), host.allowed_host.clone()))
+        }
+    });
+    let factors = TestFactors {
+        wasi: WasiFactor::new(DummyFilesMounter),
+        variables: VariablesFactor::default(),
+        networking,
+    };
+    let env = TestEnvironment::new(factors)
+        .extend_manifest(toml! {
+            [component.test-component]
+            source = "does-not-exist.wasm"
+            allowed_outbound_hosts = ["*://123.0.2.1:12345"]
+        })
+        .runtime_config(TestFactorsRuntimeConfig {
+            networking: SpinRuntimeConfig::new("").config_from_table(&toml! {
+                [outbound_networking]
+                block_networks = ["private"]
+            })?,
+            ..Default::default()
+        })?;
+    let mut state = env.build_instance_state().await?;
+    let mut sockets = WasiFactor::get_sockets_impl(&mut state, get_sockets_view).unwrap();
+
+    let network_resource = sockets.instance_network()?;
+    let network = sockets.table.get(&network_resource)?;
+    for allowed in ["123.0.2.1:12345", "123.0.2.1:25", "123.0.2.1:25"] {
+        network
+            .check_socket_addr(allowed.parse().unwrap(), SocketAddrUse::TcpConnect)
+            .await?;
+    }
+    // Learn mode doesn't lift block_networks
+    network
+        .check_socket_addr("127.0.0.1:3000".parse().unwrap(), SocketAddrUse::TcpConnect)
+        .await
+        .unwrap_err();
+
+    assert_eq!(
+        *learned.lock().unwrap(),
+        [
+            (
+                "test-component".to_string(),
+                "tcp://123.0.2.1:25".to_string()
+            ),
+            (
+                "test-component".to_string(),
+                "tcp://127.0.0.1:3000".to_string()
+            ),
+        ]
+    );
+    Ok(())
+}
+
 #[tokio::test]
 async fn wasi_factor_is_optional() -> anyhow::Result<()> {
     #[derive(RuntimeFactors)]
@@ -1,7 +1,7 @@
 use std::sync::Arc;
 
 use spin_factor_outbound_networking::config::allowed_hosts::OutboundAllowedHosts;
-use spin_world::spin::postgres4_2_0::postgres::{self as v4};
+use spin_world::spin::postgres4_3_0::postgres::{self as v4};
 
 /// Encapsulates checking of a PostgreSQL address/connection string against
 /// an allow-list.
@@ -1,12 +1,19 @@
 #![allow(clippy::result_large_err)]
 
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
 use anyhow::Result;
 use opentelemetry_semantic_conventions::attribute as otel_attribute;
 use spin_core::wasmtime::component::{Accessor, FutureReader, Resource, StreamReader};
+use spin_factor_outbound_networking::ConnectionPermit;
+use spin_factor_outbound_networking::audit::{AuditedConnection, EgressAudit};
+use spin_factor_outbound_networking::config::dns_overrides::DnsOverrides;
 use spin_telemetry::traces::{self, Blame};
 use spin_world::MAX_HOST_BUFFERED_BYTES;
+use spin_world::async_trait;
 use spin_world::spin::postgres3_0_0::postgres::{self as v3};
-use spin_world::spin::postgres4_2_0::postgres::{self as v4};
+use spin_world::spin::postgres4_3_0::postgres::{self as v4};
 use spin_world::v1::postgres as v1;
 use spin_world::v1::rdbms_types as v1_types;
 use spin_world::v2::postgres::{self as v2};
@@ -17,7 +24,203 @@ use tracing::instrument;
 
 use crate::InstanceState;
 use crate::allowed_hosts::AllowedHostChecker;
-use crate::client::{Client, ClientFactory, HashableCertificate, QueryAsyncResult};
+use crate::client::{Client, ClientFactory, HashableCertificate, QueryAsyncResult, audit_target};
+
+pub(crate) struct Connection<C> {
+    client: AuditedClient<C>,
+    _permit: ConnectionPermit,
+    /// Set while a transaction is open on the connection.
+    in_transaction: Arc<AtomicBool>,
+}
+
+impl<C> Connection<C> {
+    fn new(client: C, permit: ConnectionPermit, audit: Option<AuditedConnection>) -> Self {
+        Self {
+            client: AuditedClient {
+                client,
+                audit: audit.map(Arc::new),
+            },
+            _permit: permit,
+            in_transaction: Default::default(),
+        }
+    }
+
+    /// The connection's client, for statements run outside a transaction.
+    ///
+    /// Statements can't be run on the connection itself while a transaction
+    /// is open on it, as they would silently run as part of the transaction.
+    #[allow(clippy::result_large_err)]
+    fn client(&self) -> Result<&AuditedClient<C>, v4::Error> {
+        if self.in_transaction.load(Ordering::Acquire) {
+            let err = v4::Error::Other(
+                "a transaction is open on this connection; run statements on the transaction instead"
+                    .into(),
+            );
+            traces::mark_as_error(&err, Some(Blame::Guest));
+            return Err(err);
+        }
+        Ok(&self.client)
+    }
+}
+
+/// A transaction, pinned to the pooled client of the connection it was begun on.
+///
+/// A transaction which is dropped while still open, e.g. because the instance
+/// was torn down, is rolled back so its connection isn't returned to the pool
+/// mid-transaction.
+pub(crate) struct Transaction<C: Client> {
+    client: AuditedClient<C>,
+    /// Cleared once the transaction has been committed or rolled back.
+    open: bool,
+    _guard: TransactionGuard,
+}
+
+impl<C: Client> Transaction<C> {
+    async fn commit(mut self) -> Result<(), v4::Error> {
+        let result = self.client.commit_transaction().await;
+        self.open = false;
+        result
+    }
+
+    async fn rollback(mut self) -> Result<(), v4::Error> {
+        let result = self.client.rollback_transaction().await;
+        self.open = false;
+        result
+    }
+}
+
+impl<C: Client> Drop for Transaction<C> {
+    fn drop(&mut self) {
+        if !self.open {
+            return;
+        }
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            tracing::warn!("no runtime to roll back abandoned Postgres transaction");
+            return;
+        };
+        let client = self.client.clone();
+        runtime.spawn(async move {
+            if let Err(e) = client.rollback_transaction().await {
+                tracing::warn!("failed to roll back abandoned Postgres transaction: {e:?}");
+            }
+        });
+    }
+}
+
+/// A client which tallies the statements sent and rows received on the
+/// connection it was opened for, which is recorded to the egress audit log
+/// once the connection and any transactions on it are dropped.
+#[derive(Clone)]
+pub(crate) struct AuditedClient<C> {
+    client: C,
+    audit: Option<Arc<AuditedConnection>>,
+}
+
+impl<C> AuditedClient<C> {
+    fn sent(&self, statement: &str, params: &[v4::ParameterValue]) {
+        if let Some(audit) = &self.audit {
+            audit.sent(statement.len() + params.iter().map(|p| p.memory_size()).sum::<usize>());
+        }
+    }
+
+    fn received(&self, rows: &[v4::Row]) {
+        if let Some(audit) = &self.audit {
+            audit.received(rows.iter().map(row_size).sum());
+        }
+    }
+}
+
+fn row_size(row: &v4::Row) -> usize {
+    row.iter().map(|v| v.memory_size()).sum()
+}
+
+#[async_trait]
+impl<C: Client> Client for AuditedClient<C> {
+    async fn execute(
+        &self,
+        statement: String,
+        params: Vec<v4::ParameterValue>,
+    ) -> Result<u64, v4::Error> {
+        self.sent(&statement, &params);
+        self.client.execute(statement, params).await
+    }
+
+    async fn query(
+        &self,
+        statement: String,
+        params: Vec<v4::ParameterValue>,
+        max_result_bytes: usize,
+    ) -> Result<v4::RowSet, v4::Error> {
+        self.sent(&statement, &params);
+        let rowset = self
+            .client
+            .query(statement, params, max_result_bytes)
+            .await?;
+        self.received(&rowset.rows);
+        Ok(rowset)
+    }
+
+    async fn query_async(
+        &self,
+        statement: String,
+        params: Vec<v4::ParameterValue>,
+        max_result_bytes: usize,
+    ) -> Result<QueryAsyncResult, v4::Error> {
+        self.sent(&statement, &params);
+        let result = self
+            .client
+            .query_async(statement, params, max_result_bytes)
+            .await?;
+        let Some(audit) = self.audit.clone() else {
+            return Ok(result);
+        };
+        // Count the rows as they're streamed to the guest
+        let (rows_tx, rows) = tokio::sync::mpsc::channel(4);
+        let mut streamed = result.rows;
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = rows_tx.closed() => return,
+                    row = streamed.recv() => {
+                        let Some(row) = row else { return };
+                        audit.received(row_size(&row));
+                        if rows_tx.send(row).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+            }
+        });
+        Ok(QueryAsyncResult { rows, ..result })
+    }
+
+    async fn begin_transaction(&self) -> Result<(), v4::Error> {
+        self.client.begin_transaction().await
+    }
+
+    async fn commit_transaction(&self) -> Result<(), v4::Error> {
+        self.client.commit_transaction().await
+    }
+
+    async fn rollback_transaction(&self) -> Result<(), v4::Error> {
+        self.client.rollback_transaction().await
+    }
+}
+
+/// Marks a connection as having an open transaction for as long as it lives.
+struct TransactionGuard(Arc<AtomicBool>);
+
+impl TransactionGuard {
+    fn acquire(in_transaction: &Arc<AtomicBool>) -> Option<Self> {
+        (!in_transaction.swap(true, Ordering::AcqRel)).then(|| Self(in_transaction.clone()))
+    }
+}
+
+impl Drop for TransactionGuard {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
 
 impl<CF: ClientFactory> InstanceState<CF> {
     async fn open_connection<Conn: 'static>(
@@ -32,7 +235,12 @@ impl<CF: ClientFactory> InstanceState<CF> {
         })?;
         let client = self
             .client_factory
-            .get_client(address, root_ca)
+            .get_client(
+                address,
+                root_ca,
+                &self.dns_overrides,
+                self.pools.get(address),
+            )
             .await
             .map_err(|e| {
                 // The guest supplies the address and credentials; connection
@@ -42,8 +250,9 @@ impl<CF: ClientFactory> InstanceState<CF> {
                 traces::mark_as_error(&err, Some(Blame::Guest));
                 err
             })?;
+        let audit = audit_connection(self.egress_audit.as_ref(), address, &self.dns_overrides);
         self.connections
-            .push((client, permit))
+            .push(Connection::new(client, permit, audit))
             .map_err(|_| {
                 // The guest exceeded the host-imposed connection limit.
                 let err = v4::Error::ConnectionFailed("too many connections".into());
@@ -56,17 +265,33 @@ impl<CF: ClientFactory> InstanceState<CF> {
     async fn get_client<Conn: 'static>(
         &self,
         connection: Resource<Conn>,
-    ) -> Result<&CF::Client, v4::Error> {
+    ) -> Result<&AuditedClient<CF::Client>, v4::Error> {
         self.connections
             .get(connection.rep())
-            .map(|(client, _permit)| client)
             .ok_or_else(|| {
                 // The connection table is managed entirely by the host, so a
                 // missing handle indicates a host-side bug, not a guest mistake.
                 let err = v4::Error::ConnectionFailed("no connection found".into());
                 traces::mark_as_error(&err, Some(Blame::Host));
                 err
-            })
+            })?
+            .client()
+    }
+
+    fn get_transaction(&self, rep: u32) -> Result<&Transaction<CF::Client>, v4::Error> {
+        self.transactions.get(rep).ok_or_else(|| {
+            let err = v4::Error::Other("no transaction found".into());
+            traces::mark_as_error(&err, Some(Blame::Host));
+            err
+        })
+    }
+
+    fn take_transaction(&mut self, rep: u32) -> Result<Transaction<CF::Client>, v4::Error> {
+        self.transactions.remove(rep).ok_or_else(|| {
+            let err = v4::Error::Other("no transaction found".into());
+            traces::mark_as_error(&err, Some(Blame::Host));
+            err
+        })
     }
 
     fn allowed_host_checker(&self) -> AllowedHostChecker {
@@ -81,6 +306,20 @@ impl<CF: ClientFactory> InstanceState<CF> {
     }
 }
 
+/// Starts auditing a connection to the given address, if auditing is enabled.
+fn audit_connection(
+    egress_audit: Option<&EgressAudit>,
+    address: &str,
+    dns_overrides: &DnsOverrides,
+) -> Option<AuditedConnection> {
+    let (authority, resolved_ip) = audit_target(address, dns_overrides)?;
+    let audit = egress_audit?.connection("postgres", authority);
+    if let Some(ip) = resolved_ip {
+        audit.resolved_ip(ip);
+    }
+    Some(audit)
+}
+
 fn v2_params_to_v3(
     params: Vec<v2_types::ParameterValue>,
 ) -> Result<Vec<v4::ParameterValue>, v2::Error> {
@@ -244,13 +483,50 @@ impl<CF: ClientFactory> v4::HostConnection for InstanceState<CF> {
             .map_err(track_db_error_on_span_v4)
     }
 
+    #[instrument(name = "spin_outbound_pg.begin_transaction", skip(self, connection), err(level = Level::INFO),
+        fields(otel.kind = "client", {otel_attribute::DB_SYSTEM_NAME} = "postgresql"))]
+    async fn begin_transaction(
+        &mut self,
+        connection: Resource<v4::Connection>,
+    ) -> Result<Resource<v4::Transaction>, v4::Error> {
+        let connection = self.connections.get(connection.rep()).ok_or_else(|| {
+            let err = v4::Error::ConnectionFailed("no connection found".into());
+            traces::mark_as_error(&err, Some(Blame::Host));
+            err
+        })?;
+        let guard = TransactionGuard::acquire(&connection.in_transaction).ok_or_else(|| {
+            let err = v4::Error::Other("a transaction is already open on this connection".into());
+            traces::mark_as_error(&err, Some(Blame::Guest));
+            err
+        })?;
+        let transaction = Transaction {
+            client: connection.client.clone(),
+            open: true,
+            _guard: guard,
+        };
+        let rep = self.transactions.push(transaction).map_err(|_| {
+            let err = v4::Error::Other("too many transactions".into());
+            traces::mark_as_error(&err, Some(Blame::Guest));
+            err
+        })?;
+
+        let begun = self.get_transaction(rep)?.client.begin_transaction().await;
+        if let Err(err) = begun {
+            if let Some(mut transaction) = self.transactions.remove(rep) {
+                transaction.open = false;
+            }
+            return Err(track_db_error_on_span_v4(err));
+        }
+        Ok(Resource::new_own(rep))
+    }
+
     async fn drop(&mut self, connection: Resource<v4::Connection>) -> anyhow::Result<()> {
         self.connections.remove(connection.rep());
         Ok(())
     }
 }
 
-impl<T, CF: ClientFactory> spin_world::spin::postgres4_2_0::postgres::HostConnectionWithStore<T>
+impl<T, CF: ClientFactory> spin_world::spin::postgres4_3_0::postgres::HostConnectionWithStore<T>
     for crate::PgFactorData<CF>
 {
     #[instrument(name = "spin_outbound_pg.open_async", skip(accessor, address), err(level = Level::INFO),
@@ -275,13 +551,7 @@ impl<T, CF: ClientFactory> spin_world::spin::postgres4_2_0::postgres::HostConnec
         statement: String,
         params: Vec<v4::ParameterValue>,
     ) -> Result<u64, v4::Error> {
-        let client = accessor.with(|mut access| {
-            let host = access.get();
-            host.connections
-                .get(connection.rep())
-                .map(|(client, _permit)| client.clone())
-                .unwrap()
-        });
+        let client = Self::get_connection_client(accessor, connection)?;
 
         client
             .execute(statement, params)
@@ -305,14 +575,159 @@ impl<T, CF: ClientFactory> spin_world::spin::postgres4_2_0::postgres::HostConnec
         ),
         v4::Error,
     > {
-        let client = accessor.with(|mut access| {
+        let client = Self::get_connection_client(accessor, connection)?;
+
+        Self::query_async_on_client(accessor, client, statement, params).await
+    }
+}
+
+impl<CF: ClientFactory> v4::HostTransaction for InstanceState<CF> {
+    #[instrument(name = "spin_outbound_pg.execute", skip(self, transaction, params), err(level = Level::INFO),
+        fields(otel.kind = "client", {otel_attribute::DB_SYSTEM_NAME} = "postgresql"))]
+    async fn execute(
+        &mut self,
+        transaction: Resource<v4::Transaction>,
+        statement: String,
+        params: Vec<v4::ParameterValue>,
+    ) -> Result<u64, v4::Error> {
+        self.get_transaction(transaction.rep())?
+            .client
+            .execute(statement, params)
+            .await
+            .map_err(track_db_error_on_span_v4)
+    }
+
+    #[instrument(name = "spin_outbound_pg.query", skip(self, transaction, params), err(level = Level::INFO),
+        fields(otel.kind = "client", {otel_attribute::DB_SYSTEM_NAME} = "postgresql"))]
+    async fn query(
+        &mut self,
+        transaction: Resource<v4::Transaction>,
+        statement: String,
+        params: Vec<v4::ParameterValue>,
+    ) -> Result<v4::RowSet, v4::Error> {
+        self.get_transaction(transaction.rep())?
+            .client
+            .query(statement, params, MAX_HOST_BUFFERED_BYTES)
+            .await
+            .map_err(track_db_error_on_span_v4)
+    }
+
+    #[instrument(name = "spin_outbound_pg.commit", skip(self, this), err(level = Level::INFO),
+        fields(otel.kind = "client", {otel_attribute::DB_SYSTEM_NAME} = "postgresql"))]
+    async fn commit(&mut self, this: Resource<v4::Transaction>) -> Result<(), v4::Error> {
+        self.take_transaction(this.rep())?
+            .commit()
+            .await
+            .map_err(track_db_error_on_span_v4)
+    }
+
+    #[instrument(name = "spin_outbound_pg.rollback", skip(self, this), err(level = Level::INFO),
+        fields(otel.kind = "client", {otel_attribute::DB_SYSTEM_NAME} = "postgresql"))]
+    async fn rollback(&mut self, this: Resource<v4::Transaction>) -> Result<(), v4::Error> {
+        self.take_transaction(this.rep())?
+            .rollback()
+            .await
+            .map_err(track_db_error_on_span_v4)
+    }
+
+    async fn drop(&mut self, transaction: Resource<v4::Transaction>) -> anyhow::Result<()> {
+        // The guest dropped the transaction without committing it
+        if let Some(transaction) = self.transactions.remove(transaction.rep())
+            && let Err(e) = transaction.rollback().await
+        {
+            tracing::warn!("failed to roll back dropped Postgres transaction: {e:?}");
+        }
+        Ok(())
+    }
+}
+
+impl<T, CF: ClientFactory> spin_world::spin::postgres4_3_0::postgres::HostTransactionWithStore<T>
+    for crate::PgFactorData<CF>
+{
+    #[instrument(name = "spin_outbound_pg.execute", skip(accessor, transaction, params), err(level = Level::INFO),
+        fields(otel.kind = "client", {otel_attribute::DB_SYSTEM_NAME} = "postgresql"))]
+    async fn execute_async(
+        accessor: &Accessor<T, Self>,
+        transaction: Resource<v4::Transaction>,
+        statement: String,
+        params: Vec<v4::ParameterValue>,
+    ) -> Result<u64, v4::Error> {
+        let client = Self::get_transaction_client(accessor, transaction)?;
+
+        client
+            .execute(statement, params)
+            .await
+            .map_err(track_db_error_on_span_v4)
+    }
+
+    #[allow(clippy::type_complexity)] // blame bindgen, clippy, blame bindgen
+    #[instrument(name = "spin_outbound_pg.query_async", skip(accessor, transaction, params), err(level = Level::INFO),
+        fields(otel.kind = "client", {otel_attribute::DB_SYSTEM_NAME} = "postgresql"))]
+    async fn query_async(
+        accessor: &Accessor<T, Self>,
+        transaction: Resource<v4::Transaction>,
+        statement: String,
+        params: Vec<v4::ParameterValue>,
+    ) -> Result<
+        (
+            Vec<v4::Column>,
+            StreamReader<v4::Row>,
+            FutureReader<Result<(), v4::Error>>,
+        ),
+        v4::Error,
+    > {
+        let client = Self::get_transaction_client(accessor, transaction)?;
+
+        Self::query_async_on_client(accessor, client, statement, params).await
+    }
+}
+
+impl<CF: ClientFactory> crate::PgFactorData<CF> {
+    #[allow(clippy::result_large_err)]
+    fn get_connection_client<T>(
+        accessor: &Accessor<T, Self>,
+        connection: Resource<v4::Connection>,
+    ) -> Result<AuditedClient<CF::Client>, v4::Error> {
+        accessor.with(|mut access| {
             let host = access.get();
             host.connections
                 .get(connection.rep())
-                .map(|(client, _permit)| client.clone())
-                .unwrap()
-        });
+                .ok_or_else(|| {
+                    let err = v4::Error::ConnectionFailed("no connection found".into());
+                    traces::mark_as_error(&err, Some(Blame::Host));
+                    err
+                })?
+                .client()
+                .cloned()
+        })
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn get_transaction_client<T>(
+        accessor: &Accessor<T, Self>,
+        transaction: Resource<v4::Transaction>,
+    ) -> Result<AuditedClient<CF::Client>, v4::Error> {
+        accessor.with(|mut access| {
+            let host = access.get();
+            host.get_transaction(transaction.rep())
+                .map(|transaction| transaction.client.clone())
+        })
+    }
 
+    #[allow(clippy::type_complexity)]
+    async fn query_async_on_client<T>(
+        accessor: &Accessor<T, Self>,
+        client: AuditedClient<CF::Client>,
+        statement: String,
+        params: Vec<v4::ParameterValue>,
+    ) -> Result<
+        (
+            Vec<v4::Column>,
+            StreamReader<v4::Row>,
+            FutureReader<Result<(), v4::Error>>,
+        ),
+        v4::Error,
+    > {
         let QueryAsyncResult {
             columns,
             rows,
@@ -392,9 +807,15 @@ impl<CF: ClientFactory> crate::PgFactorData<CF> {
         address: &str,
         root_ca: Option<HashableCertificate>,
     ) -> Result<Resource<v4::Connection>, v4::Error> {
-        let (cf, semaphore) = accessor.with(|mut access| {
+        let (cf, semaphore, dns_overrides, pools, egress_audit) = accessor.with(|mut access| {
             let host = access.get();
-            (host.client_factory.clone(), host.semaphore.clone())
+            (
+                host.client_factory.clone(),
+                host.semaphore.clone(),
+                host.dns_overrides.clone(),
+                host.pools.clone(),
+                host.egress_audit.clone(),
+            )
         });
 
         let permit = semaphore.acquire().await.map_err(|_| {
@@ -403,16 +824,20 @@ impl<CF: ClientFactory> crate::PgFactorData<CF> {
             err
         })?;
 
-        let client = cf.get_client(address, root_ca).await.map_err(|e| {
-            let err = v4::Error::ConnectionFailed(format!("{e:?}"));
-            traces::mark_as_error(&err, Some(Blame::Guest));
-            err
-        })?;
+        let client = cf
+            .get_client(address, root_ca, &dns_overrides, pools.get(address))
+            .await
+            .map_err(|e| {
+                let err = v4::Error::ConnectionFailed(format!("{e:?}"));
+                traces::mark_as_error(&err, Some(Blame::Guest));
+                err
+            })?;
 
+        let audit = audit_connection(egress_audit.as_ref(), address, &dns_overrides);
         accessor.with(|mut access| {
             let host = access.get();
             host.connections
-                .push((client, permit))
+                .push(Connection::new(client, permit, audit))
                 .map_err(|_| {
                     let err = v4::Error::ConnectionFailed("too many connections".into());
                     traces::mark_as_error(&err, Some(Blame::Guest));
@@ -424,7 +849,7 @@ impl<CF: ClientFactory> crate::PgFactorData<CF> {
 }
 
 impl<T, CF: ClientFactory>
-    spin_world::spin::postgres4_2_0::postgres::HostConnectionBuilderWithStore<T>
+    spin_world::spin::postgres4_3_0::postgres::HostConnectionBuilderWithStore<T>
     for crate::PgFactorData<CF>
 {
     async fn build_async(
@@ -9,10 +9,11 @@ use std::sync::Arc;
 
 use allowed_hosts::AllowedHostChecker;
 use client::ClientFactory;
-use runtime_config::RuntimeConfig;
+use runtime_config::{PoolsConfig, RuntimeConfig};
 use spin_factor_otel::OtelFactorState;
 use spin_factor_outbound_networking::{
-    ConnectionSemaphore, OutboundNetworkingFactor, build_connection_semaphore,
+    ConnectionSemaphore, OutboundNetworkingFactor, audit::EgressAudit, build_connection_semaphore,
+    config::dns_overrides::DnsOverrides,
 };
 use spin_factors::{
     ConfigureAppContext, Factor, PrepareContext, RuntimeFactors, SelfInstanceBuilder, anyhow,
@@ -26,6 +27,8 @@ pub struct AppState<CF> {
     pub client_factories: HashMap<String, Arc<CF>>,
     /// Semaphore to limit concurrent outbound PostgreSQL connections.
     pub semaphore: ConnectionSemaphore,
+    /// Connection pool settings.
+    pub pools: Arc<PoolsConfig>,
 }
 
 impl<CF: ClientFactory> Factor for OutboundPgFactor<CF> {
@@ -40,7 +43,7 @@ impl<CF: ClientFactory> Factor for OutboundPgFactor<CF> {
             spin_world::spin::postgres3_0_0::postgres::add_to_linker::<_, PgFactorData<CF>>,
         )?;
         ctx.link_bindings(
-            spin_world::spin::postgres4_2_0::postgres::add_to_linker::<_, PgFactorData<CF>>,
+            spin_world::spin::postgres4_3_0::postgres::add_to_linker::<_, PgFactorData<CF>>,
         )?;
         Ok(())
     }
@@ -64,6 +67,7 @@ impl<CF: ClientFactory> Factor for OutboundPgFactor<CF> {
                 config.max_connections,
                 config.wait_timeout,
             ),
+            pools: Arc::new(config.pools),
         })
     }
 
@@ -71,9 +75,10 @@ impl<CF: ClientFactory> Factor for OutboundPgFactor<CF> {
         &self,
         mut ctx: PrepareContext<T, Self>,
     ) -> anyhow::Result<Self::InstanceBuilder> {
-        let allowed_hosts = ctx
-            .instance_builder::<OutboundNetworkingFactor>()?
-            .allowed_hosts();
+        let outbound_networking = ctx.instance_builder::<OutboundNetworkingFactor>()?;
+        let allowed_hosts = outbound_networking.allowed_hosts();
+        let dns_overrides = outbound_networking.dns_overrides();
+        let egress_audit = outbound_networking.egress_audit();
         let otel = OtelFactorState::from_prepare_context(&mut ctx)?;
         let cf = ctx
             .app_state()
@@ -84,10 +89,14 @@ impl<CF: ClientFactory> Factor for OutboundPgFactor<CF> {
         Ok(InstanceState {
             allowed_host_checker: AllowedHostChecker::new(allowed_hosts),
             client_factory: cf.clone(),
+            dns_overrides,
+            egress_audit,
             connections: Default::default(),
+            transactions: Default::default(),
             otel,
             builders: Default::default(),
             semaphore: ctx.app_state().semaphore.clone(),
+            pools: ctx.app_state().pools.clone(),
         })
     }
 }
@@ -109,13 +118,15 @@ impl<C> OutboundPgFactor<C> {
 pub struct InstanceState<CF: ClientFactory> {
     allowed_host_checker: AllowedHostChecker,
     client_factory: Arc<CF>,
-    connections: spin_resource_table::Table<(
-        CF::Client,
-        spin_factor_outbound_networking::ConnectionPermit,
-    )>,
+    dns_overrides: DnsOverrides,
+    /// Records connections to the egress audit log, if enabled.
+    egress_audit: Option<EgressAudit>,
+    connections: spin_resource_table::Table<host::Connection<CF::Client>>,
+    transactions: spin_resource_table::Table<host::Transaction<CF::Client>>,
     otel: OtelFactorState,
     builders: spin_resource_table::Table<host::ConnectionBuilder>,
     pub semaphore: ConnectionSemaphore,
+    pools: Arc<PoolsConfig>,
 }
 
 impl<CF: ClientFactory> SelfInstanceBuilder for InstanceState<CF> {}
@@ -1,10 +1,61 @@
 pub mod spin;
 
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Max connections in a given address' connection pool, unless configured.
+pub const DEFAULT_POOL_SIZE: usize = 64;
+/// Max prepared statements cached per pooled connection, unless configured.
+pub const DEFAULT_STATEMENT_CACHE_SIZE: usize = 100;
+
 /// Runtime configuration for outbound PostgreSQL.
 #[derive(Default)]
 pub struct RuntimeConfig {
     /// If set, limits the number of concurrent outbound PostgreSQL connections.
     pub max_connections: Option<usize>,
     /// If set, limits how long `acquire` will wait for a connection permit.
-    pub wait_timeout: Option<std::time::Duration>,
+    pub wait_timeout: Option<Duration>,
+    /// Connection pool settings.
+    pub pools: PoolsConfig,
+}
+
+/// Connection pool settings, by connection string.
+#[derive(Clone, Debug, Default)]
+pub struct PoolsConfig {
+    /// Settings for connection strings without their own.
+    pub default: PoolConfig,
+    /// Settings for particular connection strings.
+    pub by_address: HashMap<String, PoolConfig>,
+}
+
+impl PoolsConfig {
+    /// Returns the settings for the pool of connections to `address`.
+    pub fn get(&self, address: &str) -> &PoolConfig {
+        self.by_address.get(address).unwrap_or(&self.default)
+    }
+}
+
+/// Settings for the pool of connections to a connection string.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PoolConfig {
+    /// The maximum number of connections in the pool.
+    pub max_size: usize,
+    /// If set, idle connections are closed after this long.
+    pub idle_timeout: Option<Duration>,
+    /// If set, connections are closed once they are this old.
+    pub max_lifetime: Option<Duration>,
+    /// The maximum number of prepared statements cached per connection. Zero
+    /// disables caching.
+    pub statement_cache_size: usize,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            max_size: DEFAULT_POOL_SIZE,
+            idle_timeout: None,
+            max_lifetime: None,
+            statement_cache_size: DEFAULT_STATEMENT_CACHE_SIZE,
+        }
+    }
 }
@@ -1,21 +1,51 @@
+use std::time::Duration;
+
 use serde::Deserialize;
 use spin_factors::runtime_config::toml::GetTomlValue;
 
+use super::{PoolConfig, PoolsConfig};
+
 /// Get the runtime configuration for outbound PostgreSQL from a TOML table.
 ///
 /// Expects table to be in the format:
 /// ```toml
 /// [outbound_pg]
 /// max_connections = 10 # optional, defaults to unlimited
+///
+/// # Optional connection pool settings for all connection strings
+/// [outbound_pg.pool]
+/// max_size = 64              # optional, defaults to 64 connections
+/// idle_timeout = "5m"        # optional, defaults to none
+/// max_lifetime = "30m"       # optional, defaults to none
+/// statement_cache_size = 100 # optional, defaults to 100; 0 disables caching
+///
+/// # Zero or more overrides of the pool settings for particular connection
+/// # strings; settings not given are taken from `[outbound_pg.pool]`
+/// [[outbound_pg.pools]]
+/// address = "postgres://app@db.example.com/app"
+/// max_size = 16
 /// ```
 pub fn config_from_table(
     table: &impl GetTomlValue,
 ) -> anyhow::Result<Option<super::RuntimeConfig>> {
     if let Some(outbound_pg) = table.get("outbound_pg") {
         let toml = outbound_pg.clone().try_into::<OutboundPgToml>()?;
+        let default = toml.pool.apply(&PoolConfig::default())?;
+        let by_address = toml
+            .pools
+            .into_iter()
+            .map(|pool| {
+                let (address, settings) = pool.into_parts();
+                anyhow::Ok((address, settings.apply(&default)?))
+            })
+            .collect::<anyhow::Result<_>>()?;
         Ok(Some(super::RuntimeConfig {
             max_connections: toml.max_connections,
             wait_timeout: None,
+            pools: PoolsConfig {
+                default,
+                by_address,
+            },
         }))
     } else {
         Ok(None)
@@ -27,4 +57,116 @@ pub fn config_from_table(
 struct OutboundPgToml {
     #[serde(default)]
     max_connections: Option<usize>,
+    #[serde(default)]
+    pool: PoolToml,
+    #[serde(default)]
+    pools: Vec<AddressPoolToml>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PoolToml {
+    #[serde(default)]
+    max_size: Option<usize>,
+    #[serde(default, with = "spin_serde::duration::option")]
+    idle_timeout: Option<Duration>,
+    #[serde(default, with = "spin_serde::duration::option")]
+    max_lifetime: Option<Duration>,
+    #[serde(default)]
+    statement_cache_size: Option<usize>,
+}
+
+impl PoolToml {
+    /// Overrides the given settings with those set in this table.
+    fn apply(self, base: &PoolConfig) -> anyhow::Result<PoolConfig> {
+        let max_size = self.max_size.unwrap_or(base.max_size);
+        anyhow::ensure!(max_size > 0, "pool 'max_size' must be greater than zero");
+        Ok(PoolConfig {
+            max_size,
+            idle_timeout: self.idle_timeout.or(base.idle_timeout),
+            max_lifetime: self.max_lifetime.or(base.max_lifetime),
+            statement_cache_size: self
+                .statement_cache_size
+                .unwrap_or(base.statement_cache_size),
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AddressPoolToml {
+    address: String,
+    #[serde(default)]
+    max_size: Option<usize>,
+    #[serde(default, with = "spin_serde::duration::option")]
+    idle_timeout: Option<Duration>,
+    #[serde(default, with = "spin_serde::duration::option")]
+    max_lifetime: Option<Duration>,
+    #[serde(default)]
+    statement_cache_size: Option<usize>,
+}
+
+impl AddressPoolToml {
+    fn into_parts(self) -> (String, PoolToml) {
+        let settings = PoolToml {
+            max_size: self.max_size,
+            idle_timeout: self.idle_timeout,
+            max_lifetime: self.max_lifetime,
+            statement_cache_size: self.statement_cache_size,
+        };
+        (self.address, settings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime_config::DEFAULT_POOL_SIZE;
+
+    #[test]
+    fn test_pool_config() {
+        let table = toml::toml! {
+            [outbound_pg]
+            max_connections = 10
+
+            [outbound_pg.pool]
+            idle_timeout = "5m"
+            statement_cache_size = 0
+
+            [[outbound_pg.pools]]
+            address = "postgres://app@db.example.com/app"
+            max_size = 16
+            max_lifetime = "1h"
+        };
+        let config = config_from_table(&table).unwrap().unwrap();
+        assert_eq!(config.max_connections, Some(10));
+
+        let default = config.pools.get("postgres://other@db.example.com/app");
+        assert_eq!(
+            *default,
+            PoolConfig {
+                max_size: DEFAULT_POOL_SIZE,
+                idle_timeout: Some(Duration::from_secs(300)),
+                max_lifetime: None,
+                statement_cache_size: 0,
+            }
+        );
+
+        let app = config.pools.get("postgres://app@db.example.com/app");
+        assert_eq!(
+            *app,
+            PoolConfig {
+                max_size: 16,
+                idle_timeout: Some(Duration::from_secs(300)),
+                max_lifetime: Some(Duration::from_secs(3600)),
+                statement_cache_size: 0,
+            }
+        );
+
+        let table = toml::toml! {
+            [outbound_pg.pool]
+            max_size = 0
+        };
+        assert!(config_from_table(&table).is_err());
+    }
 }
@@ -1,5 +1,5 @@
 use anyhow::Result;
-use spin_world::spin::postgres4_2_0::postgres::{self as v4, DbDataType, DbValue, ParameterValue};
+use spin_world::spin::postgres4_3_0::postgres::{self as v4, DbDataType, DbValue, ParameterValue};
 use tokio_postgres::types::{FromSql, Type};
 use tokio_postgres::{Row, types::ToSql};
 
@@ -2,7 +2,7 @@
 //! the tokio_postgres driver.
 
 use anyhow::{Context, anyhow};
-use spin_world::spin::postgres4_2_0::postgres::{self as v4};
+use spin_world::spin::postgres4_3_0::postgres::{self as v4};
 
 use super::decimal::RangeableDecimal;
 
@@ -1,18 +1,21 @@
 use anyhow::{Result, bail};
+use spin_core::wasmtime::component::Resource;
 use spin_factor_outbound_networking::OutboundNetworkingFactor;
+use spin_factor_outbound_networking::config::dns_overrides::DnsOverrides;
 use spin_factor_outbound_pg::OutboundPgFactor;
 use spin_factor_outbound_pg::client::Client;
 use spin_factor_outbound_pg::client::ClientFactory;
 use spin_factor_outbound_pg::client::HashableCertificate;
 use spin_factor_outbound_pg::client::QueryAsyncResult;
+use spin_factor_outbound_pg::runtime_config::PoolConfig;
 use spin_factor_variables::VariablesFactor;
 use spin_factors::{RuntimeFactors, anyhow};
 use spin_factors_test::{TestEnvironment, toml};
 use spin_world::async_trait;
-use spin_world::spin::postgres4_2_0::postgres::Error as PgError;
-use spin_world::spin::postgres4_2_0::postgres::HostConnection;
-use spin_world::spin::postgres4_2_0::postgres::{self as v2};
-use spin_world::spin::postgres4_2_0::postgres::{ParameterValue, RowSet};
+use spin_world::spin::postgres4_3_0::postgres::Error as PgError;
+use spin_world::spin::postgres4_3_0::postgres::HostConnection;
+use spin_world::spin::postgres4_3_0::postgres::{self as v2};
+use spin_world::spin::postgres4_3_0::postgres::{ParameterValue, RowSet};
 
 #[derive(RuntimeFactors)]
 struct TestFactors {
@@ -106,6 +109,70 @@ async fn exercise_query() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn one_transaction_per_connection() -> anyhow::Result<()> {
+    let mut state = test_env().build_instance_state().await?;
+
+    let connection = state
+        .pg
+        .open("postgres://localhost:5432/test".to_string())
+        .await?;
+    let rep = connection.rep();
+
+    let transaction = state.pg.begin_transaction(connection).await?;
+    let res = state.pg.begin_transaction(Resource::new_borrow(rep)).await;
+    assert!(matches!(res, Err(PgError::Other(_))));
+
+    v2::HostTransaction::commit(&mut state.pg, transaction).await?;
+    let transaction = state
+        .pg
+        .begin_transaction(Resource::new_borrow(rep))
+        .await?;
+
+    // Dropping the transaction rolls it back and frees the connection
+    v2::HostTransaction::drop(&mut state.pg, transaction).await?;
+    state
+        .pg
+        .begin_transaction(Resource::new_borrow(rep))
+        .await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn connection_statements_fail_during_transaction() -> anyhow::Result<()> {
+    let mut state = test_env().build_instance_state().await?;
+
+    let connection = state
+        .pg
+        .open("postgres://localhost:5432/test".to_string())
+        .await?;
+    let rep = connection.rep();
+
+    let transaction = state.pg.begin_transaction(connection).await?;
+    let res = state
+        .pg
+        .execute(
+            Resource::new_borrow(rep),
+            "SELECT * FROM test".to_string(),
+            vec![],
+        )
+        .await;
+    assert!(matches!(res, Err(PgError::Other(_))));
+
+    v2::HostTransaction::commit(&mut state.pg, transaction).await?;
+    state
+        .pg
+        .execute(
+            Resource::new_borrow(rep),
+            "SELECT * FROM test".to_string(),
+            vec![],
+        )
+        .await?;
+
+    Ok(())
+}
+
 // TODO: We can expand this mock to track calls and simulate return values
 #[derive(Default)]
 pub struct MockClientFactory {}
@@ -119,6 +186,8 @@ impl ClientFactory for MockClientFactory {
         &self,
         _address: &str,
         _root_ca: Option<HashableCertificate>,
+        _dns_overrides: &DnsOverrides,
+        _pool_config: &PoolConfig,
     ) -> Result<Self::Client> {
         Ok(MockClient {})
     }
@@ -154,4 +223,16 @@ impl Client for MockClient {
     ) -> Result<QueryAsyncResult, v2::Error> {
         panic!("not implemented");
     }
+
+    async fn begin_transaction(&self) -> Result<(), v2::Error> {
+        Ok(())
+    }
+
+    async fn commit_transaction(&self) -> Result<(), v2::Error> {
+        Ok(())
+    }
+
+    async fn rollback_transaction(&self) -> Result<(), v2::Error> {
+        Ok(())
+    }
 }
@@ -1,6 +1,7 @@
 use std::sync::Arc;
 
 use spin_factor_outbound_networking::config::allowed_hosts::OutboundAllowedHosts;
+use spin_redis_connection::RedisAddress;
 
 /// Encapsulates checking of a PostgreSQL address/connection string against
 /// an allow-list.
@@ -21,7 +22,20 @@ impl AllowedHostChecker {
 }
 
 impl AllowedHostChecker {
-    pub async fn is_address_allowed(&self, address: &str) -> anyhow::Result<bool> {
-        self.allowed_hosts.check_url(address, "redis").await
+    /// Checks every host that connecting to `address` contacts directly: the
+    /// server, each cluster seed node, or each Sentinel.
+    pub async fn is_address_allowed(&self, address: &RedisAddress) -> anyhow::Result<bool> {
+        for url in address.host_urls() {
+            if !self.is_url_allowed(&url).await? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Checks a single `redis://` or `rediss://` URL, such as that of a
+    /// primary discovered through Sentinel or a node of a cluster.
+    pub async fn is_url_allowed(&self, url: &str) -> anyhow::Result<bool> {
+        self.allowed_hosts.check_url(url, "redis").await
     }
 }
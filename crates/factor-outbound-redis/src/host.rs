@@ -1,18 +1,28 @@
+use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
 use anyhow::Result;
 use opentelemetry_semantic_conventions::attribute as otel_attribute;
-use redis::AsyncConnectionConfig;
+use redis::aio::{AsyncPushSender, ConnectionLike, SendError};
 use redis::io::AsyncDNSResolver;
-use redis::{AsyncCommands, FromRedisValue, Value, aio::MultiplexedConnection};
+use redis::{AsyncCommands, FromRedisValue, PushInfo, PushKind, Value, aio::MultiplexedConnection};
+use redis::{Cmd, Pipeline, RedisFuture};
 use spin_core::wasmtime::component::{Accessor, Resource};
 use spin_factor_otel::OtelFactorState;
-use spin_factor_outbound_networking::ConnectionSemaphore;
-use spin_factor_outbound_networking::config::blocked_networks::BlockedNetworks;
+use spin_factor_outbound_networking::audit::{AuditedConnection, EgressAudit};
+use spin_factor_outbound_networking::config::{
+    blocked_networks::BlockedNetworks, dns_overrides::DnsOverrides,
+};
+use spin_factor_outbound_networking::{ConnectionPermit, ConnectionSemaphore};
+use spin_redis_connection::{ConnectOptions, RedisAddress, RedisConnection};
 use spin_world::MAX_HOST_BUFFERED_BYTES;
-use spin_world::spin::redis::redis as v3;
+use spin_world::spin::redis3_1_0::redis as v3;
 use spin_world::v1::{redis as v1, redis_types};
 use spin_world::v2::redis as v2;
+use tokio::sync::{Mutex, mpsc};
 use tracing::field::Empty;
 use tracing::{Level, instrument};
 
@@ -21,37 +31,168 @@ use crate::allowed_hosts::AllowedHostChecker;
 pub struct InstanceState {
     pub(crate) allowed_host_checker: AllowedHostChecker,
     pub blocked_networks: BlockedNetworks,
-    pub connections: spin_resource_table::Table<(
-        MultiplexedConnection,
-        spin_factor_outbound_networking::ConnectionPermit,
-    )>,
+    pub dns_overrides: DnsOverrides,
+    pub connections: spin_resource_table::Table<(AuditedRedisConnection, ConnectionPermit)>,
+    pub subscriptions: spin_resource_table::Table<Arc<Mutex<Subscription>>>,
     pub semaphore: ConnectionSemaphore,
+    /// Where connections are audited, if egress auditing is enabled.
+    pub egress_audit: Option<EgressAudit>,
     pub otel: OtelFactorState,
 }
 
+/// The most push messages buffered for a subscription before the guest
+/// receives them.
+const MAX_BUFFERED_MESSAGES: usize = 1024;
+
+/// A connection subscribed to pub/sub channels.
+pub struct Subscription {
+    /// Held to keep the connection open.
+    _conn: MultiplexedConnection,
+    /// Push messages received on the connection.
+    messages: mpsc::Receiver<PushInfo>,
+    /// Set once a message is dropped because the buffer was full.
+    overflowed: Arc<AtomicBool>,
+    _permit: ConnectionPermit,
+    /// Tallies the messages received, if egress auditing is enabled.
+    audit: Option<Arc<AuditedConnection>>,
+}
+
+/// Forwards push messages into a bounded channel.
+///
+/// The connection can't wait for the guest to catch up, so once the channel
+/// is full further messages are dropped and the subscription is marked as
+/// overflowed.
+struct BoundedPushSender {
+    sender: mpsc::Sender<PushInfo>,
+    overflowed: Arc<AtomicBool>,
+}
+
+impl AsyncPushSender for BoundedPushSender {
+    fn send(&self, info: PushInfo) -> Result<(), SendError> {
+        if self.overflowed.load(Ordering::Relaxed) {
+            return Err(SendError);
+        }
+        self.sender.try_send(info).map_err(|e| {
+            if let mpsc::error::TrySendError::Full(_) = e {
+                self.overflowed.store(true, Ordering::Relaxed);
+            }
+            SendError
+        })
+    }
+}
+
+/// A [`RedisConnection`] which tallies the commands sent and replies received
+/// on it, which are recorded to the egress audit log once it and all its
+/// clones are dropped.
+#[derive(Clone)]
+pub struct AuditedRedisConnection {
+    conn: RedisConnection,
+    audit: Option<Arc<AuditedConnection>>,
+}
+
+impl AuditedRedisConnection {
+    fn new(conn: RedisConnection, audit: Option<Arc<AuditedConnection>>) -> Self {
+        Self { conn, audit }
+    }
+
+    fn sent<'a>(&self, cmds: impl IntoIterator<Item = &'a Cmd>) {
+        if let Some(audit) = &self.audit {
+            audit.sent(cmds.into_iter().map(cmd_size).sum());
+        }
+    }
+
+    fn received<'a>(&self, values: impl IntoIterator<Item = &'a Value>) {
+        if let Some(audit) = &self.audit {
+            audit.received(values.into_iter().map(value_size).sum());
+        }
+    }
+}
+
+impl ConnectionLike for AuditedRedisConnection {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+        Box::pin(async move {
+            self.sent([cmd]);
+            let value = self.conn.req_packed_command(cmd).await?;
+            self.received([&value]);
+            Ok(value)
+        })
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        cmd: &'a Pipeline,
+        offset: usize,
+        count: usize,
+    ) -> RedisFuture<'a, Vec<Value>> {
+        Box::pin(async move {
+            self.sent(cmd.cmd_iter());
+            let values = self.conn.req_packed_commands(cmd, offset, count).await?;
+            self.received(&values);
+            Ok(values)
+        })
+    }
+
+    fn get_db(&self) -> i64 {
+        self.conn.get_db()
+    }
+}
+
+/// The size of the arguments of a command.
+fn cmd_size(cmd: &Cmd) -> usize {
+    cmd.args_iter()
+        .map(|arg| match arg {
+            redis::Arg::Simple(arg) => arg.len(),
+            redis::Arg::Cursor => std::mem::size_of::<u64>(),
+        })
+        .sum()
+}
+
+/// The in-memory size of a reply.
+fn value_size(value: &Value) -> usize {
+    let pairs_size = |pairs: &[(Value, Value)]| {
+        pairs
+            .iter()
+            .map(|(k, v)| value_size(k) + value_size(v))
+            .sum::<usize>()
+    };
+    std::mem::size_of::<Value>()
+        + match value {
+            Value::BulkString(data) => data.len(),
+            Value::SimpleString(s) => s.len(),
+            Value::VerbatimString { text, .. } => text.len(),
+            Value::Array(values) | Value::Set(values) | Value::Push { data: values, .. } => {
+                values.iter().map(value_size).sum()
+            }
+            Value::Map(pairs) => pairs_size(pairs),
+            Value::Attribute { data, attributes } => value_size(data) + pairs_size(attributes),
+            _ => 0,
+        }
+}
+
 impl InstanceState {
-    async fn is_address_allowed(&self, address: &str) -> Result<bool> {
+    async fn is_address_allowed(&self, address: &RedisAddress) -> Result<bool> {
         self.allowed_host_checker.is_address_allowed(address).await
     }
 
     async fn establish_connection(
         &mut self,
-        address: String,
+        address: RedisAddress,
     ) -> Result<Resource<v2::Connection>, v2::Error> {
         let permit = self
             .semaphore
             .acquire()
             .await
             .map_err(|_| v2::Error::TooManyConnections)?;
-        let config = AsyncConnectionConfig::new()
-            .set_dns_resolver(SpinDnsResolver(self.blocked_networks.clone()));
-        let conn = redis::Client::open(address.as_str())
-            .map_err(|_| v2::Error::InvalidAddress)?
-            .get_multiplexed_async_connection_with_config(&config)
-            .await
-            .map_err(other_error_v2)?;
+        let audit = audit_connection(self.egress_audit.as_ref(), &address);
+        let options = connect_options(
+            self.allowed_host_checker.clone(),
+            self.blocked_networks.clone(),
+            self.dns_overrides.clone(),
+            audit.clone(),
+        );
+        let conn = address.connect(&options).await.map_err(other_error_v2)?;
         self.connections
-            .push((conn, permit))
+            .push((AuditedRedisConnection::new(conn, audit), permit))
             .map(Resource::new_own)
             .map_err(|_| v2::Error::TooManyConnections)
     }
@@ -59,7 +200,7 @@ impl InstanceState {
     async fn get_conn(
         &mut self,
         connection: Resource<v2::Connection>,
-    ) -> Result<&mut MultiplexedConnection, v2::Error> {
+    ) -> Result<&mut AuditedRedisConnection, v2::Error> {
         self.connections
             .get_mut(connection.rep())
             .map(|(conn, _permit)| conn)
@@ -71,7 +212,7 @@ impl InstanceState {
     fn get_conn_v3(
         &mut self,
         connection: Resource<v3::Connection>,
-    ) -> Result<MultiplexedConnection, v3::Error> {
+    ) -> Result<AuditedRedisConnection, v3::Error> {
         self.connections
             .get(connection.rep())
             .map(|(conn, _permit)| conn.clone())
@@ -85,7 +226,7 @@ mod operations {
     use super::*;
 
     pub async fn publish(
-        conn: &mut MultiplexedConnection,
+        conn: &mut (impl ConnectionLike + Send + Sync),
         channel: String,
         payload: v3::Payload,
     ) -> Result<(), v3::Error> {
@@ -99,7 +240,7 @@ mod operations {
     }
 
     pub async fn get(
-        conn: &mut MultiplexedConnection,
+        conn: &mut (impl ConnectionLike + Send + Sync),
         key: String,
     ) -> Result<Option<Vec<u8>>, v3::Error> {
         let value = conn
@@ -122,7 +263,7 @@ mod operations {
     }
 
     pub async fn set(
-        conn: &mut MultiplexedConnection,
+        conn: &mut (impl ConnectionLike + Send + Sync),
         key: String,
         value: Vec<u8>,
     ) -> Result<(), v3::Error> {
@@ -132,19 +273,22 @@ mod operations {
         Ok(())
     }
 
-    pub async fn incr(conn: &mut MultiplexedConnection, key: String) -> Result<i64, v3::Error> {
+    pub async fn incr(
+        conn: &mut (impl ConnectionLike + Send + Sync),
+        key: String,
+    ) -> Result<i64, v3::Error> {
         conn.incr(&key, 1).await.map_err(other_error_v3)
     }
 
     pub async fn del(
-        conn: &mut MultiplexedConnection,
+        conn: &mut (impl ConnectionLike + Send + Sync),
         keys: Vec<String>,
     ) -> Result<u32, v3::Error> {
         conn.del(&keys).await.map_err(other_error_v3)
     }
 
     pub async fn sadd(
-        conn: &mut MultiplexedConnection,
+        conn: &mut (impl ConnectionLike + Send + Sync),
         key: String,
         values: Vec<String>,
     ) -> Result<u32, v3::Error> {
@@ -159,14 +303,14 @@ mod operations {
     }
 
     pub async fn smembers(
-        conn: &mut MultiplexedConnection,
+        conn: &mut (impl ConnectionLike + Send + Sync),
         key: String,
     ) -> Result<Vec<String>, v3::Error> {
         conn.smembers(&key).await.map_err(other_error_v3)
     }
 
     pub async fn srem(
-        conn: &mut MultiplexedConnection,
+        conn: &mut (impl ConnectionLike + Send + Sync),
         key: String,
         values: Vec<String>,
     ) -> Result<u32, v3::Error> {
@@ -174,11 +318,219 @@ mod operations {
     }
 
     pub async fn execute(
-        conn: &mut MultiplexedConnection,
+        conn: &mut (impl ConnectionLike + Send + Sync),
         command: String,
         arguments: impl IntoIterator<Item = v3::RedisParameter>,
     ) -> Result<RedisResults, v3::Error> {
-        let mut cmd = redis::cmd(&command);
+        let results = build_command(&command, arguments)
+            .query_async::<RedisResults>(conn)
+            .await
+            .map_err(other_error_v3)?;
+
+        ensure_within_limit(
+            std::mem::size_of::<Vec<v3::RedisResult>>()
+                + results.0.iter().map(memory_size).sum::<usize>(),
+        )?;
+        Ok(results)
+    }
+
+    pub async fn execute_pipeline(
+        conn: &mut (impl ConnectionLike + Send + Sync),
+        commands: Vec<v3::Command>,
+        atomic: bool,
+    ) -> Result<Vec<Vec<v3::RedisResult>>, v3::Error> {
+        let mut pipe = redis::pipe();
+        if atomic {
+            pipe.atomic();
+        }
+        for command in commands {
+            pipe.add_command(build_command(&command.name, command.arguments));
+        }
+
+        let values = pipe
+            .query_async::<Vec<Value>>(conn)
+            .await
+            .map_err(other_error_v3)?;
+        let results = values
+            .iter()
+            .map(|value| RedisResults::from_redis_value(value).map(RedisResults::into_v3))
+            .collect::<redis::RedisResult<Vec<_>>>()
+            .map_err(other_error_v3)?;
+
+        ensure_within_limit(
+            results
+                .iter()
+                .map(|r| {
+                    std::mem::size_of::<Vec<v3::RedisResult>>()
+                        + r.iter().map(memory_size).sum::<usize>()
+                })
+                .sum(),
+        )?;
+        Ok(results)
+    }
+
+    pub async fn eval_script(
+        conn: &mut (impl ConnectionLike + Send + Sync),
+        script: String,
+        keys: Vec<String>,
+        arguments: Vec<v3::RedisParameter>,
+    ) -> Result<Vec<v3::RedisResult>, v3::Error> {
+        // `Script` invokes the script with `EVALSHA`, loading it into the
+        // server's script cache only if it isn't there already.
+        let script = redis::Script::new(&script);
+        let mut invocation = script.prepare_invoke();
+        for key in keys {
+            invocation.key(key);
+        }
+        for argument in arguments {
+            match argument {
+                v3::RedisParameter::Int64(v) => invocation.arg(v),
+                v3::RedisParameter::Binary(v) => invocation.arg(v),
+            };
+        }
+
+        let results = invocation
+            .invoke_async::<RedisResults>(conn)
+            .await
+            .map_err(other_error_v3)?
+            .into_v3();
+
+        ensure_within_limit(results.iter().map(memory_size).sum())?;
+        Ok(results)
+    }
+
+    pub async fn hget(
+        conn: &mut (impl ConnectionLike + Send + Sync),
+        key: String,
+        field: String,
+    ) -> Result<Option<Vec<u8>>, v3::Error> {
+        let value = conn
+            .hget::<_, _, Option<Vec<u8>>>(&key, &field)
+            .await
+            .map_err(other_error_v3)?;
+        ensure_within_limit(value.as_ref().map(|v| v.len()).unwrap_or(0))?;
+        Ok(value)
+    }
+
+    pub async fn hset(
+        conn: &mut (impl ConnectionLike + Send + Sync),
+        key: String,
+        fields: Vec<(String, Vec<u8>)>,
+    ) -> Result<u32, v3::Error> {
+        redis::cmd("HSET")
+            .arg(&key)
+            .arg(&fields)
+            .query_async(conn)
+            .await
+            .map_err(other_error_v3)
+    }
+
+    pub async fn hgetall(
+        conn: &mut (impl ConnectionLike + Send + Sync),
+        key: String,
+    ) -> Result<Vec<(String, Vec<u8>)>, v3::Error> {
+        let fields = conn
+            .hgetall::<_, HashMap<String, Vec<u8>>>(&key)
+            .await
+            .map_err(other_error_v3)?;
+        ensure_within_limit(
+            fields
+                .iter()
+                .map(|(field, value)| field.len() + value.len())
+                .sum(),
+        )?;
+        Ok(fields.into_iter().collect())
+    }
+
+    pub async fn hdel(
+        conn: &mut (impl ConnectionLike + Send + Sync),
+        key: String,
+        fields: Vec<String>,
+    ) -> Result<u32, v3::Error> {
+        conn.hdel(&key, &fields).await.map_err(other_error_v3)
+    }
+
+    pub async fn hincrby(
+        conn: &mut (impl ConnectionLike + Send + Sync),
+        key: String,
+        field: String,
+        increment: i64,
+    ) -> Result<i64, v3::Error> {
+        conn.hincr(&key, &field, increment)
+            .await
+            .map_err(other_error_v3)
+    }
+
+    pub async fn zadd(
+        conn: &mut (impl ConnectionLike + Send + Sync),
+        key: String,
+        members: Vec<(f64, String)>,
+    ) -> Result<u32, v3::Error> {
+        conn.zadd_multiple(&key, &members)
+            .await
+            .map_err(other_error_v3)
+    }
+
+    pub async fn zrem(
+        conn: &mut (impl ConnectionLike + Send + Sync),
+        key: String,
+        members: Vec<String>,
+    ) -> Result<u32, v3::Error> {
+        conn.zrem(&key, &members).await.map_err(other_error_v3)
+    }
+
+    pub async fn zscore(
+        conn: &mut (impl ConnectionLike + Send + Sync),
+        key: String,
+        member: String,
+    ) -> Result<Option<f64>, v3::Error> {
+        conn.zscore(&key, &member).await.map_err(other_error_v3)
+    }
+
+    pub async fn zcard(
+        conn: &mut (impl ConnectionLike + Send + Sync),
+        key: String,
+    ) -> Result<u64, v3::Error> {
+        conn.zcard(&key).await.map_err(other_error_v3)
+    }
+
+    pub async fn zrange(
+        conn: &mut (impl ConnectionLike + Send + Sync),
+        key: String,
+        start: i64,
+        stop: i64,
+    ) -> Result<Vec<(String, f64)>, v3::Error> {
+        let members = redis::cmd("ZRANGE")
+            .arg(&key)
+            .arg(start)
+            .arg(stop)
+            .arg("WITHSCORES")
+            .query_async::<Vec<(String, f64)>>(conn)
+            .await
+            .map_err(other_error_v3)?;
+        ensure_within_limit(sorted_set_size(&members))?;
+        Ok(members)
+    }
+
+    pub async fn zrange_by_score(
+        conn: &mut (impl ConnectionLike + Send + Sync),
+        key: String,
+        min: f64,
+        max: f64,
+    ) -> Result<Vec<(String, f64)>, v3::Error> {
+        let members = conn
+            .zrangebyscore_withscores::<_, _, _, Vec<(String, f64)>>(&key, min, max)
+            .await
+            .map_err(other_error_v3)?;
+        ensure_within_limit(sorted_set_size(&members))?;
+        Ok(members)
+    }
+
+    fn build_command(
+        name: &str,
+        arguments: impl IntoIterator<Item = v3::RedisParameter>,
+    ) -> redis::Cmd {
+        let mut cmd = redis::cmd(name);
         arguments.into_iter().for_each(|value| match value {
             v3::RedisParameter::Int64(v) => {
                 cmd.arg(v);
@@ -187,24 +539,26 @@ mod operations {
                 cmd.arg(v);
             }
         });
+        cmd
+    }
 
-        let results = cmd
-            .query_async::<RedisResults>(conn)
-            .await
-            .map_err(other_error_v3)?;
+    fn sorted_set_size(members: &[(String, f64)]) -> usize {
+        members
+            .iter()
+            .map(|(member, _)| std::mem::size_of::<(String, f64)>() + member.len())
+            .sum()
+    }
 
-        // Currently there's no way to stream results using the `redis`
-        // crate without buffering, so the damage (in terms of host memory
-        // usage) is already done, but we can still enforce the limit:
-        if std::mem::size_of::<Vec<v3::RedisResult>>()
-            + results.0.iter().map(memory_size).sum::<usize>()
-            > MAX_HOST_BUFFERED_BYTES
-        {
+    /// Currently there's no way to stream results using the `redis` crate
+    /// without buffering, so the damage (in terms of host memory usage) is
+    /// already done, but we can still enforce the limit.
+    fn ensure_within_limit(byte_count: usize) -> Result<(), v3::Error> {
+        if byte_count > MAX_HOST_BUFFERED_BYTES {
             Err(v3::Error::Other(format!(
                 "query result exceeds limit of {MAX_HOST_BUFFERED_BYTES} bytes"
             )))
         } else {
-            Ok(results)
+            Ok(())
         }
     }
 }
@@ -222,36 +576,46 @@ impl v3::HostConnection for crate::InstanceState {
     }
 }
 
+impl v3::HostSubscription for crate::InstanceState {
+    async fn drop(&mut self, subscription: Resource<v3::Subscription>) -> anyhow::Result<()> {
+        self.subscriptions.remove(subscription.rep());
+        Ok(())
+    }
+}
+
 impl crate::RedisFactorData {
     fn get_conn<T: Send>(
         accessor: &Accessor<T, Self>,
         connection: Resource<v3::Connection>,
-    ) -> Result<MultiplexedConnection, v3::Error> {
+    ) -> Result<AuditedRedisConnection, v3::Error> {
         accessor.with(|mut access| {
             let host = access.get();
             host.otel.reparent_tracing_span();
             host.get_conn_v3(connection)
         })
     }
-}
 
-impl<T: Send> v3::HostConnectionWithStore<T> for crate::RedisFactorData {
-    #[instrument(name = "spin_outbound_redis.open_connection", skip(accessor, address), err(level = Level::INFO),
-        fields(otel.kind = "client", {otel_attribute::DB_SYSTEM_NAME} = "redis", {otel_attribute::SERVER_ADDRESS} = Empty, {otel_attribute::SERVER_PORT} = Empty, {otel_attribute::DB_NAMESPACE} = Empty))]
-    async fn open(
+    /// Checks that `address` is allowed and acquires a connection permit,
+    /// returning the parsed address, the config to connect with, and the
+    /// connection's audit tally.
+    async fn prepare_connection<T: Send>(
         accessor: &Accessor<T, Self>,
-        address: String,
-    ) -> Result<Resource<v3::Connection>, v3::Error> {
-        let (allowed_host_checker, blocked_networks, semaphore) = accessor.with(|mut access| {
-            let host = access.get();
-            host.otel.reparent_tracing_span();
-            (
-                host.allowed_host_checker.clone(),
-                host.blocked_networks.clone(),
-                host.semaphore.clone(),
-            )
-        });
-
+        address: &str,
+    ) -> Result<PreparedConnection, v3::Error> {
+        let (allowed_host_checker, blocked_networks, dns_overrides, semaphore, egress_audit) =
+            accessor.with(|mut access| {
+                let host = access.get();
+                host.otel.reparent_tracing_span();
+                (
+                    host.allowed_host_checker.clone(),
+                    host.blocked_networks.clone(),
+                    host.dns_overrides.clone(),
+                    host.semaphore.clone(),
+                    host.egress_audit.clone(),
+                )
+            });
+
+        let address: RedisAddress = address.parse().map_err(|_| v3::Error::InvalidAddress)?;
         if !allowed_host_checker
             .is_address_allowed(&address)
             .await
@@ -265,18 +629,40 @@ impl<T: Send> v3::HostConnectionWithStore<T> for crate::RedisFactorData {
             .await
             .map_err(|_| v3::Error::TooManyConnections)?;
 
-        let config =
-            AsyncConnectionConfig::new().set_dns_resolver(SpinDnsResolver(blocked_networks));
-        let conn = redis::Client::open(address.as_str())
-            .map_err(|_| v3::Error::InvalidAddress)?
-            .get_multiplexed_async_connection_with_config(&config)
-            .await
-            .map_err(other_error_v3)?;
+        let audit = audit_connection(egress_audit.as_ref(), &address);
+        let options = connect_options(
+            allowed_host_checker,
+            blocked_networks,
+            dns_overrides,
+            audit.clone(),
+        );
+
+        Ok((address, options, permit, audit))
+    }
+}
+
+type PreparedConnection = (
+    RedisAddress,
+    ConnectOptions,
+    ConnectionPermit,
+    Option<Arc<AuditedConnection>>,
+);
+
+impl<T: Send> v3::HostConnectionWithStore<T> for crate::RedisFactorData {
+    #[instrument(name = "spin_outbound_redis.open_connection", skip(accessor, address), err(level = Level::INFO),
+        fields(otel.kind = "client", {otel_attribute::DB_SYSTEM_NAME} = "redis", {otel_attribute::SERVER_ADDRESS} = Empty, {otel_attribute::SERVER_PORT} = Empty, {otel_attribute::DB_NAMESPACE} = Empty))]
+    async fn open(
+        accessor: &Accessor<T, Self>,
+        address: String,
+    ) -> Result<Resource<v3::Connection>, v3::Error> {
+        let (address, options, permit, audit) =
+            Self::prepare_connection(accessor, &address).await?;
+        let conn = address.connect(&options).await.map_err(other_error_v3)?;
 
         accessor.with(|mut access| {
             let host = access.get();
             host.connections
-                .push((conn, permit))
+                .push((AuditedRedisConnection::new(conn, audit), permit))
                 .map(Resource::new_own)
                 .map_err(|_| v3::Error::TooManyConnections)
         })
@@ -387,6 +773,261 @@ impl<T: Send> v3::HostConnectionWithStore<T> for crate::RedisFactorData {
             .await?
             .into_v3())
     }
+
+    #[instrument(name = "spin_outbound_redis.execute_pipeline", skip(accessor, connection, commands), err(level = Level::INFO),
+        fields(otel.kind = "client", {otel_attribute::DB_SYSTEM_NAME} = "redis", otel.name = "PIPELINE"))]
+    async fn execute_pipeline(
+        accessor: &Accessor<T, Self>,
+        connection: Resource<v3::Connection>,
+        commands: Vec<v3::Command>,
+        atomic: bool,
+    ) -> Result<Vec<Vec<v3::RedisResult>>, v3::Error> {
+        let mut conn = Self::get_conn(accessor, connection)?;
+        operations::execute_pipeline(&mut conn, commands, atomic).await
+    }
+
+    #[instrument(name = "spin_outbound_redis.eval_script", skip(accessor, connection, script, keys, arguments), err(level = Level::INFO),
+        fields(otel.kind = "client", {otel_attribute::DB_SYSTEM_NAME} = "redis", otel.name = "EVALSHA"))]
+    async fn eval_script(
+        accessor: &Accessor<T, Self>,
+        connection: Resource<v3::Connection>,
+        script: String,
+        keys: Vec<String>,
+        arguments: Vec<v3::RedisParameter>,
+    ) -> Result<Vec<v3::RedisResult>, v3::Error> {
+        let mut conn = Self::get_conn(accessor, connection)?;
+        operations::eval_script(&mut conn, script, keys, arguments).await
+    }
+
+    #[instrument(name = "spin_outbound_redis.hget", skip(accessor, connection), err(level = Level::INFO),
+        fields(otel.kind = "client", {otel_attribute::DB_SYSTEM_NAME} = "redis", otel.name = "HGET"))]
+    async fn hget(
+        accessor: &Accessor<T, Self>,
+        connection: Resource<v3::Connection>,
+        key: String,
+        field: String,
+    ) -> Result<Option<v3::Payload>, v3::Error> {
+        let mut conn = Self::get_conn(accessor, connection)?;
+        operations::hget(&mut conn, key, field).await
+    }
+
+    #[instrument(name = "spin_outbound_redis.hset", skip(accessor, connection, fields), err(level = Level::INFO),
+        fields(otel.kind = "client", {otel_attribute::DB_SYSTEM_NAME} = "redis", otel.name = "HSET"))]
+    async fn hset(
+        accessor: &Accessor<T, Self>,
+        connection: Resource<v3::Connection>,
+        key: String,
+        fields: Vec<(String, v3::Payload)>,
+    ) -> Result<u32, v3::Error> {
+        let mut conn = Self::get_conn(accessor, connection)?;
+        operations::hset(&mut conn, key, fields).await
+    }
+
+    #[instrument(name = "spin_outbound_redis.hgetall", skip(accessor, connection), err(level = Level::INFO),
+        fields(otel.kind = "client", {otel_attribute::DB_SYSTEM_NAME} = "redis", otel.name = "HGETALL"))]
+    async fn hgetall(
+        accessor: &Accessor<T, Self>,
+        connection: Resource<v3::Connection>,
+        key: String,
+    ) -> Result<Vec<(String, v3::Payload)>, v3::Error> {
+        let mut conn = Self::get_conn(accessor, connection)?;
+        operations::hgetall(&mut conn, key).await
+    }
+
+    #[instrument(name = "spin_outbound_redis.hdel", skip(accessor, connection, fields), err(level = Level::INFO),
+        fields(otel.kind = "client", {otel_attribute::DB_SYSTEM_NAME} = "redis", otel.name = "HDEL"))]
+    async fn hdel(
+        accessor: &Accessor<T, Self>,
+        connection: Resource<v3::Connection>,
+        key: String,
+        fields: Vec<String>,
+    ) -> Result<u32, v3::Error> {
+        let mut conn = Self::get_conn(accessor, connection)?;
+        operations::hdel(&mut conn, key, fields).await
+    }
+
+    #[instrument(name = "spin_outbound_redis.hincrby", skip(accessor, connection), err(level = Level::INFO),
+        fields(otel.kind = "client", {otel_attribute::DB_SYSTEM_NAME} = "redis", otel.name = "HINCRBY"))]
+    async fn hincrby(
+        accessor: &Accessor<T, Self>,
+        connection: Resource<v3::Connection>,
+        key: String,
+        field: String,
+        increment: i64,
+    ) -> Result<i64, v3::Error> {
+        let mut conn = Self::get_conn(accessor, connection)?;
+        operations::hincrby(&mut conn, key, field, increment).await
+    }
+
+    #[instrument(name = "spin_outbound_redis.zadd", skip(accessor, connection, members), err(level = Level::INFO),
+        fields(otel.kind = "client", {otel_attribute::DB_SYSTEM_NAME} = "redis", otel.name = "ZADD"))]
+    async fn zadd(
+        accessor: &Accessor<T, Self>,
+        connection: Resource<v3::Connection>,
+        key: String,
+        members: Vec<(f64, String)>,
+    ) -> Result<u32, v3::Error> {
+        let mut conn = Self::get_conn(accessor, connection)?;
+        operations::zadd(&mut conn, key, members).await
+    }
+
+    #[instrument(name = "spin_outbound_redis.zrem", skip(accessor, connection, members), err(level = Level::INFO),
+        fields(otel.kind = "client", {otel_attribute::DB_SYSTEM_NAME} = "redis", otel.name = "ZREM"))]
+    async fn zrem(
+        accessor: &Accessor<T, Self>,
+        connection: Resource<v3::Connection>,
+        key: String,
+        members: Vec<String>,
+    ) -> Result<u32, v3::Error> {
+        let mut conn = Self::get_conn(accessor, connection)?;
+        operations::zrem(&mut conn, key, members).await
+    }
+
+    #[instrument(name = "spin_outbound_redis.zscore", skip(accessor, connection), err(level = Level::INFO),
+        fields(otel.kind = "client", {otel_attribute::DB_SYSTEM_NAME} = "redis", otel.name = "ZSCORE"))]
+    async fn zscore(
+        accessor: &Accessor<T, Self>,
+        connection: Resource<v3::Connection>,
+        key: String,
+        member: String,
+    ) -> Result<Option<f64>, v3::Error> {
+        let mut conn = Self::get_conn(accessor, connection)?;
+        operations::zscore(&mut conn, key, member).await
+    }
+
+    #[instrument(name = "spin_outbound_redis.zcard", skip(accessor, connection), err(level = Level::INFO),
+        fields(otel.kind = "client", {otel_attribute::DB_SYSTEM_NAME} = "redis", otel.name = "ZCARD"))]
+    async fn zcard(
+        accessor: &Accessor<T, Self>,
+        connection: Resource<v3::Connection>,
+        key: String,
+    ) -> Result<u64, v3::Error> {
+        let mut conn = Self::get_conn(accessor, connection)?;
+        operations::zcard(&mut conn, key).await
+    }
+
+    #[instrument(name = "spin_outbound_redis.zrange", skip(accessor, connection), err(level = Level::INFO),
+        fields(otel.kind = "client", {otel_attribute::DB_SYSTEM_NAME} = "redis", otel.name = "ZRANGE"))]
+    async fn zrange(
+        accessor: &Accessor<T, Self>,
+        connection: Resource<v3::Connection>,
+        key: String,
+        start: i64,
+        stop: i64,
+    ) -> Result<Vec<(String, f64)>, v3::Error> {
+        let mut conn = Self::get_conn(accessor, connection)?;
+        operations::zrange(&mut conn, key, start, stop).await
+    }
+
+    #[instrument(name = "spin_outbound_redis.zrange_by_score", skip(accessor, connection), err(level = Level::INFO),
+        fields(otel.kind = "client", {otel_attribute::DB_SYSTEM_NAME} = "redis", otel.name = "ZRANGEBYSCORE"))]
+    async fn zrange_by_score(
+        accessor: &Accessor<T, Self>,
+        connection: Resource<v3::Connection>,
+        key: String,
+        min: f64,
+        max: f64,
+    ) -> Result<Vec<(String, f64)>, v3::Error> {
+        let mut conn = Self::get_conn(accessor, connection)?;
+        operations::zrange_by_score(&mut conn, key, min, max).await
+    }
+}
+
+impl<T: Send> v3::HostSubscriptionWithStore<T> for crate::RedisFactorData {
+    #[instrument(name = "spin_outbound_redis.subscribe", skip(accessor, address, channels), err(level = Level::INFO),
+        fields(otel.kind = "client", {otel_attribute::DB_SYSTEM_NAME} = "redis", otel.name = "SUBSCRIBE"))]
+    async fn open(
+        accessor: &Accessor<T, Self>,
+        address: String,
+        channels: Vec<String>,
+    ) -> Result<Resource<v3::Subscription>, v3::Error> {
+        let (sender, messages) = mpsc::channel(MAX_BUFFERED_MESSAGES);
+        let overflowed = Arc::new(AtomicBool::new(false));
+        let push_sender = BoundedPushSender {
+            sender,
+            overflowed: overflowed.clone(),
+        };
+        let (mut address, options, permit, audit) =
+            Self::prepare_connection(accessor, &address).await?;
+        // Subscriptions are delivered as RESP3 push messages on a connection
+        // to a single node.
+        address.set_protocol(redis::ProtocolVersion::RESP3);
+        let mut conn = address
+            .connect_node(&options.with_push_sender(push_sender))
+            .await
+            .map_err(other_error_v3)?;
+        conn.subscribe(&channels).await.map_err(other_error_v3)?;
+        if let Some(audit) = &audit {
+            audit.sent(channels.iter().map(|channel| channel.len()).sum());
+        }
+
+        let subscription = Subscription {
+            _conn: conn,
+            messages,
+            overflowed,
+            _permit: permit,
+            audit,
+        };
+        accessor.with(|mut access| {
+            access
+                .get()
+                .subscriptions
+                .push(Arc::new(Mutex::new(subscription)))
+                .map(Resource::new_own)
+                .map_err(|_| v3::Error::TooManyConnections)
+        })
+    }
+
+    #[instrument(name = "spin_outbound_redis.receive", skip(accessor, subscription), err(level = Level::INFO),
+        fields(otel.kind = "client", {otel_attribute::DB_SYSTEM_NAME} = "redis"))]
+    async fn receive(
+        accessor: &Accessor<T, Self>,
+        subscription: Resource<v3::Subscription>,
+        timeout_ms: u32,
+    ) -> Result<Option<v3::Message>, v3::Error> {
+        let subscription = accessor.with(|mut access| {
+            let host = access.get();
+            host.otel.reparent_tracing_span();
+            host.subscriptions
+                .get(subscription.rep())
+                .cloned()
+                .ok_or(v3::Error::Other(
+                    "could not find subscription for resource".into(),
+                ))
+        })?;
+        let mut subscription = subscription.lock().await;
+        // Deliver the messages buffered before the overflow, then report the gap
+        if subscription.overflowed.load(Ordering::Relaxed) && subscription.messages.is_empty() {
+            return Err(v3::Error::Other(format!(
+                "subscription closed: more than {MAX_BUFFERED_MESSAGES} messages were waiting to be received"
+            )));
+        }
+
+        let deadline = tokio::time::Instant::now() + Duration::from_millis(timeout_ms.into());
+        loop {
+            let push = match tokio::time::timeout_at(deadline, subscription.messages.recv()).await {
+                Ok(Some(push)) => push,
+                Ok(None) => return Err(v3::Error::Other("subscription connection closed".into())),
+                Err(_elapsed) => return Ok(None),
+            };
+            if let Some(audit) = &subscription.audit {
+                audit.received(push.data.iter().map(value_size).sum());
+            }
+            match push.kind {
+                PushKind::Message => {
+                    let (channel, payload) =
+                        redis::from_redis_value::<(String, Vec<u8>)>(&Value::Array(push.data))
+                            .map_err(other_error_v3)?;
+                    return Ok(Some(v3::Message { channel, payload }));
+                }
+                PushKind::Disconnection => {
+                    return Err(v3::Error::Other("subscription connection closed".into()));
+                }
+                // Subscription confirmations and other push messages
+                _ => continue,
+            }
+        }
+    }
 }
 
 impl v2::Host for crate::InstanceState {
@@ -400,6 +1041,7 @@ impl v2::HostConnection for crate::InstanceState {
         fields(otel.kind = "client", {otel_attribute::DB_SYSTEM_NAME} = "redis", {otel_attribute::SERVER_ADDRESS} = Empty, {otel_attribute::SERVER_PORT} = Empty, {otel_attribute::DB_NAMESPACE} = Empty))]
     async fn open(&mut self, address: String) -> Result<Resource<v2::Connection>, v2::Error> {
         self.otel.reparent_tracing_span();
+        let address: RedisAddress = address.parse().map_err(|_| v2::Error::InvalidAddress)?;
         if !self
             .is_address_allowed(&address)
             .await
@@ -563,10 +1205,11 @@ fn other_error_v3(e: impl std::fmt::Display) -> v3::Error {
 /// Delegate a function call to the v2::HostConnection implementation
 macro_rules! delegate {
     ($self:ident.$name:ident($address:expr, $($arg:expr),*)) => {{
-        if !$self.is_address_allowed(&$address).await.map_err(|_| v1::Error::Error)?  {
+        let address: RedisAddress = $address.parse().map_err(|_| v1::Error::Error)?;
+        if !$self.is_address_allowed(&address).await.map_err(|_| v1::Error::Error)?  {
             return Err(v1::Error::Error);
         }
-        let connection = match $self.establish_connection($address).await {
+        let connection = match $self.establish_connection(address).await {
             Ok(c) => c,
             Err(_) => return Err(v1::Error::Error),
         };
@@ -751,8 +1394,46 @@ fn memory_size(value: &v3::RedisResult) -> usize {
     }
 }
 
-/// Resolves DNS using Tokio's resolver, filtering out blocked IPs.
-struct SpinDnsResolver(BlockedNetworks);
+/// Starts auditing a connection to `address`, if egress auditing is enabled.
+fn audit_connection(
+    egress_audit: Option<&EgressAudit>,
+    address: &RedisAddress,
+) -> Option<Arc<AuditedConnection>> {
+    egress_audit.map(|audit| Arc::new(audit.connection("redis", address.to_string())))
+}
+
+/// Builds the options for connecting to an address, resolving host names
+/// with Spin's DNS overrides and network blocklist, recording the first
+/// address resolved to `audit`, and checking a primary discovered through
+/// Sentinel or a node of a cluster against the allowed hosts.
+fn connect_options(
+    allowed_host_checker: AllowedHostChecker,
+    blocked_networks: BlockedNetworks,
+    dns_overrides: DnsOverrides,
+    audit: Option<Arc<AuditedConnection>>,
+) -> ConnectOptions {
+    let resolver = SpinDnsResolver(blocked_networks, dns_overrides, audit);
+    ConnectOptions::default()
+        .with_dns_resolver(resolver)
+        .with_node_check(move |url| {
+            let allowed_host_checker = allowed_host_checker.clone();
+            async move {
+                allowed_host_checker
+                    .is_url_allowed(&url)
+                    .await
+                    .unwrap_or_else(|err| {
+                        tracing::error!("Failed to check Redis node {url}: {err}");
+                        false
+                    })
+            }
+        })
+}
+
+struct SpinDnsResolver(
+    BlockedNetworks,
+    DnsOverrides,
+    Option<Arc<AuditedConnection>>,
+);
 
 impl AsyncDNSResolver for SpinDnsResolver {
     fn resolve<'a, 'b: 'a>(
@@ -761,9 +1442,12 @@ impl AsyncDNSResolver for SpinDnsResolver {
         port: u16,
     ) -> redis::RedisFuture<'a, Box<dyn Iterator<Item = std::net::SocketAddr> + Send + 'a>> {
         Box::pin(async move {
-            let mut addrs = tokio::net::lookup_host((host, port))
-                .await?
-                .collect::<Vec<_>>();
+            let mut addrs = match self.1.get(host, port) {
+                Some(addr) => vec![addr],
+                None => tokio::net::lookup_host((host, port))
+                    .await?
+                    .collect::<Vec<_>>(),
+            };
             // Remove blocked IPs
             let blocked_addrs = self.0.remove_blocked(&mut addrs);
             if addrs.is_empty() && !blocked_addrs.is_empty() {
@@ -773,7 +1457,241 @@ impl AsyncDNSResolver for SpinDnsResolver {
                     "all destination IP(s) prohibited by runtime config"
                 );
             }
+            if let (Some(audit), Some(addr)) = (&self.2, addrs.first()) {
+                audit.resolved_ip(addr.ip());
+            }
             Ok(Box::new(addrs.into_iter()) as Box<dyn Iterator<Item = SocketAddr> + Send>)
         })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::collections::VecDeque;
+
+    use super::*;
+
+    /// A connection which records the commands sent on it and answers them
+    /// with canned replies.
+    #[derive(Default)]
+    struct MockConnection {
+        sent: Vec<Vec<String>>,
+        replies: VecDeque<Value>,
+    }
+
+    impl MockConnection {
+        fn new(replies: impl IntoIterator<Item = Value>) -> Self {
+            Self {
+                sent: Vec::new(),
+                replies: replies.into_iter().collect(),
+            }
+        }
+
+        fn record(&mut self, cmd: &Cmd) {
+            self.sent.push(
+                cmd.args_iter()
+                    .map(|arg| match arg {
+                        redis::Arg::Simple(arg) => String::from_utf8_lossy(arg).into_owned(),
+                        redis::Arg::Cursor => "<cursor>".into(),
+                    })
+                    .collect(),
+            );
+        }
+    }
+
+    impl ConnectionLike for MockConnection {
+        fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+            self.record(cmd);
+            let reply = self.replies.pop_front().expect("no reply for command");
+            Box::pin(async move { Ok(reply) })
+        }
+
+        fn req_packed_commands<'a>(
+            &'a mut self,
+            cmd: &'a Pipeline,
+            _offset: usize,
+            count: usize,
+        ) -> RedisFuture<'a, Vec<Value>> {
+            if cmd.is_transaction() {
+                self.sent.push(vec!["MULTI".into()]);
+            }
+            cmd.cmd_iter().for_each(|cmd| self.record(cmd));
+            if cmd.is_transaction() {
+                self.sent.push(vec!["EXEC".into()]);
+            }
+            let replies = self.replies.drain(..count).collect();
+            Box::pin(async move { Ok(replies) })
+        }
+
+        fn get_db(&self) -> i64 {
+            0
+        }
+    }
+
+    fn bulk(s: &str) -> Value {
+        Value::BulkString(s.as_bytes().to_vec())
+    }
+
+    fn command(name: &str, arguments: &[&str]) -> v3::Command {
+        v3::Command {
+            name: name.into(),
+            arguments: arguments
+                .iter()
+                .map(|a| v3::RedisParameter::Binary(a.as_bytes().to_vec()))
+                .collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn pipeline_returns_results_per_command() {
+        let mut conn = MockConnection::new([Value::Okay, bulk("value")]);
+        let results = operations::execute_pipeline(
+            &mut conn,
+            vec![command("SET", &["key", "value"]), command("GET", &["key"])],
+            false,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(conn.sent, [vec!["SET", "key", "value"], vec!["GET", "key"]]);
+        assert_eq!(results.len(), 2);
+        assert!(matches!(&results[0][..], [v3::RedisResult::Status(s)] if s == "OK"));
+        assert!(matches!(&results[1][..], [v3::RedisResult::Binary(b)] if b == b"value"));
+    }
+
+    #[tokio::test]
+    async fn atomic_pipeline_is_wrapped_in_a_transaction() {
+        let mut conn = MockConnection::new([Value::Array(vec![Value::Int(1), Value::Int(2)])]);
+        let results = operations::execute_pipeline(
+            &mut conn,
+            vec![command("INCR", &["a"]), command("INCR", &["b"])],
+            true,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            conn.sent,
+            [
+                vec!["MULTI"],
+                vec!["INCR", "a"],
+                vec!["INCR", "b"],
+                vec!["EXEC"]
+            ]
+        );
+        assert!(matches!(&results[0][..], [v3::RedisResult::Int64(1)]));
+        assert!(matches!(&results[1][..], [v3::RedisResult::Int64(2)]));
+    }
+
+    #[tokio::test]
+    async fn script_is_invoked_by_hash() {
+        let script = "return redis.call('GET', KEYS[1])";
+        let mut conn = MockConnection::new([bulk("value")]);
+        let results = operations::eval_script(
+            &mut conn,
+            script.into(),
+            vec!["key".into()],
+            vec![v3::RedisParameter::Int64(7)],
+        )
+        .await
+        .unwrap();
+
+        let hash = redis::Script::new(script).get_hash().to_owned();
+        assert_eq!(conn.sent, [vec!["EVALSHA", &hash, "1", "key", "7"]]);
+        assert!(matches!(&results[..], [v3::RedisResult::Binary(b)] if b == b"value"));
+    }
+
+    #[tokio::test]
+    async fn hash_operations() {
+        let mut conn = MockConnection::new([
+            Value::Int(2),
+            Value::Array(vec![bulk("a"), bulk("1"), bulk("b"), bulk("2")]),
+            Value::Int(5),
+        ]);
+        let added = operations::hset(
+            &mut conn,
+            "hash".into(),
+            vec![("a".into(), b"1".to_vec()), ("b".into(), b"2".to_vec())],
+        )
+        .await
+        .unwrap();
+        let mut fields = operations::hgetall(&mut conn, "hash".into()).await.unwrap();
+        let incremented = operations::hincrby(&mut conn, "hash".into(), "c".into(), 5)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            conn.sent,
+            [
+                vec!["HSET", "hash", "a", "1", "b", "2"],
+                vec!["HGETALL", "hash"],
+                vec!["HINCRBY", "hash", "c", "5"]
+            ]
+        );
+        assert_eq!(added, 2);
+        fields.sort();
+        assert_eq!(
+            fields,
+            [("a".into(), b"1".to_vec()), ("b".into(), b"2".to_vec())]
+        );
+        assert_eq!(incremented, 5);
+    }
+
+    #[tokio::test]
+    async fn sorted_set_operations() {
+        let mut conn = MockConnection::new([
+            Value::Int(2),
+            Value::Array(vec![bulk("a"), bulk("1.5"), bulk("b"), bulk("2")]),
+            bulk("2"),
+        ]);
+        let added = operations::zadd(
+            &mut conn,
+            "zset".into(),
+            vec![(1.5, "a".into()), (2.0, "b".into())],
+        )
+        .await
+        .unwrap();
+        let members = operations::zrange(&mut conn, "zset".into(), 0, -1)
+            .await
+            .unwrap();
+        let score = operations::zscore(&mut conn, "zset".into(), "b".into())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            conn.sent,
+            [
+                vec!["ZADD", "zset", "1.5", "a", "2.0", "b"],
+                vec!["ZRANGE", "zset", "0", "-1", "WITHSCORES"],
+                vec!["ZSCORE", "zset", "b"]
+            ]
+        );
+        assert_eq!(added, 2);
+        assert_eq!(members, [("a".into(), 1.5), ("b".into(), 2.0)]);
+        assert_eq!(score, Some(2.0));
+    }
+
+    #[test]
+    fn push_messages_beyond_buffer_are_dropped() {
+        let (sender, mut messages) = mpsc::channel(1);
+        let overflowed = Arc::new(AtomicBool::new(false));
+        let push_sender = BoundedPushSender {
+            sender,
+            overflowed: overflowed.clone(),
+        };
+        let message = |payload: &str| PushInfo {
+            kind: PushKind::Message,
+            data: vec![bulk("channel"), bulk(payload)],
+        };
+
+        assert!(push_sender.send(message("first")).is_ok());
+        assert!(!overflowed.load(Ordering::Relaxed));
+        assert!(push_sender.send(message("second")).is_err());
+        assert!(overflowed.load(Ordering::Relaxed));
+
+        // Nothing is delivered after the gap, even once there's room
+        assert_eq!(messages.try_recv().unwrap().data[1], bulk("first"));
+        assert!(push_sender.send(message("third")).is_err());
+        assert!(messages.try_recv().is_err());
+    }
+}
@@ -12,7 +12,7 @@ use spin_factors::{
     ConfigureAppContext, Factor, FactorData, PrepareContext, RuntimeFactors, SelfInstanceBuilder,
     anyhow,
 };
-use spin_world::spin::redis::redis as v3;
+use spin_world::spin::redis3_1_0::redis as v3;
 
 use crate::allowed_hosts::AllowedHostChecker;
 
@@ -72,7 +72,10 @@ impl Factor for OutboundRedisFactor {
         Ok(InstanceState {
             allowed_host_checker: AllowedHostChecker::new(outbound_networking.allowed_hosts()),
             blocked_networks: outbound_networking.blocked_networks(),
+            dns_overrides: outbound_networking.dns_overrides(),
             connections: spin_resource_table::Table::new(1024),
+            subscriptions: spin_resource_table::Table::new(1024),
+            egress_audit: outbound_networking.egress_audit(),
             semaphore: ctx.app_state().semaphore.clone(),
             otel,
         })
@@ -41,3 +41,33 @@ async fn no_outbound_hosts_fails() -> anyhow::Result<()> {
     assert!(matches!(err, Error::InvalidAddress));
     Ok(())
 }
+
+#[tokio::test]
+async fn cluster_requires_every_seed_host_allowed() -> anyhow::Result<()> {
+    let factors = TestFactors {
+        variables: VariablesFactor::default(),
+        networking: OutboundNetworkingFactor::new(),
+        redis: OutboundRedisFactor::new(),
+    };
+    let env = TestEnvironment::new(factors).extend_manifest(toml! {
+        spin_manifest_version = 2
+        application.name = "test-app"
+        [[trigger.test]]
+
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        allowed_outbound_hosts = ["redis://a.redis.test:7000"]
+    });
+    let mut state = env.build_instance_state().await?;
+    let connection = state
+        .redis
+        .open("redis+cluster://a.redis.test:7000,b.redis.test:7000".to_string())
+        .await;
+
+    let Err(err) = connection else {
+        bail!("expected Error, got Ok");
+    };
+
+    assert!(matches!(err, Error::InvalidAddress));
+    Ok(())
+}
@@ -14,6 +14,7 @@ use std::{
 };
 
 use io::{PipeReadStream, PipedWriteStream};
+use sockets::SocketAuditState;
 use spin_factors::{
     AppComponent, Factor, FactorInstanceBuilder, InitContext, PrepareContext, RuntimeFactors,
     RuntimeFactorsInstanceState, anyhow,
@@ -26,7 +27,10 @@ use wasmtime_wasi::random::{WasiRandom, WasiRandomCtx};
 use wasmtime_wasi::sockets::{WasiSockets, WasiSocketsCtxView};
 use wasmtime_wasi::{DirPerms, FilePerms, ResourceTable, WasiCtx, WasiCtxBuilder, WasiCtxView};
 
-pub use sockets::{SocketPermitState, SpinSockets, SpinSocketsView};
+pub use sockets::{
+    NameLookupOverride, NameOverride, SocketAuditor, SocketPermitState, SocketTally, SpinSockets,
+    SpinSocketsView,
+};
 pub use wasi_2023_10_18::convert_result;
 pub use wasi_2026_03_15::{FutureReaderExt, StreamReaderExt, reborrow};
 pub use wasmtime_wasi::sockets::SocketAddrUse;
@@ -73,6 +77,8 @@ impl WasiFactor {
                 table,
             },
             permit_state: state.socket_permit_state.clone(),
+            name_lookup_override: state.name_lookup_override.clone(),
+            audit_state: state.socket_audit_state.clone(),
             getter,
         })
     }
@@ -184,6 +190,8 @@ trait InitContextExt: InitContext<WasiFactor> {
                 table,
             },
             permit_state: state.socket_permit_state.clone(),
+            name_lookup_override: state.name_lookup_override.clone(),
+            audit_state: state.socket_audit_state.clone(),
             getter: Self::get_wasi_sockets,
         }
     }
@@ -261,7 +269,6 @@ trait InitContextExt: InitContext<WasiFactor> {
             fn(&mut Self::StoreData) -> WasiCliCtxView<'_>,
             fn(&mut Self::StoreData) -> WasiFilesystemCtxView<'_>,
             fn(&mut Self::StoreData) -> SpinSocketsView<'_, Self::StoreData>,
-            fn(&mut Self::StoreData) -> WasiSocketsCtxView<'_>,
         ) -> anyhow::Result<()>,
     ) -> anyhow::Result<()> {
         add_to_linker(
@@ -271,7 +278,6 @@ trait InitContextExt: InitContext<WasiFactor> {
             Self::get_cli,
             Self::get_filesystem,
             Self::get_spin_sockets,
-            Self::get_wasi_sockets,
         )
     }
 }
@@ -365,11 +371,11 @@ impl Factor for WasiFactor {
         ctx.link_sockets_default_bindings(
             p2::bindings::sockets::network::add_to_linker::<_, WasiSockets>,
         )?;
-        ctx.link_sockets_bindings(
-            p2::bindings::sockets::ip_name_lookup::add_to_linker::<_, WasiSockets>,
+        ctx.link_spin_sockets_bindings(
+            p2::bindings::sockets::ip_name_lookup::add_to_linker::<_, SpinSockets<T::StoreData>>,
         )?;
-        ctx.link_sockets_bindings(
-            p3::bindings::sockets::ip_name_lookup::add_to_linker::<_, WasiSockets>,
+        ctx.link_spin_sockets_bindings(
+            p3::bindings::sockets::ip_name_lookup::add_to_linker::<_, SpinSockets<T::StoreData>>,
         )?;
         ctx.link_spin_sockets_bindings(
             p3::bindings::sockets::types::add_to_linker::<_, SpinSockets<T::StoreData>>,
@@ -402,6 +408,8 @@ impl Factor for WasiFactor {
         let mut builder = InstanceBuilder {
             ctx: wasi_ctx,
             socket_permit_state: None,
+            name_lookup_override: None,
+            socket_audit_state: None,
         };
 
         // Apply environment variables
@@ -460,6 +468,8 @@ impl MountFilesContext<'_> {
 pub struct InstanceBuilder {
     ctx: WasiCtxBuilder,
     socket_permit_state: Option<Arc<SocketPermitState>>,
+    name_lookup_override: Option<NameLookupOverride>,
+    socket_audit_state: Option<Arc<SocketAuditState>>,
 }
 
 impl InstanceBuilder {
@@ -533,10 +543,14 @@ impl FactorInstanceBuilder for InstanceBuilder {
         let InstanceBuilder {
             ctx: mut wasi_ctx,
             socket_permit_state,
+            name_lookup_override,
+            socket_audit_state,
         } = self;
         Ok(InstanceState {
             ctx: wasi_ctx.build(),
             socket_permit_state,
+            name_lookup_override,
+            socket_audit_state,
         })
     }
 }
@@ -547,6 +561,18 @@ impl InstanceBuilder {
         self.socket_permit_state = Some(state);
     }
 
+    /// Sets a [`NameOverride`] which may override the resolution of host
+    /// names looked up with `wasi:sockets/ip-name-lookup`.
+    pub fn override_name_lookup(&mut self, lookup: impl NameOverride + 'static) {
+        self.name_lookup_override = Some(Arc::new(lookup));
+    }
+
+    /// Sets a [`SocketAuditor`] to tally the bytes carried by TCP connections
+    /// made with `wasi:sockets`.
+    pub fn audit_sockets(&mut self, auditor: impl SocketAuditor + 'static) {
+        self.socket_audit_state = Some(SocketAuditState::new(Arc::new(auditor)));
+    }
+
     pub fn outbound_socket_addr_check<F, Fut>(&mut self, check: F)
     where
         F: Fn(SocketAddr, SocketAddrUse) -> Fut + Send + Sync + Clone + 'static,
@@ -570,6 +596,8 @@ impl InstanceBuilder {
 pub struct InstanceState {
     ctx: WasiCtx,
     socket_permit_state: Option<Arc<SocketPermitState>>,
+    name_lookup_override: Option<NameLookupOverride>,
+    socket_audit_state: Option<Arc<SocketAuditState>>,
 }
 
 impl InstanceState {
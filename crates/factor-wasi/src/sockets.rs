@@ -3,16 +3,31 @@
 //! This module provides [`SocketPermitState`], [`SpinSocketsView`], and
 //! [`SpinSockets`] — the types needed to intercept WASI TCP/UDP socket
 //! creation and enforce a per-app cap on the number of concurrently open
-//! sockets.
+//! sockets. [`SpinSocketsView`] also applies any [`NameLookupOverride`] to
+//! `wasi:sockets/ip-name-lookup` lookups and the TCP connections which follow,
+//! and tallies the bytes carried by TCP connections for a [`SocketAuditor`].
 
 use std::{
     collections::HashMap,
     marker::PhantomData,
+    net::{IpAddr, SocketAddr},
+    pin::Pin,
     sync::{Arc, Mutex},
+    task::{Context, Poll},
 };
 
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::{Stream as _, channel::mpsc};
 use spin_connection_semaphore::{ConnectionPermit, ConnectionSemaphore};
-use wasmtime::component::{HasData, Resource};
+use wasmtime::component::{
+    Destination, HasData, Resource, Source, StreamConsumer, StreamProducer, StreamReader,
+    StreamResult,
+};
+use wasmtime::{AsContextMut, StoreContextMut};
+use wasmtime_wasi::p2::bindings::sockets::ip_name_lookup::{
+    self as p2_lookup, IpAddress, ResolveAddressStream,
+};
 use wasmtime_wasi::p2::bindings::sockets::network::{
     ErrorCode as SocketErrorCode, Host as NetworkHost, Network,
 };
@@ -20,7 +35,9 @@ use wasmtime_wasi::p2::bindings::sockets::tcp::{self as p2_tcp, IpSocketAddress,
 use wasmtime_wasi::p2::bindings::sockets::tcp_create_socket as p2_tcp_create;
 use wasmtime_wasi::p2::bindings::sockets::udp as p2_udp;
 use wasmtime_wasi::p2::bindings::sockets::udp_create_socket as p2_udp_create;
-use wasmtime_wasi::p2::{DynInputStream, DynOutputStream, DynPollable};
+use wasmtime_wasi::p2::{
+    DynInputStream, DynOutputStream, DynPollable, InputStream, OutputStream, Pollable, StreamError,
+};
 use wasmtime_wasi::sockets::{TcpSocket, UdpSocket, WasiSockets, WasiSocketsCtxView};
 
 /// Shared state for tracking per-socket semaphore permits. Permits are
@@ -41,11 +58,59 @@ impl SocketPermitState {
     }
 }
 
+/// Overrides the resolution of host names looked up with
+/// `wasi:sockets/ip-name-lookup`.
+pub trait NameOverride: Send + Sync {
+    /// Returns the IP address to resolve `name` to, if overridden.
+    fn resolve(&self, name: &str) -> Option<IpAddr>;
+
+    /// Returns the address to connect a TCP socket to in place of `addr`, if
+    /// `addr` is the result of an overridden lookup.
+    fn redirect(&self, addr: SocketAddr) -> Option<SocketAddr>;
+}
+
+pub type NameLookupOverride = Arc<dyn NameOverride>;
+
+/// Audits the TCP connections made with `wasi:sockets`.
+pub trait SocketAuditor: Send + Sync {
+    /// Starts auditing a connection to `addr`, returning the tally of the
+    /// bytes it carries. The connection ends once the tally is dropped.
+    fn connect(&self, addr: SocketAddr) -> Arc<dyn SocketTally>;
+}
+
+/// Tallies the bytes carried by an audited connection.
+pub trait SocketTally: Send + Sync {
+    /// Adds `bytes` to the bytes sent on the connection.
+    fn sent(&self, bytes: usize);
+
+    /// Adds `bytes` to the bytes received on the connection.
+    fn received(&self, bytes: usize);
+}
+
+/// Per-instance state for auditing TCP connections.
+pub(crate) struct SocketAuditState {
+    auditor: Arc<dyn SocketAuditor>,
+    /// Tallies of connected sockets keyed by socket resource rep, released
+    /// when the resource is dropped. The socket's streams hold them too.
+    active: Mutex<HashMap<u32, Arc<dyn SocketTally>>>,
+}
+
+impl SocketAuditState {
+    pub(crate) fn new(auditor: Arc<dyn SocketAuditor>) -> Arc<Self> {
+        Arc::new(Self {
+            auditor,
+            active: Mutex::new(HashMap::new()),
+        })
+    }
+}
+
 /// A view over WASI socket state that carries an optional per-instance socket
 /// permit store, enabling per-connection quota tracking.
 pub struct SpinSocketsView<'a, T> {
     pub(crate) inner: WasiSocketsCtxView<'a>,
     pub(crate) permit_state: Option<Arc<SocketPermitState>>,
+    pub(crate) name_lookup_override: Option<NameLookupOverride>,
+    pub(crate) audit_state: Option<Arc<SocketAuditState>>,
     pub(crate) getter: fn(&mut T) -> WasiSocketsCtxView<'_>,
 }
 
@@ -106,6 +171,37 @@ impl<T> SpinSocketsView<'_, T> {
             .insert(socket_rep, permit);
     }
 
+    /// Returns the name to look up in place of `name`; an overridden name is
+    /// replaced by its IP address, which resolves to itself.
+    pub(crate) fn override_name(&self, name: String) -> String {
+        match self
+            .name_lookup_override
+            .as_ref()
+            .and_then(|lookup| lookup.resolve(&name))
+        {
+            Some(ip) => {
+                tracing::debug!(name, %ip, "Overriding name lookup");
+                ip.to_string()
+            }
+            None => name,
+        }
+    }
+
+    /// Returns the address to connect to in place of `addr`.
+    pub(crate) fn override_connect_addr(&self, addr: SocketAddr) -> SocketAddr {
+        match self
+            .name_lookup_override
+            .as_ref()
+            .and_then(|lookup| lookup.redirect(addr))
+        {
+            Some(redirected) => {
+                tracing::debug!(%addr, %redirected, "Overriding connection address");
+                redirected
+            }
+            None => addr,
+        }
+    }
+
     /// Releases the connection permit for `socket_rep`, if any.
     pub(crate) fn release_permit(&self, socket_rep: u32) {
         if let Some(state) = &self.permit_state {
@@ -116,6 +212,40 @@ impl<T> SpinSocketsView<'_, T> {
                 .remove(&socket_rep);
         }
     }
+
+    /// Starts auditing the connection of `socket_rep` to `addr`, if auditing.
+    pub(crate) fn audit_connect(&self, socket_rep: u32, addr: SocketAddr) {
+        if let Some(state) = &self.audit_state {
+            let tally = state.auditor.connect(addr);
+            state
+                .active
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .insert(socket_rep, tally);
+        }
+    }
+
+    /// Returns the tally of the connection of `socket_rep`, if audited.
+    pub(crate) fn tally(&self, socket_rep: u32) -> Option<Arc<dyn SocketTally>> {
+        let state = self.audit_state.as_ref()?;
+        state
+            .active
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&socket_rep)
+            .cloned()
+    }
+
+    /// Releases the tally of the connection of `socket_rep`, if any.
+    pub(crate) fn release_tally(&self, socket_rep: u32) {
+        if let Some(state) = &self.audit_state {
+            state
+                .active
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .remove(&socket_rep);
+        }
+    }
 }
 
 impl<T> p2_tcp::Host for SpinSocketsView<'_, T> {}
@@ -149,11 +279,17 @@ impl<T> p2_tcp::HostTcpSocket for SpinSocketsView<'_, T> {
             tracing::warn!("TCP socket connection refused: connection quota exhausted");
             return Err(SocketErrorCode::NewSocketLimit.into());
         };
-        let result =
-            p2_tcp::HostTcpSocket::start_connect(&mut self.inner, this, network, remote_address)
-                .await;
+        let remote_address = self.override_connect_addr(remote_address.into());
+        let result = p2_tcp::HostTcpSocket::start_connect(
+            &mut self.inner,
+            this,
+            network,
+            remote_address.into(),
+        )
+        .await;
         if result.is_ok() {
             self.register_permit(socket_rep, permit);
+            self.audit_connect(socket_rep, remote_address);
         }
         // On error, `permit` is dropped here, automatically releasing the semaphore slot.
         result
@@ -164,7 +300,24 @@ impl<T> p2_tcp::HostTcpSocket for SpinSocketsView<'_, T> {
         this: Resource<TcpSocket>,
     ) -> wasmtime_wasi::p2::SocketResult<(Resource<DynInputStream>, Resource<DynOutputStream>)>
     {
-        p2_tcp::HostTcpSocket::finish_connect(&mut self.inner, this)
+        let socket_rep = this.rep();
+        let (input, output) = p2_tcp::HostTcpSocket::finish_connect(&mut self.inner, this)?;
+        let Some(tally) = self.tally(socket_rep) else {
+            return Ok((input, output));
+        };
+        // Replace the streams with ones which tally the bytes they carry
+        let socket = Resource::<TcpSocket>::new_borrow(socket_rep);
+        let input: DynInputStream = Box::new(TalliedInputStream {
+            inner: self.inner.table.delete(input)?,
+            tally: tally.clone(),
+        });
+        let output: DynOutputStream = Box::new(TalliedOutputStream {
+            inner: self.inner.table.delete(output)?,
+            tally,
+        });
+        let input = self.inner.table.push_child(input, &socket)?;
+        let output = self.inner.table.push_child(output, &socket)?;
+        Ok((input, output))
     }
 
     fn start_listen(&mut self, this: Resource<TcpSocket>) -> wasmtime_wasi::p2::SocketResult<()> {
@@ -335,6 +488,7 @@ impl<T> p2_tcp::HostTcpSocket for SpinSocketsView<'_, T> {
 
     fn drop(&mut self, this: Resource<TcpSocket>) -> wasmtime::Result<()> {
         self.release_permit(this.rep());
+        self.release_tally(this.rep());
         p2_tcp::HostTcpSocket::drop(&mut self.inner, this)
     }
 }
@@ -361,6 +515,37 @@ impl<T> wasmtime_wasi::p2::bindings::sockets::network::HostNetwork for SpinSocke
     }
 }
 
+impl<T> p2_lookup::Host for SpinSocketsView<'_, T> {
+    fn resolve_addresses(
+        &mut self,
+        network: Resource<Network>,
+        name: String,
+    ) -> wasmtime_wasi::p2::SocketResult<Resource<ResolveAddressStream>> {
+        let name = self.override_name(name);
+        p2_lookup::Host::resolve_addresses(&mut self.inner, network, name)
+    }
+}
+
+impl<T> p2_lookup::HostResolveAddressStream for SpinSocketsView<'_, T> {
+    fn resolve_next_address(
+        &mut self,
+        this: Resource<ResolveAddressStream>,
+    ) -> wasmtime_wasi::p2::SocketResult<Option<IpAddress>> {
+        p2_lookup::HostResolveAddressStream::resolve_next_address(&mut self.inner, this)
+    }
+
+    fn subscribe(
+        &mut self,
+        this: Resource<ResolveAddressStream>,
+    ) -> wasmtime::Result<Resource<DynPollable>> {
+        p2_lookup::HostResolveAddressStream::subscribe(&mut self.inner, this)
+    }
+
+    fn drop(&mut self, this: Resource<ResolveAddressStream>) -> wasmtime::Result<()> {
+        p2_lookup::HostResolveAddressStream::drop(&mut self.inner, this)
+    }
+}
+
 impl<T> p2_tcp_create::Host for SpinSocketsView<'_, T> {
     fn create_tcp_socket(
         &mut self,
@@ -548,8 +733,8 @@ impl<T> p2_udp_create::Host for SpinSocketsView<'_, T> {
 
 // ===== p3 impls =====
 
-use wasmtime::AsContextMut as _;
 use wasmtime::component::{Access, Accessor};
+use wasmtime_wasi::p3::bindings::sockets::ip_name_lookup as p3_lookup;
 use wasmtime_wasi::p3::bindings::sockets::types::{
     self as p3_types, Duration as p3_Duration, ErrorCode as p3_ErrorCode, Host as p3_Host,
     HostTcpSocket as p3_HostTcpSocket, HostTcpSocketWithStore, HostUdpSocket as p3_HostUdpSocket,
@@ -723,6 +908,7 @@ impl<T> p3_HostTcpSocket for SpinSocketsView<'_, T> {
 
     fn drop(&mut self, sock: Resource<p3_types::TcpSocket>) -> wasmtime::Result<()> {
         self.release_permit(sock.rep());
+        self.release_tally(sock.rep());
         p3_HostTcpSocket::drop(&mut self.inner, sock)
     }
 }
@@ -852,17 +1038,21 @@ impl<T: Send + 'static> HostTcpSocketWithStore<T> for SpinSockets<T> {
                 return Err(p3_ErrorCode::Other(Some("connection quota exhausted".into())).into());
             }
         };
+        let remote_address =
+            store.with(|mut access| access.get().override_connect_addr(remote_address.into()));
         let getter = store.with(|mut store| store.get().getter);
         let wasi_accessor = store.with_getter::<WasiSockets>(getter);
         let result: P3SocketResult<()> = <WasiSockets as HostTcpSocketWithStore<T>>::connect(
             &wasi_accessor,
             socket,
-            remote_address,
+            remote_address.into(),
         )
         .await;
         if result.is_ok() {
             store.with(|mut access| {
-                access.get().register_permit(socket_rep, permit);
+                let view = access.get();
+                view.register_permit(socket_rep, permit);
+                view.audit_connect(socket_rep, remote_address);
             });
         }
         result
@@ -882,6 +1072,10 @@ impl<T: Send + 'static> HostTcpSocketWithStore<T> for SpinSockets<T> {
         socket: Resource<p3_types::TcpSocket>,
         data: wasmtime::component::StreamReader<u8>,
     ) -> wasmtime::Result<wasmtime::component::FutureReader<Result<(), p3_ErrorCode>>> {
+        let data = match store.get().tally(socket.rep()) {
+            Some(tally) => tally_stream(&mut store, data, move |n| tally.sent(n))?,
+            None => data,
+        };
         let getter = store.get().getter;
         let wasi_store = Access::<T, WasiSockets>::new(store.as_context_mut(), getter);
         <WasiSockets as HostTcpSocketWithStore<T>>::send(wasi_store, socket, data)
@@ -894,9 +1088,16 @@ impl<T: Send + 'static> HostTcpSocketWithStore<T> for SpinSockets<T> {
         wasmtime::component::StreamReader<u8>,
         wasmtime::component::FutureReader<Result<(), p3_ErrorCode>>,
     )> {
+        let tally = store.get().tally(socket.rep());
         let getter = store.get().getter;
         let wasi_store = Access::<T, WasiSockets>::new(store.as_context_mut(), getter);
-        <WasiSockets as HostTcpSocketWithStore<T>>::receive(wasi_store, socket)
+        let (data, result) =
+            <WasiSockets as HostTcpSocketWithStore<T>>::receive(wasi_store, socket)?;
+        let data = match tally {
+            Some(tally) => tally_stream(&mut store, data, move |n| tally.received(n))?,
+            None => data,
+        };
+        Ok((data, result))
     }
 }
 
@@ -927,3 +1128,207 @@ impl<T: 'static> HostUdpSocketWithStore<T> for SpinSockets<T> {
         <WasiSockets as HostUdpSocketWithStore<T>>::receive(&wasi_accessor, socket).await
     }
 }
+
+impl<T> p3_lookup::Host for SpinSocketsView<'_, T> {}
+
+impl<T: Send + 'static> p3_lookup::HostWithStore<T> for SpinSockets<T> {
+    async fn resolve_addresses(
+        store: &Accessor<T, Self>,
+        name: String,
+    ) -> wasmtime::Result<Result<Vec<p3_types::IpAddress>, p3_lookup::ErrorCode>> {
+        let name = store.with(|mut access| access.get().override_name(name));
+        let getter = store.with(|mut store| store.get().getter);
+        let wasi_accessor = store.with_getter::<WasiSockets>(getter);
+        <WasiSockets as p3_lookup::HostWithStore<T>>::resolve_addresses(&wasi_accessor, name).await
+    }
+}
+
+/// A p2 input stream which tallies the bytes read from it.
+struct TalliedInputStream {
+    inner: DynInputStream,
+    tally: Arc<dyn SocketTally>,
+}
+
+#[async_trait]
+impl Pollable for TalliedInputStream {
+    async fn ready(&mut self) {
+        self.inner.ready().await
+    }
+}
+
+#[async_trait]
+impl InputStream for TalliedInputStream {
+    fn read(&mut self, size: usize) -> Result<Bytes, StreamError> {
+        let bytes = self.inner.read(size)?;
+        self.tally.received(bytes.len());
+        Ok(bytes)
+    }
+
+    async fn blocking_read(&mut self, size: usize) -> Result<Bytes, StreamError> {
+        let bytes = self.inner.blocking_read(size).await?;
+        self.tally.received(bytes.len());
+        Ok(bytes)
+    }
+
+    fn skip(&mut self, nelem: usize) -> Result<usize, StreamError> {
+        let skipped = self.inner.skip(nelem)?;
+        self.tally.received(skipped);
+        Ok(skipped)
+    }
+
+    async fn blocking_skip(&mut self, nelem: usize) -> Result<usize, StreamError> {
+        let skipped = self.inner.blocking_skip(nelem).await?;
+        self.tally.received(skipped);
+        Ok(skipped)
+    }
+
+    async fn cancel(&mut self) {
+        self.inner.cancel().await
+    }
+}
+
+/// A p2 output stream which tallies the bytes written to it.
+struct TalliedOutputStream {
+    inner: DynOutputStream,
+    tally: Arc<dyn SocketTally>,
+}
+
+#[async_trait]
+impl Pollable for TalliedOutputStream {
+    async fn ready(&mut self) {
+        self.inner.ready().await
+    }
+}
+
+#[async_trait]
+impl OutputStream for TalliedOutputStream {
+    fn write(&mut self, bytes: Bytes) -> Result<(), StreamError> {
+        let len = bytes.len();
+        self.inner.write(bytes)?;
+        self.tally.sent(len);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), StreamError> {
+        self.inner.flush()
+    }
+
+    fn check_write(&mut self) -> Result<usize, StreamError> {
+        self.inner.check_write()
+    }
+
+    async fn blocking_write_and_flush(&mut self, bytes: Bytes) -> Result<(), StreamError> {
+        let len = bytes.len();
+        self.inner.blocking_write_and_flush(bytes).await?;
+        self.tally.sent(len);
+        Ok(())
+    }
+
+    fn write_zeroes(&mut self, nelem: usize) -> Result<(), StreamError> {
+        self.inner.write_zeroes(nelem)?;
+        self.tally.sent(nelem);
+        Ok(())
+    }
+
+    async fn write_ready(&mut self) -> Result<usize, StreamError> {
+        self.inner.write_ready().await
+    }
+
+    async fn cancel(&mut self) {
+        self.inner.cancel().await
+    }
+}
+
+/// Relays a p3 byte stream through a new one, passing the size of each chunk
+/// relayed to `tally`.
+fn tally_stream(
+    mut store: impl AsContextMut,
+    stream: StreamReader<u8>,
+    tally: impl Fn(usize) + Send + Unpin + 'static,
+) -> wasmtime::Result<StreamReader<u8>> {
+    struct Producer {
+        rx: mpsc::Receiver<Bytes>,
+        pending: Bytes,
+    }
+
+    impl<D> StreamProducer<D> for Producer {
+        type Item = u8;
+        type Buffer = Option<u8>;
+
+        fn poll_produce<'a>(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            mut store: StoreContextMut<'a, D>,
+            destination: Destination<'a, Self::Item, Self::Buffer>,
+            finish: bool,
+        ) -> Poll<wasmtime::Result<StreamResult>> {
+            let me = &mut *self;
+            if me.pending.is_empty() {
+                match Pin::new(&mut me.rx).poll_next(cx) {
+                    Poll::Pending if finish => return Poll::Ready(Ok(StreamResult::Cancelled)),
+                    Poll::Pending => return Poll::Pending,
+                    Poll::Ready(None) => return Poll::Ready(Ok(StreamResult::Dropped)),
+                    Poll::Ready(Some(chunk)) => me.pending = chunk,
+                }
+            }
+            // A 0-length read only waits for data to be ready
+            if destination.remaining(store.as_context_mut()) == Some(0) {
+                return Poll::Ready(Ok(StreamResult::Completed));
+            }
+            let mut destination = destination.as_direct(store, me.pending.len());
+            let buf = destination.remaining();
+            let n = buf.len().min(me.pending.len());
+            buf[..n].copy_from_slice(&me.pending.split_to(n));
+            destination.mark_written(n);
+            Poll::Ready(Ok(StreamResult::Completed))
+        }
+    }
+
+    struct Consumer<F> {
+        tx: mpsc::Sender<Bytes>,
+        tally: F,
+    }
+
+    impl<D, F: Fn(usize) + Send + Unpin + 'static> StreamConsumer<D> for Consumer<F> {
+        type Item = u8;
+
+        fn poll_consume(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            store: StoreContextMut<D>,
+            source: Source<'_, Self::Item>,
+            finish: bool,
+        ) -> Poll<wasmtime::Result<StreamResult>> {
+            let me = &mut *self;
+            match me.tx.poll_ready(cx) {
+                Poll::Pending if finish => Poll::Ready(Ok(StreamResult::Cancelled)),
+                Poll::Pending => Poll::Pending,
+                Poll::Ready(Ok(())) => {
+                    let mut source = source.as_direct(store);
+                    let chunk = Bytes::copy_from_slice(source.remaining());
+                    source.mark_read(chunk.len());
+                    if !chunk.is_empty() {
+                        (me.tally)(chunk.len());
+                        me.tx.start_send(chunk)?;
+                    }
+                    Poll::Ready(Ok(StreamResult::Completed))
+                }
+                Poll::Ready(Err(error)) if error.is_disconnected() => {
+                    Poll::Ready(Ok(StreamResult::Dropped))
+                }
+                Poll::Ready(Err(error)) => Poll::Ready(Err(error.into())),
+            }
+        }
+    }
+
+    let (tx, rx) = mpsc::channel(1);
+    let relayed = StreamReader::new(
+        store.as_context_mut(),
+        Producer {
+            rx,
+            pending: Bytes::new(),
+        },
+    )?;
+    stream.pipe(store, Consumer { tx, tally })?;
+    Ok(relayed)
+}
@@ -1565,9 +1565,7 @@ impl<T> wasi::sockets::ip_name_lookup::Host for SpinSocketsView<'_, T> {
         _include_unavailable: bool,
     ) -> wasmtime::Result<Result<Resource<ResolveAddressStream>, SocketErrorCode>> {
         convert_result(latest::sockets::ip_name_lookup::Host::resolve_addresses(
-            &mut self.inner,
-            network,
-            name,
+            self, network, name,
         ))
     }
 }
@@ -1388,9 +1388,7 @@ impl<T> wasi::sockets::ip_name_lookup::Host for SpinSocketsView<'_, T> {
         name: String,
     ) -> wasmtime::Result<Result<Resource<ResolveAddressStream>, SocketErrorCode>> {
         convert_result(latest::sockets::ip_name_lookup::Host::resolve_addresses(
-            &mut self.inner,
-            network,
-            name,
+            self, network, name,
         ))
     }
 }
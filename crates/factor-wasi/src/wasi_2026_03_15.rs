@@ -19,7 +19,6 @@ use wasmtime_wasi::clocks::{WasiClocks, WasiClocksCtxView};
 use wasmtime_wasi::filesystem::{WasiFilesystem, WasiFilesystemCtxView};
 use wasmtime_wasi::p3::bindings as latest;
 use wasmtime_wasi::random::{WasiRandom, WasiRandomCtx};
-use wasmtime_wasi::sockets::{WasiSockets, WasiSocketsCtxView};
 
 mod bindings {
     use super::latest;
@@ -69,7 +68,6 @@ pub fn add_to_linker<T>(
     cli_closure: fn(&mut T) -> WasiCliCtxView<'_>,
     filesystem_closure: fn(&mut T) -> WasiFilesystemCtxView<'_>,
     sockets_closure: fn(&mut T) -> SpinSocketsView<'_, T>,
-    wasi_sockets_closure: fn(&mut T) -> WasiSocketsCtxView<'_>,
 ) -> anyhow::Result<()>
 where
     T: Send + 'static,
@@ -96,7 +94,7 @@ where
     wasi::cli::terminal_stdout::add_to_linker::<_, WasiCli>(linker, cli_closure)?;
     wasi::cli::terminal_stderr::add_to_linker::<_, WasiCli>(linker, cli_closure)?;
     wasi::sockets::types::add_to_linker::<_, SpinSockets<T>>(linker, sockets_closure)?;
-    wasi::sockets::ip_name_lookup::add_to_linker::<_, WasiSockets>(linker, wasi_sockets_closure)?;
+    wasi::sockets::ip_name_lookup::add_to_linker::<_, SpinSockets<T>>(linker, sockets_closure)?;
     Ok(())
 }
 
@@ -1061,7 +1059,7 @@ impl<T> wasi::sockets::types::HostTcpSocket for SpinSocketsView<'_, T> {
     }
 }
 
-impl<T> wasi::sockets::ip_name_lookup::HostWithStore<T> for WasiSockets {
+impl<T: Send + 'static> wasi::sockets::ip_name_lookup::HostWithStore<T> for SpinSockets<T> {
     async fn resolve_addresses(
         store: &Accessor<T, Self>,
         name: String,
@@ -1077,7 +1075,7 @@ impl<T> wasi::sockets::ip_name_lookup::HostWithStore<T> for WasiSockets {
     }
 }
 
-impl wasi::sockets::ip_name_lookup::Host for WasiSocketsCtxView<'_> {}
+impl<T> wasi::sockets::ip_name_lookup::Host for SpinSocketsView<'_, T> {}
 
 convert! {
     struct latest::clocks::system_clock::Instant [<=>] wasi::clocks::system_clock::Instant {
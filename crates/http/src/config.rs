@@ -9,25 +9,42 @@ pub struct HttpTriggerConfig {
     pub component: Option<String>,
     /// Static response to send
     pub static_response: Option<StaticResponse>,
+    /// Static files to serve
+    pub static_files: Option<StaticFiles>,
     /// HTTP route the component will be invoked for
     pub route: HttpTriggerRouteConfig,
     /// The HTTP executor the component requires
     #[serde(default)]
     pub executor: Option<HttpExecutorType>,
+    /// Compression of responses by the host, if enabled
+    #[serde(default)]
+    pub compression: Option<CompressionConfig>,
+    /// The CORS policy enforced by the host, if any
+    #[serde(default)]
+    pub cors: Option<CorsConfig>,
+    /// Limits on the size of requests, overriding the server's defaults
+    #[serde(default)]
+    pub limits: Option<RequestLimits>,
+    /// The rate at which each client may make requests, if limited
+    #[serde(default)]
+    pub rate_limit: Option<RateLimitConfig>,
+    /// The maximum number of requests handled at once, if limited
+    #[serde(default)]
+    pub max_concurrent_requests: Option<usize>,
 }
 
 impl HttpTriggerConfig {
     pub fn lookup_key(&self, trigger_id: &str) -> anyhow::Result<crate::routes::TriggerLookupKey> {
-        match (&self.component, &self.static_response) {
-            (None, None) => Err(anyhow::anyhow!(
-                "Triggers must specify either component or static_response - {trigger_id} has neither"
-            )),
-            (Some(_), Some(_)) => Err(anyhow::anyhow!(
-                "Triggers must specify either component or static_response - {trigger_id} has both"
+        match (&self.component, &self.static_response, &self.static_files) {
+            (None, None, None) => Err(anyhow::anyhow!(
+                "Triggers must specify one of component, static_response or static_files - {trigger_id} has none"
             )),
-            (Some(c), None) => Ok(crate::routes::TriggerLookupKey::Component(c.to_string())),
-            (None, Some(_)) => Ok(crate::routes::TriggerLookupKey::Trigger(
-                trigger_id.to_string(),
+            (Some(c), None, None) => Ok(crate::routes::TriggerLookupKey::Component(c.to_string())),
+            (None, Some(_), None) | (None, None, Some(_)) => Ok(
+                crate::routes::TriggerLookupKey::Trigger(trigger_id.to_string()),
+            ),
+            _ => Err(anyhow::anyhow!(
+                "Triggers must specify only one of component, static_response or static_files - {trigger_id} has more than one"
             )),
         }
     }
@@ -84,6 +101,220 @@ impl Default for WagiTriggerConfig {
     }
 }
 
+/// Compression of responses by the host, negotiated with each client using
+/// the `Accept-Encoding` request header.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CompressionConfig {
+    /// Responses with a `content-length` of fewer bytes than this are not
+    /// compressed.
+    pub min_size: u64,
+    /// The content types to compress. An entry of the form `type/*` matches
+    /// all subtypes of `type`.
+    pub content_types: Vec<String>,
+}
+
+impl CompressionConfig {
+    /// Whether responses with the given `content-type` should be compressed.
+    pub fn allows_content_type(&self, content_type: &str) -> bool {
+        let essence = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        self.content_types.iter().any(|allowed| {
+            let allowed = allowed.to_ascii_lowercase();
+            match allowed.strip_suffix("/*") {
+                Some(main_type) => essence
+                    .split_once('/')
+                    .is_some_and(|(ty, _)| ty == main_type),
+                None => essence == allowed,
+            }
+        })
+    }
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            min_size: 1024,
+            content_types: [
+                "text/*",
+                "application/javascript",
+                "application/json",
+                "application/wasm",
+                "application/xml",
+                "image/svg+xml",
+            ]
+            .map(String::from)
+            .to_vec(),
+        }
+    }
+}
+
+/// A CORS policy enforced by the host. Preflight requests are answered
+/// without instantiating the component.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsConfig {
+    /// The origins allowed to make requests. `*` allows any origin, and an
+    /// entry such as `https://*.example.com` allows any origin matching it.
+    pub allowed_origins: Vec<String>,
+    /// The methods allowed in requests. `*` allows any method.
+    pub allowed_methods: Vec<String>,
+    /// The request headers allowed in requests. `*` allows any header.
+    pub allowed_headers: Vec<String>,
+    /// The response headers scripts are allowed to read.
+    pub exposed_headers: Vec<String>,
+    /// Whether requests may include credentials such as cookies. This cannot
+    /// be combined with allowing any origin.
+    pub allow_credentials: bool,
+    /// How long, in seconds, clients may cache the result of a preflight
+    /// request.
+    pub max_age: Option<u64>,
+}
+
+impl CorsConfig {
+    /// Checks that the policy is one clients can honour.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        // Clients reject a wildcard origin on requests with credentials, and
+        // echoing back every origin instead would let any site make
+        // credentialed requests.
+        anyhow::ensure!(
+            !(self.allows_any_origin() && self.allow_credentials),
+            "allow_credentials cannot be combined with allowing any origin (\"*\"); list the allowed origins instead"
+        );
+        Ok(())
+    }
+
+    /// Whether requests from `origin` are allowed.
+    pub fn allows_origin(&self, origin: &str) -> bool {
+        self.allowed_origins.iter().any(|allowed| {
+            if allowed == "*" {
+                return true;
+            }
+            match allowed.split_once('*') {
+                Some((prefix, suffix)) => {
+                    origin.len() > prefix.len() + suffix.len()
+                        && starts_with_ignore_ascii_case(origin, prefix)
+                        && ends_with_ignore_ascii_case(origin, suffix)
+                }
+                None => allowed.eq_ignore_ascii_case(origin),
+            }
+        })
+    }
+
+    /// Whether any origin is allowed.
+    pub fn allows_any_origin(&self) -> bool {
+        self.allowed_origins.iter().any(|allowed| allowed == "*")
+    }
+
+    /// Whether requests using `method` are allowed.
+    pub fn allows_method(&self, method: &str) -> bool {
+        self.allowed_methods
+            .iter()
+            .any(|allowed| allowed == "*" || allowed.eq_ignore_ascii_case(method))
+    }
+
+    /// Whether requests including the header `name` are allowed.
+    pub fn allows_header(&self, name: &str) -> bool {
+        self.allowed_headers
+            .iter()
+            .any(|allowed| allowed == "*" || allowed.eq_ignore_ascii_case(name))
+    }
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allowed_origins: vec![],
+            allowed_methods: ["GET", "HEAD", "POST"].map(String::from).to_vec(),
+            allowed_headers: vec![],
+            exposed_headers: vec![],
+            allow_credentials: false,
+            max_age: None,
+        }
+    }
+}
+
+fn starts_with_ignore_ascii_case(s: &str, prefix: &str) -> bool {
+    s.get(..prefix.len())
+        .is_some_and(|start| start.eq_ignore_ascii_case(prefix))
+}
+
+fn ends_with_ignore_ascii_case(s: &str, suffix: &str) -> bool {
+    s.len() >= suffix.len()
+        && s.get(s.len() - suffix.len()..)
+            .is_some_and(|end| end.eq_ignore_ascii_case(suffix))
+}
+
+/// Limits on the size of requests, enforced by the host before and while
+/// streaming the request to the component. A limit which is not set does
+/// not apply.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct RequestLimits {
+    /// The maximum size of the request body, in bytes.
+    pub max_body_size: Option<u64>,
+    /// The maximum number of request headers.
+    pub max_header_count: Option<usize>,
+    /// The maximum total size of the request headers' names and values, in
+    /// bytes.
+    pub max_header_size: Option<usize>,
+    /// The maximum length of the request URI, in bytes.
+    pub max_uri_length: Option<usize>,
+}
+
+impl RequestLimits {
+    /// These limits, with any which are not set taken from `defaults`.
+    pub fn or(&self, defaults: &RequestLimits) -> RequestLimits {
+        RequestLimits {
+            max_body_size: self.max_body_size.or(defaults.max_body_size),
+            max_header_count: self.max_header_count.or(defaults.max_header_count),
+            max_header_size: self.max_header_size.or(defaults.max_header_size),
+            max_uri_length: self.max_uri_length.or(defaults.max_uri_length),
+        }
+    }
+}
+
+/// A limit on the rate of requests from each client, enforced by the host
+/// with a token bucket per client.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimitConfig {
+    /// The sustained number of requests per second allowed from each client.
+    pub requests_per_second: f64,
+    /// The number of requests a client may make in a burst, after being idle.
+    /// Defaults to `requests_per_second`, rounded up.
+    #[serde(default)]
+    pub burst: Option<u32>,
+    /// How to tell clients apart.
+    #[serde(default)]
+    pub key: RateLimitKey,
+}
+
+impl RateLimitConfig {
+    /// The size of each client's token bucket.
+    pub fn burst(&self) -> u32 {
+        self.burst
+            .unwrap_or_else(|| self.requests_per_second.ceil() as u32)
+            .max(1)
+    }
+}
+
+/// How requests are attributed to clients for rate limiting.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub enum RateLimitKey {
+    /// The IP address of the client.
+    #[default]
+    ClientIp,
+    /// The value of a request header, such as an API key. Requests without
+    /// the header are attributed to the IP address of the client.
+    Header(String),
+}
+
 /// A static response to be served directly by the host
 /// without instantiating a component.
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -110,6 +341,37 @@ impl StaticResponse {
     }
 }
 
+/// Static files to be served directly by the host from a component's
+/// `files` mounts, without instantiating the component.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct StaticFiles {
+    /// The component whose `files` mounts contain the files.
+    pub component: String,
+    /// The directory to serve, as a path in the component's file system.
+    #[serde(default = "StaticFiles::default_root")]
+    pub root: String,
+    /// The files to serve, in order of preference, for a request naming a
+    /// directory.
+    #[serde(default = "StaticFiles::default_index")]
+    pub index: Vec<String>,
+    /// A file, relative to `root`, to serve for requests that match no file.
+    ///
+    /// This is typically the entry point of a single-page application.
+    #[serde(default)]
+    pub fallback: Option<String>,
+}
+
+impl StaticFiles {
+    fn default_root() -> String {
+        "/".into()
+    }
+
+    fn default_index() -> Vec<String> {
+        vec!["index.html".into()]
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -123,4 +385,117 @@ mod tests {
         assert_eq!(config.entrypoint, "_start");
         assert_eq!(config.argv, "${SCRIPT_NAME} ${ARGS}");
     }
+
+    #[test]
+    fn static_files_defaults() {
+        let config: StaticFiles = toml::toml! { component = "assets" }.try_into().unwrap();
+        assert_eq!(config.root, "/");
+        assert_eq!(config.index, ["index.html"]);
+        assert!(config.fallback.is_none());
+    }
+
+    #[test]
+    fn compression_content_types() {
+        let config: CompressionConfig = toml::toml! { min_size = 0 }.try_into().unwrap();
+        assert!(config.allows_content_type("text/html; charset=utf-8"));
+        assert!(config.allows_content_type("Application/JSON"));
+        assert!(!config.allows_content_type("image/png"));
+        assert!(!config.allows_content_type("textual/plain"));
+    }
+
+    #[test]
+    fn request_limits_fall_back_to_defaults() {
+        let limits: RequestLimits = toml::toml! { max_body_size = 1024 }.try_into().unwrap();
+        let defaults = RequestLimits {
+            max_body_size: Some(1 << 20),
+            max_header_count: Some(50),
+            ..Default::default()
+        };
+        assert_eq!(
+            limits.or(&defaults),
+            RequestLimits {
+                max_body_size: Some(1024),
+                max_header_count: Some(50),
+                max_header_size: None,
+                max_uri_length: None,
+            }
+        );
+    }
+
+    #[test]
+    fn rate_limit_config() {
+        let config: RateLimitConfig = toml::toml! { requests_per_second = 2.5 }
+            .try_into()
+            .unwrap();
+        assert_eq!(config.burst(), 3);
+        assert_eq!(config.key, RateLimitKey::ClientIp);
+
+        let config: RateLimitConfig = toml::toml! {
+            requests_per_second = 10
+            burst = 50
+            key = { header = "x-api-key" }
+        }
+        .try_into()
+        .unwrap();
+        assert_eq!(config.burst(), 50);
+        assert_eq!(config.key, RateLimitKey::Header("x-api-key".into()));
+    }
+
+    #[test]
+    fn cors_origin_wildcards() {
+        let config: CorsConfig = toml::toml! {
+            allowed_origins = ["https://example.com", "https://*.example.org"]
+        }
+        .try_into()
+        .unwrap();
+        assert!(config.allows_origin("https://example.com"));
+        assert!(config.allows_origin("https://app.example.org"));
+        assert!(!config.allows_origin("https://example.org"));
+        assert!(!config.allows_origin("https://evil.com"));
+        assert!(!config.allows_any_origin());
+        assert!(config.allows_method("post"));
+        assert!(!config.allows_method("DELETE"));
+    }
+
+    #[test]
+    fn cors_rejects_credentials_with_any_origin() {
+        let config: CorsConfig = toml::toml! {
+            allowed_origins = ["*"]
+            allow_credentials = true
+        }
+        .try_into()
+        .unwrap();
+        config.validate().unwrap_err();
+
+        let config: CorsConfig = toml::toml! {
+            allowed_origins = ["https://*.example.org"]
+            allow_credentials = true
+        }
+        .try_into()
+        .unwrap();
+        config.validate().unwrap();
+    }
+
+    #[test]
+    fn lookup_key_requires_single_handler() {
+        let config: HttpTriggerConfig = toml::toml! {
+            route = "/..."
+            static_files = { component = "assets" }
+        }
+        .try_into()
+        .unwrap();
+        assert!(matches!(
+            config.lookup_key("t").unwrap(),
+            crate::routes::TriggerLookupKey::Trigger(_)
+        ));
+
+        let config: HttpTriggerConfig = toml::toml! {
+            route = "/..."
+            component = "app"
+            static_files = { component = "assets" }
+        }
+        .try_into()
+        .unwrap();
+        config.lookup_key("t").unwrap_err();
+    }
 }
@@ -20,7 +20,8 @@ impl RedisKeyValueStore {
 /// Runtime configuration for the Redis key-value store.
 #[derive(Deserialize)]
 pub struct RedisKeyValueRuntimeConfig {
-    /// The URL of the Redis server.
+    /// The URL of the Redis server, or a `redis+sentinel://` or
+    /// `redis+cluster://` address.
     url: String,
 }
 
@@ -1,24 +1,24 @@
 use anyhow::{Context, Result};
-use redis::{AsyncCommands, Client, RedisError, aio::ConnectionManager, parse_redis_url};
+use redis::{AsyncCommands, RedisError};
 use spin_core::async_trait;
 use spin_factor_key_value::{
     Cas, Error, Store, StoreManager, SwapError, log_error, log_error_v3, v3,
 };
+use spin_redis_connection::{ConnectOptions, RedisAddress, RedisConnection};
 use std::sync::Arc;
 use tokio::sync::OnceCell;
-use url::Url;
 
 pub struct KeyValueRedis {
-    database_url: Url,
-    connection: OnceCell<ConnectionManager>,
+    address: RedisAddress,
+    connection: OnceCell<RedisConnection>,
 }
 
 impl KeyValueRedis {
     pub fn new(address: String) -> Result<Self> {
-        let database_url = parse_redis_url(&address).context("Invalid Redis URL")?;
+        let address = address.parse().context("Invalid Redis URL")?;
 
         Ok(Self {
-            database_url,
+            address,
             connection: OnceCell::new(),
         })
     }
@@ -29,17 +29,13 @@ impl StoreManager for KeyValueRedis {
     async fn get(&self, _name: &str) -> Result<Arc<dyn Store>, Error> {
         let connection = self
             .connection
-            .get_or_try_init(|| async {
-                Client::open(self.database_url.clone())?
-                    .get_connection_manager()
-                    .await
-            })
+            .get_or_try_init(|| async { self.address.connect(&ConnectOptions::default()).await })
             .await
             .map_err(log_error)?;
 
         Ok(Arc::new(RedisStore {
             connection: connection.clone(),
-            database_url: self.database_url.clone(),
+            address: self.address.clone(),
         }))
     }
 
@@ -48,19 +44,18 @@ impl StoreManager for KeyValueRedis {
     }
 
     fn summary(&self, _store_name: &str) -> Option<String> {
-        let redis::ConnectionInfo { addr, .. } = self.database_url.as_str().parse().ok()?;
-        Some(format!("Redis at {addr}"))
+        Some(format!("Redis at {}", self.address))
     }
 }
 
 struct RedisStore {
-    connection: ConnectionManager,
-    database_url: Url,
+    connection: RedisConnection,
+    address: RedisAddress,
 }
 
 struct CompareAndSwap {
     key: String,
-    connection: ConnectionManager,
+    connection: RedisConnection,
     bucket_rep: u32,
 }
 
@@ -68,7 +63,7 @@ struct CompareAndSwap {
 impl Store for RedisStore {
     async fn after_open(&self) -> Result<(), Error> {
         if let Err(_error) = self.connection.clone().ping::<()>().await {
-            // If an IO error happens, ConnectionManager will start reconnection in the background
+            // If an IO error happens, RedisConnection reconnects before the next command
             // so we do not take any action and just pray re-connection will be successful.
         }
         Ok(())
@@ -152,22 +147,22 @@ impl Store for RedisStore {
         let (keys_tx, keys_rx) = tokio::sync::mpsc::channel(4);
         let (err_tx, err_rx) = tokio::sync::oneshot::channel();
 
-        let mut conn = self.connection.clone();
+        let conn = self.connection.clone();
 
         let the_work = async move {
-            let mut scan = conn.scan::<String>().await.map_err(log_error_v3)?;
-            loop {
-                match scan.next_item().await {
-                    None => break,
-                    Some(k) => {
-                        if k.len() > max_result_bytes {
-                            return Err(v3::Error::Other(format!(
-                                "query result exceeds limit of {max_result_bytes} bytes"
-                            )));
-                        }
-                        keys_tx.send(k).await.map_err(log_error_v3)?;
-                    }
+            let check_len = |k: &String| {
+                if k.len() > max_result_bytes {
+                    Err(v3::Error::Other(format!(
+                        "query result exceeds limit of {max_result_bytes} bytes"
+                    )))
+                } else {
+                    Ok(())
                 }
+            };
+            let mut scan = conn.scan_keys().await.map_err(log_error_v3)?;
+            while let Some(k) = scan.next_key().await.map_err(log_error_v3)? {
+                check_len(&k)?;
+                keys_tx.send(k).await.map_err(log_error_v3)?;
             }
             Ok(())
         };
@@ -241,9 +236,9 @@ impl Store for RedisStore {
         bucket_rep: u32,
         key: &str,
     ) -> Result<Arc<dyn Cas>, Error> {
-        let cx = Client::open(self.database_url.clone())
-            .map_err(log_error)?
-            .get_connection_manager()
+        let cx = self
+            .address
+            .connect(&ConnectOptions::default())
             .await
             .map_err(log_error)?;
 
@@ -19,6 +19,8 @@ pub type SharedFutureResult<T> = Shared<BoxFuture<'static, Result<Arc<T>, Arc<an
 pub struct OutboundAllowedHosts {
     allowed_hosts_future: SharedFutureResult<AllowedHostsConfig>,
     disallowed_host_handler: Option<Arc<dyn DisallowedHostHandler>>,
+    auditor: Option<Arc<dyn AllowedHostsAuditor>>,
+    learner: Option<Arc<dyn AllowedHostsLearner>>,
 }
 
 impl OutboundAllowedHosts {
@@ -30,9 +32,25 @@ impl OutboundAllowedHosts {
         Self {
             allowed_hosts_future,
             disallowed_host_handler,
+            auditor: None,
+            learner: None,
         }
     }
 
+    /// Sets an [`AllowedHostsAuditor`] to be told the outcome of every check.
+    pub fn with_auditor(mut self, auditor: Arc<dyn AllowedHostsAuditor>) -> Self {
+        self.auditor = Some(auditor);
+        self
+    }
+
+    /// Puts these allowed hosts in learn mode: checks which would be denied
+    /// are instead allowed, and the [`AllowedHostsLearner`] is told the
+    /// `allowed_outbound_hosts` entry which would have allowed them.
+    pub fn with_learner(mut self, learner: Arc<dyn AllowedHostsLearner>) -> Self {
+        self.learner = Some(learner);
+        self
+    }
+
     /// Checks address against allowed hosts
     ///
     /// Calls the [`DisallowedHostHandler`] if set and URL is disallowed.
@@ -50,7 +68,13 @@ impl OutboundAllowedHosts {
         };
 
         let allowed_hosts = self.resolve().await?;
-        let is_allowed = allowed_hosts.allows(&url);
+        let mut is_allowed = allowed_hosts.allows(&url);
+        if !is_allowed && let Some(learner) = &self.learner {
+            tracing::debug!("Learned outbound networking request to '{url}'");
+            learner.learn(&url.minimal_allowed_host());
+            is_allowed = true;
+        }
+        self.audit(url.scheme(), &url.authority(), is_allowed);
         if !is_allowed {
             tracing::debug!("Disallowed outbound networking request to '{url}'");
             self.report_disallowed_host(url.scheme(), &url.authority());
@@ -65,7 +89,13 @@ impl OutboundAllowedHosts {
     pub async fn check_relative_url(&self, schemes: &[&str]) -> anyhow::Result<bool> {
         tracing::debug!("Checking relative outbound networking request with schemes {schemes:?}");
         let allowed_hosts = self.resolve().await?;
-        let is_allowed = allowed_hosts.allows_relative_url(schemes);
+        let mut is_allowed = allowed_hosts.allows_relative_url(schemes);
+        if !is_allowed && let Some(learner) = &self.learner {
+            let scheme = schemes.first().unwrap_or(&"*");
+            learner.learn(&format!("{scheme}://self"));
+            is_allowed = true;
+        }
+        self.audit(schemes.first().unwrap_or(&""), "self", is_allowed);
         if !is_allowed {
             tracing::debug!(
                 "Disallowed relative outbound networking request with schemes {schemes:?}"
@@ -88,6 +118,12 @@ impl OutboundAllowedHosts {
             handler.handle_disallowed_host(scheme, authority);
         }
     }
+
+    fn audit(&self, scheme: &str, authority: &str, allowed: bool) {
+        if let Some(auditor) = &self.auditor {
+            auditor.audit(scheme, authority, allowed);
+        }
+    }
 }
 
 /// A trait for handling disallowed hosts
@@ -102,6 +138,25 @@ impl<F: Fn(&str, &str) + Send + Sync> DisallowedHostHandler for F {
     }
 }
 
+/// A trait for auditing allowed hosts checks
+pub trait AllowedHostsAuditor: Send + Sync {
+    /// Called with the outcome of every check, allowed or not
+    fn audit(&self, scheme: &str, authority: &str, allowed: bool);
+}
+
+/// A trait for learning the allowed hosts a component needs
+pub trait AllowedHostsLearner: Send + Sync {
+    /// Called with the `allowed_outbound_hosts` entry which would have allowed
+    /// an otherwise disallowed check
+    fn learn(&self, allowed_host: &str);
+}
+
+impl<F: Fn(&str) + Send + Sync> AllowedHostsLearner for F {
+    fn learn(&self, allowed_host: &str) {
+        self(allowed_host);
+    }
+}
+
 /// Represents a single `allowed_outbound_hosts` item.
 #[derive(Eq, Debug, Clone)]
 pub struct AllowedHostConfig {
@@ -666,6 +721,20 @@ impl OutboundUrl {
             self.host.clone()
         }
     }
+
+    /// Returns the most specific `allowed_outbound_hosts` entry which allows
+    /// this URL.
+    pub fn minimal_allowed_host(&self) -> String {
+        let default_port = well_known_port(&self.scheme);
+        match self.port {
+            Some(port) if Some(port) != default_port => {
+                format!("{}://{}:{port}", self.scheme, self.host)
+            }
+            // A port is required for schemes without a default
+            None if default_port.is_none() => format!("{}://{}:*", self.scheme, self.host),
+            _ => format!("{}://{}", self.scheme, self.host),
+        }
+    }
 }
 
 impl std::fmt::Display for OutboundUrl {
@@ -1113,6 +1182,29 @@ mod test {
         assert!(allowed.allows(&OutboundUrl::parse("tcp://127.0.0.1:63551", "tcp").unwrap()));
     }
 
+    #[test]
+    fn test_minimal_allowed_host() {
+        for (url, scheme, expected) in [
+            ("https://example.com/path", "https", "https://example.com"),
+            ("http://example.com:80", "http", "http://example.com"),
+            (
+                "https://example.com:8443/",
+                "https",
+                "https://example.com:8443",
+            ),
+            ("redis://user:pw@cache:6379", "redis", "redis://cache"),
+            ("postgres://db:6543/app", "postgres", "postgres://db:6543"),
+            ("127.0.0.1:3000", "tcp", "tcp://127.0.0.1:3000"),
+            ("rediss://cache", "rediss", "rediss://cache:*"),
+        ] {
+            let url = OutboundUrl::parse(url, scheme).unwrap();
+            let minimal = url.minimal_allowed_host();
+            assert_eq!(minimal, expected);
+            let allowed = AllowedHostsConfig::parse(&[&minimal], &dummy_resolver(), &[]).unwrap();
+            assert!(allowed.allows(&url), "{minimal} should allow {url}");
+        }
+    }
+
     fn exact_host(ahc: &AllowedHostConfig) -> String {
         match ahc.host() {
             HostConfig::Literal(host) => host.to_string(),
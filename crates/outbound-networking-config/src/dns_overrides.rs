@@ -0,0 +1,199 @@
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+};
+
+/// A cheaply-clonable set of static host resolutions, overriding DNS.
+///
+/// Each override maps a host name and port to the address to connect to
+/// instead. Only the connection address changes; TLS server names and
+/// `allowed_outbound_hosts` checks still use the original host name.
+#[derive(Clone, Debug, Default)]
+pub struct DnsOverrides {
+    /// (lowercased host name, port) -> address
+    overrides: Arc<HashMap<(String, u16), SocketAddr>>,
+}
+
+impl DnsOverrides {
+    /// Creates a new `DnsOverrides` from (host, port) -> address pairs.
+    pub fn new(overrides: impl IntoIterator<Item = ((String, u16), SocketAddr)>) -> Self {
+        let overrides = overrides
+            .into_iter()
+            .map(|((host, port), addr)| ((normalize_host(&host), port), addr))
+            .collect::<HashMap<_, _>>();
+        Self {
+            overrides: overrides.into(),
+        }
+    }
+
+    /// Returns true iff there are no overrides.
+    pub fn is_empty(&self) -> bool {
+        self.overrides.is_empty()
+    }
+
+    /// Returns the address to connect to in place of the given host and port,
+    /// if overridden.
+    pub fn get(&self, host: &str, port: u16) -> Option<SocketAddr> {
+        if self.is_empty() {
+            return None;
+        }
+        self.overrides.get(&(normalize_host(host), port)).copied()
+    }
+
+    /// Returns the IP address to resolve the given host to, if overridden for
+    /// any port.
+    ///
+    /// This is for resolvers which don't know which port will be connected
+    /// to; if the host is overridden to different IPs for different ports,
+    /// the lowest IP is returned so that the choice is stable.
+    pub fn get_ip(&self, host: &str) -> Option<IpAddr> {
+        if self.is_empty() {
+            return None;
+        }
+        let host = normalize_host(host);
+        self.overrides
+            .iter()
+            .filter(|((override_host, _), _)| *override_host == host)
+            .map(|(_, addr)| addr.ip())
+            .min()
+    }
+}
+
+/// Applies [`DnsOverrides`] to `wasi:sockets`, where looking up a name and
+/// connecting to one of its addresses are separate steps.
+///
+/// Looking up an overridden name returns its overriding IP. A connection to
+/// that IP is then redirected to the address overriding the name for the
+/// port connected to, and checked against `allowed_outbound_hosts` as a
+/// connection to the name. If several overridden names share an IP, a
+/// connection to it is attributed to the name most recently looked up.
+#[derive(Debug, Default)]
+pub struct SocketDnsOverrides {
+    overrides: DnsOverrides,
+    /// Overriding IP -> the name most recently resolved to it
+    resolved: Mutex<HashMap<IpAddr, String>>,
+    /// Redirected address -> the name and port originally connected to
+    redirected: Mutex<HashMap<SocketAddr, (String, u16)>>,
+}
+
+impl SocketDnsOverrides {
+    pub fn new(overrides: DnsOverrides) -> Self {
+        Self {
+            overrides,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the IP address to resolve the given name to, if overridden.
+    pub fn resolve(&self, name: &str) -> Option<IpAddr> {
+        let ip = self.overrides.get_ip(name)?;
+        lock(&self.resolved).insert(ip, normalize_host(name));
+        Some(ip)
+    }
+
+    /// Returns the address to connect to in place of the given address, if
+    /// it is the result of an overridden lookup.
+    pub fn redirect(&self, addr: SocketAddr) -> Option<SocketAddr> {
+        let name = lock(&self.resolved).get(&addr.ip())?.clone();
+        let redirected = self.overrides.get(&name, addr.port())?;
+        lock(&self.redirected).insert(redirected, (name, addr.port()));
+        Some(redirected)
+    }
+
+    /// Returns the host name and port a redirected address was originally
+    /// connected to, for checking against `allowed_outbound_hosts`.
+    pub fn original_host(&self, addr: SocketAddr) -> Option<(String, u16)> {
+        lock(&self.redirected).get(&addr).cloned()
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Lowercases the given host name and strips any brackets around an IPv6
+/// address or trailing dot from a fully-qualified name.
+fn normalize_host(host: &str) -> String {
+    let host = host
+        .strip_prefix('[')
+        .and_then(|h| h.strip_suffix(']'))
+        .unwrap_or(host);
+    host.strip_suffix('.').unwrap_or(host).to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overrides() -> DnsOverrides {
+        DnsOverrides::new([
+            (
+                ("API.partner.com".into(), 443),
+                "127.0.0.1:8443".parse().unwrap(),
+            ),
+            (
+                ("api.partner.com".into(), 80),
+                "127.0.0.2:8080".parse().unwrap(),
+            ),
+        ])
+    }
+
+    #[test]
+    fn test_get() {
+        let overrides = overrides();
+        assert_eq!(
+            overrides.get("api.partner.com", 443),
+            Some("127.0.0.1:8443".parse().unwrap())
+        );
+        assert_eq!(
+            overrides.get("Api.Partner.com.", 80),
+            Some("127.0.0.2:8080".parse().unwrap())
+        );
+        assert_eq!(overrides.get("api.partner.com", 8443), None);
+        assert_eq!(overrides.get("partner.com", 443), None);
+    }
+
+    #[test]
+    fn test_get_ip() {
+        let overrides = overrides();
+        assert_eq!(
+            overrides.get_ip("api.partner.com"),
+            Some("127.0.0.1".parse().unwrap())
+        );
+        assert_eq!(overrides.get_ip("partner.com"), None);
+        assert_eq!(DnsOverrides::default().get_ip("api.partner.com"), None);
+    }
+
+    #[test]
+    fn test_socket_overrides() {
+        let overrides = SocketDnsOverrides::new(overrides());
+        assert_eq!(overrides.resolve("partner.com"), None);
+
+        let ip = overrides.resolve("API.partner.com").unwrap();
+        assert_eq!(ip, "127.0.0.1".parse::<IpAddr>().unwrap());
+
+        // Connections are redirected according to the port connected to
+        let redirected = overrides.redirect(SocketAddr::new(ip, 80)).unwrap();
+        assert_eq!(redirected, "127.0.0.2:8080".parse().unwrap());
+        assert_eq!(
+            overrides.original_host(redirected),
+            Some(("api.partner.com".into(), 80))
+        );
+        let redirected = overrides.redirect(SocketAddr::new(ip, 443)).unwrap();
+        assert_eq!(redirected, "127.0.0.1:8443".parse().unwrap());
+        assert_eq!(
+            overrides.original_host(redirected),
+            Some(("api.partner.com".into(), 443))
+        );
+
+        // Ports which aren't overridden, and IPs which weren't looked up, are
+        // left alone
+        assert_eq!(overrides.redirect(SocketAddr::new(ip, 8443)), None);
+        assert_eq!(overrides.redirect("127.0.0.2:80".parse().unwrap()), None);
+        assert_eq!(
+            overrides.original_host("127.0.0.1:80".parse().unwrap()),
+            None
+        );
+    }
+}
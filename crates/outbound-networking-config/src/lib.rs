@@ -1,2 +1,3 @@
 pub mod allowed_hosts;
 pub mod blocked_networks;
+pub mod dns_overrides;
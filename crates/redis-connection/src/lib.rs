@@ -0,0 +1,969 @@
+//! Connections to Redis deployments shared by Spin's Redis integrations.
+//!
+//! A [`RedisAddress`] describes a standalone server, a Sentinel-managed
+//! primary, or a cluster, and a [`RedisConnection`] is a multiplexed
+//! connection to any of them that re-establishes itself on failover.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::{Context, bail};
+use redis::aio::{AsyncPushSender, ConnectionLike, MultiplexedConnection, SendError};
+use redis::cluster::ClusterClient;
+use redis::cluster_async::ClusterConnection;
+use redis::cluster_routing::{RoutingInfo, SingleNodeRoutingInfo};
+use redis::io::AsyncDNSResolver;
+use redis::{
+    AsyncConnectionConfig, Client, Cmd, ConnectionAddr, ConnectionInfo, ErrorKind,
+    IntoConnectionInfo, Pipeline, ProtocolVersion, PushInfo, RedisError, RedisFuture, RedisResult,
+    Value,
+};
+use tokio::sync::Mutex;
+
+/// The address of a Redis deployment.
+///
+/// In addition to the `redis://`, `rediss://` and `redis+unix://` URLs
+/// understood by the `redis` crate, this accepts:
+///
+/// * `redis+cluster://[user:password@]host1:port1,host2:port2[/][?query]`
+///   (or `rediss+cluster://`) for a cluster, given a list of seed nodes.
+/// * `redis+sentinel://[user:password@]host1:port1,host2:port2/service[/db][?query]`
+///   (or `rediss+sentinel://`) for the primary of `service` as reported by
+///   the given Sentinels. Credentials are used for the Redis nodes, not the
+///   Sentinels.
+#[derive(Clone, Debug)]
+pub enum RedisAddress {
+    /// A single server.
+    Server(ConnectionInfo),
+    /// A cluster, reached through its seed nodes.
+    Cluster(Vec<ConnectionInfo>),
+    /// A primary managed by Sentinel.
+    Sentinel {
+        sentinels: Vec<ConnectionInfo>,
+        service_name: String,
+        /// The settings for connecting to the primary, whose host and port
+        /// are placeholders until it's discovered.
+        node: ConnectionInfo,
+    },
+}
+
+impl FromStr for RedisAddress {
+    type Err = anyhow::Error;
+
+    fn from_str(address: &str) -> anyhow::Result<Self> {
+        let Some((scheme, rest)) = address.split_once("://") else {
+            bail!("invalid Redis address: missing URL scheme");
+        };
+        match scheme {
+            "redis+cluster" | "rediss+cluster" => {
+                let node_scheme = scheme.trim_end_matches("+cluster");
+                let parts = MultiHostUrl::parse(rest)?;
+                if !parts.path.is_empty() {
+                    bail!(
+                        "invalid Redis cluster address: cluster addresses cannot select a database"
+                    );
+                }
+                let nodes = parts
+                    .hosts
+                    .iter()
+                    .map(|host| parts.node_info(node_scheme, host, ""))
+                    .collect::<anyhow::Result<_>>()?;
+                Ok(Self::Cluster(nodes))
+            }
+            "redis+sentinel" | "rediss+sentinel" => {
+                let node_scheme = scheme.trim_end_matches("+sentinel");
+                let parts = MultiHostUrl::parse(rest)?;
+                let (service_name, db) = match parts.path.split_once('/') {
+                    Some((service_name, db)) => (service_name, db),
+                    None => (parts.path, ""),
+                };
+                if service_name.is_empty() {
+                    bail!("invalid Redis Sentinel address: missing service name in path");
+                }
+                let sentinels = parts
+                    .hosts
+                    .iter()
+                    .map(|host| {
+                        format!("{node_scheme}://{host}")
+                            .into_connection_info()
+                            .with_context(|| format!("invalid Redis Sentinel host {host:?}"))
+                    })
+                    .collect::<anyhow::Result<_>>()?;
+                // The primary's host is discovered from the Sentinels; only the
+                // connection settings in this placeholder are used.
+                let node = parts.node_info(node_scheme, "localhost", db)?;
+                Ok(Self::Sentinel {
+                    sentinels,
+                    service_name: service_name.to_owned(),
+                    node,
+                })
+            }
+            _ => Ok(Self::Server(
+                address
+                    .into_connection_info()
+                    .context("invalid Redis address")?,
+            )),
+        }
+    }
+}
+
+impl RedisAddress {
+    /// Returns a `redis://` or `rediss://` URL (without credentials) for each
+    /// host that connecting to this address contacts directly: the server,
+    /// the cluster seed nodes, or the Sentinels.
+    ///
+    /// Unix socket addresses are returned as `redis+unix://` URLs.
+    pub fn host_urls(&self) -> Vec<String> {
+        let infos = match self {
+            Self::Server(info) => std::slice::from_ref(info),
+            Self::Cluster(nodes) => nodes.as_slice(),
+            Self::Sentinel { sentinels, .. } => sentinels.as_slice(),
+        };
+        infos.iter().map(|info| host_url(&info.addr)).collect()
+    }
+
+    /// Whether this is the address of a cluster.
+    pub fn is_cluster(&self) -> bool {
+        matches!(self, Self::Cluster(_))
+    }
+
+    /// Sets the protocol used to talk to Redis nodes.
+    pub fn set_protocol(&mut self, protocol: ProtocolVersion) {
+        match self {
+            Self::Server(info) => info.redis.protocol = protocol,
+            Self::Cluster(nodes) => {
+                for node in nodes {
+                    node.redis.protocol = protocol;
+                }
+            }
+            Self::Sentinel { node, .. } => node.redis.protocol = protocol,
+        }
+    }
+
+    /// Opens a connection that follows the address across failovers.
+    ///
+    /// For a cluster, commands are routed to the node owning their keys and
+    /// the topology is refreshed as nodes move. Otherwise, the connection is
+    /// re-established (re-resolving the primary when using Sentinel) after an
+    /// I/O error or when a failed-over primary starts refusing writes.
+    ///
+    /// The push sender in `options` is only used for connections to servers
+    /// and primaries, not for cluster connections.
+    pub async fn connect(&self, options: &ConnectOptions) -> RedisResult<RedisConnection> {
+        let inner = match self {
+            Self::Cluster(nodes) => {
+                let mut builder = ClusterClient::builder(nodes.clone());
+                if let Some(check) = &options.node_check {
+                    // Nodes learned from the cluster topology, through
+                    // `CLUSTER SLOTS` or redirections, are only known once
+                    // they're resolved.
+                    let tls = nodes
+                        .iter()
+                        .any(|node| matches!(node.addr, ConnectionAddr::TcpTls { .. }));
+                    builder = builder.async_dns_resolver(CheckedResolver {
+                        resolver: options.dns_resolver.clone(),
+                        check: check.clone(),
+                        scheme: if tls { "rediss" } else { "redis" },
+                    });
+                } else if let Some(resolver) = &options.dns_resolver {
+                    builder = builder.async_dns_resolver(resolver.clone());
+                }
+                Inner::Cluster(builder.build()?.get_async_connection().await?)
+            }
+            _ => {
+                let connection = self.connect_node(options).await?;
+                Inner::Node(Arc::new(NodeConnection {
+                    address: self.clone(),
+                    options: options.clone(),
+                    current: Mutex::new((0, connection)),
+                }))
+            }
+        };
+        Ok(RedisConnection(inner))
+    }
+
+    /// Opens a plain connection to a single node: the server, the current
+    /// primary when using Sentinel, or the first reachable seed node of a
+    /// cluster. This is suitable for pub/sub, which is not tied to a key.
+    pub async fn connect_node(
+        &self,
+        options: &ConnectOptions,
+    ) -> RedisResult<MultiplexedConnection> {
+        let mut config = options.base_config();
+        if let Some(sender) = &options.push_sender {
+            config = config.set_push_sender(sender.clone());
+        }
+        self.node_client(options)
+            .await?
+            .get_multiplexed_async_connection_with_config(&config)
+            .await
+    }
+
+    /// Returns a client for a single node, chosen as for
+    /// [`connect_node`](Self::connect_node).
+    ///
+    /// Only the DNS resolver and node check in `options` are used, to
+    /// locate the node; the client itself connects with the defaults of the
+    /// `redis` crate.
+    pub async fn node_client(&self, options: &ConnectOptions) -> RedisResult<Client> {
+        match self {
+            Self::Server(info) => Client::open(info.clone()),
+            Self::Sentinel {
+                sentinels,
+                service_name,
+                node,
+            } => {
+                let primary = discover_primary(sentinels, service_name, node, options).await?;
+                if let Some(check) = &options.node_check {
+                    let url = host_url(&primary.addr);
+                    if !check(url.clone()).await {
+                        return Err((
+                            ErrorKind::InvalidClientConfig,
+                            "Redis primary is not an allowed host",
+                            url,
+                        )
+                            .into());
+                    }
+                }
+                Client::open(primary)
+            }
+            Self::Cluster(nodes) => {
+                let config = options.base_config();
+                let mut last_error = None;
+                for node in nodes {
+                    let client = Client::open(node.clone())?;
+                    match client
+                        .get_multiplexed_async_connection_with_config(&config)
+                        .await
+                    {
+                        Ok(_) => return Ok(client),
+                        Err(err) => last_error = Some(err),
+                    }
+                }
+                Err(last_error.unwrap_or_else(|| {
+                    (ErrorKind::InvalidClientConfig, "no Redis cluster nodes").into()
+                }))
+            }
+        }
+    }
+}
+
+/// Asks each Sentinel in turn for the address of the primary of
+/// `service_name`, and returns the settings in `node` for connecting to it.
+async fn discover_primary(
+    sentinels: &[ConnectionInfo],
+    service_name: &str,
+    node: &ConnectionInfo,
+    options: &ConnectOptions,
+) -> RedisResult<ConnectionInfo> {
+    let config = options.base_config();
+    let mut last_error = None;
+    for sentinel in sentinels {
+        let primary = async {
+            let mut connection = Client::open(sentinel.clone())?
+                .get_multiplexed_async_connection_with_config(&config)
+                .await?;
+            redis::cmd("SENTINEL")
+                .arg("GET-MASTER-ADDR-BY-NAME")
+                .arg(service_name)
+                .query_async::<Option<(String, u16)>>(&mut connection)
+                .await
+        };
+        match primary.await {
+            Ok(Some((host, port))) => {
+                let mut primary = node.clone();
+                match &mut primary.addr {
+                    ConnectionAddr::Tcp(h, p)
+                    | ConnectionAddr::TcpTls {
+                        host: h, port: p, ..
+                    } => {
+                        *h = host;
+                        *p = port;
+                    }
+                    ConnectionAddr::Unix(_) => unreachable!("Sentinel nodes are reached over TCP"),
+                }
+                return Ok(primary);
+            }
+            Ok(None) => {
+                last_error = Some(
+                    (
+                        ErrorKind::MasterNameNotFoundBySentinel,
+                        "Sentinel does not know the service",
+                        service_name.to_owned(),
+                    )
+                        .into(),
+                )
+            }
+            Err(err) => last_error = Some(err),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| (ErrorKind::InvalidClientConfig, "no Redis Sentinels").into()))
+}
+
+/// A check of whether a node discovered through Sentinel or the cluster
+/// topology may be connected to, given its `redis://` or `rediss://` URL.
+type NodeCheck = Arc<dyn Fn(String) -> Pin<Box<dyn Future<Output = bool> + Send>> + Send + Sync>;
+
+/// How to connect to a [`RedisAddress`].
+#[derive(Clone, Default)]
+pub struct ConnectOptions {
+    dns_resolver: Option<SharedResolver>,
+    push_sender: Option<SharedPushSender>,
+    node_check: Option<NodeCheck>,
+}
+
+impl ConnectOptions {
+    /// Resolves host names with `resolver` for every connection made,
+    /// including those to Sentinels and to cluster nodes.
+    pub fn with_dns_resolver(mut self, resolver: impl AsyncDNSResolver) -> Self {
+        self.dns_resolver = Some(SharedResolver(Arc::new(resolver)));
+        self
+    }
+
+    /// Sends the push messages received on connections to single nodes to
+    /// `sender`. This requires the address to use RESP3.
+    pub fn with_push_sender(mut self, sender: impl AsyncPushSender) -> Self {
+        self.push_sender = Some(SharedPushSender(Arc::new(sender)));
+        self
+    }
+
+    /// Refuses to connect to a primary discovered through Sentinel, or to a
+    /// cluster node, unless `check` accepts its `redis://` or `rediss://`
+    /// URL.
+    ///
+    /// Cluster nodes are checked as they are resolved, which covers the nodes
+    /// reported by `CLUSTER SLOTS` and by `MOVED` and `ASK` redirections as
+    /// well as the seed nodes.
+    pub fn with_node_check<F, Fut>(mut self, check: F) -> Self
+    where
+        F: Fn(String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = bool> + Send + 'static,
+    {
+        self.node_check = Some(Arc::new(move |url| Box::pin(check(url))));
+        self
+    }
+
+    /// The config for connections other than those receiving push messages.
+    fn base_config(&self) -> AsyncConnectionConfig {
+        let config = AsyncConnectionConfig::new();
+        match &self.dns_resolver {
+            Some(resolver) => config.set_dns_resolver(resolver.clone()),
+            None => config,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct SharedResolver(Arc<dyn AsyncDNSResolver>);
+
+impl AsyncDNSResolver for SharedResolver {
+    fn resolve<'a, 'b: 'a>(
+        &'a self,
+        host: &'b str,
+        port: u16,
+    ) -> RedisFuture<'a, Box<dyn Iterator<Item = SocketAddr> + Send + 'a>> {
+        self.0.resolve(host, port)
+    }
+}
+
+/// Resolves cluster nodes once `check` accepts them, with the given resolver
+/// or else the system's.
+struct CheckedResolver {
+    resolver: Option<SharedResolver>,
+    check: NodeCheck,
+    scheme: &'static str,
+}
+
+impl AsyncDNSResolver for CheckedResolver {
+    fn resolve<'a, 'b: 'a>(
+        &'a self,
+        host: &'b str,
+        port: u16,
+    ) -> RedisFuture<'a, Box<dyn Iterator<Item = SocketAddr> + Send + 'a>> {
+        Box::pin(async move {
+            let url = format!("{}://{host}:{port}", self.scheme);
+            if !(self.check)(url.clone()).await {
+                return Err((
+                    ErrorKind::InvalidClientConfig,
+                    "Redis cluster node is not an allowed host",
+                    url,
+                )
+                    .into());
+            }
+            match &self.resolver {
+                Some(resolver) => resolver.resolve(host, port).await,
+                None => {
+                    let addrs = tokio::net::lookup_host((host, port)).await?;
+                    Ok(Box::new(addrs) as Box<_>)
+                }
+            }
+        })
+    }
+}
+
+#[derive(Clone)]
+struct SharedPushSender(Arc<dyn AsyncPushSender>);
+
+impl AsyncPushSender for SharedPushSender {
+    fn send(&self, info: PushInfo) -> Result<(), SendError> {
+        self.0.send(info)
+    }
+}
+
+impl fmt::Display for RedisAddress {
+    /// Formats the hosts of the address, leaving out any credentials.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let join = |infos: &[ConnectionInfo]| {
+            infos
+                .iter()
+                .map(|info| info.addr.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        match self {
+            Self::Server(info) => write!(f, "{}", info.addr),
+            Self::Cluster(nodes) => write!(f, "cluster {}", join(nodes)),
+            Self::Sentinel {
+                sentinels,
+                service_name,
+                ..
+            } => write!(f, "{service_name} via Sentinel {}", join(sentinels)),
+        }
+    }
+}
+
+/// The parts of a URL with a comma-separated list of hosts, which the `url`
+/// crate does not accept.
+struct MultiHostUrl<'a> {
+    userinfo: Option<&'a str>,
+    hosts: Vec<&'a str>,
+    path: &'a str,
+    query: Option<&'a str>,
+}
+
+impl<'a> MultiHostUrl<'a> {
+    /// Parses everything after the `://` of a URL.
+    fn parse(rest: &'a str) -> anyhow::Result<Self> {
+        let (rest, query) = match rest.split_once('?') {
+            Some((rest, query)) => (rest, Some(query)),
+            None => (rest, None),
+        };
+        let (authority, path) = match rest.split_once('/') {
+            Some((authority, path)) => (authority, path.trim_end_matches('/')),
+            None => (rest, ""),
+        };
+        let (userinfo, hosts) = match authority.rsplit_once('@') {
+            Some((userinfo, hosts)) => (Some(userinfo), hosts),
+            None => (None, authority),
+        };
+        let hosts: Vec<_> = hosts.split(',').filter(|host| !host.is_empty()).collect();
+        if hosts.is_empty() {
+            bail!("invalid Redis address: no hosts given");
+        }
+        Ok(Self {
+            userinfo,
+            hosts,
+            path,
+            query,
+        })
+    }
+
+    /// Builds the connection info for one node, reusing the `redis` crate's
+    /// handling of credentials, TLS and query parameters.
+    fn node_info(&self, scheme: &str, host: &str, db: &str) -> anyhow::Result<ConnectionInfo> {
+        let mut url = format!("{scheme}://");
+        if let Some(userinfo) = self.userinfo {
+            url.push_str(userinfo);
+            url.push('@');
+        }
+        url.push_str(host);
+        url.push('/');
+        url.push_str(db);
+        if let Some(query) = self.query {
+            url.push('?');
+            url.push_str(query);
+        }
+        url.into_connection_info()
+            .with_context(|| format!("invalid Redis host {host:?}"))
+    }
+}
+
+fn host_url(addr: &ConnectionAddr) -> String {
+    match addr {
+        ConnectionAddr::Tcp(host, port) => format!("redis://{host}:{port}"),
+        ConnectionAddr::TcpTls { host, port, .. } => format!("rediss://{host}:{port}"),
+        ConnectionAddr::Unix(path) => format!("redis+unix://{}", path.display()),
+    }
+}
+
+/// A multiplexed connection to a [`RedisAddress`].
+///
+/// Clones share the underlying connection.
+#[derive(Clone)]
+pub struct RedisConnection(Inner);
+
+#[derive(Clone)]
+enum Inner {
+    Node(Arc<NodeConnection>),
+    Cluster(ClusterConnection),
+}
+
+impl RedisConnection {
+    /// Whether this is a connection to a cluster.
+    pub fn is_cluster(&self) -> bool {
+        matches!(self.0, Inner::Cluster(_))
+    }
+
+    /// Starts iterating over every key with `SCAN`.
+    ///
+    /// A `SCAN` cursor is only meaningful on the node that returned it, so
+    /// for a cluster each primary is scanned in turn.
+    pub async fn scan_keys(&self) -> RedisResult<KeyScan> {
+        let nodes = match &self.0 {
+            Inner::Node(_) => VecDeque::from([None]),
+            Inner::Cluster(connection) => {
+                let mut cmd = redis::cmd("CLUSTER");
+                cmd.arg("SLOTS");
+                let slots = connection
+                    .clone()
+                    .route_command(&cmd, RoutingInfo::SingleNode(SingleNodeRoutingInfo::Random))
+                    .await?;
+                primaries(&slots)?.into_iter().map(Some).collect()
+            }
+        };
+        Ok(KeyScan {
+            connection: self.clone(),
+            nodes,
+            cursor: 0,
+            keys: VecDeque::new(),
+        })
+    }
+}
+
+/// An iteration over the keys of a [`RedisConnection`], started by
+/// [`RedisConnection::scan_keys`].
+pub struct KeyScan {
+    connection: RedisConnection,
+    /// The nodes left to scan, starting with the current one. `None` stands
+    /// for the only node of a connection that isn't to a cluster.
+    nodes: VecDeque<Option<(String, u16)>>,
+    cursor: u64,
+    keys: VecDeque<String>,
+}
+
+impl KeyScan {
+    /// Returns the next key, or `None` once every node has been scanned.
+    pub async fn next_key(&mut self) -> RedisResult<Option<String>> {
+        loop {
+            if let Some(key) = self.keys.pop_front() {
+                return Ok(Some(key));
+            }
+            let Some(node) = self.nodes.front() else {
+                return Ok(None);
+            };
+            let mut cmd = redis::cmd("SCAN");
+            cmd.arg(self.cursor);
+            let (cursor, keys): (u64, Vec<String>) = match (node, &mut self.connection.0) {
+                (Some((host, port)), Inner::Cluster(connection)) => {
+                    let routing = RoutingInfo::SingleNode(SingleNodeRoutingInfo::ByAddress {
+                        host: host.clone(),
+                        port: *port,
+                    });
+                    redis::from_redis_value(&connection.route_command(&cmd, routing).await?)?
+                }
+                _ => cmd.query_async(&mut self.connection).await?,
+            };
+            if cursor == 0 {
+                self.nodes.pop_front();
+            }
+            self.cursor = cursor;
+            self.keys.extend(keys);
+        }
+    }
+}
+
+/// Returns the address of each primary in a `CLUSTER SLOTS` reply.
+fn primaries(slots: &Value) -> RedisResult<Vec<(String, u16)>> {
+    let slots: Vec<Vec<Value>> = redis::from_redis_value(slots)?;
+    let mut primaries = Vec::new();
+    for slot in slots {
+        // Each slot range is `[start, end, primary, replicas...]`, and each
+        // node `[host, port, id, ...]`.
+        let Some(primary) = slot.get(2) else {
+            continue;
+        };
+        let primary: Vec<Value> = redis::from_redis_value(primary)?;
+        if let [host, port, ..] = primary.as_slice() {
+            let node = (
+                redis::from_redis_value(host)?,
+                redis::from_redis_value(port)?,
+            );
+            if !primaries.contains(&node) {
+                primaries.push(node);
+            }
+        }
+    }
+    Ok(primaries)
+}
+
+impl ConnectionLike for RedisConnection {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+        match &mut self.0 {
+            Inner::Cluster(connection) => connection.req_packed_command(cmd),
+            Inner::Node(node) => Box::pin(node.with_failover(move |mut connection| async move {
+                connection.req_packed_command(cmd).await
+            })),
+        }
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        cmd: &'a Pipeline,
+        offset: usize,
+        count: usize,
+    ) -> RedisFuture<'a, Vec<Value>> {
+        match &mut self.0 {
+            Inner::Cluster(connection) => connection.req_packed_commands(cmd, offset, count),
+            Inner::Node(node) => Box::pin(node.with_failover(move |mut connection| async move {
+                connection.req_packed_commands(cmd, offset, count).await
+            })),
+        }
+    }
+
+    fn get_db(&self) -> i64 {
+        match &self.0 {
+            Inner::Cluster(connection) => connection.get_db(),
+            Inner::Node(node) => match &node.address {
+                RedisAddress::Server(info) => info.redis.db,
+                RedisAddress::Sentinel { node, .. } => node.redis.db,
+                RedisAddress::Cluster(_) => 0,
+            },
+        }
+    }
+}
+
+/// A connection to a server or Sentinel-managed primary that is replaced when
+/// it fails.
+struct NodeConnection {
+    address: RedisAddress,
+    options: ConnectOptions,
+    /// The current connection, and how many times it has been replaced.
+    current: Mutex<(u64, MultiplexedConnection)>,
+}
+
+impl NodeConnection {
+    /// Runs `op` on the current connection. If it fails in a way that
+    /// suggests the connection is broken or no longer talks to the primary,
+    /// reconnects, and retries `op` if it cannot have taken effect.
+    async fn with_failover<T, F, Fut>(&self, op: F) -> RedisResult<T>
+    where
+        F: Fn(MultiplexedConnection) -> Fut,
+        Fut: Future<Output = RedisResult<T>>,
+    {
+        let (generation, connection) = self.current.lock().await.clone();
+        let err = match op(connection).await {
+            Err(err) if needs_reconnect(&err) => err,
+            result => return result,
+        };
+        match self.reconnect(generation).await {
+            Ok(connection) if is_safe_to_retry(&err) => op(connection).await,
+            Ok(_) => Err(err),
+            Err(reconnect_err) => {
+                tracing::warn!(
+                    "Failed to reconnect to Redis at {}: {reconnect_err}",
+                    self.address
+                );
+                Err(err)
+            }
+        }
+    }
+
+    /// Replaces connection `generation`, unless another caller has already
+    /// replaced it, and returns the new connection.
+    async fn reconnect(&self, generation: u64) -> RedisResult<MultiplexedConnection> {
+        let mut current = self.current.lock().await;
+        if current.0 == generation {
+            tracing::info!("Reconnecting to Redis at {}", self.address);
+            current.1 = self.address.connect_node(&self.options).await?;
+            current.0 += 1;
+        }
+        Ok(current.1.clone())
+    }
+}
+
+/// Whether `err` indicates that the connection is broken or that the node is
+/// no longer the primary.
+fn needs_reconnect(err: &RedisError) -> bool {
+    is_safe_to_retry(err) || err.is_io_error() || err.is_connection_dropped()
+}
+
+/// Whether `err` guarantees that the command was not executed.
+fn is_safe_to_retry(err: &RedisError) -> bool {
+    matches!(err.kind(), ErrorKind::ReadOnly | ErrorKind::MasterDown) || err.is_connection_refusal()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_single_server() {
+        let address: RedisAddress = "redis://user:pw@example.com:6380/2".parse().unwrap();
+        let RedisAddress::Server(info) = &address else {
+            panic!("expected server address, got {address:?}");
+        };
+        assert_eq!(info.redis.db, 2);
+        assert_eq!(address.host_urls(), ["redis://example.com:6380"]);
+        assert_eq!(address.to_string(), "example.com:6380");
+    }
+
+    #[test]
+    fn parses_cluster_seed_list() {
+        let address: RedisAddress = "rediss+cluster://:secret@a.example:7000,b.example:7001"
+            .parse()
+            .unwrap();
+        let RedisAddress::Cluster(nodes) = &address else {
+            panic!("expected cluster address, got {address:?}");
+        };
+        assert_eq!(nodes.len(), 2);
+        assert!(
+            nodes
+                .iter()
+                .all(|node| node.redis.password.as_deref() == Some("secret"))
+        );
+        assert_eq!(
+            address.host_urls(),
+            ["rediss://a.example:7000", "rediss://b.example:7001"]
+        );
+        assert!(!address.to_string().contains("secret"));
+    }
+
+    #[test]
+    fn rejects_cluster_database() {
+        "redis+cluster://a.example:7000/1"
+            .parse::<RedisAddress>()
+            .unwrap_err();
+    }
+
+    #[test]
+    fn parses_sentinel() {
+        let address: RedisAddress =
+            "redis+sentinel://:pw@s1.example:26379,s2.example:26379/mymaster/3"
+                .parse()
+                .unwrap();
+        let RedisAddress::Sentinel {
+            sentinels,
+            service_name,
+            node,
+        } = &address
+        else {
+            panic!("expected sentinel address, got {address:?}");
+        };
+        assert_eq!(sentinels.len(), 2);
+        assert!(sentinels.iter().all(|s| s.redis.password.is_none()));
+        assert_eq!(service_name, "mymaster");
+        assert_eq!(node.redis.db, 3);
+        assert_eq!(node.redis.password.as_deref(), Some("pw"));
+        assert!(matches!(node.addr, ConnectionAddr::Tcp(..)));
+        assert_eq!(
+            address.host_urls(),
+            ["redis://s1.example:26379", "redis://s2.example:26379"]
+        );
+    }
+
+    #[test]
+    fn rejects_sentinel_without_service() {
+        "redis+sentinel://s1.example:26379"
+            .parse::<RedisAddress>()
+            .unwrap_err();
+        "redis+sentinel://s1.example:26379/"
+            .parse::<RedisAddress>()
+            .unwrap_err();
+    }
+
+    /// Resolves every host name to the address of a fake Sentinel, recording
+    /// the names resolved.
+    struct FakeResolver {
+        sentinel: SocketAddr,
+        resolved: Arc<std::sync::Mutex<Vec<String>>>,
+    }
+
+    impl AsyncDNSResolver for FakeResolver {
+        fn resolve<'a, 'b: 'a>(
+            &'a self,
+            host: &'b str,
+            _port: u16,
+        ) -> RedisFuture<'a, Box<dyn Iterator<Item = SocketAddr> + Send + 'a>> {
+            self.resolved.lock().unwrap().push(host.to_owned());
+            let sentinel = self.sentinel;
+            Box::pin(async move { Ok(Box::new(std::iter::once(sentinel)) as Box<_>) })
+        }
+    }
+
+    /// Serves a single connection, reporting `primary.example:6380` as the
+    /// primary and acknowledging any other command.
+    async fn fake_sentinel() -> SocketAddr {
+        use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            // Each command is an array of bulk strings: `*<n>`, then `$<len>`
+            // and the argument for each.
+            while let Some(header) = lines.next_line().await.unwrap() {
+                let count: usize = header.trim_start_matches('*').parse().unwrap();
+                let mut args = Vec::new();
+                for _ in 0..count {
+                    lines.next_line().await.unwrap();
+                    args.push(lines.next_line().await.unwrap().unwrap());
+                }
+                let reply: &[u8] = if args[0].eq_ignore_ascii_case("SENTINEL") {
+                    b"*2\r\n$15\r\nprimary.example\r\n$4\r\n6380\r\n"
+                } else {
+                    b"+OK\r\n"
+                };
+                writer.write_all(reply).await.unwrap();
+            }
+        });
+        addr
+    }
+
+    fn sentinel_options(
+        sentinel: SocketAddr,
+    ) -> (ConnectOptions, Arc<std::sync::Mutex<Vec<String>>>) {
+        let resolved = Arc::default();
+        let options = ConnectOptions::default().with_dns_resolver(FakeResolver {
+            sentinel,
+            resolved: Arc::clone(&resolved),
+        });
+        (options, resolved)
+    }
+
+    #[tokio::test]
+    async fn discovers_primary_through_resolver() {
+        let address: RedisAddress = "redis+sentinel://:pw@sentinel.example:26379/mymaster/3"
+            .parse()
+            .unwrap();
+        let (options, resolved) = sentinel_options(fake_sentinel().await);
+        let client = address.node_client(&options).await.unwrap();
+
+        let info = client.get_connection_info();
+        assert_eq!(host_url(&info.addr), "redis://primary.example:6380");
+        assert_eq!(info.redis.db, 3);
+        assert_eq!(info.redis.password.as_deref(), Some("pw"));
+        assert_eq!(*resolved.lock().unwrap(), ["sentinel.example"]);
+    }
+
+    #[tokio::test]
+    async fn checks_discovered_primary() {
+        let address: RedisAddress = "redis+sentinel://sentinel.example:26379/mymaster"
+            .parse()
+            .unwrap();
+        let checked = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let (options, _) = sentinel_options(fake_sentinel().await);
+        let options = options.with_node_check({
+            let checked = Arc::clone(&checked);
+            move |url| {
+                checked.lock().unwrap().push(url);
+                async { false }
+            }
+        });
+
+        let err = address.node_client(&options).await.unwrap_err();
+        assert!(err.to_string().contains("not an allowed host"), "{err}");
+        assert_eq!(*checked.lock().unwrap(), ["redis://primary.example:6380"]);
+    }
+
+    #[tokio::test]
+    async fn checks_cluster_nodes_before_resolving() {
+        let resolved = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let checked = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let resolver = CheckedResolver {
+            resolver: Some(SharedResolver(Arc::new(FakeResolver {
+                sentinel: "127.0.0.1:7000".parse().unwrap(),
+                resolved: Arc::clone(&resolved),
+            }))),
+            check: {
+                let checked = Arc::clone(&checked);
+                Arc::new(move |url: String| {
+                    let allowed = url.starts_with("rediss://seed.example:");
+                    checked.lock().unwrap().push(url);
+                    Box::pin(async move { allowed })
+                })
+            },
+            scheme: "rediss",
+        };
+
+        let addrs: Vec<_> = resolver
+            .resolve("seed.example", 7000)
+            .await
+            .unwrap()
+            .collect();
+        assert_eq!(addrs, ["127.0.0.1:7000".parse::<SocketAddr>().unwrap()]);
+        let err = resolver
+            .resolve("moved.example", 7001)
+            .await
+            .map(|_| ())
+            .unwrap_err();
+        assert!(err.to_string().contains("not an allowed host"), "{err}");
+
+        assert_eq!(
+            *checked.lock().unwrap(),
+            ["rediss://seed.example:7000", "rediss://moved.example:7001"]
+        );
+        assert_eq!(*resolved.lock().unwrap(), ["seed.example"]);
+    }
+
+    #[test]
+    fn lists_each_primary_once() {
+        let node = |host: &str, port: i64, id: &str| {
+            Value::Array(vec![
+                Value::BulkString(host.into()),
+                Value::Int(port),
+                Value::BulkString(id.into()),
+            ])
+        };
+        let slots = Value::Array(vec![
+            Value::Array(vec![
+                Value::Int(0),
+                Value::Int(5460),
+                node("a.example", 7000, "a"),
+                node("b.example", 7003, "b"),
+            ]),
+            Value::Array(vec![
+                Value::Int(5461),
+                Value::Int(10922),
+                node("c.example", 7001, "c"),
+            ]),
+            Value::Array(vec![
+                Value::Int(10923),
+                Value::Int(16383),
+                node("a.example", 7000, "a"),
+            ]),
+        ]);
+
+        assert_eq!(
+            primaries(&slots).unwrap(),
+            [
+                ("a.example".to_owned(), 7000),
+                ("c.example".to_owned(), 7001)
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_missing_hosts() {
+        "redis+cluster://".parse::<RedisAddress>().unwrap_err();
+        "redis+sentinel://user@/mymaster"
+            .parse::<RedisAddress>()
+            .unwrap_err();
+    }
+}
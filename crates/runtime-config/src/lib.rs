@@ -116,6 +116,15 @@ impl<T> ResolvedRuntimeConfig<T> {
                 "[outbound_http: max_concurrent_requests={max} (deprecated, use max_connections)]"
             ));
         }
+        // [outbound_http: proxy=URL]
+        if let Some(table) = self.toml.get("outbound_http").and_then(Value::as_table)
+            && let Some(url) = table
+                .get("proxy")
+                .and_then(|proxy| proxy.get("url"))
+                .and_then(Value::as_str)
+        {
+            summaries.push(format!("[outbound_http: proxy={url}]"));
+        }
         if !summaries.is_empty() {
             let summaries = summaries.join(", ");
             let from_path = runtime_config_path
@@ -172,22 +181,27 @@ where
             .and_then(Path::parent)
             .map(ToOwned::to_owned);
         let state_dir = toml_resolver.state_dir()?;
-        let outbound_networking = runtime_config_dir
-            .clone()
-            .map(OutboundNetworkingSpinRuntimeConfig::new);
-        let key_value_resolver = key_value_config_resolver(runtime_config_dir, state_dir.clone());
+        let log_dir = toml_resolver.log_dir()?;
+        // Without a runtime config file, relative paths are resolved against
+        // the working directory
+        let outbound_networking = OutboundNetworkingSpinRuntimeConfig::new(
+            runtime_config_dir.clone().unwrap_or_default(),
+        )
+        .with_log_dir(log_dir.clone());
+        let key_value_resolver =
+            key_value_config_resolver(runtime_config_dir.clone(), state_dir.clone());
         let sqlite_resolver = sqlite_config_resolver(state_dir.clone())
             .context("failed to resolve sqlite runtime config")?;
 
         let toml = toml_resolver.toml();
-        let log_dir = toml_resolver.log_dir()?;
         let max_instance_memory = toml_resolver.max_instance_memory()?;
 
         let source = TomlRuntimeConfigSource::new(
             toml_resolver,
             &key_value_resolver,
-            outbound_networking.as_ref(),
+            &outbound_networking,
             &sqlite_resolver,
+            runtime_config_dir.as_deref(),
         );
 
         // Note: all valid fields in the runtime config must have been referenced at
@@ -338,22 +352,26 @@ impl<'a> TomlResolver<'a> {
 pub struct TomlRuntimeConfigSource<'a, 'b> {
     toml: TomlResolver<'b>,
     key_value: &'a key_value::RuntimeConfigResolver,
-    outbound_networking: Option<&'a OutboundNetworkingSpinRuntimeConfig>,
+    outbound_networking: &'a OutboundNetworkingSpinRuntimeConfig,
     sqlite: &'a sqlite::RuntimeConfigResolver,
+    /// The directory containing the runtime config file, if any.
+    runtime_config_dir: Option<&'a Path>,
 }
 
 impl<'a, 'b> TomlRuntimeConfigSource<'a, 'b> {
     pub fn new(
         toml_resolver: TomlResolver<'b>,
         key_value: &'a key_value::RuntimeConfigResolver,
-        outbound_networking: Option<&'a OutboundNetworkingSpinRuntimeConfig>,
+        outbound_networking: &'a OutboundNetworkingSpinRuntimeConfig,
         sqlite: &'a sqlite::RuntimeConfigResolver,
+        runtime_config_dir: Option<&'a Path>,
     ) -> Self {
         Self {
             toml: toml_resolver,
             key_value,
             outbound_networking,
             sqlite,
+            runtime_config_dir,
         }
     }
 }
@@ -371,10 +389,7 @@ impl FactorRuntimeConfigSource<OutboundNetworkingFactor> for TomlRuntimeConfigSo
         &mut self,
     ) -> anyhow::Result<Option<<OutboundNetworkingFactor as spin_factors::Factor>::RuntimeConfig>>
     {
-        let Some(tls) = self.outbound_networking else {
-            return Ok(None);
-        };
-        tls.config_from_table(&self.toml.table)
+        self.outbound_networking.config_from_table(&self.toml.table)
     }
 }
 
@@ -426,7 +441,11 @@ impl FactorRuntimeConfigSource<OutboundHttpFactor> for TomlRuntimeConfigSource<'
     fn get_runtime_config(
         &mut self,
     ) -> anyhow::Result<Option<<OutboundHttpFactor as spin_factors::Factor>::RuntimeConfig>> {
-        spin_factor_outbound_http::runtime_config::spin::config_from_table(&self.toml.table)
+        spin_factor_outbound_http::runtime_config::spin::config_from_table(
+            &self.toml.table,
+            self.toml.state_dir()?,
+            self.runtime_config_dir,
+        )
     }
 }
 
@@ -679,6 +698,34 @@ mod tests {
         )
     }
 
+    #[test]
+    fn egress_audit_defaults_to_log_dir_without_runtime_config_file() {
+        use spin_factor_outbound_networking::audit::EgressAuditSink;
+        define_test_factor!(networking: OutboundNetworkingFactor);
+
+        let toml = toml::toml! {
+            [outbound_networking.audit]
+        };
+        let resolver = TomlResolver::new(
+            &toml,
+            None,
+            UserProvidedPath::Default,
+            UserProvidedPath::Provided("/logs".into()),
+        );
+        let runtime_config = ResolvedRuntimeConfig::<TestFactorsRuntimeConfig>::new(resolver, None)
+            .unwrap()
+            .runtime_config;
+        let audit = runtime_config
+            .networking
+            .unwrap()
+            .egress_audit
+            .expect("audit should be configured");
+        assert_eq!(
+            audit.sink,
+            EgressAuditSink::File("/logs/egress_audit.jsonl".into())
+        );
+    }
+
     #[test]
     fn dirs_are_resolved() {
         define_test_factor!(sqlite: SqliteFactor);
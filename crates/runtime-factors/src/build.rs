@@ -1,8 +1,9 @@
 use std::path::PathBuf;
 
-use super::{TriggerAppArgs, TriggerFactors, TriggerFactorsRuntimeConfig};
+use super::{TriggerAppArgs, TriggerFactors, TriggerFactorsRuntimeConfig, learned_host_handler};
 
 use anyhow::Context as _;
+use spin_factor_outbound_http::cassette::CassetteConfig;
 use spin_factors_executor::FactorsExecutor;
 use spin_runtime_config::ResolvedRuntimeConfig;
 use spin_trigger::cli::{
@@ -44,12 +45,31 @@ impl RuntimeFactorsBuilder for FactorsBuilder {
             .providers
             .insert(0, Box::new(cli_static_variables_provider));
 
+        // A cassette given on the command line overrides any in the runtime
+        // config, keeping its request matching settings.
+        if let Some((path, mode)) = args.outbound_http_cassette() {
+            let outbound_http = runtime_config
+                .runtime_config
+                .outbound_http
+                .get_or_insert_with(Default::default);
+            let matcher = outbound_http
+                .cassette
+                .take()
+                .map(|cassette| cassette.matcher)
+                .unwrap_or_default();
+            outbound_http.cassette = Some(CassetteConfig {
+                path,
+                mode,
+                matcher,
+            });
+        }
+
         runtime_config.summarize(config.runtime_config_file.as_deref());
 
         // This is a hack b/c we know the version of this crate will be the same as the version of Spin
         let spin_version = env!("CARGO_PKG_VERSION");
 
-        let factors = TriggerFactors::new(
+        let mut factors = TriggerFactors::new(
             runtime_config.state_dir(),
             config.working_dir.clone(),
             args.allow_transient_write,
@@ -57,6 +77,14 @@ impl RuntimeFactorsBuilder for FactorsBuilder {
             spin_version,
         )
         .context("failed to create factors")?;
+        if let Some(mode) = args.learn_outbound_hosts {
+            terminal::warn!(
+                "Learning outbound hosts: all outbound network connections are allowed."
+            );
+            factors
+                .outbound_networking
+                .set_learned_host_handler(learned_host_handler(mode));
+        }
         Ok((factors, runtime_config))
     }
 
@@ -11,10 +11,13 @@ use spin_common::arg_parser::parse_kv;
 use spin_factor_key_value::KeyValueFactor;
 use spin_factor_llm::LlmFactor;
 use spin_factor_otel::OtelFactor;
-use spin_factor_outbound_http::OutboundHttpFactor;
+use spin_factor_outbound_http::{OutboundHttpFactor, cassette::CassetteMode};
 use spin_factor_outbound_mqtt::{NetworkedMqttClient, OutboundMqttFactor};
 use spin_factor_outbound_mysql::OutboundMysqlFactor;
-use spin_factor_outbound_networking::OutboundNetworkingFactor;
+use spin_factor_outbound_networking::{
+    OutboundNetworkingFactor,
+    learn::{LearnedHost, LearnedHostHandler},
+};
 use spin_factor_outbound_pg::OutboundPgFactor;
 use spin_factor_outbound_redis::OutboundRedisFactor;
 use spin_factor_sqlite::SqliteFactor;
@@ -95,6 +98,43 @@ fn outbound_networking_factor() -> OutboundNetworkingFactor {
     factor
 }
 
+fn learned_host_handler(mode: LearnOutboundHostsMode) -> impl LearnedHostHandler {
+    move |learned: &LearnedHost| {
+        let LearnedHost {
+            component_id,
+            allowed_host,
+            ..
+        } = learned;
+        tracing::info!("Learned outbound host for {component_id}: {allowed_host}");
+        if mode == LearnOutboundHostsMode::Write {
+            match learned.add_to_manifest() {
+                Ok(true) => terminal::einfo!(
+                    "Learned outbound host:",
+                    "added '{allowed_host}' to the allowed_outbound_hosts of component '{component_id}'."
+                ),
+                Ok(false) => (),
+                Err(err) => terminal::warn!(
+                    "Couldn't add '{allowed_host}' to the allowed_outbound_hosts of component '{component_id}': {err:#}"
+                ),
+            }
+        } else {
+            terminal::einfo!(
+                "Learned outbound host:",
+                "component '{component_id}' needs '{allowed_host}' in its allowed_outbound_hosts."
+            );
+        }
+    }
+}
+
+/// How `--learn-outbound-hosts` reports the allowed hosts components need.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum LearnOutboundHostsMode {
+    /// Print the needed `allowed_outbound_hosts` entries.
+    Print,
+    /// Also add the needed entries to the app manifest.
+    Write,
+}
+
 /// Options for building a [`TriggerFactors`].
 #[derive(Default, clap::Args)]
 pub struct TriggerAppArgs {
@@ -121,6 +161,31 @@ pub struct TriggerAppArgs {
     #[clap(long, env = "SPIN_MAX_INSTANCE_MEMORY")]
     pub max_instance_memory: Option<usize>,
 
+    /// Record outbound HTTP requests and their responses to a cassette file.
+    #[clap(
+        long = "record-outbound-http",
+        value_name = "FILE",
+        conflicts_with = "replay_outbound_http"
+    )]
+    pub record_outbound_http: Option<PathBuf>,
+
+    /// Serve outbound HTTP requests from a cassette file recorded with
+    /// `--record-outbound-http`. Requests which match no recorded response fail.
+    #[clap(long = "replay-outbound-http", value_name = "FILE")]
+    pub replay_outbound_http: Option<PathBuf>,
+
+    /// Allow all outbound network connections, reporting the
+    /// `allowed_outbound_hosts` entries which components would need. With
+    /// `write`, the entries are also added to the app manifest.
+    #[clap(
+        long = "learn-outbound-hosts",
+        value_name = "MODE",
+        value_enum,
+        num_args = 0..=1,
+        default_missing_value = "print"
+    )]
+    pub learn_outbound_hosts: Option<LearnOutboundHostsMode>,
+
     /// Variable(s) to be passed to the app
     ///
     /// A single key-value pair can be passed as `key=value`, or `key=@file` to
@@ -154,6 +219,15 @@ impl TryFrom<TomlRuntimeConfigSource<'_, '_>> for TriggerFactorsRuntimeConfig {
 }
 
 impl TriggerAppArgs {
+    /// The outbound HTTP cassette path and mode given on the command line, if any.
+    pub fn outbound_http_cassette(&self) -> Option<(PathBuf, CassetteMode)> {
+        match (&self.record_outbound_http, &self.replay_outbound_http) {
+            (Some(path), _) => Some((path.clone(), CassetteMode::Record)),
+            (None, Some(path)) => Some((path.clone(), CassetteMode::Replay)),
+            (None, None) => None,
+        }
+    }
+
     /// Parse all variable sources into a single merged map.
     pub fn get_variables(&self) -> anyhow::Result<&HashMap<String, String>> {
         if self.variables_cache.get().is_none() {
@@ -0,0 +1,84 @@
+//! Human-readable duration deserialization
+//!
+//! Durations may be given as a bare integer number of seconds (`30`) or as a
+//! string with a unit suffix (`"250ms"`, `"30s"`, `"5m"`, `"1h"`).
+
+use std::time::Duration;
+
+use serde::{Deserialize, Deserializer, de};
+
+/// Deserializes a [`Duration`].
+pub fn deserialize<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match DurationRepr::deserialize(deserializer)? {
+        DurationRepr::Seconds(secs) => Ok(Duration::from_secs(secs)),
+        DurationRepr::String(s) => parse(&s).map_err(de::Error::custom),
+    }
+}
+
+/// Parses a duration string such as `"250ms"` or `"30s"`.
+pub fn parse(s: &str) -> anyhow::Result<Duration> {
+    let s = s.trim();
+    let (num, unit) = s
+        .find(|c: char| !c.is_ascii_digit())
+        .map(|idx| s.split_at(idx))
+        .unwrap_or((s, "s"));
+    let num: u64 = num.parse().map_err(|_| {
+        anyhow::anyhow!("invalid duration {s:?}; expected e.g. \"500ms\" or \"30s\"")
+    })?;
+    let secs_per_unit = match unit.trim() {
+        "ms" => return Ok(Duration::from_millis(num)),
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        other => anyhow::bail!(
+            "invalid duration unit {other:?} in {s:?}; expected one of `ms`, `s`, `m`, or `h`"
+        ),
+    };
+    let secs = num
+        .checked_mul(secs_per_unit)
+        .ok_or_else(|| anyhow::anyhow!("duration {s:?} is too long"))?;
+    Ok(Duration::from_secs(secs))
+}
+
+/// Deserialization of an optional [`Duration`].
+///
+/// Use together with `#[serde(default)]`.
+pub mod option {
+    use super::*;
+
+    /// Deserializes an optional [`Duration`].
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        super::deserialize(deserializer).map(Some)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DurationRepr {
+    Seconds(u64),
+    String(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse("250ms").unwrap(), Duration::from_millis(250));
+        assert_eq!(parse("30s").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse("5m").unwrap(), Duration::from_secs(300));
+        assert_eq!(parse("1h").unwrap(), Duration::from_secs(3600));
+        assert_eq!(parse("10").unwrap(), Duration::from_secs(10));
+        parse("ms").unwrap_err();
+        parse("10d").unwrap_err();
+        parse("-1s").unwrap_err();
+        parse(&format!("{}h", u64::MAX / 60)).unwrap_err();
+    }
+}
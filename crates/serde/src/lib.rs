@@ -4,6 +4,7 @@
 
 pub mod base64;
 pub mod dependencies;
+pub mod duration;
 pub mod id;
 mod version;
 
@@ -48,6 +48,25 @@ fn app_log_to_otel(buf: &[u8], component_id: &str) {
     }
 }
 
+/// Forward a structured log emitted by the host itself, such as an audit
+/// record, to OTel.
+pub fn handle_host_log(body: String, attributes: impl IntoIterator<Item = (&'static str, String)>) {
+    if !otel_logs_enabled() {
+        return;
+    }
+
+    if let Some(logger) = LOGGER.get() {
+        let mut record = logger.create_log_record();
+        record.set_body(body.into());
+        for (key, value) in attributes {
+            record.add_attribute(key, value);
+        }
+        logger.emit(record);
+    } else {
+        tracing::trace!("OTel logger not initialized, failed to log");
+    }
+}
+
 /// Takes a Spin application log and emits it as a tracing event. This acts as a compatibility layer
 /// to easily get Spin app logs as events in our OTel traces.
 fn app_log_to_tracing_event(buf: &[u8]) {
@@ -0,0 +1,330 @@
+//! Compression of responses by the host.
+
+use async_compression::tokio::write::{BrotliEncoder, GzipEncoder};
+use http::{HeaderMap, HeaderValue, Response, StatusCode, header};
+use http_body_util::{BodyExt, StreamBody};
+use hyper::body::{Bytes, Frame};
+use spin_http::config::CompressionConfig;
+use tokio::io::AsyncWriteExt;
+use wasmtime_wasi_http::p2::bindings::http::types::ErrorCode;
+
+use crate::{Body, headers::encoding_quality};
+
+/// A content coding that responses can be compressed with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Encoding {
+    Brotli,
+    Gzip,
+}
+
+impl Encoding {
+    /// Chooses the encoding the client prefers according to its
+    /// `Accept-Encoding` header, if it accepts any.
+    pub fn negotiate(headers: &HeaderMap) -> Option<Self> {
+        let brotli = encoding_quality(headers, "br");
+        let gzip = encoding_quality(headers, "gzip");
+        if brotli <= 0.0 && gzip <= 0.0 {
+            None
+        } else if brotli >= gzip {
+            Some(Self::Brotli)
+        } else {
+            Some(Self::Gzip)
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Brotli => "br",
+            Self::Gzip => "gzip",
+        }
+    }
+}
+
+/// Compresses `res` with `encoding` if `config` allows it.
+///
+/// Compressible responses are marked as varying with `Accept-Encoding` even if
+/// the client accepts no supported encoding, so that caches don't serve an
+/// uncompressed response to clients that could have had a compressed one.
+pub(crate) fn compress_response(
+    config: &CompressionConfig,
+    encoding: Option<Encoding>,
+    res: Response<Body>,
+) -> Response<Body> {
+    if !is_compressible(config, &res) {
+        return res;
+    }
+    let (mut parts, body) = res.into_parts();
+    add_vary_accept_encoding(&mut parts.headers);
+    let Some(encoding) = encoding else {
+        return Response::from_parts(parts, body);
+    };
+
+    let headers = &mut parts.headers;
+    headers.insert(
+        header::CONTENT_ENCODING,
+        HeaderValue::from_static(encoding.name()),
+    );
+    headers.remove(header::CONTENT_LENGTH);
+    // Byte ranges of the uncompressed content don't apply to the compressed
+    // content.
+    headers.remove(header::ACCEPT_RANGES);
+    // The compressed content is only semantically equivalent to the original.
+    if let Some(etag) = headers.get(header::ETAG)
+        && !etag.as_bytes().starts_with(b"W/")
+    {
+        let mut weak = b"W/".to_vec();
+        weak.extend_from_slice(etag.as_bytes());
+        if let Ok(weak) = HeaderValue::from_bytes(&weak) {
+            headers.insert(header::ETAG, weak);
+        }
+    }
+
+    Response::from_parts(parts, compress_body(body, encoding))
+}
+
+/// Whether `res` is worth compressing and can be compressed without changing
+/// its meaning.
+fn is_compressible(config: &CompressionConfig, res: &Response<Body>) -> bool {
+    let status = res.status();
+    if status.is_informational()
+        || status == StatusCode::NO_CONTENT
+        || status == StatusCode::PARTIAL_CONTENT
+        || status == StatusCode::NOT_MODIFIED
+    {
+        return false;
+    }
+
+    let headers = res.headers();
+    let header_str = |name| headers.get(name).and_then(|v| v.to_str().ok());
+    let already_encoded = header_str(header::CONTENT_ENCODING)
+        .is_some_and(|enc| !enc.eq_ignore_ascii_case("identity"));
+    let no_transform = header_str(header::CACHE_CONTROL)
+        .is_some_and(|cc| cc.to_ascii_lowercase().contains("no-transform"));
+    if already_encoded || no_transform || headers.contains_key(header::CONTENT_RANGE) {
+        return false;
+    }
+    if header_str(header::CONTENT_LENGTH)
+        .and_then(|len| len.parse::<u64>().ok())
+        .is_some_and(|len| len < config.min_size)
+    {
+        return false;
+    }
+
+    // Event streams are consumed as they arrive; compression gains little
+    // and intermediaries may buffer compressed streams.
+    header_str(header::CONTENT_TYPE).is_some_and(|content_type| {
+        !content_type
+            .trim_start()
+            .to_ascii_lowercase()
+            .starts_with("text/event-stream")
+            && config.allows_content_type(content_type)
+    })
+}
+
+fn add_vary_accept_encoding(headers: &mut HeaderMap) {
+    let already_varies = headers
+        .get_all(header::VARY)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|name| {
+            let name = name.trim();
+            name == "*" || name.eq_ignore_ascii_case("accept-encoding")
+        });
+    if !already_varies {
+        headers.append(header::VARY, HeaderValue::from_static("Accept-Encoding"));
+    }
+}
+
+/// A compressor writing to an in-memory buffer.
+enum Encoder {
+    // Boxed, as Brotli's state is much larger than gzip's.
+    Brotli(Box<BrotliEncoder<Vec<u8>>>),
+    Gzip(GzipEncoder<Vec<u8>>),
+}
+
+impl Encoder {
+    fn new(encoding: Encoding) -> Self {
+        match encoding {
+            Encoding::Brotli => Self::Brotli(Box::new(BrotliEncoder::new(Vec::new()))),
+            Encoding::Gzip => Self::Gzip(GzipEncoder::new(Vec::new())),
+        }
+    }
+
+    /// Compresses `data`, returning all output produced so far.
+    ///
+    /// The encoder is flushed so that each chunk of a streaming response is
+    /// delivered without waiting for the next.
+    async fn write(&mut self, data: &[u8]) -> std::io::Result<Bytes> {
+        match self {
+            Self::Brotli(encoder) => {
+                encoder.write_all(data).await?;
+                encoder.flush().await?;
+                Ok(std::mem::take(encoder.get_mut()).into())
+            }
+            Self::Gzip(encoder) => {
+                encoder.write_all(data).await?;
+                encoder.flush().await?;
+                Ok(std::mem::take(encoder.get_mut()).into())
+            }
+        }
+    }
+
+    /// Finishes the compressed stream, returning the remaining output.
+    async fn finish(&mut self) -> std::io::Result<Bytes> {
+        match self {
+            Self::Brotli(encoder) => {
+                encoder.shutdown().await?;
+                Ok(std::mem::take(encoder.get_mut()).into())
+            }
+            Self::Gzip(encoder) => {
+                encoder.shutdown().await?;
+                Ok(std::mem::take(encoder.get_mut()).into())
+            }
+        }
+    }
+}
+
+enum CompressState {
+    Streaming(Body, Encoder),
+    Trailers(Frame<Bytes>),
+    Done,
+}
+
+fn compress_body(body: Body, encoding: Encoding) -> Body {
+    let io_error = |err: std::io::Error| ErrorCode::InternalError(Some(err.to_string()));
+    let frames = futures::stream::unfold(
+        CompressState::Streaming(body, Encoder::new(encoding)),
+        move |state| async move {
+            let (mut body, mut encoder) = match state {
+                CompressState::Streaming(body, encoder) => (body, encoder),
+                CompressState::Trailers(trailers) => {
+                    return Some((Ok(trailers), CompressState::Done));
+                }
+                CompressState::Done => return None,
+            };
+            loop {
+                let frame = match body.frame().await {
+                    Some(Ok(frame)) => frame,
+                    Some(Err(err)) => return Some((Err(err), CompressState::Done)),
+                    None => {
+                        let out = encoder.finish().await.map(Frame::data).map_err(io_error);
+                        return Some((out, CompressState::Done));
+                    }
+                };
+                match frame.into_data() {
+                    Ok(data) => match encoder.write(&data).await {
+                        Ok(out) if out.is_empty() => continue,
+                        Ok(out) => {
+                            return Some((
+                                Ok(Frame::data(out)),
+                                CompressState::Streaming(body, encoder),
+                            ));
+                        }
+                        Err(err) => return Some((Err(io_error(err)), CompressState::Done)),
+                    },
+                    Err(trailers) => {
+                        return match encoder.finish().await {
+                            Ok(out) => {
+                                Some((Ok(Frame::data(out)), CompressState::Trailers(trailers)))
+                            }
+                            Err(err) => Some((Err(io_error(err)), CompressState::Done)),
+                        };
+                    }
+                }
+            }
+        },
+    );
+    StreamBody::new(frames).boxed_unsync()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(headers: &[(&str, &str)], body: &'static str) -> Response<Body> {
+        let mut builder = Response::builder();
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        builder
+            .body(spin_http::body::full(Bytes::from_static(body.as_bytes())))
+            .unwrap()
+    }
+
+    fn config() -> CompressionConfig {
+        CompressionConfig {
+            min_size: 4,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn negotiates_preferred_encoding() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT_ENCODING, "gzip, br".parse().unwrap());
+        assert_eq!(Encoding::negotiate(&headers), Some(Encoding::Brotli));
+        headers.insert(header::ACCEPT_ENCODING, "gzip, br;q=0.5".parse().unwrap());
+        assert_eq!(Encoding::negotiate(&headers), Some(Encoding::Gzip));
+        headers.insert(header::ACCEPT_ENCODING, "identity".parse().unwrap());
+        assert_eq!(Encoding::negotiate(&headers), None);
+    }
+
+    #[test]
+    fn skips_incompressible_responses() {
+        let config = config();
+        assert!(is_compressible(
+            &config,
+            &response(&[("content-type", "text/plain")], "")
+        ));
+        assert!(!is_compressible(&config, &response(&[], "")));
+        assert!(!is_compressible(
+            &config,
+            &response(&[("content-type", "image/png")], "")
+        ));
+        assert!(!is_compressible(
+            &config,
+            &response(
+                &[("content-type", "text/plain"), ("content-length", "3")],
+                ""
+            )
+        ));
+        assert!(!is_compressible(
+            &config,
+            &response(
+                &[("content-type", "text/plain"), ("content-encoding", "gzip")],
+                ""
+            )
+        ));
+        assert!(!is_compressible(
+            &config,
+            &response(&[("content-type", "text/event-stream")], "")
+        ));
+    }
+
+    #[tokio::test]
+    async fn compresses_with_gzip() {
+        let res = compress_response(
+            &config(),
+            Some(Encoding::Gzip),
+            response(
+                &[
+                    ("content-type", "text/plain"),
+                    ("content-length", "11"),
+                    ("etag", "\"abc\""),
+                ],
+                "hello world",
+            ),
+        );
+        assert_eq!(res.headers()[header::CONTENT_ENCODING], "gzip");
+        assert_eq!(res.headers()[header::VARY], "Accept-Encoding");
+        assert_eq!(res.headers()[header::ETAG], "W/\"abc\"");
+        assert!(!res.headers().contains_key(header::CONTENT_LENGTH));
+
+        let compressed = res.into_body().collect().await.unwrap().to_bytes();
+        let mut decoder = async_compression::tokio::write::GzipDecoder::new(Vec::new());
+        decoder.write_all(&compressed).await.unwrap();
+        decoder.shutdown().await.unwrap();
+        assert_eq!(decoder.into_inner(), b"hello world");
+    }
+}
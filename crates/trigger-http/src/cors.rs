@@ -0,0 +1,244 @@
+//! Enforcement of CORS policies by the host.
+
+use http::{HeaderMap, HeaderValue, Method, Request, Response, StatusCode, header};
+use spin_http::config::CorsConfig;
+
+use crate::Body;
+
+/// Whether `req` is a CORS preflight request.
+pub(crate) fn is_preflight(req: &Request<Body>) -> bool {
+    req.method() == Method::OPTIONS
+        && req.headers().contains_key(header::ORIGIN)
+        && req
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_REQUEST_METHOD)
+}
+
+/// Answers a CORS preflight request.
+///
+/// If the policy does not allow the request, the response carries no CORS
+/// headers, so the client will not make the actual request.
+pub(crate) fn preflight_response(config: &CorsConfig, req_headers: &HeaderMap) -> Response<Body> {
+    let mut res = Response::builder()
+        .status(StatusCode::NO_CONTENT)
+        .body(spin_http::body::empty())
+        .unwrap();
+    let headers = res.headers_mut();
+    for name in [
+        header::ORIGIN,
+        header::ACCESS_CONTROL_REQUEST_METHOD,
+        header::ACCESS_CONTROL_REQUEST_HEADERS,
+    ] {
+        headers.append(header::VARY, HeaderValue::from_name(name));
+    }
+
+    let Some(origin) = allowed_origin(config, req_headers.get(header::ORIGIN)) else {
+        return res;
+    };
+    let Some(method) = req_headers
+        .get(header::ACCESS_CONTROL_REQUEST_METHOD)
+        .filter(|method| {
+            method
+                .to_str()
+                .is_ok_and(|method| config.allows_method(method))
+        })
+    else {
+        return res;
+    };
+    let requested_headers = req_headers
+        .get_all(header::ACCESS_CONTROL_REQUEST_HEADERS)
+        .iter()
+        .map(|value| value.to_str().ok())
+        .collect::<Option<Vec<_>>>();
+    let Some(requested_headers) = requested_headers else {
+        return res;
+    };
+    let requested_headers = requested_headers
+        .iter()
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .collect::<Vec<_>>();
+    if !requested_headers
+        .iter()
+        .all(|name| config.allows_header(name))
+    {
+        return res;
+    }
+
+    add_allow_origin(config, origin, headers);
+    headers.insert(header::ACCESS_CONTROL_ALLOW_METHODS, method.clone());
+    if !requested_headers.is_empty()
+        && let Ok(value) = HeaderValue::from_str(&requested_headers.join(", "))
+    {
+        headers.insert(header::ACCESS_CONTROL_ALLOW_HEADERS, value);
+    }
+    if let Some(max_age) = config.max_age {
+        headers.insert(header::ACCESS_CONTROL_MAX_AGE, max_age.into());
+    }
+    res
+}
+
+/// Adds the CORS headers the policy calls for to the response to an actual
+/// (non-preflight) request with the given `Origin` header.
+pub(crate) fn add_response_headers(
+    config: &CorsConfig,
+    origin: Option<&HeaderValue>,
+    res_headers: &mut HeaderMap,
+) {
+    if !config.allows_any_origin() {
+        res_headers.append(header::VARY, HeaderValue::from_name(header::ORIGIN));
+    }
+    let Some(origin) = allowed_origin(config, origin) else {
+        return;
+    };
+    add_allow_origin(config, origin, res_headers);
+    if !config.exposed_headers.is_empty()
+        && let Ok(value) = HeaderValue::from_str(&config.exposed_headers.join(", "))
+    {
+        res_headers.insert(header::ACCESS_CONTROL_EXPOSE_HEADERS, value);
+    }
+}
+
+/// The `Origin` header, if the policy allows it.
+fn allowed_origin<'a>(
+    config: &CorsConfig,
+    origin: Option<&'a HeaderValue>,
+) -> Option<&'a HeaderValue> {
+    origin.filter(|origin| {
+        origin
+            .to_str()
+            .is_ok_and(|origin| config.allows_origin(origin))
+    })
+}
+
+fn add_allow_origin(config: &CorsConfig, origin: &HeaderValue, headers: &mut HeaderMap) {
+    // Validation rules out allowing any origin with credentials, which
+    // clients would reject.
+    let allow_origin = if config.allows_any_origin() {
+        HeaderValue::from_static("*")
+    } else {
+        origin.clone()
+    };
+    headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, allow_origin);
+    if config.allow_credentials {
+        headers.insert(
+            header::ACCESS_CONTROL_ALLOW_CREDENTIALS,
+            HeaderValue::from_static("true"),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> CorsConfig {
+        CorsConfig {
+            allowed_origins: vec!["https://*.example.com".into()],
+            allowed_methods: vec!["GET".into(), "PUT".into()],
+            allowed_headers: vec!["content-type".into()],
+            exposed_headers: vec!["x-request-id".into()],
+            allow_credentials: true,
+            max_age: Some(600),
+        }
+    }
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| {
+                (
+                    http::HeaderName::from_static(name),
+                    HeaderValue::from_static(value),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn allowed_preflight() {
+        let res = preflight_response(
+            &config(),
+            &headers(&[
+                ("origin", "https://app.example.com"),
+                ("access-control-request-method", "PUT"),
+                ("access-control-request-headers", "Content-Type"),
+            ]),
+        );
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+        let headers = res.headers();
+        assert_eq!(
+            headers[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://app.example.com"
+        );
+        assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_METHODS], "PUT");
+        assert_eq!(
+            headers[header::ACCESS_CONTROL_ALLOW_HEADERS],
+            "Content-Type"
+        );
+        assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_CREDENTIALS], "true");
+        assert_eq!(headers[header::ACCESS_CONTROL_MAX_AGE], "600");
+    }
+
+    #[test]
+    fn disallowed_preflight() {
+        for req_headers in [
+            headers(&[
+                ("origin", "https://evil.com"),
+                ("access-control-request-method", "GET"),
+            ]),
+            headers(&[
+                ("origin", "https://app.example.com"),
+                ("access-control-request-method", "DELETE"),
+            ]),
+            headers(&[
+                ("origin", "https://app.example.com"),
+                ("access-control-request-method", "GET"),
+                ("access-control-request-headers", "authorization"),
+            ]),
+        ] {
+            let res = preflight_response(&config(), &req_headers);
+            assert!(
+                !res.headers()
+                    .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+            );
+        }
+    }
+
+    #[test]
+    fn actual_response_headers() {
+        let mut res_headers = HeaderMap::new();
+        add_response_headers(
+            &config(),
+            Some(&HeaderValue::from_static("https://app.example.com")),
+            &mut res_headers,
+        );
+        assert_eq!(
+            res_headers[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://app.example.com"
+        );
+        assert_eq!(
+            res_headers[header::ACCESS_CONTROL_ALLOW_CREDENTIALS],
+            "true"
+        );
+        assert_eq!(res_headers[header::VARY], "origin");
+        assert_eq!(
+            res_headers[header::ACCESS_CONTROL_EXPOSE_HEADERS],
+            "x-request-id"
+        );
+
+        let mut res_headers = HeaderMap::new();
+        add_response_headers(
+            &CorsConfig {
+                allowed_origins: vec!["*".into()],
+                allow_credentials: false,
+                ..config()
+            },
+            Some(&HeaderValue::from_static("https://other.com")),
+            &mut res_headers,
+        );
+        assert_eq!(res_headers[header::ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+        assert!(!res_headers.contains_key(header::VARY));
+    }
+}
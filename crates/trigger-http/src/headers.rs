@@ -10,7 +10,7 @@ use hyper::Request;
 use spin_factor_outbound_networking::config::allowed_hosts::is_service_chaining_host;
 use spin_http::routes::RouteMatch;
 
-use crate::Body;
+use crate::{Body, tls::ClientCert};
 
 // We need to make the following pieces of information available to both executors.
 // While the values we set are identical, the way they are passed to the
@@ -24,6 +24,18 @@ pub const COMPONENT_ROUTE: [&str; 2] = ["SPIN_COMPONENT_ROUTE", "X_COMPONENT_ROU
 pub const RAW_COMPONENT_ROUTE: [&str; 2] = ["SPIN_RAW_COMPONENT_ROUTE", "X_RAW_COMPONENT_ROUTE"];
 pub const BASE_PATH: [&str; 2] = ["SPIN_BASE_PATH", "X_BASE_PATH"];
 pub const CLIENT_ADDR: [&str; 2] = ["SPIN_CLIENT_ADDR", "X_CLIENT_ADDR"];
+pub const CLIENT_CERT_SUBJECT: [&str; 2] = ["SPIN_CLIENT_CERT_SUBJECT", "X_CLIENT_CERT_SUBJECT"];
+pub const CLIENT_CERT_SANS: [&str; 2] = ["SPIN_CLIENT_CERT_SANS", "X_CLIENT_CERT_SANS"];
+pub const CLIENT_CERT_FINGERPRINT: [&str; 2] =
+    ["SPIN_CLIENT_CERT_FINGERPRINT", "X_CLIENT_CERT_FINGERPRINT"];
+
+// Headers the host sets to describe the client's verified certificate, which
+// must not be accepted from the client itself.
+const CLIENT_CERT_HEADERS: [&str; 3] = [
+    "spin-client-cert-subject",
+    "spin-client-cert-sans",
+    "spin-client-cert-fingerprint",
+];
 
 // Header key/value pairs that use copy on write to avoid allocation
 pub type HeaderPair<'a> = ([Cow<'static, str>; 2], Cow<'a, str>);
@@ -34,6 +46,7 @@ pub fn compute_default_headers<'a>(
     host: &str,
     route_match: &'a RouteMatch,
     client_addr: SocketAddr,
+    client_cert: Option<&'a ClientCert>,
 ) -> anyhow::Result<Vec<HeaderPair<'a>>> {
     fn owned(strs: &[&'static str; 2]) -> [Cow<'static, str>; 2] {
         [strs[0].into(), strs[1].into()]
@@ -71,6 +84,18 @@ pub fn compute_default_headers<'a>(
     ));
     res.push((owned_client_addr, client_addr.to_string().into()));
 
+    if let Some(client_cert) = client_cert {
+        res.push((
+            owned(&CLIENT_CERT_SUBJECT),
+            client_cert.subject.as_str().into(),
+        ));
+        res.push((owned(&CLIENT_CERT_SANS), client_cert.sans.join(", ").into()));
+        res.push((
+            owned(&CLIENT_CERT_FINGERPRINT),
+            client_cert.fingerprint.as_str().into(),
+        ));
+    }
+
     for (wild_name, wild_value) in route_match.named_wildcards() {
         let wild_header = format!("SPIN_PATH_MATCH_{}", wild_name.to_ascii_uppercase()).into();
         let wild_wagi_header = format!("X_PATH_MATCH_{}", wild_name.to_ascii_uppercase()).into();
@@ -91,6 +116,10 @@ pub fn strip_forbidden_headers(req: &mut Request<Body>) {
     // This header is transport information - only meaningful to the server
     // itself - and causes problems if guests need to forward requests.
     headers.remove("Connection");
+    // Only the host may vouch for the client's certificate.
+    for name in CLIENT_CERT_HEADERS {
+        headers.remove(name);
+    }
 }
 
 pub fn prepare_request_headers(
@@ -119,7 +148,10 @@ pub fn prepare_request_headers(
     // Set the environment information (path info, base path, etc) as headers.
     // In the future, we might want to have this information in a context
     // object as opposed to headers.
-    for (keys, val) in compute_default_headers(req.uri(), host, route_match, client_addr)? {
+    let client_cert = req.extensions().get::<ClientCert>();
+    for (keys, val) in
+        compute_default_headers(req.uri(), host, route_match, client_addr, client_cert)?
+    {
         res.push((prepare_header_key(&keys[0]), val.into_owned()));
     }
 
@@ -142,6 +174,32 @@ pub fn append_headers(
     Ok(())
 }
 
+/// Returns the quality value (0 to 1) that an `Accept-Encoding` header gives
+/// `encoding`, where 0 means the encoding is not acceptable.
+pub fn encoding_quality(headers: &http::HeaderMap, encoding: &str) -> f32 {
+    let mut explicit = None;
+    let mut wildcard = None;
+    for item in headers
+        .get_all(http::header::ACCEPT_ENCODING)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+    {
+        let mut params = item.split(';');
+        let name = params.next().unwrap_or_default().trim();
+        let quality = params
+            .find_map(|param| param.trim().strip_prefix("q="))
+            .map(|q| q.trim().parse().unwrap_or(0.0))
+            .unwrap_or(1.0);
+        if name.eq_ignore_ascii_case(encoding) {
+            explicit = Some(quality);
+        } else if name == "*" {
+            wildcard = Some(quality);
+        }
+    }
+    explicit.or(wildcard).unwrap_or(0.0)
+}
+
 fn prepare_header_key(key: &str) -> String {
     key.replace('_', "-").to_ascii_lowercase()
 }
@@ -199,7 +257,8 @@ mod tests {
         )?;
         let route_match = router.route("/foo/bar")?;
 
-        let default_headers = compute_default_headers(req.uri(), host, &route_match, client_addr)?;
+        let default_headers =
+            compute_default_headers(req.uri(), host, &route_match, client_addr, None)?;
 
         assert_eq!(
             search(&FULL_URL, &default_headers).unwrap(),
@@ -229,6 +288,48 @@ mod tests {
             search(&CLIENT_ADDR, &default_headers).unwrap(),
             "127.0.0.1:8777".to_string()
         );
+        assert!(search(&CLIENT_CERT_SUBJECT, &default_headers).is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_default_headers_with_client_cert() -> Result<()> {
+        let router = Router::build(
+            "/",
+            [(
+                &spin_http::routes::TriggerLookupKey::Component("DUMMY".into()),
+                &"/...".into(),
+            )],
+            None,
+        )?;
+        let route_match = router.route("/")?;
+        let client_cert = ClientCert {
+            subject: "CN=client, O=Example".into(),
+            sans: vec!["DNS:client.example.com".into(), "IP:10.0.0.1".into()],
+            fingerprint: "abc123".into(),
+        };
+
+        let default_headers = compute_default_headers(
+            &"http://localhost/".parse()?,
+            "localhost",
+            &route_match,
+            "127.0.0.1:8777".parse()?,
+            Some(&client_cert),
+        )?;
+
+        assert_eq!(
+            search(&CLIENT_CERT_SUBJECT, &default_headers).unwrap(),
+            "CN=client, O=Example"
+        );
+        assert_eq!(
+            search(&CLIENT_CERT_SANS, &default_headers).unwrap(),
+            "DNS:client.example.com, IP:10.0.0.1"
+        );
+        assert_eq!(
+            search(&CLIENT_CERT_FINGERPRINT, &default_headers).unwrap(),
+            "abc123"
+        );
 
         Ok(())
     }
@@ -262,7 +363,8 @@ mod tests {
         )?;
         let route_match = router.route("/foo/42/bar")?;
 
-        let default_headers = compute_default_headers(req.uri(), host, &route_match, client_addr)?;
+        let default_headers =
+            compute_default_headers(req.uri(), host, &route_match, client_addr, None)?;
 
         assert_eq!(
             search(&FULL_URL, &default_headers).unwrap(),
@@ -343,6 +445,18 @@ mod tests {
         assert_eq!(1, req.headers().len());
         assert!(req.headers().get("Host").is_none());
         assert!(req.headers().get("connection").is_none());
+
+        let mut req = Request::get("http://test.example.com")
+            .header("accept", "text/plain")
+            .header("spin-client-cert-subject", "CN=admin")
+            .header("Spin-Client-Cert-Fingerprint", "forged")
+            .body(Default::default())
+            .unwrap();
+
+        strip_forbidden_headers(&mut req);
+
+        assert_eq!(1, req.headers().len());
+        assert!(req.headers().get("spin-client-cert-subject").is_none());
     }
 
     #[test]
@@ -359,6 +473,20 @@ mod tests {
         assert!(req.headers().get("Host").is_some());
     }
 
+    #[test]
+    fn encoding_quality_prefers_explicit_entries() {
+        let mut headers = http::HeaderMap::new();
+        headers.insert(
+            http::header::ACCEPT_ENCODING,
+            "gzip;q=0.5, br;q=0, *".parse().unwrap(),
+        );
+
+        assert_eq!(encoding_quality(&headers, "gzip"), 0.5);
+        assert_eq!(encoding_quality(&headers, "br"), 0.0);
+        assert_eq!(encoding_quality(&headers, "zstd"), 1.0);
+        assert_eq!(encoding_quality(&http::HeaderMap::new(), "gzip"), 0.0);
+    }
+
     fn search(
         keys: &[&str; 2],
         headers: &[([Cow<'static, str>; 2], Cow<'_, str>)],
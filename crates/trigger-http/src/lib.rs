@@ -1,27 +1,25 @@
 //! Implementation for the Spin HTTP engine.
 
+mod compression;
+mod cors;
 mod headers;
 mod instrument;
+mod limits;
+mod listener;
 mod middleware;
 mod outbound_http;
 mod server;
 mod spin;
+mod static_files;
+mod throttle;
 mod tls;
 mod wagi;
 mod wasi;
 mod wasip3;
 
-use std::{
-    error::Error,
-    fmt::Display,
-    net::{Ipv4Addr, SocketAddr, ToSocketAddrs},
-    path::PathBuf,
-    str::FromStr,
-    sync::Arc,
-    time::Duration,
-};
+use std::{error::Error, fmt::Display, path::PathBuf, str::FromStr, sync::Arc, time::Duration};
 
-use anyhow::{Context, bail};
+use anyhow::bail;
 use clap::Args;
 use rand::{
     distr::uniform::{SampleRange, SampleUniform},
@@ -30,12 +28,14 @@ use rand::{
 use serde::Deserialize;
 use spin_app::App;
 use spin_factors::RuntimeFactors;
-use spin_trigger::Trigger;
+use spin_http::config::RequestLimits;
+use spin_trigger::{ShutdownSignal, Trigger};
 use wasmtime_wasi_http::p2::bindings::http::types::ErrorCode;
 
+pub use listener::{ListenAddr, ListenSpec, ListenerConfig};
 pub use server::HttpServer;
 
-pub use tls::TlsConfig;
+pub use tls::{ClientAuthConfig, ClientAuthMode, TlsCertificate, TlsConfig};
 
 pub(crate) use wasmtime_wasi_http::p2::body::HyperIncomingBody as Body;
 
@@ -54,6 +54,34 @@ pub enum OutputFormat {
     Json,
 }
 
+/// The HTTP versions to accept on TCP connections.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum HttpVersions {
+    /// HTTP/1.1 and HTTP/2 (the default). With TLS, HTTP/2 is negotiated
+    /// using ALPN; without TLS, clients may use HTTP/2 with prior knowledge
+    /// (h2c).
+    #[default]
+    Auto,
+    /// HTTP/1.1 only.
+    Http1,
+    /// HTTP/2 only. Without TLS, clients must use HTTP/2 with prior
+    /// knowledge (h2c).
+    Http2,
+}
+
+/// The protocols the server speaks and their settings.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ProtocolConfig {
+    /// The maximum buffer size for an HTTP/1 connection.
+    pub http1_max_buf_size: Option<usize>,
+    /// The HTTP versions to accept on TCP connections.
+    pub http_versions: HttpVersions,
+    /// Whether to also serve HTTP/3 over QUIC. Requires TLS.
+    pub http3: bool,
+    /// The limits on the size of requests for triggers which do not set their own.
+    pub request_limits: RequestLimits,
+}
+
 /// A [`spin_trigger::TriggerApp`] for the HTTP trigger.
 pub(crate) type TriggerApp<F> = spin_trigger::TriggerApp<HttpTrigger, F>;
 
@@ -63,11 +91,20 @@ pub(crate) type TriggerInstanceBuilder<'a, F> =
 
 #[derive(Args)]
 pub struct CliArgs {
-    /// IP address and port to listen on
-    #[clap(long = "listen", env = "SPIN_HTTP_LISTEN_ADDR", default_value = "127.0.0.1:3000", value_parser = parse_listen_addr)]
-    pub address: SocketAddr,
-
-    /// The path to the certificate to use for https, if this is not set, normal http will be used. The cert should be in PEM format
+    /// IP address and port to listen on. May be repeated to listen on several addresses.
+    ///
+    /// A plain `host:port` address uses TLS if `--tls-cert` is set. Prefix the address
+    /// with `http://` or `https://` to choose for each address, or use `unix:/path` to
+    /// listen on a Unix domain socket (without TLS).
+    #[clap(
+        long = "listen",
+        env = "SPIN_HTTP_LISTEN_ADDR",
+        default_value = "127.0.0.1:3000",
+        value_delimiter = ','
+    )]
+    pub address: Vec<ListenSpec>,
+
+    /// The path to the certificate to use for https, if this is not set, normal http will be used. The cert should be in PEM format. Certificate and key files are checked for changes every 10 seconds, and reloaded without a restart when their modification time changes
     #[clap(long, env = "SPIN_TLS_CERT", requires = "tls_key")]
     pub tls_cert: Option<PathBuf>,
 
@@ -75,10 +112,52 @@ pub struct CliArgs {
     #[clap(long, env = "SPIN_TLS_KEY", requires = "tls_cert")]
     pub tls_key: Option<PathBuf>,
 
-    /// Sets the maximum buffer size (in bytes) for the HTTP connection. The minimum value allowed is 8192.
+    /// The path to an additional certificate, used for connections requesting a server name (SNI) the certificate is valid for. May be repeated, with one --tls-sni-key for each. The cert should be in PEM format
+    #[clap(long, requires = "tls_cert", requires = "tls_sni_key")]
+    pub tls_sni_cert: Vec<PathBuf>,
+
+    /// The path to the key for each --tls-sni-cert, in the same order. The key should be in PKCS#8 format
+    #[clap(long, requires = "tls_sni_cert")]
+    pub tls_sni_key: Vec<PathBuf>,
+
+    /// The path to the certificate(s) of the CA(s) that issue client certificates. If this is set, clients connecting over https are authenticated with a certificate (mutual TLS): by default they must present one, and with --tls-client-auth optional they may connect without one, but a certificate that is presented must be valid. The certs should be in PEM format
+    #[clap(long, env = "SPIN_TLS_CLIENT_CA", requires = "tls_cert")]
+    pub tls_client_ca: Option<PathBuf>,
+
+    /// Whether clients must present a certificate when --tls-client-ca is set.
+    #[clap(value_enum, long, env = "SPIN_TLS_CLIENT_AUTH", requires = "tls_client_ca", default_value_t = ClientAuthMode::default())]
+    pub tls_client_auth: ClientAuthMode,
+
+    /// Sets the maximum buffer size (in bytes) for the HTTP connection. The minimum value allowed is 8192. If this is not set, and every trigger limits the request header size and URI length, the buffer is sized to fit a request within those limits.
     #[clap(long, env = "SPIN_HTTP1_MAX_BUF_SIZE")]
     pub http1_max_buf_size: Option<usize>,
 
+    /// The HTTP versions to accept on TCP connections.
+    #[clap(value_enum, long, env = "SPIN_HTTP_VERSIONS", default_value_t = HttpVersions::default())]
+    pub http_versions: HttpVersions,
+
+    /// Also serve HTTP/3 over QUIC, on the UDP port with the same number as
+    /// the TCP port (experimental). Requires TLS.
+    #[cfg(feature = "http3")]
+    #[clap(long, env = "SPIN_HTTP3", requires = "tls_cert")]
+    pub http3: bool,
+
+    /// The maximum size, in bytes, of request bodies. Larger requests are rejected with 413 Content Too Large. Triggers may override this with `limits.max_body_size`.
+    #[clap(long, env = "SPIN_HTTP_MAX_REQUEST_BODY_SIZE")]
+    pub max_request_body_size: Option<u64>,
+
+    /// The maximum number of request headers. Requests with more are rejected with 431 Request Header Fields Too Large. Triggers may override this with `limits.max_header_count`.
+    #[clap(long, env = "SPIN_HTTP_MAX_REQUEST_HEADER_COUNT")]
+    pub max_request_header_count: Option<usize>,
+
+    /// The maximum total size, in bytes, of request header names and values. Larger requests are rejected with 431 Request Header Fields Too Large. Triggers may override this with `limits.max_header_size`.
+    #[clap(long, env = "SPIN_HTTP_MAX_REQUEST_HEADER_SIZE")]
+    pub max_request_header_size: Option<usize>,
+
+    /// The maximum length, in bytes, of request URIs. Longer requests are rejected with 414 URI Too Long. Triggers may override this with `limits.max_uri_length`.
+    #[clap(long, env = "SPIN_HTTP_MAX_REQUEST_URI_LENGTH")]
+    pub max_request_uri_length: Option<usize>,
+
     #[clap(long = "find-free-port")]
     pub find_free_port: bool,
 
@@ -140,15 +219,51 @@ pub struct CliArgs {
 }
 
 impl CliArgs {
-    fn into_tls_config(self) -> Option<TlsConfig> {
-        match (self.tls_cert, self.tls_key) {
+    fn protocol_config(&self) -> ProtocolConfig {
+        ProtocolConfig {
+            http1_max_buf_size: self.http1_max_buf_size,
+            http_versions: self.http_versions,
+            #[cfg(feature = "http3")]
+            http3: self.http3,
+            #[cfg(not(feature = "http3"))]
+            http3: false,
+            request_limits: RequestLimits {
+                max_body_size: self.max_request_body_size,
+                max_header_count: self.max_request_header_count,
+                max_header_size: self.max_request_header_size,
+                max_uri_length: self.max_request_uri_length,
+            },
+        }
+    }
+
+    fn into_listeners(self) -> anyhow::Result<Vec<ListenerConfig>> {
+        if self.tls_sni_cert.len() != self.tls_sni_key.len() {
+            bail!("--tls-sni-cert and --tls-sni-key must be given the same number of times");
+        }
+        let sni_certs = std::iter::zip(self.tls_sni_cert, self.tls_sni_key)
+            .map(|(cert_path, key_path)| TlsCertificate {
+                cert_path,
+                key_path,
+            })
+            .collect();
+        let client_auth = self.tls_client_ca.map(|ca_path| ClientAuthConfig {
+            ca_path,
+            mode: self.tls_client_auth,
+        });
+        let tls_config = match (self.tls_cert, self.tls_key) {
             (Some(cert_path), Some(key_path)) => Some(TlsConfig {
                 cert_path,
                 key_path,
+                sni_certs,
+                client_auth,
             }),
             (None, None) => None,
             _ => unreachable!(),
-        }
+        };
+        self.address
+            .into_iter()
+            .map(|spec| spec.into_config(tls_config.as_ref()))
+            .collect()
     }
 }
 
@@ -278,14 +393,10 @@ impl InstanceReuseConfig {
 
 /// The Spin HTTP trigger.
 pub struct HttpTrigger {
-    /// The address the server should listen on.
-    ///
-    /// Note that this might not be the actual socket address that ends up being bound to.
-    /// If the port is set to 0, the actual address will be determined by the OS.
-    listen_addr: SocketAddr,
-    tls_config: Option<TlsConfig>,
+    /// The addresses the server should listen on.
+    listeners: Vec<ListenerConfig>,
     find_free_port: bool,
-    http1_max_buf_size: Option<usize>,
+    protocol_config: ProtocolConfig,
     reuse_config: InstanceReuseConfig,
     output_format: OutputFormat,
 }
@@ -298,7 +409,7 @@ impl<F: RuntimeFactors> Trigger<F> for HttpTrigger {
 
     fn new(cli_args: Self::CliArgs, app: &spin_app::App) -> anyhow::Result<Self> {
         let find_free_port = cli_args.find_free_port;
-        let http1_max_buf_size = cli_args.http1_max_buf_size;
+        let protocol_config = cli_args.protocol_config();
         let output_format = cli_args.format;
         let reuse_config = InstanceReuseConfig {
             max_instance_reuse_count: cli_args
@@ -316,10 +427,9 @@ impl<F: RuntimeFactors> Trigger<F> for HttpTrigger {
 
         Self::new(
             app,
-            cli_args.address,
-            cli_args.into_tls_config(),
+            cli_args.into_listeners()?,
             find_free_port,
-            http1_max_buf_size,
+            protocol_config,
             reuse_config,
             output_format,
         )
@@ -328,7 +438,19 @@ impl<F: RuntimeFactors> Trigger<F> for HttpTrigger {
     async fn run(self, trigger_app: TriggerApp<F>) -> anyhow::Result<()> {
         let server = self.into_server(trigger_app)?;
 
-        server.serve().await?;
+        server.serve(ShutdownSignal::never()).await?;
+
+        Ok(())
+    }
+
+    async fn run_until_shutdown(
+        self,
+        trigger_app: TriggerApp<F>,
+        shutdown: ShutdownSignal,
+    ) -> anyhow::Result<()> {
+        let server = self.into_server(trigger_app)?;
+
+        server.serve(shutdown).await?;
 
         Ok(())
     }
@@ -353,20 +475,24 @@ impl HttpTrigger {
     /// Create a new `HttpTrigger`.
     pub fn new(
         app: &spin_app::App,
-        listen_addr: SocketAddr,
-        tls_config: Option<TlsConfig>,
+        listeners: Vec<ListenerConfig>,
         find_free_port: bool,
-        http1_max_buf_size: Option<usize>,
+        protocol_config: ProtocolConfig,
         reuse_config: InstanceReuseConfig,
         output_format: OutputFormat,
     ) -> anyhow::Result<Self> {
         Self::validate_app(app)?;
+        if listeners.is_empty() {
+            bail!("The HTTP trigger requires at least one listen address");
+        }
+        if protocol_config.http3 && !listeners.iter().any(|l| l.tls_config.is_some()) {
+            bail!("HTTP/3 requires TLS to be configured");
+        }
 
         Ok(Self {
-            listen_addr,
-            tls_config,
+            listeners,
             find_free_port,
-            http1_max_buf_size,
+            protocol_config,
             reuse_config,
             output_format,
         })
@@ -378,19 +504,17 @@ impl HttpTrigger {
         trigger_app: TriggerApp<F>,
     ) -> anyhow::Result<Arc<HttpServer<F>>> {
         let Self {
-            listen_addr,
-            tls_config,
+            listeners,
             find_free_port,
-            http1_max_buf_size,
+            protocol_config,
             reuse_config,
             output_format,
         } = self;
         let server = Arc::new(HttpServer::new(
-            listen_addr,
-            tls_config,
+            listeners,
             find_free_port,
             trigger_app,
-            http1_max_buf_size,
+            protocol_config,
             reuse_config,
             output_format,
         )?);
@@ -448,19 +572,6 @@ impl HttpTrigger {
     }
 }
 
-fn parse_listen_addr(addr: &str) -> anyhow::Result<SocketAddr> {
-    let addrs: Vec<SocketAddr> = addr.to_socket_addrs()?.collect();
-    // Prefer 127.0.0.1 over e.g. [::1] because CHANGE IS HARD
-    if let Some(addr) = addrs
-        .iter()
-        .find(|addr| addr.is_ipv4() && addr.ip() == Ipv4Addr::LOCALHOST)
-    {
-        return Ok(*addr);
-    }
-    // Otherwise, take the first addr (OS preference)
-    addrs.into_iter().next().context("couldn't resolve address")
-}
-
 #[derive(Debug, PartialEq)]
 enum NotFoundRouteKind {
     Normal(String),
@@ -494,13 +605,6 @@ pub fn dns_error(rcode: String, info_code: u16) -> ErrorCode {
 mod tests {
     use super::*;
 
-    #[test]
-    fn parse_listen_addr_prefers_ipv4() {
-        let addr = parse_listen_addr("localhost:12345").unwrap();
-        assert_eq!(addr.ip(), Ipv4Addr::LOCALHOST);
-        assert_eq!(addr.port(), 12345);
-    }
-
     #[test]
     fn request_deadline_config_is_single_use() {
         let timeout = Duration::from_millis(500);
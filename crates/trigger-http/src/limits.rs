@@ -0,0 +1,319 @@
+//! Enforcement of limits on the size of requests.
+
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+
+use futures::StreamExt;
+use http::{Request, Response, StatusCode, Version, header};
+use http_body_util::{BodyExt, BodyStream, StreamBody};
+use spin_http::config::RequestLimits;
+use wasmtime_wasi_http::p2::bindings::http::types::ErrorCode;
+
+use crate::Body;
+
+/// A limit which a request exceeds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum LimitExceeded {
+    BodySize,
+    HeaderCount,
+    HeaderSize,
+    UriLength,
+}
+
+impl LimitExceeded {
+    /// The name of the limit, as reported in metrics.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::BodySize => "body_size",
+            Self::HeaderCount => "header_count",
+            Self::HeaderSize => "header_size",
+            Self::UriLength => "uri_length",
+        }
+    }
+
+    /// The response rejecting a request made with HTTP `version`.
+    ///
+    /// An HTTP/1 connection is closed, since the rest of the request may not
+    /// have been read. Later versions frame each request separately and
+    /// forbid the `connection` header.
+    pub fn response(self, version: Version) -> Response<Body> {
+        let status = match self {
+            Self::BodySize => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::HeaderCount | Self::HeaderSize => StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE,
+            Self::UriLength => StatusCode::URI_TOO_LONG,
+        };
+        let mut builder = Response::builder().status(status);
+        if version < Version::HTTP_2 {
+            builder = builder.header(header::CONNECTION, "close");
+        }
+        builder.body(spin_http::body::empty()).unwrap()
+    }
+}
+
+/// The number of request headers hyper accepts on an HTTP/1 connection
+/// unless configured otherwise.
+const HYPER_DEFAULT_MAX_HEADERS: usize = 100;
+
+/// The smallest HTTP/1 read buffer hyper allows.
+const MIN_HTTP1_BUF_SIZE: usize = 8192;
+
+/// An allowance for the method, version and delimiters of a request line.
+const REQUEST_LINE_OVERHEAD: usize = 64;
+
+/// The limits every trigger allows, which a connection can enforce before
+/// its requests are routed.
+///
+/// This is the loosest of each trigger's `limits`: a limit is only set if
+/// every trigger sets it, and is then the largest of them.
+pub(crate) fn loosest(limits: impl IntoIterator<Item = RequestLimits>) -> RequestLimits {
+    fn max<T: Ord>(a: Option<T>, b: Option<T>) -> Option<T> {
+        Some(a?.max(b?))
+    }
+    limits
+        .into_iter()
+        .reduce(|a, b| RequestLimits {
+            max_body_size: max(a.max_body_size, b.max_body_size),
+            max_header_count: max(a.max_header_count, b.max_header_count),
+            max_header_size: max(a.max_header_size, b.max_header_size),
+            max_uri_length: max(a.max_uri_length, b.max_uri_length),
+        })
+        .unwrap_or_default()
+}
+
+/// The size of the HTTP/1 read buffer needed for a request head within
+/// `limits`, if they bound it.
+pub(crate) fn http1_max_buf_size(limits: &RequestLimits) -> Option<usize> {
+    let header_count = limits.max_header_count.unwrap_or(HYPER_DEFAULT_MAX_HEADERS);
+    // Each header line adds ": " and "\r\n" to its name and value.
+    let size =
+        REQUEST_LINE_OVERHEAD + limits.max_uri_length? + limits.max_header_size? + header_count * 4;
+    Some(size.max(MIN_HTTP1_BUF_SIZE))
+}
+
+/// The HTTP/2 `SETTINGS_MAX_HEADER_LIST_SIZE` for a request within
+/// `limits`, if they bound it.
+pub(crate) fn http2_max_header_list_size(limits: &RequestLimits) -> Option<u32> {
+    // Each field, including the `:method`, `:scheme`, `:authority` and
+    // `:path` pseudo-headers, counts 32 bytes on top of its name and value.
+    let size = REQUEST_LINE_OVERHEAD
+        + limits.max_uri_length?
+        + limits.max_header_size?
+        + (limits.max_header_count? + 4) * 32;
+    Some(size.try_into().unwrap_or(u32::MAX))
+}
+
+/// Checks the parts of a request which are known before its body is read.
+///
+/// A body whose declared `content-length` exceeds the limit is rejected here;
+/// otherwise the body must also be limited while it is streamed with
+/// [`BodyLimit`].
+pub(crate) fn check_request(
+    limits: &RequestLimits,
+    req: &Request<Body>,
+) -> Result<(), LimitExceeded> {
+    let uri_length = req
+        .uri()
+        .path_and_query()
+        .map_or(0, |path_and_query| path_and_query.as_str().len());
+    if limits.max_uri_length.is_some_and(|max| uri_length > max) {
+        return Err(LimitExceeded::UriLength);
+    }
+
+    let headers = req.headers();
+    if limits
+        .max_header_count
+        .is_some_and(|max| headers.len() > max)
+    {
+        return Err(LimitExceeded::HeaderCount);
+    }
+    if let Some(max) = limits.max_header_size {
+        let size: usize = headers
+            .iter()
+            .map(|(name, value)| name.as_str().len() + value.len())
+            .sum();
+        if size > max {
+            return Err(LimitExceeded::HeaderSize);
+        }
+    }
+
+    if let Some(max) = limits.max_body_size {
+        let content_length = headers
+            .get(header::CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok());
+        if content_length.is_some_and(|length| length > max) {
+            return Err(LimitExceeded::BodySize);
+        }
+    }
+    Ok(())
+}
+
+/// Limits the size of a request body as it is streamed.
+pub(crate) struct BodyLimit {
+    max_size: u64,
+    exceeded: Arc<AtomicBool>,
+}
+
+impl BodyLimit {
+    pub fn new(max_size: u64) -> Self {
+        Self {
+            max_size,
+            exceeded: Default::default(),
+        }
+    }
+
+    /// Wraps `body` so that reading more than the maximum size fails.
+    pub fn apply(&self, body: Body) -> Body {
+        let max_size = self.max_size;
+        let exceeded = self.exceeded.clone();
+        let mut received = 0u64;
+        let frames = BodyStream::new(body).map(move |frame| {
+            let frame = frame?;
+            if let Some(data) = frame.data_ref() {
+                received += data.len() as u64;
+                if received > max_size {
+                    exceeded.store(true, Ordering::Relaxed);
+                    return Err(ErrorCode::HttpRequestBodySize(Some(received)));
+                }
+            }
+            Ok(frame)
+        });
+        StreamBody::new(frames).boxed_unsync()
+    }
+
+    /// Whether anything read the body beyond the maximum size.
+    pub fn exceeded(&self) -> bool {
+        self.exceeded.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(uri: &str, headers: &[(&str, &str)]) -> Request<Body> {
+        let mut builder = Request::post(uri);
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        builder.body(spin_http::body::empty()).unwrap()
+    }
+
+    #[test]
+    fn requests_within_limits_are_accepted() {
+        let limits = RequestLimits {
+            max_body_size: Some(10),
+            max_header_count: Some(2),
+            max_header_size: Some(32),
+            max_uri_length: Some(16),
+        };
+        let req = request("/hello?x=1", &[("content-length", "10"), ("accept", "*/*")]);
+        assert_eq!(check_request(&limits, &req), Ok(()));
+        assert_eq!(check_request(&RequestLimits::default(), &req), Ok(()));
+    }
+
+    #[test]
+    fn requests_exceeding_limits_are_rejected() {
+        let check = |limits, req| check_request(&limits, &req).unwrap_err();
+
+        let limits = RequestLimits {
+            max_uri_length: Some(8),
+            ..Default::default()
+        };
+        assert_eq!(
+            check(limits, request("/hello?x=1", &[])),
+            LimitExceeded::UriLength
+        );
+
+        let limits = RequestLimits {
+            max_header_count: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(
+            check(limits, request("/", &[("a", "1"), ("b", "2")])),
+            LimitExceeded::HeaderCount
+        );
+
+        let limits = RequestLimits {
+            max_header_size: Some(8),
+            ..Default::default()
+        };
+        assert_eq!(
+            check(limits, request("/", &[("accept", "text/plain")])),
+            LimitExceeded::HeaderSize
+        );
+
+        let limits = RequestLimits {
+            max_body_size: Some(8),
+            ..Default::default()
+        };
+        assert_eq!(
+            check(limits, request("/", &[("content-length", "9")])),
+            LimitExceeded::BodySize
+        );
+    }
+
+    #[test]
+    fn only_http1_rejections_close_the_connection() {
+        let res = LimitExceeded::BodySize.response(Version::HTTP_11);
+        assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(res.headers()[header::CONNECTION], "close");
+
+        let res = LimitExceeded::HeaderCount.response(Version::HTTP_2);
+        assert_eq!(res.status(), StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE);
+        assert!(!res.headers().contains_key(header::CONNECTION));
+    }
+
+    #[test]
+    fn connection_limits_allow_every_trigger() {
+        let limits = |max_header_count, max_uri_length| RequestLimits {
+            max_header_count,
+            max_header_size: Some(16 * 1024),
+            max_uri_length,
+            ..Default::default()
+        };
+
+        let connection = loosest([limits(Some(10), Some(100)), limits(Some(20), None)]);
+        assert_eq!(connection.max_header_count, Some(20));
+        assert_eq!(connection.max_header_size, Some(16 * 1024));
+        assert_eq!(connection.max_uri_length, None);
+        assert_eq!(connection.max_body_size, None);
+        assert_eq!(http1_max_buf_size(&connection), None);
+        assert_eq!(http2_max_header_list_size(&connection), None);
+
+        let connection = loosest([limits(Some(20), Some(1000))]);
+        assert_eq!(
+            http1_max_buf_size(&connection),
+            Some(64 + 1000 + 16 * 1024 + 20 * 4)
+        );
+        assert_eq!(
+            http2_max_header_list_size(&connection),
+            Some(64 + 1000 + 16 * 1024 + 24 * 32)
+        );
+
+        // HTTP/1 headers are limited to hyper's default count regardless.
+        let connection = limits(None, Some(10));
+        assert_eq!(
+            http1_max_buf_size(&connection),
+            Some(64 + 10 + 16 * 1024 + 100 * 4)
+        );
+        assert_eq!(http2_max_header_list_size(&connection), None);
+        assert_eq!(loosest([]), RequestLimits::default());
+    }
+
+    #[tokio::test]
+    async fn streamed_body_is_limited() {
+        let limit = BodyLimit::new(4);
+        let body = limit.apply(spin_http::body::full("hello".into()));
+        let err = body.collect().await.unwrap_err();
+        assert!(matches!(err, ErrorCode::HttpRequestBodySize(Some(5))));
+        assert!(limit.exceeded());
+
+        let limit = BodyLimit::new(5);
+        let body = limit.apply(spin_http::body::full("hello".into()));
+        assert_eq!(body.collect().await.unwrap().to_bytes(), "hello");
+        assert!(!limit.exceeded());
+    }
+}
@@ -0,0 +1,321 @@
+//! The addresses the HTTP trigger listens on.
+
+use std::{
+    fmt::Display,
+    io::ErrorKind,
+    net::{Ipv4Addr, SocketAddr, ToSocketAddrs},
+    path::PathBuf,
+    str::FromStr,
+};
+
+use anyhow::Context;
+use tokio::net::TcpListener;
+
+use crate::TlsConfig;
+
+/// The number of successive ports to try when searching for a free port.
+pub const MAX_RETRIES: u16 = 10;
+
+/// An address the HTTP trigger can listen on.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ListenAddr {
+    /// A TCP socket address.
+    Tcp(SocketAddr),
+    /// The path of a Unix domain socket.
+    Unix(PathBuf),
+}
+
+impl Display for ListenAddr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Tcp(addr) => write!(f, "{addr}"),
+            Self::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+/// A listener for the HTTP trigger.
+#[derive(Clone, Debug)]
+pub struct ListenerConfig {
+    /// The address to listen on.
+    ///
+    /// Note that this might not be the actual socket address that ends up being bound to.
+    /// If the port is set to 0, the actual address will be determined by the OS.
+    pub addr: ListenAddr,
+    /// The TLS configuration for connections to this listener, if they use TLS.
+    pub tls_config: Option<TlsConfig>,
+}
+
+impl ListenerConfig {
+    /// The URL scheme of requests made to this listener.
+    pub(crate) fn scheme(&self) -> http::uri::Scheme {
+        if self.tls_config.is_some() {
+            http::uri::Scheme::HTTPS
+        } else {
+            http::uri::Scheme::HTTP
+        }
+    }
+}
+
+/// A `--listen` value: an address and, optionally, whether to use TLS on it.
+///
+/// A plain `host:port` address uses TLS if a certificate is configured. An
+/// `http://` or `https://` prefix chooses explicitly, and `unix:/path`
+/// listens on a Unix domain socket without TLS.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ListenSpec {
+    pub addr: ListenAddr,
+    pub tls: Option<bool>,
+}
+
+impl ListenSpec {
+    /// Resolves the TLS setting of this listener given the configured
+    /// certificate, if any.
+    pub(crate) fn into_config(
+        self,
+        tls_config: Option<&TlsConfig>,
+    ) -> anyhow::Result<ListenerConfig> {
+        let tls_config = match (&self.addr, self.tls) {
+            (ListenAddr::Unix(_), _) | (_, Some(false)) => None,
+            (ListenAddr::Tcp(_), Some(true)) => Some(tls_config.cloned().with_context(|| {
+                format!(
+                    "listener https://{} requires --tls-cert and --tls-key",
+                    self.addr
+                )
+            })?),
+            (ListenAddr::Tcp(_), None) => tls_config.cloned(),
+        };
+        Ok(ListenerConfig {
+            addr: self.addr,
+            tls_config,
+        })
+    }
+}
+
+impl FromStr for ListenSpec {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(path) = s.strip_prefix("unix:") {
+            anyhow::ensure!(!path.is_empty(), "Unix socket path must not be empty");
+            return Ok(Self {
+                addr: ListenAddr::Unix(path.into()),
+                tls: Some(false),
+            });
+        }
+        let (addr, tls) = if let Some(addr) = s.strip_prefix("http://") {
+            (addr, Some(false))
+        } else if let Some(addr) = s.strip_prefix("https://") {
+            (addr, Some(true))
+        } else {
+            (s, None)
+        };
+        Ok(Self {
+            addr: ListenAddr::Tcp(parse_listen_addr(addr.trim_end_matches('/'))?),
+            tls,
+        })
+    }
+}
+
+pub(crate) fn parse_listen_addr(addr: &str) -> anyhow::Result<SocketAddr> {
+    let addrs: Vec<SocketAddr> = addr.to_socket_addrs()?.collect();
+    // Prefer 127.0.0.1 over e.g. [::1] because CHANGE IS HARD
+    if let Some(addr) = addrs
+        .iter()
+        .find(|addr| addr.is_ipv4() && addr.ip() == Ipv4Addr::LOCALHOST)
+    {
+        return Ok(*addr);
+    }
+    // Otherwise, take the first addr (OS preference)
+    addrs.into_iter().next().context("couldn't resolve address")
+}
+
+/// A socket bound to a [`ListenAddr`].
+pub(crate) enum BoundListener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(tokio::net::UnixListener),
+}
+
+impl BoundListener {
+    /// Binds to `addr`, searching successive ports for a free one if
+    /// `find_free_port` is set.
+    pub async fn bind(addr: &ListenAddr, find_free_port: bool) -> anyhow::Result<Self> {
+        match addr {
+            ListenAddr::Tcp(addr) if find_free_port => {
+                search_for_free_port(*addr).await.map(Self::Tcp)
+            }
+            ListenAddr::Tcp(addr) => TcpListener::bind(addr).await.map(Self::Tcp).map_err(|err| {
+                if err.kind() == ErrorKind::AddrInUse {
+                    anyhow::anyhow!("{addr} is already in use. To have Spin search for a free port, use the --find-free-port option.")
+                } else {
+                    anyhow::anyhow!("Unable to listen on {addr}: {err:?}")
+                }
+            }),
+            #[cfg(unix)]
+            ListenAddr::Unix(path) => {
+                remove_stale_socket(path)?;
+                tokio::net::UnixListener::bind(path)
+                    .map(Self::Unix)
+                    .with_context(|| format!("Unable to listen on unix:{}", path.display()))
+            }
+            #[cfg(not(unix))]
+            ListenAddr::Unix(_) => {
+                anyhow::bail!("Unix domain sockets are not supported on this platform")
+            }
+        }
+    }
+
+    /// The address the socket is actually bound to.
+    pub fn local_addr(&self) -> anyhow::Result<ListenAddr> {
+        match self {
+            Self::Tcp(listener) => Ok(ListenAddr::Tcp(listener.local_addr()?)),
+            #[cfg(unix)]
+            Self::Unix(listener) => {
+                let addr = listener.local_addr()?;
+                let path = addr.as_pathname().context("Unix socket has no path")?;
+                Ok(ListenAddr::Unix(path.to_owned()))
+            }
+        }
+    }
+}
+
+async fn search_for_free_port(listen_addr: SocketAddr) -> anyhow::Result<TcpListener> {
+    let mut addr = listen_addr;
+
+    for _ in 1..=MAX_RETRIES {
+        if addr.port() == u16::MAX {
+            anyhow::bail!(
+                "Couldn't find a free port as we've reached the maximum port number. Consider retrying with a lower base port."
+            );
+        }
+
+        match TcpListener::bind(addr).await {
+            Ok(listener) => return Ok(listener),
+            Err(err) if err.kind() == ErrorKind::AddrInUse => {
+                addr.set_port(addr.port() + 1);
+                continue;
+            }
+            Err(err) => anyhow::bail!("Unable to listen on {addr}: {err:?}",),
+        }
+    }
+
+    anyhow::bail!(
+        "Couldn't find a free port in the range {}-{}. Consider retrying with a different base port.",
+        listen_addr.port(),
+        listen_addr.port() + MAX_RETRIES
+    )
+}
+
+/// Removes a socket file left behind by a previous run, so that the path can
+/// be bound again.
+///
+/// A socket is only stale if nothing is listening on it, so a socket which
+/// accepts a connection is left alone.
+#[cfg(unix)]
+fn remove_stale_socket(path: &std::path::Path) -> anyhow::Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => {
+            match std::os::unix::net::UnixStream::connect(path) {
+                Ok(_) => anyhow::bail!("{} is in use by another process", path.display()),
+                Err(err) if err.kind() == ErrorKind::ConnectionRefused => {
+                    std::fs::remove_file(path).with_context(|| {
+                        format!("failed to remove stale socket {}", path.display())
+                    })
+                }
+                Err(err) if err.kind() == ErrorKind::NotFound => Ok(()),
+                Err(err) => Err(err).with_context(|| {
+                    format!("failed to check whether {} is in use", path.display())
+                }),
+            }
+        }
+        Ok(_) => anyhow::bail!("{} exists and is not a socket", path.display()),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(err).with_context(|| format!("failed to inspect {}", path.display())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_listen_addr_prefers_ipv4() {
+        let addr = parse_listen_addr("localhost:12345").unwrap();
+        assert_eq!(addr.ip(), Ipv4Addr::LOCALHOST);
+        assert_eq!(addr.port(), 12345);
+    }
+
+    #[test]
+    fn parse_listen_spec() {
+        let tcp = |s: &str| ListenAddr::Tcp(s.parse().unwrap());
+
+        let spec: ListenSpec = "127.0.0.1:3000".parse().unwrap();
+        assert_eq!(spec.addr, tcp("127.0.0.1:3000"));
+        assert_eq!(spec.tls, None);
+
+        let spec: ListenSpec = "http://0.0.0.0:8080".parse().unwrap();
+        assert_eq!(spec.addr, tcp("0.0.0.0:8080"));
+        assert_eq!(spec.tls, Some(false));
+
+        let spec: ListenSpec = "https://[::1]:8443".parse().unwrap();
+        assert_eq!(spec.addr, tcp("[::1]:8443"));
+        assert_eq!(spec.tls, Some(true));
+
+        let spec: ListenSpec = "unix:/run/spin.sock".parse().unwrap();
+        assert_eq!(spec.addr, ListenAddr::Unix("/run/spin.sock".into()));
+        assert_eq!(spec.tls, Some(false));
+
+        "unix:".parse::<ListenSpec>().unwrap_err();
+    }
+
+    #[test]
+    fn listen_spec_tls_resolution() {
+        let tls = TlsConfig {
+            cert_path: "cert.pem".into(),
+            key_path: "key.pem".into(),
+            sni_certs: vec![],
+            client_auth: None,
+        };
+        let config = |s: &str, tls| s.parse::<ListenSpec>().unwrap().into_config(tls);
+
+        assert!(
+            config("127.0.0.1:3000", Some(&tls))
+                .unwrap()
+                .tls_config
+                .is_some()
+        );
+        assert!(config("127.0.0.1:3000", None).unwrap().tls_config.is_none());
+        assert!(
+            config("http://127.0.0.1:3000", Some(&tls))
+                .unwrap()
+                .tls_config
+                .is_none()
+        );
+        assert!(
+            config("unix:/tmp/spin.sock", Some(&tls))
+                .unwrap()
+                .tls_config
+                .is_none()
+        );
+        config("https://127.0.0.1:3000", None).unwrap_err();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn only_stale_sockets_are_removed() {
+        let path = std::env::temp_dir().join(format!("spin-listener-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let listener = std::os::unix::net::UnixListener::bind(&path).unwrap();
+        remove_stale_socket(&path).unwrap_err();
+        assert!(path.exists());
+
+        drop(listener);
+        remove_stale_socket(&path).unwrap();
+        assert!(!path.exists());
+        remove_stale_socket(&path).unwrap();
+    }
+}
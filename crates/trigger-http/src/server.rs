@@ -1,6 +1,6 @@
 use std::{
     collections::HashMap,
-    io::{ErrorKind, IsTerminal},
+    io::IsTerminal,
     marker::PhantomData,
     net::SocketAddr,
     pin::Pin,
@@ -11,7 +11,7 @@ use std::{
 
 use anyhow::{Context as _, bail};
 use http::{
-    Request, Response, StatusCode, Uri,
+    Method, Request, Response, StatusCode, Uri,
     uri::{Authority, Scheme},
 };
 use http_body_util::BodyExt;
@@ -32,15 +32,17 @@ use spin_factors_executor::InstanceState;
 use spin_http::{
     app_info::AppInfo,
     body,
-    config::{HttpExecutorType, HttpTriggerConfig},
+    config::{HttpExecutorType, HttpTriggerConfig, RequestLimits},
     routes::{RouteInfo, RouteMatch, Router},
     trigger::HandlerType,
 };
+use spin_trigger::ShutdownSignal;
 use tokio::{
     io::{AsyncRead, AsyncWrite},
     net::TcpListener,
     task,
 };
+use tokio_util::task::{TaskTracker, task_tracker::TaskTrackerToken};
 use tracing::Instrument;
 use wasmtime::{Store, StoreContextMut, ToWasmtimeResult, component::GuestTaskId};
 use wasmtime_wasi::p2::bindings::CommandIndices;
@@ -52,18 +54,34 @@ use wasmtime_wasi_http::p2::body::HyperOutgoingBody;
 use wasmtime_wasi_http::p3::bindings::Service;
 
 use crate::{
-    Body, InstanceReuseConfig, NotFoundRouteKind, OutputFormat, TlsConfig, TriggerApp,
-    TriggerInstanceBuilder,
+    Body, HttpVersions, InstanceReuseConfig, ListenAddr, ListenerConfig, NotFoundRouteKind,
+    OutputFormat, ProtocolConfig, TlsConfig, TriggerApp, TriggerInstanceBuilder,
+    compression::{Encoding, compress_response},
+    cors,
     headers::strip_forbidden_headers,
     instrument::{MatchedRoute, finalize_http_span, http_span, instrument_error},
+    limits::{self, BodyLimit, LimitExceeded},
+    listener::BoundListener,
     outbound_http::OutboundHttpInterceptor,
     spin::SpinHttpExecutor,
+    static_files::StaticFileServer,
+    throttle::Throttle,
+    tls::ClientCert,
     wagi::WagiHttpExecutor,
     wasi::WasiHttpExecutor,
     wasip3::Wasip3HttpExecutor,
 };
 
-pub const MAX_RETRIES: u16 = 10;
+#[cfg(feature = "http3")]
+mod http3;
+
+/// The client address reported for connections to Unix domain sockets.
+#[cfg(unix)]
+const UNIX_CLIENT_ADDR: SocketAddr =
+    SocketAddr::new(std::net::IpAddr::V4(std::net::Ipv4Addr::LOCALHOST), 0);
+
+/// How often to report progress while draining the server.
+const DRAIN_REPORT_INTERVAL: Duration = Duration::from_secs(5);
 
 pub(crate) fn set_request_deadline<T>(
     store: &mut spin_core::Store<T>,
@@ -76,18 +94,17 @@ pub(crate) fn set_request_deadline<T>(
 
 /// An HTTP server which runs Spin apps.
 pub struct HttpServer<F: RuntimeFactors> {
-    /// The address the server was configured to listen on (the `--listen` value).
-    listen_addr: SocketAddr,
-    /// The address the server is actually bound to, captured once after binding.
+    /// The listeners the server was configured with (the `--listen` values).
+    listeners: Vec<ListenerConfig>,
+    /// The addresses the listeners are actually bound to, in the same order,
+    /// captured once after binding.
     ///
-    /// This can differ from `listen_addr` when the OS assigns the port — e.g.
+    /// These can differ from the configured addresses when the OS assigns the port — e.g.
     /// `--listen 127.0.0.1:0` or `--find-free-port`. Self-request origins must use
-    /// this real address rather than the configured one.
-    local_addr: OnceLock<SocketAddr>,
-    /// The TLS configuration for the server.
-    tls_config: Option<TlsConfig>,
-    /// The maximum buffer size for an HTTP1 connection.
-    http1_max_buf_size: Option<usize>,
+    /// these real addresses rather than the configured ones.
+    local_addrs: OnceLock<Vec<ListenAddr>>,
+    /// The protocols the server speaks and their settings.
+    protocol_config: ProtocolConfig,
     /// Whether to find a free port if the specified port is already in use.
     find_free_port: bool,
     /// The output format for the server's startup information.
@@ -102,16 +119,25 @@ pub struct HttpServer<F: RuntimeFactors> {
     component_trigger_configs: HashMap<spin_http::routes::TriggerLookupKey, HttpTriggerConfig>,
     // Component ID -> handler type
     component_handler_types: HashMap<String, HandlerType<HttpHandlerState<F>>>,
+    // Trigger ID -> static file server
+    static_file_servers: HashMap<spin_http::routes::TriggerLookupKey, StaticFileServer>,
+    // Trigger ID -> rate limit and concurrency cap
+    throttles: HashMap<spin_http::routes::TriggerLookupKey, Throttle>,
+    /// The request limits every trigger allows, enforced by the connection
+    /// while it reads the request head.
+    connection_limits: RequestLimits,
+    /// Connections being served and component instances kept for reuse, which
+    /// must finish before the server has drained.
+    in_flight: TaskTracker,
 }
 
 impl<F: RuntimeFactors> HttpServer<F> {
     /// Create a new [`HttpServer`].
     pub fn new(
-        listen_addr: SocketAddr,
-        tls_config: Option<TlsConfig>,
+        listeners: Vec<ListenerConfig>,
         find_free_port: bool,
         trigger_app: TriggerApp<F>,
-        http1_max_buf_size: Option<usize>,
+        protocol_config: ProtocolConfig,
         reuse_config: InstanceReuseConfig,
         output_format: OutputFormat,
     ) -> anyhow::Result<Self> {
@@ -173,16 +199,60 @@ impl<F: RuntimeFactors> HttpServer<F> {
                 spin_http::routes::TriggerLookupKey::Trigger(_) => None,
             })
             .collect::<anyhow::Result<_>>()?;
+
+        let static_file_servers = component_trigger_configs
+            .iter()
+            .filter_map(|(key, trigger_config)| {
+                let static_files = trigger_config.static_files.as_ref()?;
+                Some(
+                    StaticFileServer::new(trigger_app.app(), static_files)
+                        .with_context(|| format!("failed to configure static files for {key}"))
+                        .map(|server| (key.clone(), server)),
+                )
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        for (key, trigger_config) in &component_trigger_configs {
+            if let Some(cors) = &trigger_config.cors {
+                cors.validate()
+                    .with_context(|| format!("invalid CORS policy for {key}"))?;
+            }
+        }
+
+        let app_id: Arc<str> = trigger_app
+            .app()
+            .get_metadata(APP_NAME_KEY)?
+            .unwrap_or_else(|| "<unnamed>".into())
+            .into();
+        let throttles = component_trigger_configs
+            .iter()
+            .filter_map(|(key, trigger_config)| {
+                Throttle::new(app_id.clone(), key.to_string(), trigger_config)
+                    .with_context(|| format!("failed to configure throttling for {key}"))
+                    .transpose()
+                    .map(|throttle| throttle.map(|throttle| (key.clone(), throttle)))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        let connection_limits =
+            limits::loosest(component_trigger_configs.values().map(|trigger_config| {
+                trigger_config
+                    .limits
+                    .unwrap_or_default()
+                    .or(&protocol_config.request_limits)
+            }));
         Ok(Self {
-            listen_addr,
-            local_addr: OnceLock::new(),
-            tls_config,
+            listeners,
+            local_addrs: OnceLock::new(),
             find_free_port,
             router,
             trigger_app,
-            http1_max_buf_size,
+            protocol_config,
             component_trigger_configs,
             component_handler_types,
+            static_file_servers,
+            throttles,
+            connection_limits,
+            in_flight: TaskTracker::new(),
             output_format,
             request_deadline: reuse_config.request_deadline,
         })
@@ -220,67 +290,131 @@ impl<F: RuntimeFactors> HttpServer<F> {
         Ok(handler_type)
     }
 
-    /// Serve incoming requests over the provided [`TcpListener`].
-    pub async fn serve(self: Arc<Self>) -> anyhow::Result<()> {
-        let listener: TcpListener = if self.find_free_port {
-            self.search_for_free_port().await?
-        } else {
-            TcpListener::bind(self.listen_addr).await.map_err(|err| {
-                if err.kind() == ErrorKind::AddrInUse {
-                    anyhow::anyhow!("{} is already in use. To have Spin search for a free port, use the --find-free-port option.", self.listen_addr)
-                } else {
-                    anyhow::anyhow!("Unable to listen on {}: {err:?}", self.listen_addr)
+    /// Serve incoming requests on all configured listeners until `shutdown` is
+    /// requested, then wait for the requests in progress to finish.
+    pub async fn serve(self: Arc<Self>, shutdown: ShutdownSignal) -> anyhow::Result<()> {
+        let mut bound_listeners = Vec::with_capacity(self.listeners.len());
+        for listener in &self.listeners {
+            bound_listeners.push(BoundListener::bind(&listener.addr, self.find_free_port).await?);
+        }
+        let local_addrs = bound_listeners
+            .iter()
+            .map(BoundListener::local_addr)
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let _ = self.local_addrs.set(local_addrs);
+
+        self.print_startup_msgs()?;
+
+        let mut tasks = Vec::new();
+        for (config, listener) in self.listeners.iter().zip(bound_listeners) {
+            let tls_config = config.tls_config.clone();
+            match (listener, tls_config) {
+                (BoundListener::Tcp(listener), None) => {
+                    tasks.push(task::spawn(
+                        self.clone().serve_http(listener, shutdown.clone()),
+                    ));
                 }
-            })?
-        };
-
-        let _ = self.local_addr.set(listener.local_addr()?);
+                (BoundListener::Tcp(listener), Some(tls_config)) => {
+                    #[cfg(feature = "http3")]
+                    if self.protocol_config.http3 {
+                        let addr = listener.local_addr()?;
+                        tasks.push(task::spawn(self.clone().serve_http3(
+                            addr,
+                            tls_config.clone(),
+                            shutdown.clone(),
+                        )));
+                    }
+                    tasks.push(task::spawn(self.clone().serve_https(
+                        listener,
+                        tls_config,
+                        shutdown.clone(),
+                    )));
+                }
+                #[cfg(unix)]
+                (BoundListener::Unix(listener), _) => {
+                    tasks.push(task::spawn(
+                        self.clone().serve_unix(listener, shutdown.clone()),
+                    ));
+                }
+            }
+        }
 
-        if let Some(tls_config) = self.tls_config.clone() {
-            self.serve_https(listener, tls_config).await?;
-        } else {
-            self.serve_http(listener).await?;
+        // Listeners stop if one fails or shutdown is requested
+        let (res, _, rest) = futures::future::select_all(tasks).await;
+        res??;
+        for res in futures::future::join_all(rest).await {
+            res??;
         }
+
+        self.drain().await;
         Ok(())
     }
 
-    async fn search_for_free_port(&self) -> anyhow::Result<TcpListener> {
-        let mut found_listener = None;
-        let mut addr = self.listen_addr;
-
-        for _ in 1..=MAX_RETRIES {
-            if addr.port() == u16::MAX {
-                anyhow::bail!(
-                    "Couldn't find a free port as we've reached the maximum port number. Consider retrying with a lower base port."
-                );
-            }
-
-            match TcpListener::bind(addr).await {
-                Ok(listener) => {
-                    found_listener = Some(listener);
-                    break;
-                }
-                Err(err) if err.kind() == ErrorKind::AddrInUse => {
-                    addr.set_port(addr.port() + 1);
-                    continue;
+    /// Waits for the connections being served, and the component instances
+    /// kept for reuse, to finish, periodically reporting how many remain.
+    async fn drain(&self) {
+        self.in_flight.close();
+        if self.in_flight.is_empty() {
+            return;
+        }
+        let mut report = tokio::time::interval(DRAIN_REPORT_INTERVAL);
+        loop {
+            tokio::select! {
+                () = self.in_flight.wait() => break,
+                _ = report.tick() => {
+                    let remaining = self.in_flight.len();
+                    tracing::info!(remaining, "Draining HTTP server");
+                    if matches!(self.output_format, OutputFormat::Plain) {
+                        terminal::step!(
+                            "Draining",
+                            "{remaining} connection(s) or instance(s) still in progress"
+                        );
+                    }
                 }
-                Err(err) => anyhow::bail!("Unable to listen on {addr}: {err:?}",),
             }
         }
+        tracing::info!("HTTP server drained");
+    }
 
-        found_listener.ok_or_else(|| anyhow::anyhow!(
-            "Couldn't find a free port in the range {}-{}. Consider retrying with a different base port.",
-            self.listen_addr.port(),
-            self.listen_addr.port() + MAX_RETRIES
-        ))
+    async fn serve_http(
+        self: Arc<Self>,
+        listener: TcpListener,
+        shutdown: ShutdownSignal,
+    ) -> anyhow::Result<()> {
+        loop {
+            let (stream, client_addr) = tokio::select! {
+                accepted = listener.accept() => accepted?,
+                () = shutdown.requested() => return Ok(()),
+            };
+            self.clone().serve_connection(
+                stream,
+                Scheme::HTTP,
+                client_addr,
+                None,
+                shutdown.clone(),
+            );
+        }
     }
 
-    async fn serve_http(self: Arc<Self>, listener: TcpListener) -> anyhow::Result<()> {
-        self.print_startup_msgs("http", &listener)?;
+    #[cfg(unix)]
+    async fn serve_unix(
+        self: Arc<Self>,
+        listener: tokio::net::UnixListener,
+        shutdown: ShutdownSignal,
+    ) -> anyhow::Result<()> {
         loop {
-            let (stream, client_addr) = listener.accept().await?;
-            self.clone()
-                .serve_connection(stream, Scheme::HTTP, client_addr);
+            let (stream, _) = tokio::select! {
+                accepted = listener.accept() => accepted?,
+                () = shutdown.requested() => return Ok(()),
+            };
+            // Unix socket peers have no IP address.
+            self.clone().serve_connection(
+                stream,
+                Scheme::HTTP,
+                UNIX_CLIENT_ADDR,
+                None,
+                shutdown.clone(),
+            );
         }
     }
 
@@ -288,17 +422,51 @@ impl<F: RuntimeFactors> HttpServer<F> {
         self: Arc<Self>,
         listener: TcpListener,
         tls_config: TlsConfig,
+        shutdown: ShutdownSignal,
     ) -> anyhow::Result<()> {
-        self.print_startup_msgs("https", &listener)?;
-        let acceptor = tls_config.server_config()?;
+        let acceptor = tls_config.server_config(self.alpn_protocols())?;
         loop {
-            let (stream, client_addr) = listener.accept().await?;
-            match acceptor.accept(stream).await {
-                Ok(stream) => self
-                    .clone()
-                    .serve_connection(stream, Scheme::HTTPS, client_addr),
-                Err(err) => tracing::error!(?err, "Failed to start TLS session"),
-            }
+            let (stream, client_addr) = tokio::select! {
+                accepted = listener.accept() => accepted?,
+                () = shutdown.requested() => return Ok(()),
+            };
+            // Complete the handshake in a tracked task, so that a slow client
+            // neither holds up accepting other connections nor delays shutdown.
+            let server = self.clone();
+            let acceptor = acceptor.clone();
+            let shutdown = shutdown.clone();
+            self.in_flight.spawn(async move {
+                let accepted = tokio::select! {
+                    accepted = acceptor.accept(stream) => accepted,
+                    () = shutdown.requested() => return,
+                };
+                match accepted {
+                    Ok(stream) => {
+                        let client_cert = stream
+                            .get_ref()
+                            .1
+                            .peer_certificates()
+                            .and_then(ClientCert::from_chain);
+                        server.serve_connection(
+                            stream,
+                            Scheme::HTTPS,
+                            client_addr,
+                            client_cert,
+                            shutdown,
+                        )
+                    }
+                    Err(err) => tracing::error!(?err, "Failed to start TLS session"),
+                }
+            });
+        }
+    }
+
+    /// The ALPN protocols to advertise on TLS connections over TCP.
+    fn alpn_protocols(&self) -> Vec<Vec<u8>> {
+        match self.protocol_config.http_versions {
+            HttpVersions::Auto => vec![b"h2".to_vec(), b"http/1.1".to_vec()],
+            HttpVersions::Http1 => vec![b"http/1.1".to_vec()],
+            HttpVersions::Http2 => vec![b"h2".to_vec()],
         }
     }
 
@@ -357,12 +525,13 @@ impl<F: RuntimeFactors> HttpServer<F> {
             .unwrap_or_else(|| "<unnamed>".into());
 
         let lookup_key = route_match.lookup_key();
+        let component_id = lookup_key.to_string();
 
         spin_telemetry::metrics::counter!(
             spin.request_count = 1,
             trigger_type = "http",
-            app_id = app_id,
-            component_id = lookup_key.to_string()
+            app_id = app_id.clone(),
+            component_id = component_id.clone()
         );
 
         let trigger_config = self
@@ -370,32 +539,134 @@ impl<F: RuntimeFactors> HttpServer<F> {
             .get(lookup_key)
             .with_context(|| format!("unknown routing destination '{lookup_key}'"))?;
 
-        match (&trigger_config.component, &trigger_config.static_response) {
-            (Some(component), None) => {
-                self.respond_wasm_component(
-                    req,
-                    route_match,
-                    client_addr,
-                    component,
-                    &trigger_config.executor,
-                )
-                .await
+        let request_limits = trigger_config
+            .limits
+            .unwrap_or_default()
+            .or(&self.protocol_config.request_limits);
+        let version = req.version();
+        let reject = |exceeded: LimitExceeded| {
+            tracing::info!("Rejecting request exceeding {} limit", exceeded.as_str());
+            spin_telemetry::metrics::counter!(
+                spin.request_limit_exceeded = 1,
+                trigger_type = "http",
+                app_id = app_id.clone(),
+                component_id = component_id.clone(),
+                limit = exceeded.as_str()
+            );
+            exceeded.response(version)
+        };
+        if let Err(exceeded) = limits::check_request(&request_limits, &req) {
+            return Ok(reject(exceeded));
+        }
+
+        let cors = trigger_config.cors.as_ref();
+        if let Some(cors) = cors
+            && cors::is_preflight(&req)
+        {
+            return Ok(cors::preflight_response(cors, req.headers()));
+        }
+
+        let origin = req.headers().get(http::header::ORIGIN).cloned();
+
+        let permit = match self.throttles.get(lookup_key) {
+            Some(throttle) => match throttle.admit(&req, client_addr) {
+                Ok(permit) => Some(permit),
+                Err(throttled) => {
+                    spin_telemetry::metrics::counter!(
+                        spin.request_throttled = 1,
+                        trigger_type = "http",
+                        app_id = app_id.clone(),
+                        component_id = component_id.clone(),
+                        reason = throttled.as_str()
+                    );
+                    let mut res = throttled.response();
+                    if let Some(cors) = cors {
+                        cors::add_response_headers(cors, origin.as_ref(), res.headers_mut());
+                    }
+                    return Ok(res);
+                }
+            },
+            None => None,
+        };
+
+        let res = match (
+            &trigger_config.component,
+            &trigger_config.static_response,
+            &trigger_config.static_files,
+        ) {
+            (Some(component), None, None) => {
+                let encoding = (req.method() != Method::HEAD)
+                    .then(|| Encoding::negotiate(req.headers()))
+                    .flatten();
+                let body_limit = request_limits.max_body_size.map(BodyLimit::new);
+                if let Some(body_limit) = &body_limit {
+                    req = req.map(|body| body_limit.apply(body));
+                }
+                let res = self
+                    .respond_wasm_component(
+                        req,
+                        route_match,
+                        client_addr,
+                        component,
+                        &trigger_config.executor,
+                    )
+                    .await;
+                // The component may have failed, or responded with an error,
+                // because it could not read the whole body.
+                if body_limit.is_some_and(|body_limit| body_limit.exceeded()) {
+                    return Ok(reject(LimitExceeded::BodySize));
+                }
+                let res = res?;
+                Ok(match &trigger_config.compression {
+                    Some(config) => compress_response(config, encoding, res),
+                    None => res,
+                })
             }
-            (None, Some(static_response)) => Self::respond_static_response(static_response),
+            (None, Some(static_response), None) => Self::respond_static_response(static_response),
+            (None, None, Some(_)) => self.respond_static_files(req, route_match).await,
             // These error cases should have been ruled out by this point but belt and braces
-            (None, None) => Err(anyhow::anyhow!(
-                "Triggers must specify either component or static_response - neither is specified for {}",
+            (None, None, None) => Err(anyhow::anyhow!(
+                "Triggers must specify one of component, static_response or static_files - none is specified for {}",
                 route_match.raw_route()
             )),
-            (Some(_), Some(_)) => Err(anyhow::anyhow!(
-                "Triggers must specify either component or static_response - both are specified for {}",
+            _ => Err(anyhow::anyhow!(
+                "Triggers must specify only one of component, static_response or static_files - more than one is specified for {}",
                 route_match.raw_route()
             )),
+        };
+
+        let mut res = res?;
+        if let Some(cors) = cors {
+            cors::add_response_headers(cors, origin.as_ref(), res.headers_mut());
+        }
+        if let Some(permit) = permit {
+            res = res.map(|body| permit.hold_with(body));
         }
+        Ok(res)
     }
 
-    fn get_local_addr(&self) -> SocketAddr {
-        self.local_addr.get().copied().unwrap_or(self.listen_addr)
+    /// The listeners with the addresses they are bound to, or the configured
+    /// addresses if the server has not started serving.
+    fn bound_listeners(&self) -> impl Iterator<Item = (&ListenerConfig, &ListenAddr)> {
+        let local_addrs = self.local_addrs.get();
+        self.listeners.iter().enumerate().map(move |(i, listener)| {
+            let addr = local_addrs.and_then(|addrs| addrs.get(i));
+            (listener, addr.unwrap_or(&listener.addr))
+        })
+    }
+
+    /// The TCP listener to send self-requests to, preferring one that uses `scheme`.
+    fn self_request_addr(&self, scheme: &Scheme) -> Option<(Scheme, SocketAddr)> {
+        let tcp_listeners = || {
+            self.bound_listeners()
+                .filter_map(|(listener, addr)| match addr {
+                    ListenAddr::Tcp(addr) => Some((listener.scheme(), *addr)),
+                    ListenAddr::Unix(_) => None,
+                })
+        };
+        tcp_listeners()
+            .find(|(listener_scheme, _)| listener_scheme == scheme)
+            .or_else(|| tcp_listeners().next())
     }
 
     async fn respond_wasm_component(
@@ -480,9 +751,11 @@ impl<F: RuntimeFactors> HttpServer<F> {
         )?;
 
         let self_scheme = self_scheme.cloned().unwrap_or(Scheme::HTTPS);
-        let self_addr = self.get_local_addr();
-        let origin = SelfRequestOrigin::create(self_scheme, &self_addr.to_string())?;
-        outbound_http.set_self_request_origin(origin);
+        // Self-requests are not possible if the server only listens on Unix sockets.
+        if let Some((self_scheme, self_addr)) = self.self_request_addr(&self_scheme) {
+            let origin = SelfRequestOrigin::create(self_scheme, &self_addr.to_string())?;
+            outbound_http.set_self_request_origin(origin);
+        }
         outbound_http.set_request_interceptor(OutboundHttpInterceptor::new(self.clone()))?;
         Ok(instance_builder)
     }
@@ -505,6 +778,25 @@ impl<F: RuntimeFactors> HttpServer<F> {
         Ok(response.body(body)?)
     }
 
+    async fn respond_static_files(
+        &self,
+        req: Request<Body>,
+        route_match: RouteMatch<'_, '_>,
+    ) -> anyhow::Result<Response<Body>> {
+        let lookup_key = route_match.lookup_key();
+        let server = self
+            .static_file_servers
+            .get(lookup_key)
+            .with_context(|| format!("no static files configured for '{lookup_key}'"))?;
+        let res = server
+            .respond(req, &route_match.trailing_wildcard())
+            .await?;
+        Ok(MatchedRoute::with_response_extension(
+            res,
+            route_match.raw_route(),
+        ))
+    }
+
     /// Returns spin status information.
     fn app_info(&self, route: String) -> anyhow::Result<Response<Body>> {
         let info = AppInfo::new(self.trigger_app.app());
@@ -557,27 +849,63 @@ impl<F: RuntimeFactors> HttpServer<F> {
         stream: S,
         server_scheme: Scheme,
         client_addr: SocketAddr,
+        client_cert: Option<ClientCert>,
+        shutdown: ShutdownSignal,
     ) {
-        task::spawn(async move {
+        let in_flight = self.in_flight.clone();
+        in_flight.spawn(async move {
             let mut server_builder = Builder::new(TokioExecutor::new());
 
-            if let Some(http1_max_buf_size) = self.http1_max_buf_size {
+            let http1_max_buf_size = self
+                .protocol_config
+                .http1_max_buf_size
+                .or_else(|| limits::http1_max_buf_size(&self.connection_limits));
+            if let Some(http1_max_buf_size) = http1_max_buf_size {
                 server_builder.http1().max_buf_size(http1_max_buf_size);
             }
-
-            if let Err(err) = server_builder
-                .serve_connection(
-                    TokioIo::new(stream),
-                    service_fn(move |request| {
-                        self.clone().instrumented_service_fn(
-                            server_scheme.clone(),
-                            client_addr,
-                            request,
-                        )
-                    }),
-                )
-                .await
+            if let Some(max_header_count) = self.connection_limits.max_header_count {
+                server_builder.http1().max_headers(max_header_count);
+            }
+            if let Some(max_header_list_size) =
+                limits::http2_max_header_list_size(&self.connection_limits)
             {
+                server_builder
+                    .http2()
+                    .max_header_list_size(max_header_list_size);
+            }
+            match self.protocol_config.http_versions {
+                HttpVersions::Auto => {}
+                HttpVersions::Http1 => server_builder = server_builder.http1_only(),
+                HttpVersions::Http2 => server_builder = server_builder.http2_only(),
+            }
+
+            let connection = server_builder.serve_connection(
+                TokioIo::new(stream),
+                service_fn(move |mut request: Request<Incoming>| {
+                    if let Some(client_cert) = &client_cert {
+                        request.extensions_mut().insert(client_cert.clone());
+                    }
+                    self.clone().instrumented_service_fn(
+                        server_scheme.clone(),
+                        client_addr,
+                        request.map(|body| {
+                            body.map_err(wasmtime_wasi_http::p2::hyper_response_error)
+                                .boxed_unsync()
+                        }),
+                    )
+                }),
+            );
+            let mut connection = std::pin::pin!(connection);
+            let result = tokio::select! {
+                result = connection.as_mut() => result,
+                () = shutdown.requested() => {
+                    // Finish the requests in progress, closing the connection
+                    // rather than keeping it alive for more.
+                    connection.as_mut().graceful_shutdown();
+                    connection.await
+                }
+            };
+            if let Err(err) = result {
                 tracing::warn!("Error serving HTTP connection: {err:?}");
             }
         });
@@ -587,27 +915,43 @@ impl<F: RuntimeFactors> HttpServer<F> {
         self: Arc<Self>,
         server_scheme: Scheme,
         client_addr: SocketAddr,
-        request: Request<Incoming>,
+        request: Request<Body>,
     ) -> anyhow::Result<Response<HyperOutgoingBody>> {
         let span = http_span!(request, client_addr);
         let method = request.method().to_string();
+        let version = request.version();
         async {
-            let result = self
-                .handle(
-                    request.map(|body: Incoming| {
-                        body.map_err(wasmtime_wasi_http::p2::hyper_response_error)
-                            .boxed_unsync()
-                    }),
-                    server_scheme,
-                    client_addr,
-                )
-                .await;
+            let mut result = self.handle(request, server_scheme, client_addr).await;
+            if let Ok(res) = &mut result
+                && version != http::Version::HTTP_3
+            {
+                self.advertise_http3(res.headers_mut());
+            }
             finalize_http_span(result, method)
         }
         .instrument(span)
         .await
     }
 
+    /// Adds an `Alt-Svc` header advertising the HTTP/3 endpoint, if there is one.
+    fn advertise_http3(&self, headers: &mut http::HeaderMap) {
+        if !self.protocol_config.http3 || headers.contains_key(http::header::ALT_SVC) {
+            return;
+        }
+        let Some(port) = self
+            .bound_listeners()
+            .find_map(|(listener, addr)| match addr {
+                ListenAddr::Tcp(addr) if listener.tls_config.is_some() => Some(addr.port()),
+                _ => None,
+            })
+        else {
+            return;
+        };
+        if let Ok(value) = http::HeaderValue::from_str(&format!("h3=\":{port}\"; ma=86400")) {
+            headers.insert(http::header::ALT_SVC, value);
+        }
+    }
+
     fn get_description_for_route(
         &self,
         key: &spin_http::routes::TriggerLookupKey,
@@ -624,14 +968,30 @@ impl<F: RuntimeFactors> HttpServer<F> {
         }
     }
 
-    fn print_startup_msgs(&self, scheme: &str, listener: &TcpListener) -> anyhow::Result<()> {
-        let local_addr = listener.local_addr()?;
-        let base_url = format!("{scheme}://{local_addr:?}");
-        tracing::info!("Serving {base_url}");
+    fn print_startup_msgs(&self) -> anyhow::Result<()> {
+        let base_urls = self
+            .bound_listeners()
+            .map(|(listener, addr)| match addr {
+                ListenAddr::Tcp(addr) => format!("{}://{addr:?}", listener.scheme()),
+                ListenAddr::Unix(_) => addr.to_string(),
+            })
+            .collect::<Vec<_>>();
+        for base_url in &base_urls {
+            tracing::info!("Serving {base_url}");
+        }
+        // Routes are shown relative to the first TCP listener, if there is one.
+        let base_url = self
+            .bound_listeners()
+            .position(|(_, addr)| matches!(addr, ListenAddr::Tcp(_)))
+            .map(|i| base_urls[i].clone())
+            .unwrap_or_default();
 
         match self.output_format {
             OutputFormat::Plain => {
-                terminal::step!("\nServing", "{base_url}");
+                println!();
+                for base_url in &base_urls {
+                    terminal::step!("Serving", "{base_url}");
+                }
                 println!("Available Routes:");
                 for (route, key) in self.router.routes() {
                     println!("  {key}: {base_url}{route}");
@@ -644,6 +1004,7 @@ impl<F: RuntimeFactors> HttpServer<F> {
                 #[derive(serde::Serialize)]
                 struct RoutesOutput {
                     base_url: String,
+                    base_urls: Vec<String>,
                     routes: Vec<RouteEntry>,
                 }
 
@@ -665,7 +1026,11 @@ impl<F: RuntimeFactors> HttpServer<F> {
                     });
                 }
 
-                let output = RoutesOutput { base_url, routes };
+                let output = RoutesOutput {
+                    base_url,
+                    base_urls,
+                    routes,
+                };
                 println!("{}", serde_json::to_string_pretty(&output)?);
             }
         }
@@ -761,6 +1126,8 @@ pub(crate) struct HttpWorkerState<F: RuntimeFactors> {
     request_timeout: Duration,
     max_instance_reuse_count: usize,
     max_instance_concurrent_reuse_count: usize,
+    /// Keeps the server from draining while the instance may still be reused.
+    _in_flight: TaskTrackerToken,
     _phantom: PhantomData<F>,
 }
 
@@ -821,10 +1188,8 @@ impl<F: RuntimeFactors> HandlerState for HttpHandlerState<F> {
         &self,
     ) -> wasmtime::Result<Instance<Self::StoreData, Self::WorkerExpiration, Self::WorkerState>>
     {
-        let (instance, mut store) = self
-            .server
-            .get()
-            .expect("server should have been set")
+        let server = self.server.get().expect("server should have been set");
+        let (instance, mut store) = server
             .trigger_instance_builder(&self.component_id, self.self_scheme.get())
             .to_wasmtime_result()?
             .instantiate(())
@@ -862,6 +1227,7 @@ impl<F: RuntimeFactors> HandlerState for HttpHandlerState<F> {
                     .random_range(self.reuse_config.max_instance_reuse_count),
                 max_instance_concurrent_reuse_count: rand::rng()
                     .random_range(self.reuse_config.max_instance_concurrent_reuse_count),
+                _in_flight: server.in_flight.token(),
                 _phantom: PhantomData,
             },
         })
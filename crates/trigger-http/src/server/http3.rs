@@ -0,0 +1,166 @@
+//! Experimental HTTP/3 support.
+
+use std::{net::SocketAddr, sync::Arc};
+
+use anyhow::Context as _;
+use http::{Request, Response, uri::Scheme};
+use http_body_util::{BodyExt, StreamBody};
+use hyper::body::{Buf, Bytes, Frame};
+use rustls_pki_types::CertificateDer;
+use spin_factors::RuntimeFactors;
+use spin_trigger::ShutdownSignal;
+use wasmtime_wasi_http::p2::{bindings::http::types::ErrorCode, body::HyperOutgoingBody};
+
+use super::HttpServer;
+use crate::{Body, TlsConfig, tls::ClientCert};
+
+type RequestStream<S> = h3::server::RequestStream<S, Bytes>;
+
+impl<F: RuntimeFactors> HttpServer<F> {
+    /// Serves HTTP/3 over QUIC on the UDP socket with the same address as a
+    /// TLS listener.
+    pub(super) async fn serve_http3(
+        self: Arc<Self>,
+        addr: SocketAddr,
+        tls_config: TlsConfig,
+        shutdown: ShutdownSignal,
+    ) -> anyhow::Result<()> {
+        let tls = tls_config.rustls_server_config(vec![b"h3".to_vec()])?;
+        let quic_config = quinn::crypto::rustls::QuicServerConfig::try_from(tls)
+            .context("TLS configuration is not usable for QUIC")?;
+        let endpoint = quinn::Endpoint::server(
+            quinn::ServerConfig::with_crypto(Arc::new(quic_config)),
+            addr,
+        )
+        .with_context(|| format!("Unable to listen for HTTP/3 on {addr}"))?;
+        tracing::info!("Serving HTTP/3 on UDP {addr}");
+
+        loop {
+            let incoming = tokio::select! {
+                incoming = endpoint.accept() => match incoming {
+                    Some(incoming) => incoming,
+                    None => break,
+                },
+                () = shutdown.requested() => break,
+            };
+            let server = self.clone();
+            let shutdown = shutdown.clone();
+            self.in_flight.spawn(async move {
+                if let Err(err) = server.serve_http3_connection(incoming, shutdown).await {
+                    tracing::warn!("Error serving HTTP/3 connection: {err:?}");
+                }
+            });
+        }
+        Ok(())
+    }
+
+    async fn serve_http3_connection(
+        self: Arc<Self>,
+        incoming: quinn::Incoming,
+        shutdown: ShutdownSignal,
+    ) -> anyhow::Result<()> {
+        let conn = incoming.await?;
+        let client_addr = conn.remote_address();
+        let client_cert = conn
+            .peer_identity()
+            .and_then(|identity| identity.downcast::<Vec<CertificateDer<'static>>>().ok())
+            .and_then(|chain| ClientCert::from_chain(&chain));
+        let mut conn =
+            h3::server::Connection::<_, Bytes>::new(h3_quinn::Connection::new(conn)).await?;
+        let mut shutting_down = false;
+        loop {
+            let accepted = tokio::select! {
+                accepted = conn.accept() => accepted,
+                () = shutdown.requested(), if !shutting_down => {
+                    // Refuse new requests, then accept the ones the client
+                    // already sent until it closes the connection.
+                    shutting_down = true;
+                    conn.shutdown(0).await?;
+                    continue;
+                }
+            };
+            let Some(resolver) = accepted? else {
+                break;
+            };
+            let server = self.clone();
+            let client_cert = client_cert.clone();
+            self.in_flight.spawn(async move {
+                let result = match resolver.resolve_request().await {
+                    Ok((mut req, stream)) => {
+                        if let Some(client_cert) = client_cert {
+                            req.extensions_mut().insert(client_cert);
+                        }
+                        server.serve_http3_request(req, stream, client_addr).await
+                    }
+                    Err(err) => Err(err.into()),
+                };
+                if let Err(err) = result {
+                    tracing::warn!("Error serving HTTP/3 request: {err:?}");
+                }
+            });
+        }
+        Ok(())
+    }
+
+    async fn serve_http3_request(
+        self: Arc<Self>,
+        req: Request<()>,
+        stream: RequestStream<h3_quinn::BidiStream<Bytes>>,
+        client_addr: SocketAddr,
+    ) -> anyhow::Result<()> {
+        let (mut send, recv) = stream.split();
+        let req = req.map(|()| request_body(recv));
+
+        let res = self
+            .instrumented_service_fn(Scheme::HTTPS, client_addr, req)
+            .await?;
+        let (parts, body) = res.into_parts();
+        send.send_response(Response::from_parts(parts, ())).await?;
+        send_body(&mut send, body).await?;
+        send.finish().await?;
+        Ok(())
+    }
+}
+
+/// Adapts the receiving half of an HTTP/3 request stream into a request body.
+fn request_body(recv: RequestStream<h3_quinn::RecvStream>) -> Body {
+    let protocol_error = |err: h3::error::StreamError| {
+        tracing::debug!("Error receiving HTTP/3 request body: {err}");
+        ErrorCode::HttpProtocolError
+    };
+    let frames = futures::stream::unfold(Some(recv), move |recv| async move {
+        let mut recv = recv?;
+        match recv.recv_data().await {
+            Ok(Some(mut data)) => {
+                let data = data.copy_to_bytes(data.remaining());
+                Some((Ok(Frame::data(data)), Some(recv)))
+            }
+            Ok(None) => match recv.recv_trailers().await {
+                Ok(Some(trailers)) => Some((Ok(Frame::trailers(trailers)), None)),
+                Ok(None) => None,
+                Err(err) => Some((Err(protocol_error(err)), None)),
+            },
+            Err(err) => Some((Err(protocol_error(err)), None)),
+        }
+    });
+    StreamBody::new(frames).boxed_unsync()
+}
+
+/// Sends a response body on the sending half of an HTTP/3 request stream.
+async fn send_body(
+    send: &mut RequestStream<h3_quinn::SendStream<Bytes>>,
+    mut body: HyperOutgoingBody,
+) -> anyhow::Result<()> {
+    while let Some(frame) = body.frame().await {
+        let frame = frame.map_err(|err| anyhow::anyhow!("{err:?}"))?;
+        match frame.into_data() {
+            Ok(data) => send.send_data(data).await?,
+            Err(frame) => {
+                if let Ok(trailers) = frame.into_trailers() {
+                    send.send_trailers(trailers).await?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,553 @@
+//! Serving of static files by the host for `static_files` triggers.
+
+use std::{
+    fs::Metadata,
+    io::{ErrorKind, SeekFrom},
+    path::{Component, Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, ensure};
+use futures::StreamExt;
+use http::{HeaderMap, HeaderValue, Method, Request, Response, StatusCode, header};
+use http_body_util::{BodyExt, StreamBody};
+use hyper::body::Frame;
+use percent_encoding::percent_decode_str;
+use spin_app::App;
+use spin_common::{ui::quoted_path, url::parse_file_url};
+use spin_http::{body, config::StaticFiles};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::ReaderStream;
+use wasmtime_wasi_http::p2::bindings::http::types::ErrorCode;
+
+use crate::{Body, headers::encoding_quality};
+
+/// Precompressed variants that may be stored next to a file, in order of
+/// preference, as (content coding, file extension) pairs.
+const PRECOMPRESSED_VARIANTS: [(&str, &str); 2] = [("br", "br"), ("gzip", "gz")];
+
+/// Serves files from a directory of a component's `files` mounts.
+pub(crate) struct StaticFileServer {
+    /// The canonical host path of the directory being served.
+    root: PathBuf,
+    /// File names to look for in directories.
+    index: Vec<String>,
+    /// The file to serve when no file matches, relative to `root`.
+    fallback: Option<PathBuf>,
+}
+
+impl StaticFileServer {
+    /// Locates the directory that `config` refers to in the app.
+    pub fn new(app: &App, config: &StaticFiles) -> anyhow::Result<Self> {
+        let component = app
+            .get_component(&config.component)
+            .with_context(|| format!("unknown component {:?}", config.component))?;
+
+        let guest_root = Path::new(&config.root);
+        let mount = component
+            .files()
+            .filter(|mount| guest_root.starts_with(&mount.path))
+            .max_by_key(|mount| mount.path.components().count())
+            .with_context(|| {
+                format!(
+                    "component {:?} has no files mounted at {:?}",
+                    config.component, config.root
+                )
+            })?;
+        let source = mount
+            .content
+            .source
+            .as_deref()
+            .with_context(|| format!("Missing 'source' on files mount {mount:?}"))?;
+        let relative_root = guest_root
+            .strip_prefix(&mount.path)
+            .expect("mount path should be a prefix of the root");
+        let root = parse_file_url(source)?.join(relative_root);
+        let root = root
+            .canonicalize()
+            .with_context(|| format!("couldn't resolve {}", quoted_path(&root)))?;
+        ensure!(root.is_dir(), "{} is not a directory", quoted_path(&root));
+
+        let fallback = config
+            .fallback
+            .as_deref()
+            .map(|fallback| {
+                relative_file_path(fallback)
+                    .with_context(|| format!("invalid static files fallback {fallback:?}"))
+            })
+            .transpose()?;
+
+        Ok(Self {
+            root,
+            index: config.index.clone(),
+            fallback,
+        })
+    }
+
+    /// Responds to a request for `path`, the part of the request path matched
+    /// by the route's trailing wildcard.
+    pub async fn respond(&self, req: Request<Body>, path: &str) -> anyhow::Result<Response<Body>> {
+        // The request body is never read.
+        let (req, _) = req.into_parts();
+        if req.method != Method::GET && req.method != Method::HEAD {
+            return Ok(Response::builder()
+                .status(StatusCode::METHOD_NOT_ALLOWED)
+                .header(header::ALLOW, "GET, HEAD")
+                .body(body::empty())?);
+        }
+
+        let found = match relative_file_path(path) {
+            Some(relative) => self.find(&relative, req.uri.path().ends_with('/')).await?,
+            None => Found::Nothing,
+        };
+        let file = match found {
+            Found::File(file) => file,
+            Found::Directory => {
+                let mut location = format!("{}/", req.uri.path());
+                if let Some(query) = req.uri.query() {
+                    location.push('?');
+                    location.push_str(query);
+                }
+                return Ok(Response::builder()
+                    .status(StatusCode::MOVED_PERMANENTLY)
+                    .header(header::LOCATION, location)
+                    .body(body::empty())?);
+            }
+            Found::Nothing => match &self.fallback {
+                Some(fallback) => match self.find(fallback, false).await? {
+                    Found::File(file) => file,
+                    _ => return not_found(),
+                },
+                None => return not_found(),
+            },
+        };
+
+        self.respond_with_file(&req, &file).await
+    }
+
+    /// Looks up `relative` in the served directory, trying index files if it
+    /// names a directory.
+    ///
+    /// Directories are only searched for index files if `is_directory_request`
+    /// (i.e. the request path ends with `/`), so that relative links in the
+    /// index file resolve correctly.
+    async fn find(&self, relative: &Path, is_directory_request: bool) -> anyhow::Result<Found> {
+        let path = self.root.join(relative);
+        let Some(metadata) = self.metadata(&path).await? else {
+            return Ok(Found::Nothing);
+        };
+        if metadata.is_file() {
+            return Ok(Found::File(path));
+        }
+        if !metadata.is_dir() {
+            return Ok(Found::Nothing);
+        }
+        if !is_directory_request && !relative.as_os_str().is_empty() {
+            return Ok(Found::Directory);
+        }
+        for index in &self.index {
+            let index_path = path.join(index);
+            if let Some(metadata) = self.metadata(&index_path).await?
+                && metadata.is_file()
+            {
+                return Ok(Found::File(index_path));
+            }
+        }
+        Ok(Found::Nothing)
+    }
+
+    /// Returns the metadata of `path`, or `None` if it doesn't exist or
+    /// resolves (through symbolic links) to outside the served directory.
+    async fn metadata(&self, path: &Path) -> anyhow::Result<Option<Metadata>> {
+        let canonical = match tokio::fs::canonicalize(path).await {
+            Ok(canonical) => canonical,
+            Err(err) if is_not_found(&err) => return Ok(None),
+            Err(err) => return Err(err).context(format!("couldn't resolve {}", quoted_path(path))),
+        };
+        if !canonical.starts_with(&self.root) {
+            return Ok(None);
+        }
+        Ok(Some(tokio::fs::metadata(&canonical).await?))
+    }
+
+    /// Responds with the contents of `path`, or a precompressed variant of it,
+    /// honoring conditional and range requests.
+    async fn respond_with_file(
+        &self,
+        req: &http::request::Parts,
+        path: &Path,
+    ) -> anyhow::Result<Response<Body>> {
+        let headers = &req.headers;
+
+        // Ranges are only served from the uncompressed file so that they
+        // refer to the same bytes whatever encodings the client accepts.
+        let mut selected = (path.to_owned(), None);
+        if !headers.contains_key(header::RANGE) {
+            for (encoding, extension) in PRECOMPRESSED_VARIANTS {
+                if encoding_quality(headers, encoding) <= 0.0 {
+                    continue;
+                }
+                let mut variant = path.as_os_str().to_owned();
+                variant.push(".");
+                variant.push(extension);
+                let variant = PathBuf::from(variant);
+                if let Some(metadata) = self.metadata(&variant).await?
+                    && metadata.is_file()
+                {
+                    selected = (variant, Some(encoding));
+                    break;
+                }
+            }
+        }
+        let (file_path, encoding) = selected;
+
+        let mut file = match tokio::fs::File::open(&file_path).await {
+            Ok(file) => file,
+            Err(err) if is_not_found(&err) => return not_found(),
+            Err(err) => {
+                return Err(err).context(format!("couldn't open {}", quoted_path(&file_path)));
+            }
+        };
+        let metadata = file.metadata().await?;
+        let len = metadata.len();
+        let modified = metadata.modified().ok();
+        let etag = entity_tag(len, modified);
+        let last_modified = modified.map(httpdate::fmt_http_date);
+
+        let mut response = Response::builder()
+            .header(header::ETAG, &etag)
+            .header(header::VARY, "Accept-Encoding");
+        if let Some(last_modified) = &last_modified {
+            response = response.header(header::LAST_MODIFIED, last_modified);
+        }
+
+        if is_not_modified(headers, &etag, modified) {
+            return Ok(response
+                .status(StatusCode::NOT_MODIFIED)
+                .body(body::empty())?);
+        }
+
+        response = response
+            .header(header::CONTENT_TYPE, content_type(path))
+            .header(header::ACCEPT_RANGES, "bytes");
+        if let Some(encoding) = encoding {
+            response = response.header(header::CONTENT_ENCODING, encoding);
+        }
+
+        let range = if if_range_matches(headers, &etag, last_modified.as_deref()) {
+            parse_range(headers.get(header::RANGE), len)
+        } else {
+            ByteRange::Full
+        };
+        let (status, start, count) = match range {
+            ByteRange::Full => (StatusCode::OK, 0, len),
+            ByteRange::Partial { start, end } => {
+                response =
+                    response.header(header::CONTENT_RANGE, format!("bytes {start}-{end}/{len}"));
+                (StatusCode::PARTIAL_CONTENT, start, end - start + 1)
+            }
+            ByteRange::Unsatisfiable => {
+                return Ok(response
+                    .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                    .header(header::CONTENT_RANGE, format!("bytes */{len}"))
+                    .body(body::empty())?);
+            }
+        };
+        response = response
+            .status(status)
+            .header(header::CONTENT_LENGTH, count);
+
+        if req.method == Method::HEAD {
+            return Ok(response.body(body::empty())?);
+        }
+        if start > 0 {
+            file.seek(SeekFrom::Start(start)).await?;
+        }
+        Ok(response.body(file_body(file, count))?)
+    }
+}
+
+/// The result of looking up a request path.
+enum Found {
+    /// A file to serve.
+    File(PathBuf),
+    /// A directory requested without a trailing slash.
+    Directory,
+    /// Nothing to serve.
+    Nothing,
+}
+
+/// The part of a file selected by a `Range` header.
+#[derive(Debug, PartialEq)]
+enum ByteRange {
+    /// The whole file.
+    Full,
+    /// The bytes from `start` to `end`, inclusive.
+    Partial { start: u64, end: u64 },
+    /// A range that doesn't overlap the file.
+    Unsatisfiable,
+}
+
+/// Converts a percent-encoded request path into a path relative to the served
+/// directory, or returns `None` if it doesn't name a file inside it.
+fn relative_file_path(path: &str) -> Option<PathBuf> {
+    let mut relative = PathBuf::new();
+    for segment in path.split('/') {
+        let segment = percent_decode_str(segment).decode_utf8().ok()?;
+        if segment.is_empty() || segment == "." {
+            continue;
+        }
+        let mut components = Path::new(segment.as_ref()).components();
+        match (components.next(), components.next()) {
+            (Some(Component::Normal(name)), None) if !segment.contains('\0') => relative.push(name),
+            _ => return None,
+        }
+    }
+    Some(relative)
+}
+
+/// Builds a strong entity tag from a file's size and modification time.
+fn entity_tag(len: u64, modified: Option<SystemTime>) -> String {
+    let modified = modified
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .unwrap_or_default();
+    format!(
+        "\"{:x}-{:x}-{len:x}\"",
+        modified.as_secs(),
+        modified.subsec_nanos()
+    )
+}
+
+/// Whether the request's conditional headers show that the client's cached
+/// copy is current.
+fn is_not_modified(headers: &HeaderMap, etag: &str, modified: Option<SystemTime>) -> bool {
+    if let Some(if_none_match) = headers.get(header::IF_NONE_MATCH) {
+        return if_none_match.to_str().is_ok_and(|tags| {
+            tags.trim() == "*"
+                || tags
+                    .split(',')
+                    .any(|tag| tag.trim().trim_start_matches("W/") == etag)
+        });
+    }
+    let if_modified_since = headers
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| httpdate::parse_http_date(value).ok());
+    match (if_modified_since, modified) {
+        // HTTP dates have a resolution of one second.
+        (Some(since), Some(modified)) => !modified
+            .duration_since(since)
+            .is_ok_and(|newer_by| newer_by.as_secs() > 0),
+        _ => false,
+    }
+}
+
+/// Whether a `Range` header should be honored given the request's `If-Range`
+/// header, which must name the current representation.
+fn if_range_matches(headers: &HeaderMap, etag: &str, last_modified: Option<&str>) -> bool {
+    match headers.get(header::IF_RANGE).map(HeaderValue::to_str) {
+        None => true,
+        Some(Ok(value)) => value == etag || Some(value) == last_modified,
+        Some(Err(_)) => false,
+    }
+}
+
+/// Parses a `Range` header for a file of `len` bytes.
+///
+/// Only a single byte range is supported; other ranges are ignored, which
+/// results in the whole file being served.
+fn parse_range(range: Option<&HeaderValue>, len: u64) -> ByteRange {
+    let Some(spec) = range
+        .and_then(|range| range.to_str().ok())
+        .and_then(|range| range.trim().strip_prefix("bytes="))
+    else {
+        return ByteRange::Full;
+    };
+    let Some((start, end)) = spec.split_once('-') else {
+        return ByteRange::Full;
+    };
+    if spec.contains(',') {
+        return ByteRange::Full;
+    }
+    let (start, end) = (start.trim(), end.trim());
+
+    if start.is_empty() {
+        // A suffix range: the last `end` bytes.
+        return match end.parse::<u64>() {
+            Ok(0) => ByteRange::Unsatisfiable,
+            Ok(_) if len == 0 => ByteRange::Unsatisfiable,
+            Ok(suffix) => ByteRange::Partial {
+                start: len.saturating_sub(suffix),
+                end: len - 1,
+            },
+            Err(_) => ByteRange::Full,
+        };
+    }
+
+    let Ok(start) = start.parse::<u64>() else {
+        return ByteRange::Full;
+    };
+    let end = if end.is_empty() {
+        u64::MAX
+    } else {
+        match end.parse::<u64>() {
+            Ok(end) if end >= start => end,
+            _ => return ByteRange::Full,
+        }
+    };
+    if start >= len {
+        return ByteRange::Unsatisfiable;
+    }
+    ByteRange::Partial {
+        start,
+        end: end.min(len - 1),
+    }
+}
+
+/// Guesses a file's content type from its extension.
+fn content_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    match extension.as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "json" | "map" => "application/json",
+        "webmanifest" => "application/manifest+json",
+        "txt" => "text/plain; charset=utf-8",
+        "md" => "text/markdown; charset=utf-8",
+        "csv" => "text/csv; charset=utf-8",
+        "xml" => "application/xml",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "ico" => "image/x-icon",
+        "wasm" => "application/wasm",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "pdf" => "application/pdf",
+        "mp3" => "audio/mpeg",
+        "wav" => "audio/wav",
+        "ogg" => "audio/ogg",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        "zip" => "application/zip",
+        "gz" => "application/gzip",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Streams `count` bytes from the current position of `file`.
+fn file_body(file: tokio::fs::File, count: u64) -> Body {
+    let frames = ReaderStream::new(file.take(count)).map(|chunk| {
+        chunk
+            .map(Frame::data)
+            .map_err(|err| ErrorCode::InternalError(Some(err.to_string())))
+    });
+    StreamBody::new(frames).boxed_unsync()
+}
+
+fn is_not_found(err: &std::io::Error) -> bool {
+    matches!(err.kind(), ErrorKind::NotFound | ErrorKind::NotADirectory)
+}
+
+fn not_found() -> anyhow::Result<Response<Body>> {
+    Ok(Response::builder()
+        .status(StatusCode::NOT_FOUND)
+        .body(body::empty())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(value: &str, len: u64) -> ByteRange {
+        parse_range(Some(&HeaderValue::from_str(value).unwrap()), len)
+    }
+
+    #[test]
+    fn parses_byte_ranges() {
+        assert_eq!(
+            range("bytes=0-9", 100),
+            ByteRange::Partial { start: 0, end: 9 }
+        );
+        assert_eq!(
+            range("bytes=90-", 100),
+            ByteRange::Partial { start: 90, end: 99 }
+        );
+        assert_eq!(
+            range("bytes=-10", 100),
+            ByteRange::Partial { start: 90, end: 99 }
+        );
+        assert_eq!(
+            range("bytes=50-1000", 100),
+            ByteRange::Partial { start: 50, end: 99 }
+        );
+        assert_eq!(range("bytes=100-", 100), ByteRange::Unsatisfiable);
+        assert_eq!(range("bytes=-0", 100), ByteRange::Unsatisfiable);
+        assert_eq!(range("bytes=0-1,5-6", 100), ByteRange::Full);
+        assert_eq!(range("bytes=9-0", 100), ByteRange::Full);
+        assert_eq!(range("items=0-9", 100), ByteRange::Full);
+        assert_eq!(parse_range(None, 100), ByteRange::Full);
+    }
+
+    #[test]
+    fn request_paths_stay_inside_root() {
+        assert_eq!(
+            relative_file_path("/css/site%20main.css"),
+            Some(PathBuf::from("css/site main.css"))
+        );
+        assert_eq!(relative_file_path("/"), Some(PathBuf::new()));
+        assert_eq!(relative_file_path("/./a//b/"), Some(PathBuf::from("a/b")));
+        assert_eq!(relative_file_path("/../secret"), None);
+        assert_eq!(relative_file_path("/a/%2e%2e/%2e%2e/secret"), None);
+        assert_eq!(relative_file_path("/a%2Fb"), None);
+        assert_eq!(relative_file_path("/a%00b"), None);
+    }
+
+    #[test]
+    fn conditional_requests() {
+        let modified = UNIX_EPOCH + std::time::Duration::from_millis(1_700_000_000_500);
+        let etag = entity_tag(42, Some(modified));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::IF_NONE_MATCH,
+            HeaderValue::from_str(&format!("\"other\", W/{etag}")).unwrap(),
+        );
+        assert!(is_not_modified(&headers, &etag, Some(modified)));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::IF_MODIFIED_SINCE,
+            HeaderValue::from_str(&httpdate::fmt_http_date(modified)).unwrap(),
+        );
+        assert!(is_not_modified(&headers, &etag, Some(modified)));
+        assert!(!is_not_modified(
+            &headers,
+            &etag,
+            Some(modified + std::time::Duration::from_secs(5))
+        ));
+    }
+
+    #[test]
+    fn content_types() {
+        assert_eq!(
+            content_type(Path::new("index.HTML")),
+            "text/html; charset=utf-8"
+        );
+        assert_eq!(content_type(Path::new("app.wasm")), "application/wasm");
+        assert_eq!(
+            content_type(Path::new("unknown")),
+            "application/octet-stream"
+        );
+    }
+}
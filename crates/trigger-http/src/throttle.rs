@@ -0,0 +1,382 @@
+//! Rate limiting and concurrency caps for HTTP triggers.
+
+use std::{
+    net::{IpAddr, SocketAddr},
+    num::NonZeroUsize,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use http::{HeaderValue, Request, Response, StatusCode, header};
+use http_body_util::BodyExt;
+use lru::LruCache;
+use spin_connection_semaphore::{ConnectionPermit, ConnectionSemaphore, LimitedSemaphore};
+use spin_http::config::{HttpTriggerConfig, RateLimitConfig, RateLimitKey};
+
+use crate::Body;
+
+/// The number of clients a rate limiter tracks. Beyond this, the bucket of
+/// the least recently seen client is discarded, so clients choosing their own
+/// keys (e.g. with a header) cannot grow the limiter without bound.
+const MAX_CLIENTS: NonZeroUsize = NonZeroUsize::new(10_000).unwrap();
+
+/// The label of the HTTP trigger's permits in connection semaphore metrics.
+const SEMAPHORE_KIND: &str = "inbound_http";
+
+/// The rate limit and concurrency cap of a trigger.
+pub(crate) struct Throttle {
+    rate_limiter: Option<RateLimiter>,
+    /// Permits for the requests the trigger may handle at once.
+    concurrency: Option<ConnectionSemaphore>,
+    /// The trigger's routing destination, for diagnostics.
+    destination: String,
+    /// Edge-trigger guard for the rejection warning.
+    ///
+    /// Set to `true` once a rejection has been logged, and reset to `false` on the next admitted request.
+    rejecting: AtomicBool,
+}
+
+/// Why a request was not admitted.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Throttled {
+    /// The client has exceeded its rate limit, and may retry after the given time.
+    RateLimited { retry_after: Duration },
+    /// The trigger is already handling as many requests as it may.
+    Overloaded,
+}
+
+impl Throttled {
+    /// The reason for the rejection, as reported in metrics.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::RateLimited { .. } => "rate_limit",
+            Self::Overloaded => "concurrency",
+        }
+    }
+
+    /// The response rejecting the request.
+    pub fn response(self) -> Response<Body> {
+        let (status, retry_after) = match self {
+            Self::RateLimited { retry_after } => (StatusCode::TOO_MANY_REQUESTS, retry_after),
+            Self::Overloaded => (StatusCode::SERVICE_UNAVAILABLE, Duration::from_secs(1)),
+        };
+        // Retry-After is in whole seconds; round up so clients don't retry too early.
+        let retry_after_secs = retry_after
+            .as_secs()
+            .saturating_add(u64::from(retry_after.subsec_nanos() > 0));
+        Response::builder()
+            .status(status)
+            .header(header::RETRY_AFTER, retry_after_secs.max(1))
+            .body(spin_http::body::empty())
+            .unwrap()
+    }
+}
+
+impl Throttle {
+    /// Creates the throttle for a trigger, or `None` if it has neither a rate
+    /// limit nor a concurrency cap.
+    pub fn new(
+        app_id: Arc<str>,
+        destination: String,
+        config: &HttpTriggerConfig,
+    ) -> anyhow::Result<Option<Self>> {
+        if config.rate_limit.is_none() && config.max_concurrent_requests.is_none() {
+            return Ok(None);
+        }
+        let rate_limiter = config
+            .rate_limit
+            .as_ref()
+            .map(RateLimiter::new)
+            .transpose()?;
+        let concurrency = config.max_concurrent_requests.map(|limit| {
+            ConnectionSemaphore::new(
+                None,
+                Some(LimitedSemaphore::new(limit)),
+                SEMAPHORE_KIND,
+                app_id,
+                None,
+            )
+        });
+        Ok(Some(Self {
+            rate_limiter,
+            concurrency,
+            destination,
+            rejecting: AtomicBool::new(false),
+        }))
+    }
+
+    /// Admits a request, returning a permit to hold until the response has
+    /// been sent.
+    pub fn admit(
+        &self,
+        req: &Request<Body>,
+        client_addr: SocketAddr,
+    ) -> Result<ThrottlePermit, Throttled> {
+        let result = self.try_admit(req, client_addr);
+        match &result {
+            Ok(_) => self.rejecting.store(false, Ordering::Relaxed),
+            // Log a warning on the first rejection to make it easier for operators
+            // to notice when limits are being hit, but avoid spamming.
+            Err(throttled) => {
+                if !self.rejecting.swap(true, Ordering::Relaxed) {
+                    tracing::warn!(
+                        destination = %self.destination,
+                        reason = throttled.as_str(),
+                        "request rejected: trigger is throttled"
+                    );
+                }
+            }
+        }
+        result
+    }
+
+    fn try_admit(
+        &self,
+        req: &Request<Body>,
+        client_addr: SocketAddr,
+    ) -> Result<ThrottlePermit, Throttled> {
+        // Take the concurrency permit first, so a request rejected for
+        // concurrency does not also use up the client's rate limit.
+        let permit = match &self.concurrency {
+            Some(concurrency) => Some(concurrency.try_acquire().ok_or(Throttled::Overloaded)?),
+            None => None,
+        };
+        if let Some(rate_limiter) = &self.rate_limiter {
+            let client = rate_limiter.client_key(req, client_addr);
+            rate_limiter
+                .check(client, Instant::now())
+                .map_err(|retry_after| Throttled::RateLimited { retry_after })?;
+        }
+        Ok(ThrottlePermit { _permit: permit })
+    }
+}
+
+/// Holds a trigger's concurrency slot, if it has a cap, until dropped.
+pub(crate) struct ThrottlePermit {
+    _permit: Option<ConnectionPermit>,
+}
+
+impl ThrottlePermit {
+    /// Holds the permit until `body` has been sent or dropped, since the
+    /// component may still be producing it after returning the response.
+    pub fn hold_with(self, body: Body) -> Body {
+        body.map_frame(move |frame| {
+            let _permit = &self;
+            frame
+        })
+        .boxed_unsync()
+    }
+}
+
+/// The client a request is attributed to for rate limiting.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+enum ClientKey {
+    Ip(IpAddr),
+    Header(HeaderValue),
+}
+
+/// A token bucket rate limiter, with a bucket per client.
+struct RateLimiter {
+    /// Tokens added to each bucket per second.
+    rate: f64,
+    /// The capacity of each bucket.
+    burst: f64,
+    key: RateLimitKey,
+    buckets: Mutex<LruCache<ClientKey, Bucket>>,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    fn new(config: &RateLimitConfig) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            config.requests_per_second.is_finite() && config.requests_per_second > 0.0,
+            "rate_limit.requests_per_second must be a positive number"
+        );
+        Ok(Self {
+            rate: config.requests_per_second,
+            burst: config.burst().into(),
+            key: config.key.clone(),
+            buckets: Mutex::new(LruCache::new(MAX_CLIENTS)),
+        })
+    }
+
+    fn client_key(&self, req: &Request<Body>, client_addr: SocketAddr) -> ClientKey {
+        match &self.key {
+            RateLimitKey::Header(name) => req
+                .headers()
+                .get(name)
+                .map(|value| ClientKey::Header(value.clone()))
+                .unwrap_or(ClientKey::Ip(client_addr.ip())),
+            RateLimitKey::ClientIp => ClientKey::Ip(client_addr.ip()),
+        }
+    }
+
+    /// Takes a token from the client's bucket, or returns how long until one
+    /// will be available.
+    fn check(&self, client: ClientKey, now: Instant) -> Result<(), Duration> {
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets.get_or_insert_mut(client, || Bucket {
+            tokens: self.burst,
+            updated: now,
+        });
+        bucket.tokens = self.refilled(bucket, now);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            // A tiny rate can make the wait too long for a `Duration`
+            Err(
+                Duration::try_from_secs_f64((1.0 - bucket.tokens) / self.rate)
+                    .unwrap_or(Duration::MAX),
+            )
+        }
+    }
+
+    fn refilled(&self, bucket: &Bucket, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(bucket.updated);
+        (bucket.tokens + elapsed.as_secs_f64() * self.rate).min(self.burst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rate_limiter(requests_per_second: f64, burst: u32, key: RateLimitKey) -> RateLimiter {
+        RateLimiter::new(&RateLimitConfig {
+            requests_per_second,
+            burst: Some(burst),
+            key,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn token_bucket_allows_bursts_then_refills() {
+        let limiter = rate_limiter(2.0, 3, RateLimitKey::ClientIp);
+        let client = ClientKey::Ip([127, 0, 0, 1].into());
+        let start = Instant::now();
+
+        for _ in 0..3 {
+            limiter.check(client.clone(), start).unwrap();
+        }
+        let retry_after = limiter.check(client.clone(), start).unwrap_err();
+        assert_eq!(retry_after, Duration::from_millis(500));
+
+        // Other clients have their own buckets.
+        limiter
+            .check(ClientKey::Ip([127, 0, 0, 2].into()), start)
+            .unwrap();
+
+        let later = start + Duration::from_millis(500);
+        limiter.check(client.clone(), later).unwrap();
+        limiter.check(client, later).unwrap_err();
+    }
+
+    #[test]
+    fn least_recently_seen_clients_are_forgotten() {
+        let limiter = rate_limiter(1.0, 1, RateLimitKey::Header("x-api-key".into()));
+        let client = |n: usize| ClientKey::Header(HeaderValue::from(n));
+        let start = Instant::now();
+
+        limiter.check(client(0), start).unwrap();
+        limiter.check(client(0), start).unwrap_err();
+        for n in 1..=MAX_CLIENTS.get() {
+            limiter.check(client(n), start).unwrap();
+        }
+        assert_eq!(limiter.buckets.lock().unwrap().len(), MAX_CLIENTS.get());
+        // The first client's empty bucket was discarded to make room.
+        limiter.check(client(0), start).unwrap();
+    }
+
+    #[test]
+    fn rate_limit_keyed_by_header_falls_back_to_client_ip() {
+        let limiter = rate_limiter(1.0, 1, RateLimitKey::Header("x-api-key".into()));
+        let client_addr: SocketAddr = "10.0.0.1:1234".parse().unwrap();
+        let req = Request::get("/")
+            .header("x-api-key", "secret")
+            .body(spin_http::body::empty())
+            .unwrap();
+        assert_eq!(
+            limiter.client_key(&req, client_addr),
+            ClientKey::Header(HeaderValue::from_static("secret"))
+        );
+        let req = Request::get("/").body(spin_http::body::empty()).unwrap();
+        assert_eq!(
+            limiter.client_key(&req, client_addr),
+            ClientKey::Ip(client_addr.ip())
+        );
+    }
+
+    #[test]
+    fn tiny_rates_saturate_retry_after() {
+        let limiter = rate_limiter(f64::MIN_POSITIVE, 1, RateLimitKey::ClientIp);
+        let client = ClientKey::Ip([127, 0, 0, 1].into());
+        let start = Instant::now();
+
+        limiter.check(client.clone(), start).unwrap();
+        let retry_after = limiter.check(client, start).unwrap_err();
+        assert_eq!(retry_after, Duration::MAX);
+        let res = Throttled::RateLimited { retry_after }.response();
+        assert_eq!(res.headers()[header::RETRY_AFTER], u64::MAX.to_string());
+    }
+
+    #[test]
+    fn invalid_rate_is_rejected() {
+        let config = RateLimitConfig {
+            requests_per_second: 0.0,
+            burst: None,
+            key: RateLimitKey::ClientIp,
+        };
+        assert!(RateLimiter::new(&config).is_err());
+    }
+
+    #[test]
+    fn concurrency_cap_releases_permits_on_drop() {
+        let throttle = Throttle {
+            rate_limiter: None,
+            concurrency: Some(ConnectionSemaphore::new(
+                None,
+                Some(LimitedSemaphore::new(1)),
+                SEMAPHORE_KIND,
+                "test-app".into(),
+                None,
+            )),
+            destination: "test".into(),
+            rejecting: AtomicBool::new(false),
+        };
+        let client_addr: SocketAddr = "127.0.0.1:1234".parse().unwrap();
+        let req = Request::get("/").body(spin_http::body::empty()).unwrap();
+
+        let permit = throttle.admit(&req, client_addr).unwrap();
+        let body = permit.hold_with(spin_http::body::empty());
+        assert_eq!(
+            throttle.admit(&req, client_addr).err(),
+            Some(Throttled::Overloaded)
+        );
+        drop(body);
+        throttle.admit(&req, client_addr).unwrap();
+    }
+
+    #[test]
+    fn rejection_responses_carry_retry_after() {
+        let res = Throttled::RateLimited {
+            retry_after: Duration::from_millis(1500),
+        }
+        .response();
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(res.headers()[header::RETRY_AFTER], "2");
+
+        let res = Throttled::Overloaded.response();
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(res.headers()[header::RETRY_AFTER], "1");
+    }
+}
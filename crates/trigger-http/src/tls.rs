@@ -2,33 +2,375 @@ use anyhow::Context;
 use rustls_pki_types::pem::PemObject;
 use std::{
     path::{Path, PathBuf},
-    sync::Arc,
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime},
 };
-use tokio_rustls::{TlsAcceptor, rustls};
+use tokio_rustls::{
+    TlsAcceptor,
+    rustls::{
+        self,
+        server::{
+            ClientHello, ResolvesServerCert, WebPkiClientVerifier, danger::ClientCertVerifier,
+        },
+        sign::CertifiedKey,
+    },
+};
+use x509_parser::extensions::GeneralName;
 
 // TODO: dedupe with spin-factor-outbound-networking (spin-tls crate?)
 
+/// How often to check whether certificate and key files have changed.
+///
+/// Changes are detected by the files' modification times, so a renewed
+/// certificate is served within this long of being written.
+const RELOAD_INTERVAL: Duration = Duration::from_secs(10);
+
 /// TLS configuration for the server.
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct TlsConfig {
     /// Path to TLS certificate.
     pub cert_path: PathBuf,
     /// Path to TLS key.
     pub key_path: PathBuf,
+    /// Additional certificates, each used for connections requesting a server
+    /// name (SNI) it is valid for.
+    pub sni_certs: Vec<TlsCertificate>,
+    /// Authentication of clients with certificates, if enabled.
+    pub client_auth: Option<ClientAuthConfig>,
+}
+
+/// Authentication of clients with certificates (mutual TLS).
+#[derive(Clone, Debug)]
+pub struct ClientAuthConfig {
+    /// Path to the certificate(s) of the CA(s) that client certificates must be issued by.
+    pub ca_path: PathBuf,
+    /// Whether clients must present a certificate.
+    pub mode: ClientAuthMode,
+}
+
+/// Whether clients must present a certificate.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum ClientAuthMode {
+    /// Reject connections from clients without a valid certificate (the default).
+    #[default]
+    Required,
+    /// Accept connections from clients without a certificate, but reject
+    /// invalid certificates.
+    Optional,
+}
+
+impl ClientAuthConfig {
+    fn verifier(&self) -> anyhow::Result<Arc<dyn ClientCertVerifier>> {
+        let mut roots = rustls::RootCertStore::empty();
+        for cert in load_certs(&self.ca_path)? {
+            roots.add(cert).with_context(|| {
+                format!(
+                    "invalid client CA certificate in '{}'",
+                    self.ca_path.display()
+                )
+            })?;
+        }
+        let builder = WebPkiClientVerifier::builder(Arc::new(roots));
+        let builder = match self.mode {
+            ClientAuthMode::Required => builder,
+            ClientAuthMode::Optional => builder.allow_unauthenticated(),
+        };
+        builder
+            .build()
+            .context("failed to configure client certificate verification")
+    }
+}
+
+/// The identity of a client which presented a verified certificate.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct ClientCert {
+    /// The certificate's subject distinguished name.
+    pub subject: String,
+    /// The certificate's subject alternative names, e.g. `DNS:example.com`.
+    pub sans: Vec<String>,
+    /// The hex-encoded SHA-256 digest of the DER-encoded certificate.
+    pub fingerprint: String,
+}
+
+impl ClientCert {
+    /// Describes the end-entity certificate of a verified client certificate chain.
+    pub fn from_chain(chain: &[rustls_pki_types::CertificateDer]) -> Option<Self> {
+        let der = chain.first()?;
+        let (_, cert) = x509_parser::parse_x509_certificate(der)
+            .inspect_err(|err| tracing::warn!("Failed to parse client certificate: {err}"))
+            .ok()?;
+        let sans = match cert.subject_alternative_name() {
+            Ok(Some(san)) => san
+                .value
+                .general_names
+                .iter()
+                .filter_map(describe_general_name)
+                .collect(),
+            _ => vec![],
+        };
+        Some(Self {
+            subject: escape_non_ascii(&cert.subject().to_string()),
+            sans,
+            fingerprint: spin_common::sha256::hex_digest_from_bytes(der),
+        })
+    }
+}
+
+fn describe_general_name(name: &GeneralName) -> Option<String> {
+    let name = match name {
+        GeneralName::DNSName(name) => format!("DNS:{name}"),
+        GeneralName::RFC822Name(name) => format!("email:{name}"),
+        GeneralName::URI(uri) => format!("URI:{uri}"),
+        GeneralName::IPAddress(bytes) => match bytes.len() {
+            4 => format!(
+                "IP:{}",
+                std::net::Ipv4Addr::from(<[u8; 4]>::try_from(*bytes).ok()?)
+            ),
+            16 => format!(
+                "IP:{}",
+                std::net::Ipv6Addr::from(<[u8; 16]>::try_from(*bytes).ok()?)
+            ),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(escape_non_ascii(&name))
+}
+
+/// Escapes characters that are not allowed in header values as `\HH` for
+/// each byte of their UTF-8 encoding, in the style of RFC 4514.
+fn escape_non_ascii(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if c.is_ascii_graphic() || c == ' ' {
+            escaped.push(c);
+        } else {
+            let mut buf = [0; 4];
+            for byte in c.encode_utf8(&mut buf).bytes() {
+                escaped.push_str(&format!("\\{byte:02X}"));
+            }
+        }
+    }
+    escaped
+}
+
+/// The paths of a certificate and its private key.
+#[derive(Clone, Debug)]
+pub struct TlsCertificate {
+    /// Path to TLS certificate.
+    pub cert_path: PathBuf,
+    /// Path to TLS key.
+    pub key_path: PathBuf,
 }
 
 impl TlsConfig {
     // Creates a TLS acceptor from server config.
-    pub(super) fn server_config(&self) -> anyhow::Result<TlsAcceptor> {
-        let certs = load_certs(&self.cert_path)?;
-        let private_key = load_key(&self.key_path)?;
+    pub(super) fn server_config(
+        &self,
+        alpn_protocols: Vec<Vec<u8>>,
+    ) -> anyhow::Result<TlsAcceptor> {
+        Ok(Arc::new(self.rustls_server_config(alpn_protocols)?).into())
+    }
+
+    // Creates a rustls server config which advertises the given ALPN protocols.
+    //
+    // Certificates are reloaded whenever their files change.
+    pub(super) fn rustls_server_config(
+        &self,
+        alpn_protocols: Vec<Vec<u8>>,
+    ) -> anyhow::Result<rustls::ServerConfig> {
+        let resolver = Arc::new(CertResolver::load(self.clone())?);
+        resolver.watch();
 
-        let cfg = rustls::ServerConfig::builder()
-            .with_no_client_auth()
-            .with_single_cert(certs, private_key)
-            .map_err(|e| anyhow::anyhow!("{}", e))?;
+        let builder = rustls::ServerConfig::builder();
+        let builder = match &self.client_auth {
+            Some(client_auth) => builder.with_client_cert_verifier(client_auth.verifier()?),
+            None => builder.with_no_client_auth(),
+        };
+        let mut cfg = builder.with_cert_resolver(resolver);
+        cfg.alpn_protocols = alpn_protocols;
 
-        Ok(Arc::new(cfg).into())
+        Ok(cfg)
+    }
+
+    fn paths(&self) -> impl Iterator<Item = &Path> {
+        [self.cert_path.as_path(), self.key_path.as_path()]
+            .into_iter()
+            .chain(
+                self.sni_certs
+                    .iter()
+                    .flat_map(|c| [c.cert_path.as_path(), c.key_path.as_path()]),
+            )
+    }
+
+    /// The modification times of the certificate and key files, used to
+    /// detect changes.
+    fn file_versions(&self) -> Vec<Option<SystemTime>> {
+        self.paths()
+            .map(|path| std::fs::metadata(path).and_then(|m| m.modified()).ok())
+            .collect()
+    }
+}
+
+/// Selects a certificate for each connection by its requested server name,
+/// reloading certificates when their files change.
+#[derive(Debug)]
+struct CertResolver {
+    config: TlsConfig,
+    certs: RwLock<Arc<LoadedCerts>>,
+}
+
+#[derive(Debug)]
+struct LoadedCerts {
+    default: Arc<CertifiedKey>,
+    sni: Vec<SniCert>,
+    /// The modification times of the files the certificates were loaded from.
+    versions: Vec<Option<SystemTime>>,
+}
+
+impl CertResolver {
+    fn load(config: TlsConfig) -> anyhow::Result<Self> {
+        let certs = LoadedCerts::load(&config)?;
+        Ok(Self {
+            config,
+            certs: RwLock::new(Arc::new(certs)),
+        })
+    }
+
+    /// Polls the certificate and key files every [`RELOAD_INTERVAL`], for as
+    /// long as the resolver is in use, and reloads them when their
+    /// modification times change.
+    fn watch(self: &Arc<Self>) {
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let resolver = Arc::downgrade(self);
+        runtime.spawn(async move {
+            let mut interval = tokio::time::interval(RELOAD_INTERVAL);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                let Some(resolver) = resolver.upgrade() else {
+                    break;
+                };
+                resolver.reload_if_changed();
+            }
+        });
+    }
+
+    fn reload_if_changed(&self) {
+        if self.config.file_versions() == self.current().versions {
+            return;
+        }
+        match LoadedCerts::load(&self.config) {
+            Ok(certs) => {
+                *self.certs.write().unwrap() = Arc::new(certs);
+                tracing::info!("Reloaded TLS certificates");
+            }
+            // Files may be caught mid-update, so keep serving the previous
+            // certificates and try again later.
+            Err(err) => tracing::error!(
+                "Failed to reload TLS certificates; continuing with the previous certificates: {err:?}"
+            ),
+        }
+    }
+
+    fn current(&self) -> Arc<LoadedCerts> {
+        self.certs.read().unwrap().clone()
+    }
+}
+
+impl ResolvesServerCert for CertResolver {
+    fn resolve(&self, client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        let certs = self.current();
+        let sni_cert = client_hello
+            .server_name()
+            .and_then(|name| certs.sni.iter().find(|cert| cert.is_valid_for(name)));
+        Some(sni_cert.map_or(&certs.default, |cert| &cert.key).clone())
+    }
+}
+
+impl LoadedCerts {
+    fn load(config: &TlsConfig) -> anyhow::Result<Self> {
+        // Record versions first so that changes made while loading are
+        // picked up by the next reload.
+        let versions = config.file_versions();
+        let default = load_certified_key(&config.cert_path, &config.key_path)?;
+        let sni = config
+            .sni_certs
+            .iter()
+            .map(|c| SniCert::load(&c.cert_path, &c.key_path))
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self {
+            default,
+            sni,
+            versions,
+        })
+    }
+}
+
+fn load_certified_key(cert_path: &Path, key_path: &Path) -> anyhow::Result<Arc<CertifiedKey>> {
+    let certs = load_certs(cert_path)?;
+    let private_key = load_key(key_path)?;
+    let signing_key = rustls::crypto::ring::sign::any_supported_type(&private_key)
+        .with_context(|| format!("unsupported private key in '{}'", key_path.display()))?;
+    let certified_key = CertifiedKey::new(certs, signing_key);
+    certified_key.keys_match().with_context(|| {
+        format!(
+            "private key '{}' does not match certificate '{}'",
+            key_path.display(),
+            cert_path.display()
+        )
+    })?;
+    Ok(Arc::new(certified_key))
+}
+
+/// A certificate selected for connections requesting a server name it is
+/// valid for.
+#[derive(Debug)]
+struct SniCert {
+    key: Arc<CertifiedKey>,
+    /// The DNS names of the end-entity certificate, parsed once at load time
+    /// rather than for every connection.
+    dns_names: Vec<String>,
+}
+
+impl SniCert {
+    fn load(cert_path: &Path, key_path: &Path) -> anyhow::Result<Self> {
+        let key = load_certified_key(cert_path, key_path)?;
+        let (_, cert) = x509_parser::parse_x509_certificate(key.end_entity_cert()?)
+            .with_context(|| format!("failed to parse certificate '{}'", cert_path.display()))?;
+        let dns_names: Vec<_> = cert
+            .subject_alternative_name()
+            .ok()
+            .flatten()
+            .into_iter()
+            .flat_map(|san| &san.value.general_names)
+            .filter_map(|name| match name {
+                GeneralName::DNSName(name) => Some(name.to_ascii_lowercase()),
+                _ => None,
+            })
+            .collect();
+        anyhow::ensure!(
+            !dns_names.is_empty(),
+            "certificate '{}' has no DNS names to match requested server names against",
+            cert_path.display()
+        );
+        Ok(Self { key, dns_names })
+    }
+
+    /// Whether the certificate is valid for the server name `name`. A
+    /// wildcard such as `*.example.com` matches a single leftmost label.
+    fn is_valid_for(&self, name: &str) -> bool {
+        self.dns_names
+            .iter()
+            .any(|pattern| match pattern.strip_prefix("*.") {
+                Some(suffix) => name.split_once('.').is_some_and(|(label, rest)| {
+                    !label.is_empty() && rest.eq_ignore_ascii_case(suffix)
+                }),
+                None => pattern.eq_ignore_ascii_case(name),
+            })
     }
 }
 
@@ -112,4 +454,57 @@ mod tests {
         let path = Path::new(TESTDATA_DIR).join("valid-private-key.pem");
         load_key(path).unwrap();
     }
+
+    #[test]
+    fn test_sni_certificate_selection() {
+        let testdata = Path::new(TESTDATA_DIR);
+        let config = TlsConfig {
+            cert_path: testdata.join("valid-cert.pem"),
+            key_path: testdata.join("valid-private-key.pem"),
+            sni_certs: vec![TlsCertificate {
+                cert_path: testdata.join("sni-cert.pem"),
+                key_path: testdata.join("sni-private-key.pem"),
+            }],
+            client_auth: None,
+        };
+        let certs = LoadedCerts::load(&config).unwrap();
+        let sni_cert = &certs.sni[0];
+
+        assert_eq!(sni_cert.dns_names, ["example.com", "*.example.org"]);
+        assert!(sni_cert.is_valid_for("example.com"));
+        assert!(sni_cert.is_valid_for("EXAMPLE.com"));
+        assert!(sni_cert.is_valid_for("www.example.org"));
+        assert!(!sni_cert.is_valid_for("example.org"));
+        assert!(!sni_cert.is_valid_for("a.b.example.org"));
+        assert!(!sni_cert.is_valid_for("example.net"));
+    }
+
+    #[test]
+    fn test_client_cert_identity() {
+        let path = Path::new(TESTDATA_DIR).join("sni-cert.pem");
+        let chain = load_certs(&path).unwrap();
+        let client_cert = ClientCert::from_chain(&chain).unwrap();
+        assert_eq!(client_cert.subject, "CN=example.com");
+        assert_eq!(client_cert.sans, ["DNS:example.com", "DNS:*.example.org"]);
+        assert_eq!(
+            client_cert.fingerprint,
+            spin_common::sha256::hex_digest_from_bytes(&chain[0])
+        );
+        assert!(ClientCert::from_chain(&[]).is_none());
+    }
+
+    #[test]
+    fn test_escape_non_ascii() {
+        assert_eq!(escape_non_ascii("CN=caf\u{e9}, O=x"), "CN=caf\\C3\\A9, O=x");
+    }
+
+    #[test]
+    fn test_mismatched_private_key() {
+        let testdata = Path::new(TESTDATA_DIR);
+        load_certified_key(
+            &testdata.join("sni-cert.pem"),
+            &testdata.join("valid-private-key.pem"),
+        )
+        .unwrap_err();
+    }
 }
@@ -11,7 +11,9 @@ use wasmtime_wasi::p2::bindings::CommandIndices;
 use wasmtime_wasi::p2::pipe::MemoryOutputPipe;
 use wasmtime_wasi_http::p2::body::HyperIncomingBody as Body;
 
-use crate::{HttpServer, headers::compute_default_headers, server::set_request_deadline};
+use crate::{
+    HttpServer, headers::compute_default_headers, server::set_request_deadline, tls::ClientCert,
+};
 
 pub struct WagiHttpExecutor<'a> {
     pub wagi_config: &'a WagiTriggerConfig,
@@ -66,7 +68,10 @@ impl WagiHttpExecutor<'_> {
         // This sets the current environment variables Wagi expects (such as
         // `PATH_INFO`, or `X_FULL_URL`).
         // Note that this overrides any existing headers previously set by Wagi.
-        for (keys, val) in compute_default_headers(&parts.uri, host, route_match, client_addr)? {
+        let client_cert = parts.extensions.get::<ClientCert>();
+        for (keys, val) in
+            compute_default_headers(&parts.uri, host, route_match, client_addr, client_cert)?
+        {
             headers.insert(keys[1].to_string(), val.into_owned());
         }
 
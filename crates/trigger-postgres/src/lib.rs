@@ -0,0 +1,424 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use anyhow::Context;
+use futures::stream::FuturesUnordered;
+use futures::{StreamExt, TryFutureExt};
+use serde::Deserialize;
+use spin_factor_outbound_pg::client::{HashableCertificate, connect_for_notifications};
+use spin_factor_variables::VariablesFactor;
+use spin_factors::RuntimeFactors;
+use spin_trigger::{App, ShutdownSignal, Trigger, TriggerApp, cli::NoCliArgs};
+use spin_world::exports::spin::postgres4_3_0::inbound_postgres as v4;
+use tokio_postgres::Notification;
+use tracing::{Level, instrument};
+
+/// The delay before the first attempt to reconnect after losing a connection.
+const INITIAL_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+/// The longest delay between attempts to reconnect.
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+/// The most notifications handled at once by a single listener.
+const MAX_CONCURRENT_NOTIFICATIONS: usize = 64;
+
+pub struct PostgresTrigger;
+
+/// Postgres trigger metadata.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TriggerMetadata {
+    address: String,
+}
+
+/// Postgres trigger configuration.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TriggerConfig {
+    /// Component ID to invoke
+    component: String,
+    /// Channel to listen on
+    channel: String,
+    /// Optionally override address for trigger
+    address: Option<String>,
+    /// Optional PEM-encoded root certificate to trust when connecting
+    ca_root: Option<String>,
+}
+
+impl<F: RuntimeFactors> Trigger<F> for PostgresTrigger {
+    const TYPE: &'static str = "postgres";
+
+    type CliArgs = NoCliArgs;
+
+    type InstanceState = ();
+
+    fn new(_cli_args: Self::CliArgs, _app: &App) -> anyhow::Result<Self> {
+        Ok(Self)
+    }
+
+    async fn run(self, trigger_app: spin_trigger::TriggerApp<Self, F>) -> anyhow::Result<()> {
+        self.run_until_shutdown(trigger_app, ShutdownSignal::never())
+            .await
+    }
+
+    async fn run_until_shutdown(
+        self,
+        trigger_app: spin_trigger::TriggerApp<Self, F>,
+        shutdown: ShutdownSignal,
+    ) -> anyhow::Result<()> {
+        let app_variables = trigger_app
+            .configured_app()
+            .app_state::<VariablesFactor>()
+            .context("PostgresTrigger depends on VariablesFactor")?;
+
+        let app = trigger_app.app();
+        let trigger_type = <Self as Trigger<F>>::TYPE;
+        let metadata = app
+            .get_trigger_metadata::<TriggerMetadata>(trigger_type)?
+            .unwrap_or_default();
+        let default_address_expr = &metadata.address;
+        let default_address = app_variables
+            .resolve_expression(default_address_expr.clone())
+            .await
+            .with_context(|| {
+                format!(
+                    "failed to resolve postgres trigger default address {default_address_expr:?}"
+                )
+            })?;
+
+        // Maps <server address> -> <listener config>
+        let mut listeners: HashMap<String, ListenerConfig> = HashMap::new();
+
+        // Resolve trigger configs before starting any listeners
+        for (_, config) in app
+            .trigger_configs::<TriggerConfig>(trigger_type)?
+            .into_iter()
+            .collect::<Vec<_>>()
+        {
+            let component_id = config.component;
+
+            let address_expr = config.address.as_ref().unwrap_or(&default_address);
+            let address = app_variables
+                .resolve_expression(address_expr.clone())
+                .await
+                .with_context(|| {
+                    format!(
+                        "failed to resolve postgres trigger address {address_expr:?} for component {component_id}"
+                    )
+                })?;
+
+            let channel_expr = &config.channel;
+            let channel = app_variables
+                .resolve_expression(channel_expr.clone())
+                .await
+                .with_context(|| {
+                    format!(
+                        "failed to resolve postgres trigger channel {channel_expr:?} for component {component_id}"
+                    )
+                })?;
+
+            let ca_root = match &config.ca_root {
+                None => None,
+                Some(ca_root_expr) => {
+                    let pem = app_variables
+                        .resolve_expression(ca_root_expr.clone())
+                        .await
+                        .with_context(|| {
+                            format!(
+                                "failed to resolve postgres trigger CA root for component {component_id}"
+                            )
+                        })?;
+                    Some(HashableCertificate::from_pem(&pem).with_context(|| {
+                        format!("invalid postgres trigger CA root for component {component_id}")
+                    })?)
+                }
+            };
+
+            listeners
+                .entry(address)
+                .or_default()
+                .add(channel, component_id, ca_root)?;
+        }
+
+        // Start listener(s)
+        let trigger_app = Arc::new(trigger_app);
+        let mut listener_tasks = Vec::new();
+        for (address, config) in listeners {
+            let listener = Listener {
+                address,
+                trigger_app: trigger_app.clone(),
+                config,
+            };
+            let task = tokio::spawn(listener.run(shutdown.clone()));
+            listener_tasks.push(task);
+        }
+
+        if listener_tasks.is_empty() {
+            return Ok(());
+        }
+
+        // Listeners stop if one fails or shutdown is requested
+        let (res, _, rest) = futures::future::select_all(listener_tasks).await;
+        res??;
+        for res in futures::future::join_all(rest).await {
+            res??;
+        }
+        Ok(())
+    }
+}
+
+/// Maps <channel> -> <component IDs>
+type ChannelComponents = HashMap<String, Vec<String>>;
+
+/// The channels to listen on at a single Postgres server.
+#[derive(Default)]
+struct ListenerConfig {
+    channel_components: ChannelComponents,
+    ca_root: Option<HashableCertificate>,
+}
+
+impl ListenerConfig {
+    /// Adds a trigger for `component_id` on `channel`.
+    fn add(
+        &mut self,
+        channel: String,
+        component_id: String,
+        ca_root: Option<HashableCertificate>,
+    ) -> anyhow::Result<()> {
+        if let Some(ca_root) = ca_root {
+            // Triggers on the same server share a connection, so they
+            // must agree on the root to trust
+            if self
+                .ca_root
+                .as_ref()
+                .is_some_and(|existing| *existing != ca_root)
+            {
+                anyhow::bail!(
+                    "postgres trigger for component {component_id} sets a different CA root than another trigger for the same address"
+                );
+            }
+            self.ca_root = Some(ca_root);
+        }
+        self.channel_components
+            .entry(channel)
+            .or_default()
+            .push(component_id);
+        Ok(())
+    }
+}
+
+/// Listens for notifications from a single Postgres server.
+struct Listener<F: RuntimeFactors> {
+    address: String,
+    trigger_app: Arc<TriggerApp<PostgresTrigger, F>>,
+    config: ListenerConfig,
+}
+
+impl<F: RuntimeFactors> Listener<F> {
+    /// Listens for notifications, reconnecting with exponential backoff
+    /// whenever the connection fails.
+    ///
+    /// Returns once shutdown is requested and the notifications being
+    /// handled, if any, have been handled.
+    async fn run(self, shutdown: ShutdownSignal) -> anyhow::Result<()> {
+        println!("Active Postgres channels:");
+        for (channel, components) in &self.config.channel_components {
+            println!("\t{channel}: [{}]", components.join(","));
+        }
+
+        let mut delay = INITIAL_RECONNECT_DELAY;
+        loop {
+            match self.listen(&shutdown).await {
+                Ok(()) => delay = INITIAL_RECONNECT_DELAY,
+                Err(err) => tracing::error!("Postgres trigger listener failed: {err:?}"),
+            }
+            if shutdown.is_requested() {
+                tracing::info!("Postgres trigger stopped listening");
+                return Ok(());
+            }
+            tracing::info!("Reconnecting to Postgres in {delay:?}");
+            tokio::select! {
+                () = tokio::time::sleep(delay) => {}
+                () = shutdown.requested() => return Ok(()),
+            }
+            delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+        }
+    }
+
+    /// Connects, listens on all configured channels, and handles
+    /// notifications until the connection fails or shutdown is requested.
+    ///
+    /// Notifications are handled concurrently, up to
+    /// `MAX_CONCURRENT_NOTIFICATIONS` at a time.
+    ///
+    /// Returns `Ok` if the connection failed after listening was established,
+    /// so that the caller can reset its backoff.
+    async fn listen(&self, shutdown: &ShutdownSignal) -> anyhow::Result<()> {
+        tracing::info!("Connecting to Postgres for notifications");
+        let mut connection = connect_for_notifications(&self.address, self.config.ca_root.clone())
+            .await
+            .context("Postgres trigger failed to connect")?;
+
+        for channel in self.config.channel_components.keys() {
+            tracing::info!("Listening on {channel:?}");
+            connection
+                .client
+                .batch_execute(&format!("LISTEN {}", quote_identifier(channel)))
+                .await
+                .with_context(|| {
+                    format!("Postgres trigger failed to listen on channel {channel:?}")
+                })?;
+        }
+
+        let mut in_flight = FuturesUnordered::new();
+        loop {
+            // No more notifications are received once shutdown is requested
+            let notification = tokio::select! {
+                notification = connection.notifications.recv(),
+                    if in_flight.len() < MAX_CONCURRENT_NOTIFICATIONS => notification,
+                Some(()) = in_flight.next() => continue,
+                () = shutdown.requested() => break,
+            };
+            match notification {
+                Some(Ok(notification)) => {
+                    in_flight.push(
+                        self.handle_notification(notification)
+                            .unwrap_or_else(|err| {
+                                tracing::error!("Error handling Postgres notification: {err}")
+                            }),
+                    );
+                }
+                Some(Err(err)) => {
+                    tracing::error!("Postgres trigger connection failed: {err}");
+                    break;
+                }
+                None => break,
+            }
+        }
+
+        // Notifications being handled are finished before returning
+        while in_flight.next().await.is_some() {}
+        Ok(())
+    }
+
+    #[instrument(name = "spin_trigger_postgres.handle_notification", skip_all, err(level = Level::INFO), fields(
+        otel.name = format!("{} receive", notification.channel()),
+        otel.kind = "consumer",
+        messaging.operation = "receive",
+        messaging.system = "postgresql"
+    ))]
+    async fn handle_notification(&self, notification: Notification) -> anyhow::Result<()> {
+        let channel = notification.channel();
+        tracing::trace!(%channel, "Received notification");
+
+        let Some(component_ids) = self.config.channel_components.get(channel) else {
+            anyhow::bail!("notification from unexpected channel {channel:?}");
+        };
+
+        let dispatch_futures = component_ids.iter().map(|component_id| {
+            tracing::trace!("Executing Postgres component {component_id}");
+            self.dispatch_handler(&notification, component_id)
+                .inspect_err(move |err| {
+                    tracing::info!("Component {component_id} handler failed: {err}");
+                })
+        });
+        futures::future::join_all(dispatch_futures).await;
+
+        Ok(())
+    }
+
+    async fn dispatch_handler(
+        &self,
+        notification: &Notification,
+        component_id: &str,
+    ) -> anyhow::Result<()> {
+        spin_telemetry::metrics::counter!(
+            spin.request_count = 1,
+            trigger_type = "postgres",
+            app_id = self.trigger_app.app().id().to_string(),
+            component_id = component_id.to_string()
+        );
+
+        let (instance, mut store) = self
+            .trigger_app
+            .prepare(component_id)?
+            .instantiate(())
+            .await?;
+
+        let pre = instance.instance_pre(&store);
+        let guest_indices = v4::GuestIndices::new(&pre)
+            .map_err(|e| anyhow::anyhow!("{e}"))
+            .context("component does not export a Postgres notification interface")?;
+        let guest = guest_indices.load(&mut store, &instance)?;
+
+        let notification = v4::Notification {
+            channel: notification.channel().to_owned(),
+            payload: notification.payload().to_owned(),
+        };
+        let res = std::pin::pin!(store.as_mut().run_concurrent(async |accessor| {
+            guest.call_handle_notification(accessor, notification).await
+        }))
+        .await;
+
+        res.map_err(|e| anyhow::anyhow!("{e}"))
+            .context("Postgres handler returned an error (run_concurrent)")?
+            .map_err(|e| anyhow::anyhow!("{e}"))
+            .context("Postgres handler returned an error")?
+            .map_err(|e| anyhow::anyhow!("{e:?}"))
+            .context("Postgres handler returned an error")
+    }
+}
+
+/// Quotes a channel name for use as an identifier in a `LISTEN` command.
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cert(pem: &str) -> HashableCertificate {
+        HashableCertificate::from_pem(pem).unwrap()
+    }
+
+    #[test]
+    fn quote_identifier_escapes_quotes() {
+        assert_eq!(quote_identifier("orders"), r#""orders""#);
+        assert_eq!(quote_identifier("Mixed Case"), r#""Mixed Case""#);
+        assert_eq!(quote_identifier(r#"a"b"#), r#""a""b""#);
+        assert_eq!(
+            quote_identifier(r#""; DROP TABLE t; --"#),
+            r#""""; DROP TABLE t; --""#
+        );
+    }
+
+    #[test]
+    fn triggers_on_a_server_share_a_listener() -> anyhow::Result<()> {
+        let mut config = ListenerConfig::default();
+        config.add("orders".into(), "a".into(), None)?;
+        config.add("orders".into(), "b".into(), None)?;
+        config.add("refunds".into(), "a".into(), None)?;
+        assert_eq!(config.channel_components["orders"], ["a", "b"]);
+        assert_eq!(config.channel_components["refunds"], ["a"]);
+        assert!(config.ca_root.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn triggers_on_a_server_must_agree_on_ca_root() -> anyhow::Result<()> {
+        let ca_root = cert(include_str!("../testdata/ca-root.pem"));
+        let other = cert(include_str!("../testdata/other-ca-root.pem"));
+
+        let mut config = ListenerConfig::default();
+        config.add("orders".into(), "a".into(), None)?;
+        config.add("orders".into(), "b".into(), Some(ca_root.clone()))?;
+        // Triggers which don't set a CA root use the one that is set
+        config.add("refunds".into(), "c".into(), None)?;
+        config.add("refunds".into(), "d".into(), Some(ca_root.clone()))?;
+        assert!(config.ca_root == Some(ca_root));
+
+        let err = config
+            .add("orders".into(), "e".into(), Some(other))
+            .unwrap_err();
+        assert!(err.to_string().contains("component e"), "{err}");
+        Ok(())
+    }
+}
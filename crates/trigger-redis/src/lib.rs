@@ -1,16 +1,23 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
 use anyhow::Context;
 use futures::{StreamExt, TryFutureExt};
-use redis::{Client, Msg};
+use redis::Msg;
+use redis::aio::PubSub;
 use serde::Deserialize;
 use spin_factor_variables::VariablesFactor;
 use spin_factors::RuntimeFactors;
-use spin_trigger::{App, Trigger, TriggerApp, cli::NoCliArgs};
+use spin_redis_connection::{ConnectOptions, RedisAddress};
+use spin_trigger::{App, ShutdownSignal, Trigger, TriggerApp, cli::NoCliArgs};
 use spin_world::exports::fermyon::spin::inbound_redis as v1;
-use spin_world::exports::spin::redis::inbound_redis as v3;
+use spin_world::exports::spin::redis3_1_0::inbound_redis as v3;
 use tracing::{Level, instrument};
 
+/// The delay before the first attempt to reconnect after losing a connection.
+const INITIAL_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+/// The longest delay between attempts to reconnect.
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+
 pub struct RedisTrigger;
 
 /// Redis trigger metadata.
@@ -44,6 +51,15 @@ impl<F: RuntimeFactors> Trigger<F> for RedisTrigger {
     }
 
     async fn run(self, trigger_app: spin_trigger::TriggerApp<Self, F>) -> anyhow::Result<()> {
+        self.run_until_shutdown(trigger_app, ShutdownSignal::never())
+            .await
+    }
+
+    async fn run_until_shutdown(
+        self,
+        trigger_app: spin_trigger::TriggerApp<Self, F>,
+        shutdown: ShutdownSignal,
+    ) -> anyhow::Result<()> {
         let app_variables = trigger_app
             .configured_app()
             .app_state::<VariablesFactor>()
@@ -106,22 +122,26 @@ impl<F: RuntimeFactors> Trigger<F> for RedisTrigger {
         let mut subscriber_tasks = Vec::new();
         for (address, channel_components) in server_channel_components {
             let subscriber = Subscriber::new(address, trigger_app.clone(), channel_components)?;
-            let task = tokio::spawn(subscriber.run_listener());
+            let task = tokio::spawn(subscriber.run_listener(shutdown.clone()));
             subscriber_tasks.push(task);
         }
 
-        // Wait for any task to complete
-        let (res, _, _) = futures::future::select_all(subscriber_tasks).await;
-        res?
+        // Subscribers stop if one fails or shutdown is requested
+        let (res, _, rest) = futures::future::select_all(subscriber_tasks).await;
+        res??;
+        for res in futures::future::join_all(rest).await {
+            res??;
+        }
+        Ok(())
     }
 }
 
 /// Maps <channel> -> <component IDs>
 type ChannelComponents = HashMap<String, Vec<String>>;
 
-/// Subscribes to channels from a single Redis server.
+/// Subscribes to channels from a single Redis deployment.
 struct Subscriber<F: RuntimeFactors> {
-    client: Client,
+    address: RedisAddress,
     trigger_app: Arc<TriggerApp<RedisTrigger, F>>,
     channel_components: ChannelComponents,
 }
@@ -132,42 +152,99 @@ impl<F: RuntimeFactors> Subscriber<F> {
         trigger_app: Arc<TriggerApp<RedisTrigger, F>>,
         channel_components: ChannelComponents,
     ) -> anyhow::Result<Self> {
-        let client = Client::open(address)?;
+        let address = address.parse()?;
         Ok(Self {
-            client,
+            address,
             trigger_app,
             channel_components,
         })
     }
 
-    async fn run_listener(self) -> anyhow::Result<()> {
-        let server_addr = &self.client.get_connection_info().addr;
+    /// Subscribes and handles messages. If the connection later drops, it is
+    /// re-established with exponential backoff; when using Sentinel, each
+    /// reconnection goes to the current primary.
+    ///
+    /// Fails if the first attempt to connect or subscribe fails. Otherwise,
+    /// returns once shutdown is requested and the message being handled, if
+    /// any, has been handled.
+    async fn run_listener(self, shutdown: ShutdownSignal) -> anyhow::Result<()> {
+        let server_addr = &self.address;
+
+        let mut pubsub = Some(self.subscribe().await?);
+
+        println!("Active Channels on {server_addr}:");
+        for (channel, components) in &self.channel_components {
+            println!("\t{server_addr}/{channel}: [{}]", components.join(","));
+        }
+
+        let mut delay = INITIAL_RECONNECT_DELAY;
+        loop {
+            if let Some(pubsub) = pubsub.take() {
+                self.handle_messages(pubsub, &shutdown).await;
+                delay = INITIAL_RECONNECT_DELAY;
+            }
+            if shutdown.is_requested() {
+                tracing::info!("Redis trigger stopped consuming from {server_addr}");
+                return Ok(());
+            }
+            tracing::info!("Reconnecting to Redis at {server_addr} in {delay:?}");
+            tokio::select! {
+                () = tokio::time::sleep(delay) => {}
+                () = shutdown.requested() => return Ok(()),
+            }
+            match self.subscribe().await {
+                Ok(subscribed) => pubsub = Some(subscribed),
+                Err(err) => {
+                    tracing::error!("Redis trigger subscriber failed: {err:?}");
+                    delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+                }
+            }
+        }
+    }
+
+    /// Connects and subscribes to all configured channels.
+    async fn subscribe(&self) -> anyhow::Result<PubSub> {
+        let server_addr = &self.address;
 
         tracing::info!("Connecting to Redis server at {server_addr}");
         let mut pubsub = self
-            .client
+            .address
+            .node_client(&ConnectOptions::default())
+            .await
+            .with_context(|| format!("Redis trigger failed to locate {server_addr}"))?
             .get_async_pubsub()
             .await
             .with_context(|| format!("Redis trigger failed to connect to {server_addr}"))?;
 
-        println!("Active Channels on {server_addr}:");
-
-        // Subscribe to channels
-        for (channel, components) in &self.channel_components {
+        for channel in self.channel_components.keys() {
             tracing::info!("Subscribing to {channel:?} on {server_addr}");
             pubsub.subscribe(channel).await.with_context(|| {
                 format!("Redis trigger failed to subscribe to channel {channel:?} on {server_addr}")
             })?;
-            println!("\t{server_addr}/{channel}: [{}]", components.join(","));
         }
+        Ok(pubsub)
+    }
+
+    /// Handles messages until the connection drops or shutdown is requested.
+    async fn handle_messages(&self, mut pubsub: PubSub, shutdown: &ShutdownSignal) {
+        let server_addr = &self.address;
 
         let mut message_stream = pubsub.on_message();
-        while let Some(msg) = message_stream.next().await {
+        loop {
+            // A message being handled is finished before stopping, but no
+            // more are received once shutdown is requested.
+            let msg = tokio::select! {
+                msg = message_stream.next() => msg,
+                () = shutdown.requested() => return,
+            };
+            let Some(msg) = msg else {
+                break;
+            };
             if let Err(err) = self.handle_message(msg).await {
                 tracing::error!("Error handling message from {server_addr}: {err}");
             }
         }
-        Err(anyhow::anyhow!("disconnected from {server_addr}"))
+        tracing::error!("Redis trigger disconnected from {server_addr}");
     }
 
     #[instrument(name = "spin_trigger_redis.handle_message", skip_all, err(level = Level::INFO), fields(
@@ -177,7 +254,7 @@ impl<F: RuntimeFactors> Subscriber<F> {
         messaging.system = "redis"
     ))]
     async fn handle_message(&self, msg: Msg) -> anyhow::Result<()> {
-        let server_addr = &self.client.get_connection_info().addr;
+        let server_addr = &self.address;
         let channel = msg.get_channel_name();
         tracing::trace!(%server_addr, %channel, "Received message");
 
@@ -7,6 +7,7 @@ mod summary;
 mod variable;
 
 use std::path::PathBuf;
+use std::time::Duration;
 use std::{future::Future, sync::Arc};
 
 use anyhow::{Context, Result};
@@ -20,7 +21,9 @@ use spin_common::url::parse_file_url;
 use spin_factors::RuntimeFactors;
 use spin_factors_executor::{ComponentLoader, FactorsExecutor};
 
-use crate::{Trigger, TriggerApp, loader::ComponentLoader as ComponentLoaderImpl};
+use crate::{
+    ShutdownSignal, Trigger, TriggerApp, loader::ComponentLoader as ComponentLoaderImpl, shutdown,
+};
 pub use initial_kv_setter::InitialKvSetterHook;
 pub use launch_metadata::LaunchMetadata;
 pub use max_instance_memory::MaxInstanceMemoryHook;
@@ -132,6 +135,16 @@ pub struct FactorsTriggerCommand<T: Trigger<B::Factors>, B: RuntimeFactorsBuilde
     #[clap(long)]
     pub state_dir: Option<String>,
 
+    /// How long, in seconds, to let work in progress finish after receiving
+    /// a shutdown signal (such as Ctrl+C or SIGTERM) before exiting anyway.
+    #[clap(
+        long = "shutdown-grace-period",
+        env = "SPIN_SHUTDOWN_GRACE_PERIOD",
+        value_name = "SECONDS",
+        default_value_t = 10
+    )]
+    pub shutdown_grace_period: u64,
+
     #[clap(flatten)]
     pub trigger_args: T::CliArgs,
 
@@ -308,25 +321,47 @@ impl<T: Trigger<B::Factors>, B: RuntimeFactorsBuilder> FactorsTriggerCommand<T,
         };
 
         let loader = ComponentLoaderImpl::new();
+        let (shutdown_handle, shutdown) = shutdown::channel();
         let run_fut = builder
-            .run(app, common_options, self.builder_args, &loader)
+            .run(
+                app,
+                common_options,
+                self.builder_args,
+                &loader,
+                shutdown.clone(),
+            )
             .await?;
 
-        let (abortable, abort_handle) = futures::future::abortable(run_fut);
-        ctrlc::set_handler(move || abort_handle.abort())?;
-        match abortable.await {
-            Ok(Ok(())) => {
+        // Further signals are ignored while draining: `spin up` forwards
+        // Ctrl+C to triggers as SIGTERM, so each trigger may receive two.
+        ctrlc::set_handler(move || shutdown_handle.request())?;
+        let grace_period = Duration::from_secs(self.shutdown_grace_period);
+        let mut run_fut = std::pin::pin!(run_fut);
+        let requested = std::pin::pin!(shutdown.requested());
+        let result = match futures::future::select(run_fut.as_mut(), requested).await {
+            futures::future::Either::Left((result, _)) => result,
+            futures::future::Either::Right(((), _)) => {
+                tracing::info!("User requested shutdown: finishing work in progress");
+                match tokio::time::timeout(grace_period, run_fut).await {
+                    Ok(result) => result,
+                    Err(_elapsed) => {
+                        tracing::warn!(
+                            "Work still in progress after shutdown grace period of {grace_period:?}: exiting"
+                        );
+                        return Ok(());
+                    }
+                }
+            }
+        };
+        match result {
+            Ok(()) => {
                 tracing::info!("Trigger executor shut down: exiting");
                 Ok(())
             }
-            Ok(Err(err)) => {
+            Err(err) => {
                 tracing::error!("Trigger executor failed");
                 Err(err)
             }
-            Err(_aborted) => {
-                tracing::info!("User requested shutdown: exiting");
-                Ok(())
-            }
         }
     }
 
@@ -423,16 +458,18 @@ impl<T: Trigger<B::Factors>, B: RuntimeFactorsBuilder> TriggerAppBuilder<T, B> {
         Ok(configured_app)
     }
 
-    /// Run the [`TriggerApp`] with the given [`App`] and options.
+    /// Run the [`TriggerApp`] with the given [`App`] and options, until it
+    /// finishes or `shutdown` is requested.
     pub async fn run(
         mut self,
         app: App,
         common_options: FactorsConfig,
         options: B::CliArgs,
         loader: &impl ComponentLoader<B::Factors, T::InstanceState>,
+        shutdown: ShutdownSignal,
     ) -> anyhow::Result<impl Future<Output = anyhow::Result<()>>> {
         let configured_app = self.build(app, common_options, options, loader).await?;
-        Ok(self.trigger.run(configured_app))
+        Ok(self.trigger.run_until_shutdown(configured_app, shutdown))
     }
 }
 
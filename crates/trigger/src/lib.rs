@@ -1,5 +1,6 @@
 pub mod cli;
 pub mod loader;
+pub mod shutdown;
 
 use heck::ToTitleCase;
 use std::future::Future;
@@ -9,6 +10,7 @@ use spin_core::Linker;
 use spin_factors::RuntimeFactors;
 use spin_factors_executor::{FactorsExecutorApp, FactorsInstanceBuilder};
 
+pub use shutdown::ShutdownSignal;
 pub use spin_app::App;
 
 /// Type alias for a [`spin_factors_executor::FactorsExecutorApp`] specialized to a [`Trigger`].
@@ -72,6 +74,26 @@ pub trait Trigger<F: RuntimeFactors>: Sized + Send {
         trigger_app: TriggerApp<Self, F>,
     ) -> impl Future<Output = anyhow::Result<()>> + Send;
 
+    /// Run this trigger until it finishes or `shutdown` is requested.
+    ///
+    /// Once shutdown is requested, the trigger should stop accepting new work
+    /// and return when the work it has in progress has finished. The default
+    /// implementation stops running the trigger immediately.
+    fn run_until_shutdown(
+        self,
+        trigger_app: TriggerApp<Self, F>,
+        shutdown: ShutdownSignal,
+    ) -> impl Future<Output = anyhow::Result<()>> + Send {
+        async move {
+            let run = std::pin::pin!(self.run(trigger_app));
+            let requested = std::pin::pin!(shutdown.requested());
+            match futures::future::select(run, requested).await {
+                futures::future::Either::Left((res, _)) => res,
+                futures::future::Either::Right(((), _)) => Ok(()),
+            }
+        }
+    }
+
     /// Returns a list of host requirements supported by this trigger specifically.
     ///
     /// See [`App::ensure_needs_only`].
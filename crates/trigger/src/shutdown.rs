@@ -0,0 +1,76 @@
+//! Graceful shutdown of triggers.
+
+use tokio::sync::watch;
+
+/// Creates a [`ShutdownHandle`] and the [`ShutdownSignal`] it raises.
+pub fn channel() -> (ShutdownHandle, ShutdownSignal) {
+    let (tx, rx) = watch::channel(false);
+    (ShutdownHandle(tx), ShutdownSignal(rx))
+}
+
+/// Requests that a trigger shut down.
+#[derive(Debug)]
+pub struct ShutdownHandle(watch::Sender<bool>);
+
+impl ShutdownHandle {
+    /// Requests shutdown. Requesting it again has no further effect.
+    pub fn request(&self) {
+        self.0.send_replace(true);
+    }
+}
+
+/// A request for a trigger to stop accepting new work and finish the work it
+/// has in progress.
+#[derive(Clone, Debug)]
+pub struct ShutdownSignal(watch::Receiver<bool>);
+
+impl ShutdownSignal {
+    /// A signal which is never raised.
+    pub fn never() -> Self {
+        channel().1
+    }
+
+    /// Whether shutdown has been requested.
+    pub fn is_requested(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    /// Waits until shutdown is requested, which may be never.
+    pub async fn requested(&self) {
+        let mut rx = self.0.clone();
+        let raised = rx.wait_for(|requested| *requested).await.is_ok();
+        if !raised {
+            // The handle was dropped without requesting shutdown.
+            std::future::pending().await
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn signal_is_raised_by_handle() {
+        let (handle, signal) = channel();
+        let waiter = tokio::spawn({
+            let signal = signal.clone();
+            async move { signal.requested().await }
+        });
+        assert!(!signal.is_requested());
+        handle.request();
+        waiter.await.unwrap();
+        assert!(signal.is_requested());
+        // Already-raised signals resolve immediately.
+        signal.requested().await;
+    }
+
+    #[tokio::test]
+    async fn never_signal_is_not_raised() {
+        let signal = ShutdownSignal::never();
+        assert!(!signal.is_requested());
+        let raised =
+            tokio::time::timeout(std::time::Duration::from_millis(10), signal.requested()).await;
+        assert!(raised.is_err());
+    }
+}
@@ -2,9 +2,9 @@ use super::*;
 
 mod rdbms_types {
     use super::*;
-    use spin::mysql::mysql as mysql3;
+    use spin::mysql3_2_0::mysql as mysql3;
     use spin::postgres3_0_0::postgres as pg3;
-    use spin::postgres4_2_0::postgres as pg4;
+    use spin::postgres4_3_0::postgres as pg4;
     use v2::mysql as mysql2;
 
     impl From<v2::rdbms_types::Column> for v1::rdbms_types::Column {
@@ -521,7 +521,7 @@ mod rdbms_types {
 mod postgres {
     use super::*;
     use spin::postgres3_0_0::postgres as pg3;
-    use spin::postgres4_2_0::postgres as pg4;
+    use spin::postgres4_3_0::postgres as pg4;
 
     impl From<pg4::RowSet> for v1::postgres::RowSet {
         fn from(value: pg4::RowSet) -> v1::postgres::RowSet {
@@ -581,7 +581,7 @@ mod mysql {
 
 mod redis {
     use super::*;
-    use crate::spin::redis::redis as v3;
+    use crate::spin::redis3_1_0::redis as v3;
 
     impl From<v1::redis::RedisParameter> for v2::redis::RedisParameter {
         fn from(value: v1::redis::RedisParameter) -> Self {
@@ -14,7 +14,8 @@ wasmtime::component::bindgen!({
         include spin:up/platform@3.4.0;
         include spin:up/platform@4.0.0;
         include wasi:keyvalue/imports@0.2.0-draft2;
-        export spin:redis/inbound-redis@3.0.0;
+        export spin:redis/inbound-redis@3.1.0;
+        export spin:postgres/inbound-postgres@4.3.0;
     }
     "#,
     path: "../../wit",
@@ -37,10 +38,10 @@ wasmtime::component::bindgen!({
         "fermyon:spin/variables@2.0.0.error" => v2::variables::Error,
         "spin:key-value/key-value@3.0.0.error" => spin::key_value::key_value::Error,
         "spin:mqtt/mqtt@3.0.0.error" => spin::mqtt::mqtt::Error,
-        "spin:mysql/mysql@3.0.0.error" => spin::mysql::mysql::Error,
+        "spin:mysql/mysql@3.2.0.error" => spin::mysql3_2_0::mysql::Error,
         "spin:postgres/postgres@3.0.0.error" => spin::postgres3_0_0::postgres::Error,
-        "spin:postgres/postgres@4.2.0.error" => spin::postgres4_2_0::postgres::Error,
-        "spin:redis/redis@3.0.0.error" => spin::redis::redis::Error,
+        "spin:postgres/postgres@4.3.0.error" => spin::postgres4_3_0::postgres::Error,
+        "spin:redis/redis@3.1.0.error" => spin::redis3_1_0::redis::Error,
         "spin:sqlite/sqlite@3.1.0.error" => spin::sqlite3_1_0::sqlite::Error,
         "spin:variables/variables@3.0.0.error" => spin::variables::variables::Error,
         "wasi:config/store@0.2.0-draft-2024-09-27.error" => wasi::config::store::Error,
@@ -73,7 +74,7 @@ impl spin::sqlite3_1_0::sqlite::Value {
     }
 }
 
-impl spin::postgres4_2_0::postgres::DbValue {
+impl spin::postgres4_3_0::postgres::DbValue {
     pub fn memory_size(&self) -> usize {
         match self {
             Self::DbNull
@@ -117,6 +118,29 @@ impl spin::postgres4_2_0::postgres::DbValue {
     }
 }
 
+impl spin::postgres4_3_0::postgres::ParameterValue {
+    pub fn memory_size(&self) -> usize {
+        let strings = |v: &[Option<String>]| {
+            v.iter()
+                .map(|v| std::mem::size_of::<Option<String>>() + v.as_ref().map_or(0, |s| s.len()))
+                .sum::<usize>()
+        };
+        std::mem::size_of::<Self>()
+            + match self {
+                Self::Str(s) | Self::Uuid(s) | Self::Decimal(s) => s.len(),
+                Self::Binary(b) | Self::Jsonb(b) => b.len(),
+                Self::RangeDecimal((lower, upper)) => [lower, upper]
+                    .iter()
+                    .map(|bound| bound.as_ref().map_or(0, |(s, _)| s.len()))
+                    .sum(),
+                Self::ArrayInt32(v) => v.len() * std::mem::size_of::<Option<i32>>(),
+                Self::ArrayInt64(v) => v.len() * std::mem::size_of::<Option<i64>>(),
+                Self::ArrayDecimal(v) | Self::ArrayStr(v) => strings(v),
+                _ => 0,
+            }
+    }
+}
+
 impl v2::rdbms_types::DbValue {
     pub fn memory_size(&self) -> usize {
         match self {
@@ -138,3 +162,33 @@ impl v2::rdbms_types::DbValue {
         }
     }
 }
+
+impl v2::rdbms_types::ParameterValue {
+    pub fn memory_size(&self) -> usize {
+        match self {
+            Self::Str(s) => std::mem::size_of::<Self>() + s.len(),
+            Self::Binary(b) => std::mem::size_of::<Self>() + b.len(),
+            _ => std::mem::size_of::<Self>(),
+        }
+    }
+}
+
+impl spin::mysql3_2_0::mysql::DbValue {
+    pub fn memory_size(&self) -> usize {
+        match self {
+            Self::Str(s) => std::mem::size_of::<Self>() + s.len(),
+            Self::Binary(b) => std::mem::size_of::<Self>() + b.len(),
+            _ => std::mem::size_of::<Self>(),
+        }
+    }
+}
+
+impl spin::mysql3_2_0::mysql::ParameterValue {
+    pub fn memory_size(&self) -> usize {
+        match self {
+            Self::Str(s) => std::mem::size_of::<Self>() + s.len(),
+            Self::Binary(b) => std::mem::size_of::<Self>() + b.len(),
+            _ => std::mem::size_of::<Self>(),
+        }
+    }
+}
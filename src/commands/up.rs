@@ -7,6 +7,10 @@ use std::{
     fmt::Debug,
     path::{Path, PathBuf},
     process::Stdio,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
 };
 
 use anyhow::{Context, Result, anyhow, bail, ensure};
@@ -272,7 +276,7 @@ impl UpCommandInner {
         let trigger_processes = self.start_trigger_processes(trigger_cmds, run_opts).await?;
         let pids = get_pids(&trigger_processes);
 
-        set_kill_on_ctrl_c(&pids)?;
+        let shutting_down = set_kill_on_ctrl_c(&pids)?;
 
         let trigger_tasks = trigger_processes
             .into_iter()
@@ -283,7 +287,7 @@ impl UpCommandInner {
             tokio::time::sleep(MULTI_TRIGGER_LET_ALL_START).await;
         }
 
-        let (first_to_finish, _index, _rest) = futures::future::select_all(trigger_tasks).await;
+        let (first_to_finish, _index, rest) = futures::future::select_all(trigger_tasks).await;
 
         if let Ok(process_result) = first_to_finish {
             let status = process_result?;
@@ -296,6 +300,12 @@ impl UpCommandInner {
             }
         }
 
+        if shutting_down.load(Ordering::SeqCst) {
+            // Let the other triggers finish draining rather than killing them
+            // when we exit.
+            futures::future::join_all(rest).await;
+        }
+
         Ok(())
     }
 
@@ -608,18 +618,25 @@ fn is_flag_arg(arg: &OsString) -> bool {
     }
 }
 
+/// Forwards Ctrl+C to the trigger processes, returning a flag which is set
+/// once it has been received.
 #[cfg(windows)]
-fn set_kill_on_ctrl_c(_pids: &[usize]) -> Result<(), anyhow::Error> {
-    Ok(())
+fn set_kill_on_ctrl_c(_pids: &[usize]) -> Result<Arc<AtomicBool>, anyhow::Error> {
+    Ok(Default::default())
 }
 
+/// Forwards Ctrl+C to the trigger processes, returning a flag which is set
+/// once it has been received.
 #[cfg(not(windows))]
-fn set_kill_on_ctrl_c(pids: &[nix::unistd::Pid]) -> Result<(), anyhow::Error> {
+fn set_kill_on_ctrl_c(pids: &[nix::unistd::Pid]) -> Result<Arc<AtomicBool>, anyhow::Error> {
     let pids = pids.to_owned();
+    let shutting_down = Arc::new(AtomicBool::new(false));
+    let flag = shutting_down.clone();
     ctrlc::set_handler(move || {
+        flag.store(true, Ordering::SeqCst);
         kill_child_processes(&pids);
     })?;
-    Ok(())
+    Ok(shutting_down)
 }
 
 #[cfg(windows)]
@@ -721,7 +738,7 @@ fn trigger_commands_for_trigger_types(trigger_types: Vec<&str>) -> Result<Vec<Ve
     trigger_types
         .iter()
         .map(|&t| match t {
-            "http" | "redis" => Ok(trigger_command(t)),
+            "http" | "redis" | "postgres" => Ok(trigger_command(t)),
             _ => {
                 let cmd = resolve_trigger_plugin(t)?;
                 Ok(vec![cmd])
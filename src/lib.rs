@@ -27,6 +27,7 @@ use spin_runtime_factors::FactorsBuilder;
 use spin_trigger::cli::FactorsTriggerCommand;
 use spin_trigger::cli::help::HelpArgsOnlyTrigger;
 use spin_trigger_http::HttpTrigger;
+use spin_trigger_postgres::PostgresTrigger;
 use spin_trigger_redis::RedisTrigger;
 
 pub use opts::HELP_ARGS_ONLY_TRIGGER_TYPE;
@@ -131,6 +132,7 @@ enum SpinApp {
 enum TriggerCommands {
     Http(FactorsTriggerCommand<HttpTrigger, FactorsBuilder>),
     Redis(FactorsTriggerCommand<RedisTrigger, FactorsBuilder>),
+    Postgres(FactorsTriggerCommand<PostgresTrigger, FactorsBuilder>),
     #[clap(name = crate::HELP_ARGS_ONLY_TRIGGER_TYPE, hide = true)]
     HelpArgsOnly(FactorsTriggerCommand<HelpArgsOnlyTrigger, FactorsBuilder>),
 }
@@ -149,6 +151,7 @@ impl SpinApp {
             Self::Build(cmd) => cmd.run().await,
             Self::Trigger(TriggerCommands::Http(cmd)) => cmd.run().await,
             Self::Trigger(TriggerCommands::Redis(cmd)) => cmd.run().await,
+            Self::Trigger(TriggerCommands::Postgres(cmd)) => cmd.run().await,
             Self::Trigger(TriggerCommands::HelpArgsOnly(cmd)) => cmd.run().await,
             Self::Plugins(cmd) => cmd.run().await,
             Self::Targets(cmd) => cmd.run().await,
@@ -10,10 +10,10 @@ wit_bindgen::generate!({
 use {
     crate::{
         exports::wasi::http0_3_0::handler::Guest,
-        spin::mysql::mysql,
+        spin::mysql3_2_0::mysql,
         wasi::http0_3_0::types::{ErrorCode, Fields, Request, Response},
     },
-    helper::{bail, ensure, ensure_matches, ensure_ok},
+    helper::{ensure, ensure_matches, ensure_ok},
     std::env,
     wit_bindgen::rt::async_support,
 };
@@ -74,8 +74,8 @@ async fn test() -> Result<(), String> {
         .await
     );
 
-    // Insert 256 copies of a 1MB string, which exceeds the 128MB query
-    // result limit we impose in `factor-outbound-mysql`:
+    // Insert 256 copies of a 1MB string, which exceeds the 128MB host
+    // buffering limit we impose in `factor-outbound-mysql`:
     let big_text = "y".repeat(1 << 20);
     for i in 0..256 {
         ensure_ok!(
@@ -90,21 +90,26 @@ async fn test() -> Result<(), String> {
         );
     }
 
-    // This should exceed the 128MB query result limit:
-    let big = async {
-        let (_, stream, future) = conn
-            .query("SELECT * FROM big_text".into(), Vec::new())
-            .await?;
-        let rows = stream.collect().await;
-        future.await?;
-        Ok(rows)
-    };
-
-    match big.await {
-        Ok(_) => bail!("large select should not have succeeded",),
-        Err(mysql::Error::Other(s)) if s.contains("query result exceeds limit") => {}
-        Err(e) => bail!("unexpected error: {e}",),
+    // Rows are only buffered until they're read, so streaming the whole
+    // result succeeds as long as each row is within the limit:
+    let (_, stream, future) = ensure_ok!(
+        conn.query_with_options(
+            "SELECT * FROM big_text".into(),
+            Vec::new(),
+            mysql::QueryOptions {
+                prefetch_rows: Some(1)
+            }
+        )
+        .await
+    );
+    let mut rows = 0;
+    let mut stream = stream;
+    while let Some(row) = stream.next().await {
+        ensure!(matches!(row[1], mysql::DbValue::Str(ref s) if s.len() == 1 << 20));
+        rows += 1;
     }
+    ensure_ok!(future.await);
+    ensure!(rows == 256);
 
     Ok(())
 }
@@ -0,0 +1,23 @@
+wit_bindgen::generate!({
+    path: "../../../../wit",
+    world: "spin:up/postgres-trigger@4.1.0",
+    merge_structurally_equal_types: true,
+    generate_all,
+});
+
+use exports::spin::postgres4_3_0::inbound_postgres::Notification;
+use spin::postgres4_3_0::postgres::Error;
+
+struct Guest;
+
+impl exports::spin::postgres4_3_0::inbound_postgres::Guest for Guest {
+    async fn handle_notification(notification: Notification) -> Result<(), Error> {
+        println!(
+            "Got notification: '{}' on '{}'",
+            notification.payload, notification.channel
+        );
+        Ok(())
+    }
+}
+
+export!(Guest);
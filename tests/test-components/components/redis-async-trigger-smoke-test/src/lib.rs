@@ -2,16 +2,22 @@ use std::str::from_utf8;
 
 const KV_KEY: &str = "message";
 
+// Exports spin:redis/inbound-redis@3.0.0 to check that guests built against
+// the original redis-trigger world still run.
 wit_bindgen::generate!({
     path: "../../../../wit",
-    world: "spin:up/redis-trigger@4.0.0",
+    inline: r#"package root:component;
+    world redis-trigger {
+        include spin:up/platform@4.0.0;
+        export spin:redis/inbound-redis@3.0.0;
+    }"#,
     generate_all,
 });
 
 struct Guest;
 
-impl exports::spin::redis::inbound_redis::Guest for Guest {
-    async fn handle_message(message: Vec<u8>) -> Result<(), spin::redis::redis::Error> {
+impl exports::spin::redis3_0_0::inbound_redis::Guest for Guest {
+    async fn handle_message(message: Vec<u8>) -> Result<(), spin::redis3_0_0::redis::Error> {
         // Do some async stuff to prove it works
         let kv = spin::key_value::key_value::Store::open("default".to_string()).await.expect("should have had access to default KV store");
         kv.set(KV_KEY.to_string(), message.clone()).await.expect("should have set KV entry");
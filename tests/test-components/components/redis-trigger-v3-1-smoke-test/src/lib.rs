@@ -0,0 +1,40 @@
+wit_bindgen::generate!({
+    path: "../../../../wit",
+    world: "spin:up/redis-trigger@4.1.0",
+    merge_structurally_equal_types: true,
+    generate_all,
+});
+
+use spin::redis3_1_0::redis::{Connection, Error};
+
+const HASH_KEY: &str = "redis-trigger-v3-1-smoke-test";
+
+struct Guest;
+
+impl exports::spin::redis3_1_0::inbound_redis::Guest for Guest {
+    async fn handle_message(message: Vec<u8>) -> Result<(), Error> {
+        // Round-trip the message through a hash to exercise the 3.1.0 operations
+        let address = spin::variables::variables::get("redis_address".to_string())
+            .await
+            .map_err(|e| Error::Other(format!("{e:?}")))?;
+        let connection = Connection::open(address).await?;
+        connection
+            .hset(HASH_KEY.to_string(), vec![("message".to_string(), message)])
+            .await?;
+        let count = connection
+            .hincrby(HASH_KEY.to_string(), "count".to_string(), 1)
+            .await?;
+        let message = connection
+            .hget(HASH_KEY.to_string(), "message".to_string())
+            .await?
+            .ok_or_else(|| Error::Other("hash field should have existed".to_string()))?;
+
+        println!(
+            "Got message {count}: '{}'",
+            String::from_utf8_lossy(&message)
+        );
+        Ok(())
+    }
+}
+
+export!(Guest);
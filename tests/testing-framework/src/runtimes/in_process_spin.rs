@@ -5,7 +5,10 @@ use std::sync::Arc;
 use anyhow::Context as _;
 use spin_runtime_factors::{FactorsBuilder, TriggerAppArgs, TriggerFactors};
 use spin_trigger::{cli::TriggerAppBuilder, loader::ComponentLoader};
-use spin_trigger_http::{HttpServer, HttpTrigger, InstanceReuseConfig, OutputFormat};
+use spin_trigger_http::{
+    HttpServer, HttpTrigger, InstanceReuseConfig, ListenAddr, ListenerConfig, OutputFormat,
+    ProtocolConfig,
+};
 use test_environment::{
     Runtime, TestEnvironment, TestEnvironmentConfig,
     http::{Request, Response},
@@ -117,10 +120,12 @@ async fn initialize_trigger(
     let app = spin_app::App::new("my-app", locked_app);
     let trigger = HttpTrigger::new(
         &app,
-        "127.0.0.1:80".parse().unwrap(),
-        None,
+        vec![ListenerConfig {
+            addr: ListenAddr::Tcp("127.0.0.1:80".parse().unwrap()),
+            tls_config: None,
+        }],
         false,
-        None,
+        ProtocolConfig::default(),
         reuse_config,
         OutputFormat::default(),
     )?;